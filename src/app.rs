@@ -1,40 +1,123 @@
 use anyhow::{anyhow, Result};
-use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use chrono::Local;
+use crossterm::event::{self, DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEventKind, KeyModifiers};
 use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::io::stdout;
 use std::path::PathBuf;
 use std::process::Command;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc;
 
-use crate::config::Config;
-use crate::mail::{self, MessageSummary};
+use crate::cache;
+use crate::contacts::{self, Contact};
+use crate::config::{expand_home, Config, MailConfig, SavedSearch, ConfirmDelete, Density, ListConfig};
+use crate::count::TextCount;
+use crate::diff;
+use crate::drafts::{self, Draft};
+use crate::edit;
+use crate::features;
+use crate::guard;
+use crate::health::AccountHealth;
+use crate::keys::{self, Action, KeyMap, ListKey};
+use crate::outbox;
+use crate::mail::{self, Attachment, Body, Delivery, Folder, MessageSummary};
+use crate::mail::imap::{FetchPhase, FetchProgress};
+use crate::mail::smtp::OutgoingMessage;
+use crate::profile::Profiler;
+use crate::redact;
+use crate::ui::BodyRows;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum View {
     List,
+    Folders,
+    Drafts,
     Mail,
     Compose,
     Config,
+    Auth, // password prompt after the server rejected the login
+    Capabilities,
+    Outbox,
+    Styles, // F11: every theme style, for auditing themes
+    Diff,   // `=`: the bodies of two marked messages compared
+    Searches, // `~`: saved searches
+    Health,   // `H`: connection, sync times and recent errors
+    Groups,   // `:groups`: the recipient groups of `[groups]`
+}
+
+impl View {
+    pub fn name(self) -> &'static str {
+        match self {
+            View::List => "list",
+            View::Folders => "folders",
+            View::Drafts => "drafts",
+            View::Mail => "mail",
+            View::Compose => "compose",
+            View::Config => "config",
+            View::Auth => "auth",
+            View::Capabilities => "capabilities",
+            View::Outbox => "outbox",
+            View::Styles => "styles",
+            View::Diff => "diff",
+            View::Searches => "searches",
+            View::Groups => "groups",
+            View::Health => "health",
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ComposeField {
     To,
+    Cc,
+    Bcc,
+    ReplyTo,
     Subject,
     Body,
 }
 
+/// `=`: what changed from the older of two marked messages to the newer.
+pub struct DiffState {
+    pub old: MessageSummary,
+    pub new: MessageSummary,
+    pub lines: Vec<(diff::Change, String)>,
+    pub scroll: u16,
+}
+
 pub struct ComposeState {
     pub to: String,
+    pub cc: String,
+    pub bcc: String,
+    pub reply_to: String, // empty: `[user] reply_to`, if set
     pub subject: String,
     pub body: String,   // editable (your reply text)
     pub quote: String,  // readonly quoted block (for Reply)
     pub focus: ComposeField,
+    pub cursor: usize, // byte offset in the focused field, see `edit`; usize::MAX is its end
+    pub suggestion: usize, // the highlighted address completion
+    pub confirm_bulk: bool, // Ctrl+S hit the recipient limit, waiting for y/b/n
+    pub confirm_discard: bool, // Esc with something typed, waiting for d/s/Esc
+    pub confirm_guard: bool, // a [send_guard] pattern matched, waiting for y/n
+    pub preview: Option<u16>, // Ctrl+S shows the mail read-only first; its scroll offset
+    pub undisclosed: bool,  // recipients go on the envelope only
+    pub high_priority: bool,
+    pub draft_path: Option<PathBuf>, // file this compose was saved to / resumed from
+    pub in_reply_to: Option<String>, // set for replies, so the answer stays in its thread
+    pub references: Option<String>,
+    pub attached_message: Option<Vec<u8>>, // original forwarded as an attachment (F)
+    pub schedule_input: Option<String>, // Ctrl+L: the send-later prompt is open
+    pub attachments: Vec<PathBuf>,
+    pub attachment_sizes: HashMap<PathBuf, u64>, // read once when attached; a path not in it is missing
+    pub attach_input: Option<String>, // Ctrl+A: the file prompt is open
+    pub send_at: Option<i64>, // Unix seconds: Ctrl+S / y / b queue the mail instead of sending
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -44,11 +127,15 @@ pub enum ConfigField {
     ImapUser,
     ImapPass,
     ImapStarttls,
+    ImapTlsVerify,
+    ImapCaFile,
     SmtpHost,
     SmtpPort,
     SmtpUser,
     SmtpPass,
     SmtpStarttls,
+    SmtpTlsVerify,
+    SmtpCaFile,
     UserName,
     UserEmail,
 }
@@ -61,12 +148,16 @@ pub struct ConfigEditState {
     pub imap_user: String,
     pub imap_pass: String,
     pub imap_starttls: bool,
+    pub imap_tls_verify: bool,
+    pub imap_ca_file: String,
 
     pub smtp_host: String,
     pub smtp_port: String,
     pub smtp_user: String,
     pub smtp_pass: String,
     pub smtp_starttls: bool,
+    pub smtp_tls_verify: bool,
+    pub smtp_ca_file: String,
 
     pub user_name: String,
     pub user_email: String,
@@ -80,14 +171,19 @@ impl ConfigEditState {
             imap_host: c.imap.host.clone(),
             imap_port: c.imap.port.to_string(),
             imap_user: c.imap.username.clone(),
-            imap_pass: c.imap.password.clone(),
+            // a password kept elsewhere isn't shown as if it were typed here
+            imap_pass: if c.imap.password_source().is_some() { String::new() } else { c.imap.password.clone() },
             imap_starttls: c.imap.starttls,
+            imap_tls_verify: c.imap.verify_certs(),
+            imap_ca_file: c.imap.tls_ca_file.clone().unwrap_or_default(),
 
             smtp_host: c.smtp.host.clone(),
             smtp_port: c.smtp.port.to_string(),
             smtp_user: c.smtp.username.clone(),
-            smtp_pass: c.smtp.password.clone(),
+            smtp_pass: if c.smtp.password_source().is_some() { String::new() } else { c.smtp.password.clone() },
             smtp_starttls: c.smtp.starttls,
+            smtp_tls_verify: c.smtp.verify_certs(),
+            smtp_ca_file: c.smtp.tls_ca_file.clone().unwrap_or_default(),
 
             user_name: c.user.name.clone(),
             user_email: c.user.email.clone(),
@@ -95,132 +191,778 @@ impl ConfigEditState {
     }
 }
 
+// What `r` / `f` in the list open once the fetched body arrives.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PendingCompose {
+    Reply,
+    Forward,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum ListFilter {
+    Sender(String),
+    Thread(String),
+}
+
+impl ListFilter {
+    pub fn matches(&self, m: &MessageSummary) -> bool {
+        match self {
+            ListFilter::Sender(addr) => extract_reply_to(&m.from).eq_ignore_ascii_case(addr),
+            ListFilter::Thread(subject) => normalize_subject(&m.subject) == *subject,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            ListFilter::Sender(addr) => format!("from: {addr}"),
+            ListFilter::Thread(subject) => format!("thread: {subject}"),
+        }
+    }
+}
+
+pub struct BodyFetch {
+    pub uid: u32,
+    pub started: Instant,
+    pub progress: FetchProgress,
+    cancel: Arc<AtomicBool>,
+}
+
+const FAILURES_BEFORE_OFFLINE: u32 = 2;
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Where the IMAP connection stands, as the list title shows it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConnState {
+    Connected,
+    Reconnecting(u32), // attempt number
+    Offline,           // retrying, but at the slowest pace
+}
+
+// Shared with the reconnect worker.
+#[derive(Default)]
+struct ReconnectCtl {
+    stop: AtomicBool,      // quitting, or the connection came back another way
+    retry_now: AtomicBool, // manual refresh: try again at once, backoff from the start
+}
+
+/// Tracks IMAP reachability. While offline, errors collapse into a single
+/// "reconnecting…" status and a reconnect worker probing with backoff decides when
+/// to refresh again.
+pub struct NetWatch {
+    offline: bool,
+    failures: u32,
+    pub state: ConnState,
+    reconnect: Option<Arc<ReconnectCtl>>,
+    last_tick: Instant,
+    last_wall: SystemTime,
+}
+
+impl NetWatch {
+    fn new() -> Self {
+        Self {
+            offline: false,
+            failures: 0,
+            state: ConnState::Connected,
+            reconnect: None,
+            last_tick: Instant::now(),
+            last_wall: SystemTime::now(),
+        }
+    }
+
+    // The monotonic clock stops during suspend while the wall clock keeps going.
+    fn woke_from_suspend(&mut self) -> bool {
+        let mono = self.last_tick.elapsed();
+        let wall = self.last_wall.elapsed().unwrap_or_default();
+        self.last_tick = Instant::now();
+        self.last_wall = SystemTime::now();
+        wall > mono + Duration::from_secs(5)
+    }
+
+    fn go_offline(&mut self) {
+        if !self.offline {
+            self.offline = true;
+            self.state = ConnState::Reconnecting(1);
+        }
+        self.retry_now();
+    }
+
+    /// Returns true when this failure tipped us into the offline state.
+    fn record_failure(&mut self) -> bool {
+        self.failures += 1;
+        if self.failures >= FAILURES_BEFORE_OFFLINE && !self.offline {
+            self.go_offline();
+            return true;
+        }
+        false
+    }
+
+    fn record_success(&mut self) {
+        self.offline = false;
+        self.failures = 0;
+        self.state = ConnState::Connected;
+        self.stop_reconnect();
+    }
+
+    // Offline with no worker trying, or the last one ended without getting through.
+    fn reconnect_due(&self) -> bool {
+        self.offline && self.reconnect.as_ref().is_none_or(|ctl| Arc::strong_count(ctl) == 1)
+    }
+
+    /// Cuts the running reconnect worker's wait short and starts its backoff over.
+    fn retry_now(&self) {
+        if let Some(ctl) = &self.reconnect {
+            ctl.retry_now.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn stop_reconnect(&mut self) {
+        if let Some(ctl) = self.reconnect.take() {
+            ctl.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The connection in a few words, for the health screen.
+    pub fn summary(&self) -> String {
+        match (self.state, self.failures) {
+            (ConnState::Offline, _) => format!("offline, retrying every {}s", RECONNECT_BACKOFF_MAX.as_secs()),
+            (ConnState::Reconnecting(n), _) => format!("reconnecting, attempt {n}"),
+            (ConnState::Connected, 0) => "connected".to_string(),
+            (ConnState::Connected, n) => format!("connected, {n} failure(s) in a row"),
+        }
+    }
+}
+
 pub struct App {
     pub view: View,
     pub return_view: View,
+    pub show_help: bool, // the key overlay over `view`
+    pub auth_failed: bool, // IMAP login rejected: no background fetches until it works again
+    pub auth_input: String,
+    auth_checking: bool,
+    auth_remember: bool, // Ctrl+K on the prompt: into the keyring once the login works
+    idle_stop: Option<Arc<AtomicBool>>, // set to end the running IDLE thread
+    update_checked: bool, // `check_updates`: done once per run, after the first list load
+    pub server_caps: Option<Vec<String>>, // fetched once per connection; None until known
+
+    pub folders: Vec<Folder>,
+    rights: HashMap<String, Option<String>>, // MYRIGHTS of each folder opened so far
+    pub folder_selected: usize,
+    pub current_folder: String,
+    uid_validity: Option<u32>,    // of current_folder, once listed
+    cached_validity: Option<u32>, // of rows shown from the cache, until the server lists the folder
+    last_uid: u32,                // highest UID seen in current_folder
 
     pub messages: Vec<MessageSummary>,
-    pub selected: usize,
+    pub selected: usize, // index into `messages`, always one of `visible_indices()`
+    pub density: Density, // rows per message in the list, starts at `[list] density`
+    pub newest_first: bool, // list order, starts at `[list] newest_first`
+
+    pub filter: Option<ListFilter>,
+    pub filter_pending: bool, // `F` pressed, waiting for s/t
+    pub confirm_delete: Option<Vec<u32>>, // uids waiting for y/n
+    confirm_archive: Option<ArchivePlan>, // `A`: moves waiting for y/n
+    pub diff: Option<DiffState>,
+    pub contacts: Vec<Contact>, // addresses seen and used, for completion in To / Cc / Bcc
+    pub marked: HashSet<u32>, // multi-selection in the list (Space)
+    loading_more: bool, // `m` fetch of older messages in flight
+    refreshing: Option<String>, // mailbox a refresh or poll is in flight for
+    pub search_input: Option<String>, // `/` prompt being typed
+    pub command_input: Option<String>, // `:` prompt being typed
+    pub search: Option<String>,       // the list shows the results of this search
+    removing: Vec<(String, usize, MessageSummary)>, // optimistically removed rows, restored on error
+    profile: Option<Profiler>,
 
     pub current_header: Option<MessageSummary>,
     pub current_body: String,
-    pub body_scroll: u16,
+    pub body_rows: RefCell<BodyRows>, // `current_body` wrapped for the screen; cleared when it changes
+    current_message_id: Option<String>,
+    current_raw: Option<Vec<u8>>, // the open mail as fetched, for forwarding it as an attachment
+    current_references: Option<String>,
+    pub current_delivery: Option<Delivery>, // reached me by Bcc or an alias
+    pub body_was_html: bool,
+    other_body: Option<String>, // the body with disclaimers shown/hidden, swapped in by `x`
+    quote_body: String,         // the body as it came, disclaimers and long lines whole; replies quote it
+    pub body_count: TextCount,  // of `current_body`, without quoted lines
+    pub attachments: Vec<Attachment>, // of the open mail
+    pub attachment_selected: usize,
+    pub attachment_prompt: bool, // `a` pressed, waiting for the attachment number
+    pub export_input: Option<String>, // `w`: the path prompt is open
+    pub body_scroll: usize,
+    pub body_fetch: Option<BodyFetch>,
+    pub body_from_cache: bool, // `current_body` is the copy kept on disk; a fetch may be refreshing it
+    pub pending_compose: Option<(u32, PendingCompose)>, // `r` / `f` from the list, compose opens when the body arrives
+    pub focus_mode: bool,
 
     pub compose: ComposeState,
+    pub drafts: Vec<(PathBuf, Draft)>,
+    pub draft_selected: usize,
+    sending_draft: Option<PathBuf>,
+
+    pub outbox: Vec<outbox::Entry>,
+    pub outbox_selected: usize,
+    pub saved_selected: usize,
+    pub saved_name_input: Option<(Option<usize>, String)>, // `a` new / `r` rename (which one) in the picker
+    pub saved_search: Option<String>, // name of the saved search the list shows, until changed by hand
+    pub group_selected: usize,
+    pub group_input: Option<(Option<String>, String)>, // `a` new / `e` edit (which group) in the group list
+    pub outbox_inflight: HashSet<PathBuf>, // claimed by this run and not reported back yet
 
     pub cfg_edit: ConfigEditState,
     pub config_path: PathBuf,
+    pub keys: KeyMap,
 
     pub status: String,
+    status_cleaned: String, // `status` as last passed through `redact`, so it isn't redone every frame
+    pub net: NetWatch,
+    pub poll: Poller,
+    pub health: AccountHealth,
 
     pub config: Config,
 }
 
+impl App {
+    /// A fresh session, before anything is loaded.
+    fn new(config: Config, config_path: PathBuf, keys: KeyMap, created: bool) -> App {
+        App {
+            view: if created { View::Config } else { View::List },
+            return_view: View::List,
+            show_help: false,
+            auth_failed: false,
+            auth_input: String::new(),
+            auth_checking: false,
+            auth_remember: false,
+            idle_stop: None,
+            update_checked: false,
+            server_caps: None,
+
+            folders: vec![],
+            rights: HashMap::new(),
+            folder_selected: 0,
+            current_folder: "INBOX".to_string(),
+            uid_validity: None,
+            cached_validity: None,
+            last_uid: 0,
+
+            messages: vec![],
+            selected: 0,
+            density: config.list.density,
+            newest_first: config.list.newest_first,
+
+            filter: None,
+            filter_pending: false,
+            confirm_delete: None,
+            confirm_archive: None,
+            diff: None,
+            contacts: contacts::load(),
+            marked: HashSet::new(),
+            loading_more: false,
+            refreshing: None,
+            search_input: None,
+            command_input: None,
+            search: None,
+            removing: vec![],
+            profile: None,
+
+            current_header: None,
+            current_body: String::new(),
+            body_rows: RefCell::default(),
+            current_message_id: None,
+            current_raw: None,
+            current_references: None,
+            current_delivery: None,
+            body_was_html: false,
+            other_body: None,
+            quote_body: String::new(),
+            body_count: TextCount::default(),
+            attachments: vec![],
+            attachment_selected: 0,
+            attachment_prompt: false,
+            export_input: None,
+            body_scroll: 0,
+            body_fetch: None,
+            body_from_cache: false,
+            pending_compose: None,
+            focus_mode: false,
+
+            compose: ComposeState {
+                to: String::new(),
+                cc: String::new(),
+                bcc: String::new(),
+                reply_to: String::new(),
+                subject: String::new(),
+                body: String::new(),
+                quote: String::new(),
+                focus: ComposeField::To,
+                cursor: usize::MAX,
+                suggestion: 0,
+                confirm_bulk: false,
+                confirm_guard: false,
+                preview: None,
+                confirm_discard: false,
+                undisclosed: false,
+                high_priority: false,
+                draft_path: None,
+                in_reply_to: None,
+                references: None,
+                attached_message: None,
+                schedule_input: None,
+                send_at: None,
+                attachments: vec![],
+                attachment_sizes: HashMap::new(),
+                attach_input: None,
+            },
+            drafts: vec![],
+            draft_selected: 0,
+            sending_draft: None,
+
+            outbox: vec![],
+            outbox_selected: 0,
+            outbox_inflight: HashSet::new(),
+            saved_selected: 0,
+            saved_name_input: None,
+            saved_search: None,
+            group_selected: 0,
+            group_input: None,
+
+            cfg_edit: ConfigEditState::from_config(&config),
+            config_path,
+            keys,
+
+            status: if created {
+                "config.toml created. Fill your credentials and press Ctrl+S to save.".to_string()
+            } else {
+                "Starting...".to_string()
+            },
+            status_cleaned: String::new(),
+
+            net: NetWatch::new(),
+            poll: Poller::new(),
+            health: AccountHealth::default(),
+
+            config,
+        }
+    }
+
+    pub fn current_folder_label(&self) -> &str {
+        self.folders
+            .iter()
+            .find(|f| f.name == self.current_folder)
+            .map(|f| f.display.as_str())
+            .unwrap_or(&self.current_folder)
+    }
+
+    pub fn visible_indices(&self) -> Vec<usize> {
+        self.messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| self.filter.as_ref().is_none_or(|f| f.matches(m)))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // By the Date header, newest or oldest first, with the UID breaking ties. Mail whose
+    // date doesn't parse stays at the bottom either way, in UID order.
+    fn sort_messages(&mut self) {
+        let newest_first = self.newest_first;
+        self.messages.sort_by(|a, b| match (a.timestamp, b.timestamp) {
+            (Some(x), Some(y)) if newest_first => (y, b.uid).cmp(&(x, a.uid)),
+            (Some(x), Some(y)) => (x, a.uid).cmp(&(y, b.uid)),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.uid.cmp(&b.uid),
+        });
+        if self.config.list.priority_first {
+            // stable, so the date order is kept within each group
+            self.messages.sort_by_key(|m| !m.high_priority || m.seen);
+        }
+    }
+
+    fn newest_index(&self) -> usize {
+        let newest = self.messages.iter().enumerate().max_by_key(|(_, m)| (m.timestamp.is_some(), m.timestamp, m.uid));
+        newest.map_or(0, |(i, _)| i)
+    }
+
+    fn clamp_selection(&mut self) {
+        let visible = self.visible_indices();
+        if visible.contains(&self.selected) {
+            return;
+        }
+        self.selected = visible
+            .iter()
+            .copied()
+            .find(|&i| i >= self.selected)
+            .or_else(|| visible.last().copied())
+            .unwrap_or(0);
+    }
+}
+
+/// `[list] adaptive_poll`: the interval between polls while in use, from how often
+/// they find mail. Fed only with poll results, so it doesn't depend on the clock.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Adaptive {
+    interval: Duration,
+}
+
+impl Adaptive {
+    fn new(cfg: &ListConfig) -> Self {
+        Self { interval: Duration::from_secs(cfg.poll_secs) }
+    }
+
+    /// A background poll found `new` messages: nothing doubles the interval, up to
+    /// `poll_max_secs`; anything puts it back to `poll_secs`.
+    fn polled(self, cfg: &ListConfig, new: usize) -> Self {
+        let min = Duration::from_secs(cfg.poll_secs);
+        let max = Duration::from_secs(cfg.poll_max_secs).max(min);
+        match new {
+            0 => Self { interval: (self.interval * 2).clamp(min, max) },
+            _ => Self::new(cfg),
+        }
+    }
+}
+
+/// Periodic new-mail check. Once the user is idle (no keypress for
+/// `idle_after_secs`, or the terminal lost focus) the interval doubles after each
+/// poll up to `idle_poll_max_secs`; the next keypress brings it straight back.
+/// With `adaptive_poll`, "back" is what `Adaptive` settled on rather than `poll_secs`.
+pub struct Poller {
+    interval: Duration,
+    next: Instant,
+    last_activity: Instant,
+    focused: bool,
+    adaptive: Option<Adaptive>, // None until the first poll result
+}
+
+impl Poller {
+    fn new() -> Self {
+        Self {
+            interval: Duration::ZERO,
+            next: Instant::now(),
+            last_activity: Instant::now(),
+            focused: true,
+            adaptive: None,
+        }
+    }
+
+    fn base(&self, cfg: &ListConfig) -> Duration {
+        match self.adaptive {
+            Some(a) if cfg.adaptive_poll => a.interval,
+            _ => Duration::from_secs(cfg.poll_secs),
+        }
+    }
+
+    /// The interval the next poll is scheduled with; zero with polling off.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// A refresh found `new` messages; `background` when it was a poll, so a
+    /// refresh by hand (or through IDLE) starts over at `poll_secs`.
+    fn refreshed(&mut self, cfg: &ListConfig, background: bool, new: usize) {
+        let a = self.adaptive.unwrap_or_else(|| Adaptive::new(cfg));
+        self.adaptive = Some(if background { a.polled(cfg, new) } else { Adaptive::new(cfg) });
+        if !cfg.adaptive_poll || cfg.poll_secs == 0 {
+            return;
+        }
+        // the poll just ended, so the next one is timed from now
+        if !self.idle(cfg) {
+            self.interval = self.base(cfg);
+            self.next = Instant::now() + self.interval;
+        }
+    }
+
+    fn activity(&mut self, cfg: &ListConfig) {
+        let base = self.base(cfg);
+        self.last_activity = Instant::now();
+        self.focused = true;
+        if self.interval > base {
+            self.interval = base;
+            self.next = self.next.min(Instant::now() + base);
+        }
+    }
+
+    fn idle(&self, cfg: &ListConfig) -> bool {
+        !self.focused || self.last_activity.elapsed() >= Duration::from_secs(cfg.idle_after_secs)
+    }
+
+    fn due(&mut self, cfg: &ListConfig) -> bool {
+        let base = self.base(cfg);
+        if base.is_zero() || Instant::now() < self.next {
+            return false;
+        }
+        self.interval = if self.idle(cfg) {
+            (self.interval * 2).clamp(base, Duration::from_secs(cfg.idle_poll_max_secs).max(base))
+        } else {
+            base
+        };
+        self.next = Instant::now() + self.interval;
+        true
+    }
+}
+
+// Results of background work, sent to the main loop over one channel and grouped
+// by the part of the app they update.
 enum AppMsg {
-    MailList(Vec<MessageSummary>),
-    MailBody { header: MessageSummary, body: String },
+    List(ListEvent),
+    Body(BodyEvent),
+    Net(NetEvent),
+    Sent { append_error: Option<String> },
+    Outbox(OutboxEvent),
+    Conn(ConnState),
     Status(String),
 }
 
+// Scheduled mail sent from the outbox.
+enum OutboxEvent {
+    Sent { path: PathBuf, append_error: Option<String> },
+    Failed { path: PathBuf, error: String },
+}
+
+// The message list of the open folder, and the folder list.
+enum ListEvent {
+    Loaded { mailbox: String, uid_validity: u32, list: Vec<MessageSummary>, rights: Option<String> },
+    Appended { mailbox: String, list: Vec<MessageSummary>, quiet: bool },
+    Older { mailbox: String, uid_validity: u32, list: Vec<MessageSummary> }, // `m`: the page before the oldest loaded
+    Searched { mailbox: String, query: String, total: usize, list: Vec<MessageSummary> },
+    Failed(String),
+    Crashed(String), // the worker panicked: a bug, not the network
+    Removed { mailbox: String, uids: Vec<u32>, status: String },
+    RemoveFailed { mailbox: String, uids: Vec<u32>, error: String },
+    Folders(Vec<Folder>),
+    ArchivePlan(ArchivePlan),
+    Compared(Box<DiffState>),
+    NewMail, // IDLE saw INBOX change
+}
+
+// `A`: where each message goes, worked out against the server's namespace.
+struct ArchivePlan {
+    mailbox: String,
+    moves: Vec<(String, Vec<u32>)>, // destination folder, UIDs
+    undated: usize,                 // targets without a readable Date, left alone
+}
+
+// The body fetch of the open mail.
+enum BodyEvent {
+    Loaded { header: Box<MessageSummary>, body: Body },
+    Progress { uid: u32, progress: FetchProgress },
+    Failed { uid: u32, error: String },
+}
+
+// Reachability and login.
+enum NetEvent {
+    AuthFailed(String),
+    AuthChecked(Result<(), String>),
+    Capabilities(Vec<String>),
+    Quota(Option<(u64, u64)>),
+}
+
 fn clamp_dec(v: usize) -> usize {
     v.saturating_sub(1)
 }
 
+fn move_selection(app: &mut App, down: bool, wrap: bool) {
+    let visible = app.visible_indices();
+    let Some(pos) = visible.iter().position(|&i| i == app.selected) else {
+        app.clamp_selection();
+        return;
+    };
+    let last = visible.len() - 1;
+    let pos = match (down, wrap) {
+        (true, true) if pos == last => 0,
+        (false, true) if pos == 0 => last,
+        (true, _) => (pos + 1).min(last),
+        (false, _) => clamp_dec(pos),
+    };
+    app.selected = visible[pos];
+}
+
 struct TuiGuard;
 impl Drop for TuiGuard {
     fn drop(&mut self) {
         let _ = disable_raw_mode();
-        let _ = execute!(stdout(), LeaveAlternateScreen, crossterm::cursor::Show);
+        let _ = execute!(stdout(), DisableFocusChange, LeaveAlternateScreen, crossterm::cursor::Show);
     }
 }
 
+const IDLE_REDRAW: Duration = Duration::from_secs(1);
+
 pub async fn run() -> Result<()> {
     let (config, created, config_path) = Config::load_or_create()?;
     let (tx, mut rx) = mpsc::unbounded_channel::<AppMsg>();
+    log_panics();
+    let (keys, key_warnings) = KeyMap::load(&config.keys);
+    if !key_warnings.is_empty() {
+        let _ = write_log(&config, "key bindings", &key_warnings);
+    }
 
-    let mut app = App {
-        view: if created { View::Config } else { View::List },
-        return_view: View::List,
-
-        messages: vec![],
-        selected: 0,
-
-        current_header: None,
-        current_body: String::new(),
-        body_scroll: 0,
-
-        compose: ComposeState {
-            to: String::new(),
-            subject: String::new(),
-            body: String::new(),
-            quote: String::new(),
-            focus: ComposeField::To,
-        },
-
-        cfg_edit: ConfigEditState::from_config(&config),
-        config_path,
-
-        status: if created {
-            "config.toml created. Fill your credentials and press Ctrl+S to save.".to_string()
-        } else {
-            "Starting...".to_string()
-        },
-
-        config: config.clone(),
-    };
+    let mut app = App::new(config, config_path, keys, created);
 
     if !created {
-        spawn_refresh_list(app.config.clone(), tx.clone());
+        show_cached_list(&mut app);
+        refresh_list(&mut app, &tx);
+        spawn_list_folders(app.config.clone(), tx.clone());
+        restart_idle(&mut app, &tx);
+        refresh_capabilities(&mut app, &tx);
+    }
+    reload_outbox(&mut app);
+    let due = app.outbox.iter().filter(|e| !e.claimed && e.mail.due()).count();
+    let cut_off = app.outbox.iter().filter(|e| e.claimed).count();
+    if let Some(m) = [&app.config.imap, &app.config.smtp].into_iter().find(|m| m.legacy_localhost()) {
+        app.status = format!(
+            "Certificates from {} go unchecked only because it is localhost; add tls_verify = false to keep that, it will be checked by default",
+            m.host
+        );
+    }
+    if let Some(first) = key_warnings.first() {
+        app.status = format!("{first} ({} problem(s) with [keys], see {PANIC_LOG})", key_warnings.len());
+    }
+    if due > 0 {
+        app.status = format!("{due} scheduled mail(s) came due while zenmail was closed; sending now");
+    } else if cut_off > 0 {
+        app.status = format!("{cut_off} scheduled mail(s) may not have been sent; see the outbox (S)");
     }
 
     enable_raw_mode()?;
-    execute!(stdout(), EnterAlternateScreen, crossterm::cursor::Hide)?;
+    execute!(stdout(), EnterAlternateScreen, EnableFocusChange, crossterm::cursor::Hide)?;
     let _guard = TuiGuard;
 
     let backend = CrosstermBackend::new(stdout());
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
+    let mut dirty = true;
+    let mut last_draw = Instant::now();
+    let mut key_at: Option<Instant> = None;
+
     loop {
         while let Ok(msg) = rx.try_recv() {
+            dirty = true;
             match msg {
-                AppMsg::MailList(list) => {
-                    app.messages = list;
-                    if app.messages.is_empty() {
-                        app.selected = 0;
-                    } else {
-                        app.selected = app.selected.min(app.messages.len() - 1);
+                AppMsg::List(ev) => on_list_event(&mut app, ev, &tx),
+                AppMsg::Body(ev) => on_body_event(&mut app, ev, &tx),
+                AppMsg::Net(ev) => on_net_event(&mut app, ev, &tx),
+                AppMsg::Sent { append_error } => {
+                    app.status = match append_error {
+                        Some(e) => format!("Sent, but saving to {} failed: {e}", app.config.folders.sent),
+                        None => "Sent".to_string(),
+                    };
+                    if let Some(path) = app.sending_draft.take() {
+                        if let Err(e) = forget_draft(&app, &path, &tx) {
+                            app.status = format!("Sent, but removing the draft failed: {e}");
+                        }
                     }
-                    app.status = format!("Loaded {} messages", app.messages.len());
-                }
-                AppMsg::MailBody { header, body } => {
-                    app.current_header = Some(header);
-                    app.current_body = body;
-                    app.body_scroll = 0;
-                    app.status = "Mail loaded".to_string();
                 }
+                AppMsg::Outbox(ev) => on_outbox_event(&mut app, ev),
+                AppMsg::Conn(state) => on_conn_state(&mut app, state, &tx),
                 AppMsg::Status(s) => app.status = s,
             }
         }
 
-        terminal.draw(|f| crate::ui::draw(f, &app))?;
+        if app.net.woke_from_suspend() {
+            app.net.go_offline();
+            app.status = "Woke from sleep, reconnecting…".to_string();
+            dirty = true;
+        }
+        if app.poll.due(&app.config.list) && !app.net.offline && !app.auth_failed && app.view != View::Config {
+            poll_list(&mut app, &tx);
+        }
+        if !app.net.offline && send_due_mail(&mut app, &tx) {
+            dirty = true;
+        }
+        if app.net.reconnect_due() {
+            let ctl = Arc::new(ReconnectCtl::default());
+            app.net.reconnect = Some(ctl.clone());
+            spawn_reconnect(app.config.clone(), ctl, tx.clone());
+        }
+
+        // error text from the mail libraries can carry logins, passwords and AUTH data
+        if app.status != app.status_cleaned {
+            app.status = redact::clean(&app.status, &app.config);
+            app.status_cleaned = app.status.clone();
+        }
+
+        // Nothing on screen changes between events except the fetch timer, so idle
+        // iterations skip the frame rebuild.
+        if dirty || app.body_fetch.is_some() || last_draw.elapsed() >= IDLE_REDRAW {
+            let started = Instant::now();
+            terminal.draw(|f| crate::ui::draw(f, &app))?;
+            if let Some(p) = app.profile.as_mut() {
+                p.record_frame(app.view.name(), started.elapsed());
+                if let Some(at) = key_at.take() {
+                    p.record_key(at.elapsed());
+                }
+            }
+            last_draw = Instant::now();
+            dirty = false;
+        }
 
         if event::poll(Duration::from_millis(50))? {
+            dirty = true;
             match event::read()? {
                 Event::Resize(_, _) => {
                     terminal.clear()?;
                     continue;
                 }
+                Event::FocusLost => app.poll.focused = false,
+                Event::FocusGained => app.poll.activity(&app.config.list),
                 Event::Key(k) => {
                     if k.kind != KeyEventKind::Press {
                         continue;
                     }
+                    key_at = Some(Instant::now());
+                    app.poll.activity(&app.config.list);
+
+                    if app.show_help {
+                        // any key closes the overlay; the quit key also quits
+                        app.show_help = false;
+                        if !app.keys.is(Action::Quit, k.code, k.modifiers) {
+                            continue;
+                        }
+                    }
+                    if k.code == KeyCode::F(1) {
+                        app.show_help = true;
+                        continue;
+                    }
+
+                    // the prompt takes every key as text, `q` included
+                    if app.view == View::Auth {
+                        handle_auth_keys(&mut app, k.code, k.modifiers, &tx);
+                        continue;
+                    }
+                    if app.view == View::Mail && app.export_input.is_some() {
+                        handle_export_keys(&mut app, k.code, &tx);
+                        continue;
+                    }
+                    if app.view == View::Searches && app.saved_name_input.is_some() {
+                        handle_saved_search_keys(&mut app, k.code, &tx);
+                        continue;
+                    }
+                    if app.view == View::Groups && app.group_input.is_some() {
+                        handle_group_keys(&mut app, k.code);
+                        continue;
+                    }
 
-                    if k.code == KeyCode::Char('q') {
+                    // where text is typed, `q`, `g` and `?` are letters; F1 still opens the overlay
+                    let typing = matches!(app.view, View::Compose | View::Config)
+                        || app.search_input.is_some()
+                        || app.command_input.is_some();
+                    if app.keys.is(Action::Quit, k.code, k.modifiers) && !typing {
                         break;
                     }
 
-                    if k.code == KeyCode::Char('g') && app.view != View::Config {
+                    if app.keys.is(Action::Help, k.code, k.modifiers) && !typing {
+                        app.show_help = true;
+                        continue;
+                    }
+
+                    if k.code == KeyCode::F(11) && app.view != View::Styles {
+                        app.return_view = app.view;
+                        app.view = View::Styles;
+                        app.status = "Styles".to_string();
+                        continue;
+                    }
+
+                    if app.keys.is(Action::Config, k.code, k.modifiers) && !typing {
                         app.return_view = app.view;
                         app.cfg_edit = ConfigEditState::from_config(&app.config);
                         app.view = View::Config;
@@ -230,9 +972,23 @@ pub async fn run() -> Result<()> {
 
                     match app.view {
                         View::List => handle_list_keys(&mut app, k.code, k.modifiers, &tx),
+                        View::Folders => handle_folder_keys(&mut app, k.code, &tx),
+                        View::Drafts => handle_draft_keys(&mut app, k.code, &tx),
                         View::Mail => handle_mail_keys(&mut app, k.code, k.modifiers, &tx),
-                        View::Compose => handle_compose_keys(&mut app, k.code, k.modifiers, &tx),
+                        View::Compose => handle_compose_keys(&mut app, k.code, k.modifiers, &tx, &mut terminal),
                         View::Config => handle_config_keys(&mut app, k.code, k.modifiers, &tx, &mut terminal),
+                        View::Auth => {}
+                        View::Capabilities => handle_capabilities_keys(&mut app, k.code, &tx),
+                        View::Outbox => handle_outbox_keys(&mut app, k.code),
+                        View::Styles if k.code == KeyCode::Esc => {
+                            app.view = app.return_view;
+                            app.status = "Back".to_string();
+                        }
+                        View::Styles => {}
+                        View::Diff => handle_diff_keys(&mut app, k.code),
+                        View::Searches => handle_saved_search_keys(&mut app, k.code, &tx),
+                        View::Health => handle_capabilities_keys(&mut app, k.code, &tx),
+                        View::Groups => handle_group_keys(&mut app, k.code),
                     }
                 }
                 _ => {}
@@ -240,275 +996,3245 @@ pub async fn run() -> Result<()> {
         }
     }
 
-    Ok(())
-}
+    app.status = "Quitting…".to_string();
+    let _ = terminal.draw(|f| crate::ui::draw(f, &app));
+    shutdown(&mut app);
 
-fn spawn_refresh_list(config: Config, tx: mpsc::UnboundedSender<AppMsg>) {
-    let _ = tx.send(AppMsg::Status("Fetching mail list...".to_string()));
-    tokio::task::spawn_blocking(move || match mail::imap::fetch_summaries(&config.imap, 50) {
-        Ok(list) => {
-            let _ = tx.send(AppMsg::MailList(list));
-        }
-        Err(e) => {
-            let _ = tx.send(AppMsg::Status(format!("IMAP list error: {e}")));
-        }
-    });
+    Ok(())
 }
 
-fn spawn_fetch_body(config: Config, header: MessageSummary, tx: mpsc::UnboundedSender<AppMsg>) {
-    let _ = tx.send(AppMsg::Status(format!("Fetching body (uid={})...", header.uid)));
-    tokio::task::spawn_blocking(move || match mail::imap::fetch_body_plain(&config.imap, header.uid) {
-        Ok(body) => {
-            let _ = tx.send(AppMsg::MailBody { header, body });
-        }
-        Err(e) => {
-            let _ = tx.send(AppMsg::Status(format!("IMAP body error: {e}")));
-        }
-    });
-}
+// Waiting longer than this for the last writes leaves them unfinished rather than
+// keep the terminal captured behind a hung disk.
+const SHUTDOWN_LIMIT: Duration = Duration::from_secs(3);
 
-fn spawn_send_mail(
-    config: Config,
-    to: String,
-    subject: String,
-    body: String,
-    tx: mpsc::UnboundedSender<AppMsg>,
-) {
-    let _ = tx.send(AppMsg::Status("Sending...".to_string()));
-    tokio::task::spawn_blocking(move || match mail::smtp::send(&config.smtp, &config.user, &to, &subject, &body) {
-        Ok(_) => {
-            let _ = tx.send(AppMsg::Status("Sent".to_string()));
-        }
-        Err(e) => {
-            let _ = tx.send(AppMsg::Status(format!("SMTP error: {e}")));
-        }
-    });
-}
+type ShutdownStep = (&'static str, Box<dyn FnOnce() -> Result<()> + Send>);
 
-fn reset_compose_new(app: &mut App) {
-    app.compose.to.clear();
-    app.compose.subject.clear();
-    app.compose.body.clear();
-    app.compose.quote.clear();
-    app.compose.focus = ComposeField::To;
-}
+// Quitting: background work is told to stop, then what only lives in memory is
+// written out, in order, on a thread of its own. Steps that fail or don't finish in
+// time are listed in `zenmail.log`. A send already in progress still completes: the
+// runtime waits for it after the terminal is restored.
+fn shutdown(app: &mut App) {
+    if let Some(stop) = app.idle_stop.take() {
+        stop.store(true, Ordering::Relaxed);
+    }
+    app.net.stop_reconnect();
 
-fn compose_full_body(c: &ComposeState) -> String {
-    let body = c.body.trim_end().to_string();
-    let quote = c.quote.trim_end().to_string();
+    let mut steps: Vec<ShutdownStep> = vec![];
+    // Compose itself doesn't quit, but Styles (F11) opened over it does; what was
+    // typed is kept as a draft
+    let composing = app.view == View::Compose || app.return_view == View::Compose;
+    if composing && compose_has_content(&app.compose) {
+        let (draft, path) = (compose_draft(&app.compose), app.compose.draft_path.clone());
+        steps.push(("saving the mail being written as a draft", Box::new(move || {
+            drafts::save(&draft, path.as_deref()).map(|_| ())
+        })));
+    }
+    if let Some(uid_validity) = app.uid_validity.filter(|_| app.search.is_none()) {
+        // read flags changed since the last save
+        let (config, mailbox, list) = (app.config.clone(), app.current_folder.clone(), app.messages.clone());
+        steps.push(("saving the list cache", Box::new(move || cache::save_list(&config, &mailbox, uid_validity, &list))));
+    }
+    if let Some(p) = app.profile.take() {
+        steps.push(("writing the profile report", Box::new(move || p.write_report().map(|_| ()))));
+    }
+    steps.push(("removing extracted images", Box::new(|| match std::fs::remove_dir_all(image_temp_dir()) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    })));
+    steps.push(("logging out of the mail server", Box::new(|| {
+        mail::imap::close_sessions();
+        Ok(())
+    })));
 
-    if quote.is_empty() {
-        body
-    } else if body.is_empty() {
-        quote
-    } else {
-        format!("{body}\n\n{quote}")
+    let problems = run_steps(steps, SHUTDOWN_LIMIT);
+    if !problems.is_empty() {
+        let _ = write_log(&app.config, "quit", &problems);
     }
 }
 
-fn extract_reply_to(from: &str) -> String {
-    let s = from.trim();
-
-    if let Some(l) = s.find('<') {
-        if let Some(r) = s[l + 1..].find('>') {
-            let addr = s[l + 1..l + 1 + r].trim();
-            if !addr.is_empty() {
-                return addr.to_string();
-            }
+// Runs `steps` in order on a thread of their own, giving up after `limit`; what
+// failed or didn't get to finish, one line each.
+fn run_steps(steps: Vec<ShutdownStep>, limit: Duration) -> Vec<String> {
+    let names: Vec<&str> = steps.iter().map(|(name, _)| *name).collect();
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for (name, step) in steps {
+            let _ = done_tx.send((name, step()));
         }
-    }
+    });
 
-    for tok in s.split_whitespace() {
-        let t = tok.trim_matches(|c: char| c == '<' || c == '>' || c == ',' || c == ';');
-        if t.contains('@') {
-            return t.to_string();
+    let deadline = Instant::now() + limit;
+    let mut problems = vec![];
+    let mut finished = 0;
+    while finished < names.len() {
+        match done_rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok((_, Ok(()))) => finished += 1,
+            Ok((name, Err(e))) => {
+                finished += 1;
+                problems.push(format!("{name} failed: {e}"));
+            }
+            Err(_) => {
+                for name in &names[finished..] {
+                    problems.push(format!("{name} didn't finish within {}s", limit.as_secs()));
+                }
+                break;
+            }
         }
     }
-
-    s.to_string()
-}
-
-fn make_reply_subject(subject: &str) -> String {
-    let s = subject.trim();
-    if s.is_empty() {
-        "Re:".to_string()
-    } else if s.to_ascii_lowercase().starts_with("re:") {
-        s.to_string()
-    } else {
-        format!("Re: {s}")
-    }
+    problems
 }
 
-fn quote_lines(text: &str) -> String {
-    let mut out = String::new();
-    for (i, line) in text.replace("\r\n", "\n").lines().enumerate() {
+fn on_list_event(app: &mut App, ev: ListEvent, tx: &mpsc::UnboundedSender<AppMsg>) {
+    match ev {
+        ListEvent::Loaded { mailbox, uid_validity, list, rights } => {
+            app.net.record_success();
+            if let Some(folder) = app.folders.iter_mut().find(|f| f.name == mailbox) {
+                folder.rights = rights.clone();
+            }
+            app.rights.insert(mailbox.clone(), rights);
+            if app.refreshing.as_ref() == Some(&mailbox) {
+                app.refreshing = None;
+            }
+            app.health.synced(&mailbox);
+            remember_senders(app, &list);
+            // the list just came in, so the network is up
+            if app.config.check_updates && !app.update_checked {
+                app.update_checked = true;
+                spawn_update_check(tx);
+            }
+            if mailbox != app.current_folder {
+                return;
+            }
+            app.loading_more = false;
+            // the same mail stays selected when the list only confirms the cached rows
+            let selected_uid = app.messages.get(app.selected).filter(|_| app.search.is_none()).map(|m| m.uid);
+            app.search = None;
+            app.uid_validity = Some(uid_validity);
+            app.cached_validity = None;
+            app.last_uid = list.iter().map(|m| m.uid).max().unwrap_or(0);
+            app.messages = list;
+            app.marked.retain(|uid| app.messages.iter().any(|m| m.uid == *uid));
+            app.sort_messages();
+            if let Some(i) = selected_uid.and_then(|uid| app.messages.iter().position(|m| m.uid == uid)) {
+                app.selected = i;
+            } else if app.messages.is_empty() {
+                app.selected = 0;
+            } else {
+                app.selected = app.selected.min(app.messages.len() - 1);
+            }
+            app.clamp_selection();
+            app.status = format!("Loaded {} messages", app.messages.len());
+            save_list_cache(app, tx);
+        }
+        ListEvent::Searched { mailbox, query, total, list } => {
+            app.net.record_success();
+            if mailbox != app.current_folder {
+                return;
+            }
+            app.status = match total {
+                0 => format!("No messages match {query:?}"),
+                n if n > list.len() => format!("{n} matches, showing the newest {} (Esc returns to the folder)", list.len()),
+                n => format!("{n} matches (Esc returns to the folder)"),
+            };
+            // the server's rows, so changes to them are safe again
+            app.messages = list;
+            app.cached_validity = None;
+            app.marked.clear();
+            app.sort_messages();
+            app.selected = app.newest_index();
+            app.search = Some(query);
+            app.clamp_selection();
+        }
+        ListEvent::Older { mailbox, uid_validity, list } => {
+            app.net.record_success();
+            remember_senders(app, &list);
+            if mailbox != app.current_folder || app.uid_validity != Some(uid_validity) || app.search.is_some() {
+                return;
+            }
+            app.loading_more = false;
+            let selected_uid = app.messages.get(app.selected).map(|m| m.uid);
+            let older: Vec<_> = list.into_iter().filter(|m| !app.messages.iter().any(|x| x.uid == m.uid)).collect();
+            let added = older.len();
+            app.messages.splice(0..0, older);
+            app.sort_messages();
+            if let Some(i) = selected_uid.and_then(|uid| app.messages.iter().position(|m| m.uid == uid)) {
+                app.selected = i;
+            }
+            app.clamp_selection();
+            app.status = match added {
+                0 => "No older messages".to_string(),
+                n => format!("Loaded {n} older message(s)"),
+            };
+            if added > 0 {
+                save_list_cache(app, tx);
+            }
+        }
+        ListEvent::Appended { mailbox, list, quiet } => {
+            app.net.record_success();
+            if app.refreshing.as_ref() == Some(&mailbox) {
+                app.refreshing = None;
+            }
+            app.health.synced(&mailbox);
+            app.poll.refreshed(&app.config.list, quiet, list.len());
+            remember_senders(app, &list);
+            // new mail need not match the search; it shows up once the search is left
+            if mailbox != app.current_folder || app.search.is_some() {
+                return;
+            }
+            let selected_uid = app.messages.get(app.selected).map(|m| m.uid);
+            let before = app.messages.len();
+            for m in list {
+                app.last_uid = app.last_uid.max(m.uid);
+                if !app.messages.iter().any(|x| x.uid == m.uid) {
+                    app.messages.push(m);
+                }
+            }
+            let added = app.messages.len() - before;
+            app.sort_messages();
+            if let Some(i) = selected_uid.and_then(|uid| app.messages.iter().position(|m| m.uid == uid)) {
+                app.selected = i;
+            }
+            app.clamp_selection();
+            match added {
+                0 if quiet => {}
+                0 => app.status = "No new mail".to_string(),
+                n => app.status = format!("{n} new message(s)"),
+            }
+            if added > 0 {
+                save_list_cache(app, tx);
+            }
+        }
+        ListEvent::Failed(e) => {
+            app.loading_more = false;
+            app.refreshing = None;
+            app.health.error("list", &e);
+            if app.net.record_failure() || app.net.offline {
+                app.status = "Connection lost, reconnecting…".to_string();
+            } else {
+                app.status = format!("IMAP list error: {e}");
+            }
+        }
+        ListEvent::ArchivePlan(plan) => {
+            // moved on in the meantime: asking now would be about something else
+            if plan.mailbox != app.current_folder || app.view != View::List {
+                app.status = "Archive by year canceled".to_string();
+                return;
+            }
+            let count: usize = plan.moves.iter().map(|(_, uids)| uids.len()).sum();
+            let dests: Vec<String> = plan.moves.iter().map(|(dest, uids)| format!("{dest} ({})", uids.len())).collect();
+            let undated = match plan.undated {
+                0 => String::new(),
+                n => format!(" ({n} without a readable date stay)"),
+            };
+            app.status = format!("Archive {count} message(s) to {}?{undated} y/n", dests.join(" · "));
+            app.confirm_archive = Some(plan);
+        }
+        ListEvent::Compared(diff) => {
+            // only if nothing else was opened meanwhile
+            if app.view == View::List {
+                app.status = format!("{} line(s) differ", diff.lines.iter().filter(|(c, _)| *c != diff::Change::Same).count());
+                app.diff = Some(*diff);
+                app.view = View::Diff;
+            }
+        }
+        ListEvent::Crashed(e) => {
+            app.loading_more = false;
+            app.refreshing = None;
+            app.health.error("list", &e);
+            app.status = e;
+        }
+        ListEvent::Removed { mailbox, uids, status } => {
+            app.removing.retain(|(mb, _, m)| !(*mb == mailbox && uids.contains(&m.uid)));
+            app.status = status;
+        }
+        ListEvent::RemoveFailed { mailbox, uids, error } => {
+            // put rows back in reverse so each lands at the index it was taken from
+            for &uid in uids.iter().rev() {
+                restore_message(app, &mailbox, uid);
+            }
+            app.status = error;
+        }
+        ListEvent::Folders(mut folders) => {
+            // rights are only known for folders opened so far
+            for folder in &mut folders {
+                folder.rights = app.rights.get(&folder.name).cloned().flatten();
+            }
+            app.folders = folders;
+        }
+        ListEvent::NewMail => {
+            if app.current_folder == "INBOX" && !app.auth_failed {
+                poll_list(app, tx);
+            }
+        }
+    }
+}
+
+fn on_body_event(app: &mut App, ev: BodyEvent, tx: &mpsc::UnboundedSender<AppMsg>) {
+    match ev {
+        BodyEvent::Loaded { header, body } => {
+            if !body_fetch_is(app, header.uid) {
+                return;
+            }
+            app.body_fetch = None;
+            if !header.seen && not_permitted(app, 's').is_none() {
+                spawn_set_seen(app.config.clone(), app.current_folder.clone(), header.uid, true, tx.clone());
+                if let Some(m) = app.messages.iter_mut().find(|m| m.uid == header.uid) {
+                    m.seen = true;
+                }
+            }
+            app.current_delivery = mail::delivery(&app.config.user.email, &app.config.user.aliases, &header, &body);
+            app.current_header = Some(*header.clone());
+            app.body_was_html = body.was_html;
+            app.current_message_id = body.message_id;
+            app.current_references = body.references;
+            app.current_raw = body.raw;
+            app.status = "Mail loaded".to_string();
+            set_body_text(app, body.text);
+            app.attachments = body.attachments;
+            app.attachment_selected = 0;
+            // reading the cached copy already: stay where the reader is
+            if !std::mem::take(&mut app.body_from_cache) {
+                app.body_scroll = 0;
+            }
+            match app.pending_compose.take() {
+                Some((uid, PendingCompose::Reply)) if uid == header.uid => {
+                    let body = app.quote_body.clone();
+                    compose_reply(app, &header, &body);
+                }
+                Some((uid, PendingCompose::Forward)) if uid == header.uid => start_forward(app, false),
+                _ => {}
+            }
+        }
+        BodyEvent::Progress { uid, progress } => {
+            if let Some(f) = app.body_fetch.as_mut().filter(|f| f.uid == uid) {
+                f.progress = progress;
+            }
+        }
+        BodyEvent::Failed { uid, error } => {
+            if !body_fetch_is(app, uid) {
+                return;
+            }
+            app.body_fetch = None;
+            app.pending_compose = None;
+            app.health.error("body", &error);
+            if app.body_from_cache {
+                app.status = format!("Showing the copy kept on disk (IMAP body error: {error})");
+                return;
+            }
+            app.current_body = String::new();
+            app.body_rows.get_mut().clear();
+            app.other_body = None;
+            app.status = format!("IMAP body error: {error}");
+        }
+    }
+}
+
+fn on_conn_state(app: &mut App, state: ConnState, tx: &mpsc::UnboundedSender<AppMsg>) {
+    // a late report from a worker that was already told to stop
+    if !app.net.offline {
+        return;
+    }
+    match state {
+        ConnState::Connected => {
+            app.net.record_success();
+            app.status = "Reconnected".to_string();
+            refresh_list(app, tx);
+        }
+        ConnState::Offline if app.net.state != ConnState::Offline => {
+            app.net.state = state;
+            app.status = format!("Offline; retrying every {}s ({} retries now)", RECONNECT_BACKOFF_MAX.as_secs(), app.keys.label(Action::Refresh));
+        }
+        _ => app.net.state = state,
+    }
+}
+
+fn on_net_event(app: &mut App, ev: NetEvent, tx: &mpsc::UnboundedSender<AppMsg>) {
+    match ev {
+        NetEvent::AuthFailed(e) => {
+            app.loading_more = false;
+            app.refreshing = None;
+            app.health.error("login", &e);
+            if !app.auth_failed {
+                app.auth_failed = true;
+                app.auth_input.clear();
+            }
+            // never pull a half-written mail out from under the user
+            if app.view != View::Compose {
+                app.view = View::Auth;
+            }
+            app.status = format!("IMAP {e}");
+        }
+        NetEvent::AuthChecked(result) => {
+            app.auth_checking = false;
+            match result {
+                Ok(()) => {
+                    app.auth_failed = false;
+                    app.auth_input.clear();
+                    app.view = View::List;
+                    app.status = "Logged in".to_string();
+                    if std::mem::take(&mut app.auth_remember) {
+                        keep_login_in_keyring(app);
+                    }
+                    refresh_list(app, tx);
+                    restart_idle(app, tx);
+                    refresh_capabilities(app, tx);
+                }
+                Err(e) => {
+                    app.auth_input.clear();
+                    app.auth_remember = false;
+                    app.status = format!("IMAP {e}");
+                }
+            }
+        }
+        NetEvent::Capabilities(caps) => app.server_caps = Some(caps),
+        NetEvent::Quota(quota) => app.health.quota = quota,
+    }
+}
+
+fn toggle_profile(app: &mut App) {
+    app.status = match app.profile.take() {
+        None => {
+            app.profile = Some(Profiler::default());
+            "Profiling on (:profile again to stop)".to_string()
+        }
+        Some(p) => match p.write_report() {
+            Ok(path) => format!("{} · report in {}", p.summary(), path.display()),
+            Err(e) => format!("{} · report error: {e}", p.summary()),
+        },
+    };
+}
+
+// Fetches only mail newer than what is loaded once the folder has been listed.
+// A refresh of the folder while one is still on its way is dropped rather than
+// queued behind it: the pending one brings the same mail.
+fn refresh_list(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if app.auth_failed {
+        // back to the prompt instead of another rejected login
+        let _ = tx.send(AppMsg::Net(NetEvent::AuthFailed("login rejected, enter the password again".to_string())));
+        return;
+    }
+    if app.net.offline {
+        // the list follows once the server answers again
+        app.net.retry_now();
+        app.status = "Reconnecting…".to_string();
+        return;
+    }
+    if app.refreshing.as_ref() == Some(&app.current_folder) {
+        app.status = format!("Still checking {}…", app.current_folder);
+        return;
+    }
+    app.refreshing = Some(app.current_folder.clone());
+    match app.uid_validity {
+        Some(v) => spawn_refresh_since(app.config.clone(), app.current_folder.clone(), app.last_uid, v, false, previews(app), tx.clone()),
+        None => spawn_refresh_list(app.config.clone(), app.current_folder.clone(), previews(app), tx.clone()),
+    }
+}
+
+fn poll_list(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if app.refreshing.as_ref() == Some(&app.current_folder) {
+        return;
+    }
+    if let Some(v) = app.uid_validity {
+        app.refreshing = Some(app.current_folder.clone());
+        spawn_refresh_since(app.config.clone(), app.current_folder.clone(), app.last_uid, v, true, previews(app), tx.clone());
+    }
+}
+
+const PAGE: usize = 50;
+
+const PANIC_LOG: &str = "zenmail.log";
+
+// Runs `job` on the blocking pool. Should it panic, `on_panic` turns the message into
+// the event that ends the operation, so its status and in-flight state are cleared
+// instead of waiting forever for a reply that never comes.
+fn spawn_worker<F, J>(tx: mpsc::UnboundedSender<AppMsg>, what: &'static str, on_panic: F, job: J)
+where
+    F: FnOnce(String) -> AppMsg + Send + 'static,
+    J: FnOnce(&mpsc::UnboundedSender<AppMsg>) + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || catch_panic(&tx, what, on_panic, job));
+}
+
+fn catch_panic<F, J>(tx: &mpsc::UnboundedSender<AppMsg>, what: &str, on_panic: F, job: J)
+where
+    F: FnOnce(String) -> AppMsg,
+    J: FnOnce(&mpsc::UnboundedSender<AppMsg>),
+{
+    if let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job(tx))) {
+        let msg = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        let _ = tx.send(on_panic(format!("{what} crashed: {msg} (details in {PANIC_LOG})")));
+    }
+}
+
+// Panics go to `zenmail.log` next to the config file, with a backtrace. Only the main
+// thread's still reach stderr: a worker's would be drawn over the screen, and
+// `catch_panic` already reports it in the status line.
+fn log_panics() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = write_panic_log(info);
+        if std::thread::current().name() == Some("main") {
+            default(info);
+        }
+    }));
+}
+
+fn open_log() -> Result<std::fs::File> {
+    let cfg = Config::path()?;
+    let path = cfg.parent().ok_or_else(|| anyhow!("no config dir"))?.join(PANIC_LOG);
+    Ok(std::fs::OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+// Lines may quote server errors, so they are redacted like the status line.
+fn write_log(config: &Config, what: &str, lines: &[String]) -> Result<()> {
+    use std::io::Write;
+
+    let mut f = open_log()?;
+    writeln!(f, "--- {} {what} ---", Local::now().format("%Y-%m-%d %H:%M:%S"))?;
+    for line in lines {
+        writeln!(f, "{}", redact::clean(line, config))?;
+    }
+    Ok(())
+}
+
+fn write_panic_log(info: &std::panic::PanicHookInfo) -> Result<()> {
+    use std::io::Write;
+
+    let mut f = open_log()?;
+    let thread = std::thread::current();
+    writeln!(f, "--- {} panic in thread {} ---", Local::now().format("%Y-%m-%d %H:%M:%S"), thread.name().unwrap_or("?"))?;
+    writeln!(f, "{info}")?;
+    writeln!(f, "{}", std::backtrace::Backtrace::force_capture())?;
+    Ok(())
+}
+
+// Only the relaxed density shows a preview line, so only it pays for fetching one.
+fn previews(app: &App) -> bool {
+    app.density == Density::Relaxed
+}
+
+fn full_list(config: &Config, mailbox: String, previews: bool) -> AppMsg {
+    match mail::imap::fetch_summaries(&config.imap, &mailbox, 0, PAGE, previews) {
+        Ok((uid_validity, list)) => {
+            // asked again with every full listing, so a changed ACL shows up too
+            let rights = mail::imap::folder_rights(&config.imap, &mailbox).unwrap_or(None);
+            AppMsg::List(ListEvent::Loaded { mailbox, uid_validity, list, rights })
+        }
+        Err(e) => list_failed(e, &config.imap),
+    }
+}
+
+// Rows still waiting for their delete/move to finish are on the server too, so they
+// count towards how many of the newest messages are already loaded.
+fn load_older(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if app.loading_more {
+        return;
+    }
+    if app.search.is_some() {
+        app.status = "Showing search results (Esc returns to the folder)".to_string();
+        return;
+    }
+    let Some(uid_validity) = app.uid_validity else {
+        app.status = "List is still loading".to_string();
+        return;
+    };
+    let offset = app.messages.len() + app.removing.iter().filter(|(mb, _, _)| *mb == app.current_folder).count();
+    app.loading_more = true;
+    app.status = "Loading more…".to_string();
+
+    let (config, mailbox, previews) = (app.config.clone(), app.current_folder.clone(), previews(app));
+    spawn_worker(tx.clone(), "Loading more", |e| AppMsg::List(ListEvent::Crashed(e)), move |tx| {
+        let msg = match mail::imap::fetch_summaries(&config.imap, &mailbox, offset, PAGE, previews) {
+            Ok((v, list)) if v == uid_validity => AppMsg::List(ListEvent::Older { mailbox, uid_validity: v, list }),
+            // UIDVALIDITY changed underneath: the loaded rows are stale, start over
+            Ok(_) => full_list(&config, mailbox, previews),
+            Err(e) => list_failed(e, &config.imap),
+        };
+        let _ = tx.send(msg);
+    });
+}
+
+const SEARCH_LIMIT: usize = 200;
+
+fn spawn_search(config: Config, mailbox: String, query: String, previews: bool, tx: mpsc::UnboundedSender<AppMsg>) {
+    let _ = tx.send(AppMsg::Status(format!("Searching {mailbox}...")));
+    spawn_worker(tx, "Search", AppMsg::Status, move |tx| {
+        let msg = match mail::imap::search_messages(&config.imap, &mailbox, &query, SEARCH_LIMIT, previews) {
+            Ok((total, list)) => AppMsg::List(ListEvent::Searched { mailbox, query, total, list }),
+            Err(e) if e.is::<mail::imap::AuthError>() => list_failed(e, &config.imap),
+            Err(e) => AppMsg::Status(format!("IMAP search error: {e}")),
+        };
+        let _ = tx.send(msg);
+    });
+}
+
+fn handle_search_input(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let Some(input) = app.search_input.as_mut() else {
+        return;
+    };
+    match code {
+        KeyCode::Esc => {
+            app.search_input = None;
+            app.status = "Search canceled".to_string();
+        }
+        KeyCode::Enter => {
+            let query = input.trim().to_string();
+            app.search_input = None;
+            app.saved_search = None;
+            if query.is_empty() {
+                app.status = "Search canceled".to_string();
+            } else {
+                spawn_search(app.config.clone(), app.current_folder.clone(), query, previews(app), tx.clone());
+            }
+        }
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        _ => {}
+    }
+}
+
+// `:` in the list: commands too rare to spend a key on.
+fn handle_command_input(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let Some(input) = app.command_input.as_mut() else {
+        return;
+    };
+    match code {
+        KeyCode::Esc => {
+            app.command_input = None;
+            app.status.clear();
+        }
+        KeyCode::Enter => {
+            let command = input.trim().to_string();
+            app.command_input = None;
+            match command.as_str() {
+                "" => app.status.clear(),
+                "profile" => toggle_profile(app),
+                "capabilities" => {
+                    if app.server_caps.is_none() {
+                        refresh_capabilities(app, tx);
+                    }
+                    app.view = View::Capabilities;
+                    app.status = "Capabilities".to_string();
+                }
+                "groups" => open_groups(app),
+                other => app.status = format!("Unknown command :{other} (try :profile, :capabilities or :groups)"),
+            }
+        }
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        _ => {}
+    }
+}
+
+// A rejected login is not a network problem: it goes to the password prompt instead
+// of the reconnect backoff, which would only keep retrying the same password.
+fn list_failed(e: anyhow::Error, cfg: &MailConfig) -> AppMsg {
+    if e.is::<mail::imap::AuthError>() {
+        AppMsg::Net(NetEvent::AuthFailed(e.to_string()))
+    } else {
+        AppMsg::List(ListEvent::Failed(mail::imap::explain(e, cfg).to_string()))
+    }
+}
+
+fn spawn_refresh_list(config: Config, mailbox: String, previews: bool, tx: mpsc::UnboundedSender<AppMsg>) {
+    let _ = tx.send(AppMsg::Status(format!("Fetching {mailbox}...")));
+    spawn_worker(tx, "Fetching the list", |e| AppMsg::List(ListEvent::Crashed(e)), move |tx| {
+        let _ = tx.send(full_list(&config, mailbox, previews));
+    });
+}
+
+// A `quiet` check (the background poll) only touches the status line when mail arrived.
+fn spawn_refresh_since(
+    config: Config,
+    mailbox: String,
+    last_uid: u32,
+    uid_validity: u32,
+    quiet: bool,
+    previews: bool,
+    tx: mpsc::UnboundedSender<AppMsg>,
+) {
+    if !quiet {
+        let _ = tx.send(AppMsg::Status(format!("Checking {mailbox}...")));
+    }
+    spawn_worker(tx, "Checking for mail", |e| AppMsg::List(ListEvent::Crashed(e)), move |tx| {
+        let msg = match mail::imap::fetch_summaries_since(&config.imap, &mailbox, last_uid, uid_validity, previews) {
+            Ok(Some(list)) => AppMsg::List(ListEvent::Appended { mailbox, list, quiet }),
+            // UIDVALIDITY changed: the UIDs we hold mean nothing any more
+            Ok(None) => full_list(&config, mailbox, previews),
+            Err(e) => list_failed(e, &config.imap),
+        };
+        let _ = tx.send(msg);
+    });
+}
+
+fn spawn_delete(config: Config, mailbox: String, uids: Vec<u32>, tx: mpsc::UnboundedSender<AppMsg>) {
+    let what = match uids.as_slice() {
+        [uid] => format!("uid={uid}"),
+        _ => format!("{} messages", uids.len()),
+    };
+    let _ = tx.send(AppMsg::Status(format!("Deleting ({what})...")));
+    let (mb, ids) = (mailbox.clone(), uids.clone());
+    let on_panic = |error| AppMsg::List(ListEvent::RemoveFailed { mailbox: mb, uids: ids, error });
+    spawn_worker(tx, "Delete", on_panic, move |tx| {
+        let mut sorted = uids.clone();
+        sorted.sort_unstable();
+        let msg = match mail::imap::delete_message(&config.imap, &mailbox, &sorted, config.folders.trash.as_deref()) {
+            Ok(_) => AppMsg::List(ListEvent::Removed { mailbox, uids, status: "Deleted".to_string() }),
+            Err(e) => AppMsg::List(ListEvent::RemoveFailed { mailbox, uids, error: format!("IMAP delete error: {e}") }),
+        };
+        let _ = tx.send(msg);
+    });
+}
+
+fn spawn_set_seen(config: Config, mailbox: String, uid: u32, seen: bool, tx: mpsc::UnboundedSender<AppMsg>) {
+    spawn_worker(tx, "Setting flags", AppMsg::Status, move |tx| {
+        let res = if seen {
+            mail::imap::mark_seen(&config.imap, &mailbox, uid)
+        } else {
+            mail::imap::mark_unseen(&config.imap, &mailbox, uid)
+        };
+        if let Err(e) = res {
+            let _ = tx.send(AppMsg::Status(format!("IMAP flag error: {e}")));
+        }
+    });
+}
+
+fn spawn_list_folders(config: Config, tx: mpsc::UnboundedSender<AppMsg>) {
+    spawn_worker(tx, "Listing folders", AppMsg::Status, move |tx| match mail::imap::list_mailboxes(&config.imap) {
+        Ok(folders) => {
+            let _ = tx.send(AppMsg::List(ListEvent::Folders(folders)));
+        }
+        Err(e) => {
+            let _ = tx.send(AppMsg::Status(format!("IMAP folder list error: {e}")));
+        }
+    });
+}
+
+fn spawn_check_login(config: Config, tx: mpsc::UnboundedSender<AppMsg>) {
+    let _ = tx.send(AppMsg::Status("Logging in...".to_string()));
+    spawn_worker(tx, "Login", |e| AppMsg::Net(NetEvent::AuthChecked(Err(e))), move |tx| {
+        let result = mail::imap::check_login(&config.imap).map_err(|e| e.to_string());
+        let _ = tx.send(AppMsg::Net(NetEvent::AuthChecked(result)));
+    });
+}
+
+// Forgets what the last connection advertised and asks the server again.
+fn refresh_capabilities(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    app.server_caps = None;
+    if app.auth_failed {
+        return;
+    }
+    let config = app.config.clone();
+    spawn_worker(tx.clone(), "Asking for capabilities", AppMsg::Status, move |tx| {
+        // a failure leaves them unknown; opening the capabilities view asks again
+        if let Ok(caps) = mail::imap::capabilities(&config.imap) {
+            let _ = tx.send(AppMsg::Net(NetEvent::Capabilities(caps)));
+        }
+    });
+}
+
+// Counts the local drafts and asks for the INBOX quota each time the health view opens.
+fn open_health(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if app.server_caps.is_none() {
+        refresh_capabilities(app, tx);
+    }
+    app.health.drafts = drafts::list().ok().map(|list| list.len());
+    if !app.auth_failed {
+        let config = app.config.clone();
+        spawn_worker(tx.clone(), "Asking for the quota", AppMsg::Status, move |tx| {
+            if let Ok(quota) = mail::imap::inbox_quota(&config.imap) {
+                let _ = tx.send(AppMsg::Net(NetEvent::Quota(quota)));
+            }
+        });
+    }
+    app.view = View::Health;
+    app.status = "Account health".to_string();
+}
+
+// Why the named feature can't be used right now (see `features::matrix`).
+fn feature_unavailable(app: &App, name: &str) -> Option<String> {
+    features::unavailable(&app.config, app.server_caps.as_deref(), &app.folders, name)
+}
+
+// Why the open folder's ACL rules out a change needing `right`, checked before the
+// list changes on screen rather than after the server refuses.
+fn not_permitted(app: &App, right: char) -> Option<String> {
+    // cached UIDs may name other mail if the folder changed since; wait for the server's
+    if app.cached_validity.is_some() {
+        return Some(format!("{} is still listed from the cache", app.current_folder));
+    }
+    let folder = app.folders.iter().find(|f| f.name == app.current_folder)?;
+    if folder.may(right) {
+        None
+    } else if folder.read_only() {
+        Some(format!("{} is read-only for you", folder.display))
+    } else {
+        Some(format!("your rights on {} don't allow it", folder.display))
+    }
+}
+
+const IDLE_BACKOFF_MIN: Duration = Duration::from_secs(5);
+const IDLE_BACKOFF_MAX: Duration = Duration::from_secs(300);
+
+// Ends any running IDLE connection and, when `[list] idle` is on, starts a new one
+// with the current config.
+fn restart_idle(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if let Some(stop) = app.idle_stop.take() {
+        stop.store(true, Ordering::Relaxed);
+    }
+    if app.config.list.idle && !app.auth_failed {
+        app.idle_stop = Some(spawn_idle(app.config.clone(), tx.clone()));
+    }
+}
+
+// A plain thread rather than spawn_blocking: it runs for the whole session, and the
+// runtime would otherwise wait for it on quit. Dropped connections are retried with
+// a growing delay.
+fn spawn_idle(config: Config, tx: mpsc::UnboundedSender<AppMsg>) -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    std::thread::spawn(move || catch_panic(&tx, "IDLE", AppMsg::Status, |tx| {
+        let mut backoff = IDLE_BACKOFF_MIN;
+        while !flag.load(Ordering::Relaxed) {
+            let started = Instant::now();
+            match mail::imap::idle(&config.imap, "INBOX", &flag, || {
+                let _ = tx.send(AppMsg::List(ListEvent::NewMail));
+            }) {
+                Ok(true) => return,
+                Ok(false) => {
+                    let _ = tx.send(AppMsg::Status("Server does not support IDLE".to_string()));
+                    return;
+                }
+                Err(e) if e.is::<mail::imap::AuthError>() => {
+                    let _ = tx.send(AppMsg::Net(NetEvent::AuthFailed(e.to_string())));
+                    return;
+                }
+                Err(_) => {
+                    // a connection that held for a while was fine; start over from the short delay
+                    if started.elapsed() > IDLE_BACKOFF_MAX {
+                        backoff = IDLE_BACKOFF_MIN;
+                    }
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(IDLE_BACKOFF_MAX);
+                }
+            }
+        }
+    }));
+    stop
+}
+
+// Only a newer release is reported; failures stay quiet, it is not worth an error.
+fn spawn_update_check(tx: &mpsc::UnboundedSender<AppMsg>) {
+    spawn_worker(tx.clone(), "Update check", AppMsg::Status, |tx| {
+        if let Ok(Some(release)) = crate::update::check() {
+            let _ = tx.send(AppMsg::Status(format!("zenmail {} is available: {}", release.version, release.url)));
+        }
+    });
+}
+
+// Probes the server until it answers: at once, then after 1s, 2s, 4s… up to a minute
+// apart. Every attempt is reported, so the list title can show it. A crashed worker
+// drops its handle and `reconnect_due` starts another.
+fn spawn_reconnect(config: Config, ctl: Arc<ReconnectCtl>, tx: mpsc::UnboundedSender<AppMsg>) {
+    spawn_worker(tx, "Reconnecting", |_| AppMsg::Conn(ConnState::Offline), move |tx| {
+        let mut backoff = RECONNECT_BACKOFF_MIN;
+        for attempt in 1.. {
+            let state = if backoff < RECONNECT_BACKOFF_MAX { ConnState::Reconnecting(attempt) } else { ConnState::Offline };
+            let _ = tx.send(AppMsg::Conn(state));
+            if ctl.stop.load(Ordering::Relaxed) {
+                return;
+            }
+            if mail::imap::probe(&config.imap) {
+                let _ = tx.send(AppMsg::Conn(ConnState::Connected));
+                return;
+            }
+            // short naps, so quitting isn't held up by a minute's wait
+            let until = Instant::now() + backoff;
+            backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+            while Instant::now() < until {
+                if ctl.stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                if ctl.retry_now.swap(false, Ordering::Relaxed) {
+                    backoff = RECONNECT_BACKOFF_MIN;
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+    });
+}
+
+// The text of the open mail, with disclaimers hidden behind `x` and counted.
+fn set_body_text(app: &mut App, text: String) {
+    app.current_body = clamp_long_lines(text.clone());
+    app.quote_body = text;
+    app.other_body = None;
+    match strip_disclaimers(&app.current_body, &app.config.view.disclaimer_patterns) {
+        Ok(Some(stripped)) => {
+            app.other_body = Some(std::mem::replace(&mut app.current_body, stripped));
+        }
+        Ok(None) => {}
+        Err(e) => app.status = format!("Bad disclaimer pattern: {e}"),
+    }
+    app.body_count = TextCount::of(&app.current_body);
+    app.body_rows.get_mut().clear();
+}
+
+fn body_fetch_is(app: &App, uid: u32) -> bool {
+    app.body_fetch.as_ref().is_some_and(|f| f.uid == uid)
+}
+
+fn cancel_body_fetch(app: &mut App) -> bool {
+    app.pending_compose = None;
+    match app.body_fetch.take() {
+        Some(f) => {
+            f.cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+const MAX_LINE_CHARS: usize = 4096;
+
+// A body that is one enormous line (a stray base64 blob, minified HTML) makes every
+// frame re-wrap it; nobody reads past a few screens of such a line anyway.
+fn clamp_long_lines(body: String) -> String {
+    if !body.lines().any(|l| l.len() > MAX_LINE_CHARS) {
+        return body;
+    }
+    body.lines()
+        .map(|line| match line.char_indices().nth(MAX_LINE_CHARS) {
+            Some((cut, _)) => {
+                let rest = line[cut..].chars().count();
+                format!("{}… [{rest} more characters]", &line[..cut])
+            }
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const DISCLAIMER_MARKER: &str = "[disclaimer hidden · x to show]";
+
+// Each pattern is a regex and whatever it matches is replaced by the marker; a pattern
+// like `(?s)CONFIDENTIALITY NOTICE.*` hides everything from the phrase to the end.
+// `None` when nothing matched, so the body is left as it was.
+fn strip_disclaimers(body: &str, patterns: &[String]) -> Result<Option<String>> {
+    let mut out = None;
+    for p in patterns {
+        let re = regex::Regex::new(p)?;
+        let text = out.as_deref().unwrap_or(body);
+        if re.is_match(text) {
+            out = Some(re.replace_all(text, DISCLAIMER_MARKER).into_owned());
+        }
+    }
+    Ok(out)
+}
+
+fn spawn_fetch_body(
+    config: Config,
+    mailbox: String,
+    uid_validity: Option<u32>,
+    header: MessageSummary,
+    tx: mpsc::UnboundedSender<AppMsg>,
+) -> BodyFetch {
+    let uid = header.uid;
+    let cancel = Arc::new(AtomicBool::new(false));
+    let worker_cancel = cancel.clone();
+
+    let on_panic = move |error| AppMsg::Body(BodyEvent::Failed { uid, error });
+    spawn_worker(tx, "Fetching the mail", on_panic, move |tx| {
+        let progress_tx = tx.clone();
+        let progress = move |progress| {
+            let _ = progress_tx.send(AppMsg::Body(BodyEvent::Progress { uid, progress }));
+        };
+
+        let max_bytes = config.view.max_message_mb * 1024 * 1024;
+        match mail::imap::fetch_body_plain(&config.imap, &mailbox, uid, max_bytes, &worker_cancel, progress) {
+            Ok(body) => {
+                if let Some(v) = uid_validity {
+                    let _ = cache::save_body(&config, &mailbox, v, uid, &body.text);
+                }
+                let _ = tx.send(AppMsg::Body(BodyEvent::Loaded { header: Box::new(header), body }));
+            }
+            Err(e) => {
+                let error = mail::imap::explain(e, &config.imap).to_string();
+                let _ = tx.send(AppMsg::Body(BodyEvent::Failed { uid, error }));
+            }
+        }
+    });
+
+    BodyFetch {
+        uid,
+        started: Instant::now(),
+        progress: FetchProgress { phase: FetchPhase::Connecting, bytes: 0, total: None },
+        cancel,
+    }
+}
+
+// Sends `msg`, then files a copy in the Sent folder; `Ok` carries the error of that
+// second step, which doesn't undo the send.
+fn send_and_file(config: &Config, msg: &OutgoingMessage, tx: &mpsc::UnboundedSender<AppMsg>) -> Result<Option<String>> {
+    let raw = mail::smtp::send(&config.smtp, &config.user, msg)?;
+    let sent = config.folders.sent.trim();
+    if sent.is_empty() {
+        return Ok(None);
+    }
+    let _ = tx.send(AppMsg::Status(format!("Sent, saving to {sent}...")));
+    Ok(mail::imap::append_to_sent(&config.imap, sent, &raw).err().map(|e| e.to_string()))
+}
+
+fn spawn_send_mail(config: Config, msg: OutgoingMessage, tx: mpsc::UnboundedSender<AppMsg>) {
+    let _ = tx.send(AppMsg::Status("Sending...".to_string()));
+    spawn_worker(tx, "Sending", AppMsg::Status, move |tx| match send_and_file(&config, &msg, tx) {
+        Ok(append_error) => {
+            let _ = tx.send(AppMsg::Sent { append_error });
+        }
+        Err(e) => {
+            let _ = tx.send(AppMsg::Status(format!("SMTP error: {e}")));
+        }
+    });
+}
+
+fn spawn_send_scheduled(config: Config, path: PathBuf, msg: OutgoingMessage, tx: mpsc::UnboundedSender<AppMsg>) {
+    let claimed = path.clone();
+    let on_panic = |error| AppMsg::Outbox(OutboxEvent::Failed { path: claimed, error });
+    spawn_worker(tx, "Sending scheduled mail", on_panic, move |tx| {
+        let ev = match send_and_file(&config, &msg, tx) {
+            Ok(append_error) => OutboxEvent::Sent { path, append_error },
+            Err(e) => OutboxEvent::Failed { path, error: e.to_string() },
+        };
+        let _ = tx.send(AppMsg::Outbox(ev));
+    });
+}
+
+// A failed scheduled send waits this long before the next try.
+const OUTBOX_RETRY_SECS: i64 = 300;
+
+fn reload_outbox(app: &mut App) {
+    match outbox::list() {
+        Ok(list) => app.outbox = list,
+        Err(e) => app.status = format!("Outbox error: {e}"),
+    }
+    app.outbox_selected = app.outbox_selected.min(app.outbox.len().saturating_sub(1));
+}
+
+// Claims and sends every queued mail whose time has come. A claimed file is never
+// picked up again, so a mail goes out at most once however the clock moves.
+fn send_due_mail(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) -> bool {
+    let due: Vec<PathBuf> = app.outbox.iter().filter(|e| !e.claimed && e.mail.due()).map(|e| e.path.clone()).collect();
+    if due.is_empty() {
+        return false;
+    }
+    for path in due {
+        let Some(entry) = app.outbox.iter_mut().find(|e| e.path == path) else { continue };
+        match outbox::claim(&path) {
+            Ok(claimed) => {
+                let msg = scheduled_message(&entry.mail);
+                entry.path = claimed.clone();
+                entry.claimed = true;
+                app.outbox_inflight.insert(claimed.clone());
+                spawn_send_scheduled(app.config.clone(), claimed, msg, tx.clone());
+                app.status = "Sending scheduled mail...".to_string();
+            }
+            // another zenmail got to it first
+            Err(_) => entry.claimed = true,
+        }
+    }
+    true
+}
+
+fn scheduled_message(s: &outbox::Scheduled) -> OutgoingMessage {
+    let m = &s.mail;
+    OutgoingMessage {
+        to: m.to.clone(),
+        cc: m.cc.clone(),
+        bcc: m.bcc.clone(),
+        reply_to: m.reply_to.clone(),
+        subject: m.subject.clone(),
+        body: compose_full_body(&m.body, &m.quote),
+        undisclosed: s.undisclosed,
+        high_priority: m.high_priority,
+        in_reply_to: m.in_reply_to.clone(),
+        references: m.references.clone(),
+        attached_message: None,
+        attachments: m.attachments.clone(),
+    }
+}
+
+fn on_outbox_event(app: &mut App, ev: OutboxEvent) {
+    let (path, result) = match ev {
+        OutboxEvent::Sent { path, append_error } => (path, Ok(append_error)),
+        OutboxEvent::Failed { path, error } => (path, Err(error)),
+    };
+    app.outbox_inflight.remove(&path);
+    let Some(entry) = app.outbox.iter().find(|e| e.path == path) else { return };
+    let subject = entry.mail.mail.subject.clone();
+    let late = (Local::now().timestamp() - entry.mail.at) / 60;
+
+    app.status = match result {
+        Ok(append_error) => {
+            let mut status = format!("Sent scheduled mail {subject:?}");
+            if late > 1 {
+                status.push_str(&format!(" ({late} min late)"));
+            }
+            if let Err(e) = outbox::delete(&path) {
+                status = format!("{status}, but removing it from the outbox failed: {e}");
+            }
+            if let Some(e) = append_error {
+                status = format!("{status}; saving to {} failed: {e}", app.config.folders.sent);
+            }
+            status
+        }
+        Err(error) => {
+            app.health.error("outbox", &error);
+            let retry = Local::now().timestamp() + OUTBOX_RETRY_SECS;
+            match outbox::release(&path, entry.mail.clone(), retry) {
+                Ok(()) => format!("Scheduled mail {subject:?} failed: {error}; retrying in {} min", OUTBOX_RETRY_SECS / 60),
+                Err(e) => format!("Scheduled mail {subject:?} failed: {error}; it stays in the outbox ({e})"),
+            }
+        }
+    };
+    reload_outbox(app);
+}
+
+fn spawn_archive(config: Config, mailbox: String, uid: u32, tx: mpsc::UnboundedSender<AppMsg>) {
+    let _ = tx.send(AppMsg::Status(format!("Archiving (uid={uid})...")));
+    let mb = mailbox.clone();
+    let on_panic = move |error| AppMsg::List(ListEvent::RemoveFailed { mailbox: mb, uids: vec![uid], error });
+    spawn_worker(tx, "Archive", on_panic, move |tx| {
+        let msg = match mail::imap::archive_message(&config.imap, &mailbox, uid, &config.folders.archive) {
+            Ok(_) => AppMsg::List(ListEvent::Removed { mailbox, uids: vec![uid], status: format!("Archived to {}", config.folders.archive) }),
+            Err(e) => AppMsg::List(ListEvent::RemoveFailed { mailbox, uids: vec![uid], error: format!("IMAP archive error: {e}") }),
+        };
+        let _ = tx.send(msg);
+    });
+}
+
+fn image_temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("zenmail-{}", std::process::id()))
+}
+
+fn open_with_system(path: &std::path::Path) -> Result<()> {
+    let mut cmd = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut c = Command::new("cmd");
+        c.args(["/C", "start", ""]);
+        c
+    } else {
+        Command::new("xdg-open")
+    };
+
+    cmd.arg(path)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+
+fn downloads_dir(config: &Config) -> Result<PathBuf> {
+    if let Some(dir) = config.view.download_dir.as_deref().filter(|d| !d.is_empty()) {
+        return expand_home(dir);
+    }
+    dirs::download_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join("Downloads")))
+        .ok_or_else(|| anyhow!("no downloads dir"))
+}
+
+// `name.ext`, then `name (1).ext`, `name (2).ext`, ... whichever is free first.
+fn unique_path(dir: &std::path::Path, filename: &str) -> PathBuf {
+    let first = dir.join(filename);
+    if !first.exists() {
+        return first;
+    }
+    let name = std::path::Path::new(filename);
+    let stem = name.file_stem().and_then(|s| s.to_str()).unwrap_or(filename);
+    let ext = name.extension().and_then(|e| e.to_str());
+    (1..)
+        .map(|n| match ext {
+            Some(ext) => dir.join(format!("{stem} ({n}).{ext}")),
+            None => dir.join(format!("{stem} ({n})")),
+        })
+        .find(|p| !p.exists())
+        .unwrap_or(first)
+}
+
+// `<subject>.eml` with whatever a file system might refuse or misread replaced by `_`.
+fn eml_filename(subject: &str) -> String {
+    let cleaned: String = subject
+        .chars()
+        .map(|c| match c {
+            _ if c.is_whitespace() => ' ',
+            _ if c.is_control() || "/\\:*?\"<>|".contains(c) => '_',
+            _ => c,
+        })
+        .collect();
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    let stem: String = cleaned.trim_matches(|c| c == '.' || c == ' ').chars().take(100).collect();
+    format!("{}.eml", if stem.is_empty() { "message" } else { stem.as_str() })
+}
+
+// Where `w` suggests saving the open mail.
+fn default_export_path(app: &App) -> Result<PathBuf> {
+    let subject = app.current_header.as_ref().map(|h| h.subject.as_str()).unwrap_or("");
+    Ok(unique_path(&downloads_dir(&app.config)?, &eml_filename(subject)))
+}
+
+fn start_export(app: &mut App, input: &str, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let Some(uid) = app.current_header.as_ref().map(|h| h.uid) else { return };
+    let path = match expand_home(input.trim()) {
+        Ok(p) => p,
+        Err(e) => {
+            app.status = format!("Export error: {e}");
+            return;
+        }
+    };
+    // a directory gets the suggested name; an existing file is never overwritten
+    let subject = app.current_header.as_ref().map(|h| h.subject.clone()).unwrap_or_default();
+    let path = if path.is_dir() {
+        unique_path(&path, &eml_filename(&subject))
+    } else if path.exists() {
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        unique_path(path.parent().unwrap_or(std::path::Path::new(".")), &name)
+    } else {
+        path
+    };
+
+    let raw = app.current_raw.clone();
+    let config = app.config.clone();
+    let mailbox = app.current_folder.clone();
+    app.status = format!("Exporting to {}...", path.display());
+    spawn_worker(tx.clone(), "Export", AppMsg::Status, move |tx| {
+        let res = match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            Some(dir) => std::fs::create_dir_all(dir).map_err(anyhow::Error::from),
+            None => Ok(()),
+        };
+        // the bytes already fetched for the view, or a fresh fetch when the mail was too big
+        let res = res.and_then(|_| match raw {
+            Some(raw) => std::fs::write(&path, raw).map_err(anyhow::Error::from),
+            None => mail::imap::export_message(&config.imap, &mailbox, uid, &path),
+        });
+        let status = match res {
+            Ok(()) => format!("Exported to {}", path.display()),
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                format!("Export error: {e}")
+            }
+        };
+        let _ = tx.send(AppMsg::Status(status));
+    });
+}
+
+fn handle_export_keys(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let Some(input) = app.export_input.as_mut() else { return };
+    match code {
+        KeyCode::Esc => {
+            app.export_input = None;
+            app.status = "Export canceled".to_string();
+        }
+        KeyCode::Tab => {
+            let (completed, _) = complete_path(input);
+            *input = completed;
+        }
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        KeyCode::Enter => {
+            let input = app.export_input.take().unwrap_or_default();
+            start_export(app, &input, tx);
+        }
+        _ => {}
+    }
+}
+
+fn save_attachment(config: &Config, dir: &std::path::Path, mailbox: &str, uid: u32, att: &Attachment) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    // the name comes from the sender; never let it pick the directory
+    let filename = std::path::Path::new(&att.filename)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("attachment");
+    let path = unique_path(dir, filename);
+    let max_bytes = config.view.max_message_mb * 1024 * 1024;
+    if let Err(e) = mail::imap::download_attachment(&config.imap, mailbox, uid, att, &path, max_bytes) {
+        let _ = std::fs::remove_file(&path);
+        return Err(e);
+    }
+    Ok(path)
+}
+
+fn save_selected_attachment(app: &mut App, index: usize, tx: &mpsc::UnboundedSender<AppMsg>) {
+    match (&app.current_header, app.attachments.get(index)) {
+        (Some(h), Some(att)) => {
+            spawn_save_attachment(app.config.clone(), app.current_folder.clone(), h.uid, att.clone(), tx.clone());
+        }
+        _ => app.status = "No attachments".to_string(),
+    }
+}
+
+fn spawn_save_attachment(config: Config, mailbox: String, uid: u32, att: Attachment, tx: mpsc::UnboundedSender<AppMsg>) {
+    let _ = tx.send(AppMsg::Status(format!("Saving {}...", att.filename)));
+    spawn_worker(tx, "Saving the attachment", AppMsg::Status, move |tx| {
+        let saved = downloads_dir(&config).and_then(|dir| save_attachment(&config, &dir, &mailbox, uid, &att));
+        let status = match saved {
+            Ok(path) => format!("Saved to {}", path.display()),
+            Err(e) => format!("Save error: {e}"),
+        };
+        let _ = tx.send(AppMsg::Status(status));
+    });
+}
+
+// `i`: the selected attachment, when it is an image, in the system viewer.
+fn open_selected_image(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let is_image = |att: &Attachment| att.content_type.starts_with("image/");
+    match (&app.current_header, app.attachments.get(app.attachment_selected)) {
+        (Some(h), Some(att)) if is_image(att) => {
+            spawn_open_image(app.config.clone(), app.current_folder.clone(), h.uid, att.clone(), tx.clone());
+        }
+        (Some(_), Some(att)) if app.attachments.iter().any(is_image) => {
+            app.status = format!("{} is not an image; Tab selects another attachment", att.filename);
+        }
+        _ => app.status = "No images in this mail".to_string(),
+    }
+}
+
+fn spawn_open_image(config: Config, mailbox: String, uid: u32, att: Attachment, tx: mpsc::UnboundedSender<AppMsg>) {
+    let _ = tx.send(AppMsg::Status(format!("Fetching {}...", att.filename)));
+    spawn_worker(tx, "Fetching the image", AppMsg::Status, move |tx| {
+        let status = match save_attachment(&config, &image_temp_dir(), &mailbox, uid, &att) {
+            Ok(path) => match open_with_system(&path) {
+                Ok(()) => format!("Opened {}", att.filename),
+                Err(e) => format!("Open image error: {e}"),
+            },
+            Err(e) => format!("IMAP image error: {e}"),
+        };
+        let _ = tx.send(AppMsg::Status(status));
+    });
+}
+
+fn reset_compose_new(app: &mut App) {
+    app.compose.to.clear();
+    app.compose.cc.clear();
+    app.compose.bcc.clear();
+    app.compose.reply_to.clear();
+    app.compose.subject.clear();
+    app.compose.body.clear();
+    app.compose.quote.clear();
+    app.compose.focus = ComposeField::To;
+    app.compose.cursor = usize::MAX;
+    app.compose.confirm_bulk = false;
+    app.compose.confirm_guard = false;
+    app.compose.preview = None;
+    app.compose.confirm_discard = false;
+    app.compose.undisclosed = false;
+    app.compose.high_priority = false;
+    app.compose.draft_path = None;
+    app.compose.in_reply_to = None;
+    app.compose.references = None;
+    app.compose.attached_message = None;
+    app.compose.attachments.clear();
+    app.compose.attachment_sizes.clear();
+}
+
+pub fn compose_full_body(body: &str, quote: &str) -> String {
+    let body = body.trim_end().to_string();
+    let quote = quote.trim_end().to_string();
+
+    if quote.is_empty() {
+        body
+    } else if body.is_empty() {
+        quote
+    } else {
+        format!("{body}\n\n{quote}")
+    }
+}
+
+fn extract_reply_to(from: &str) -> String {
+    let s = from.trim();
+
+    if let Some(l) = s.find('<') {
+        if let Some(r) = s[l + 1..].find('>') {
+            let addr = s[l + 1..l + 1 + r].trim();
+            if !addr.is_empty() {
+                return addr.to_string();
+            }
+        }
+    }
+
+    for tok in s.split_whitespace() {
+        let t = tok.trim_matches(|c: char| c == '<' || c == '>' || c == ',' || c == ';');
+        if t.contains('@') {
+            return t.to_string();
+        }
+    }
+
+    s.to_string()
+}
+
+fn normalize_subject(subject: &str) -> String {
+    let mut s = subject.trim();
+    loop {
+        let lower = s.to_ascii_lowercase();
+        let Some(prefix) = ["re:", "fwd:", "fw:"].iter().find(|p| lower.starts_with(**p)) else {
+            break;
+        };
+        s = s[prefix.len()..].trim_start();
+    }
+    s.to_lowercase()
+}
+
+fn make_reply_subject(subject: &str) -> String {
+    let s = subject.trim();
+    if s.is_empty() {
+        "Re:".to_string()
+    } else if s.to_ascii_lowercase().starts_with("re:") {
+        s.to_string()
+    } else {
+        format!("Re: {s}")
+    }
+}
+
+fn quote_lines(text: &str) -> String {
+    let mut out = String::new();
+    for (i, line) in text.replace("\r\n", "\n").lines().enumerate() {
         if i > 0 {
             out.push('\n');
         }
-        out.push_str("> ");
-        out.push_str(line);
+        out.push_str("> ");
+        out.push_str(line);
+    }
+    if out.is_empty() {
+        out.push_str("> ");
+    }
+    out
+}
+
+fn make_reply_quote(header: &MessageSummary, body: &str) -> String {
+    let from = if header.from.is_empty() { "(unknown)" } else { header.from.as_str() };
+    let date = if header.date.is_empty() { "(unknown date)" } else { header.date.as_str() };
+    let intro = format!("On {date}, {from} wrote:");
+    let quoted = quote_lines(body);
+    format!("{intro}\n{quoted}")
+}
+
+fn make_forward_subject(subject: &str) -> String {
+    let s = subject.trim();
+    if s.is_empty() {
+        "Fwd:".to_string()
+    } else if s.to_ascii_lowercase().starts_with("fwd:") {
+        s.to_string()
+    } else {
+        format!("Fwd: {s}")
+    }
+}
+
+fn make_forward_body(header: &MessageSummary, body: &str) -> String {
+    let or_unknown = |s: &str| if s.is_empty() { "(unknown)".to_string() } else { s.to_string() };
+    format!(
+        "---------- Forwarded message ----------\nFrom: {}\nDate: {}\nTo: {}\nSubject: {}\n\n{}",
+        or_unknown(&header.from),
+        or_unknown(&header.date),
+        or_unknown(&header.to),
+        header.subject,
+        body.replace("\r\n", "\n"),
+    )
+}
+
+// `attach` sends the original as a message/rfc822 attachment, with its own
+// attachments intact, instead of quoting its text.
+fn start_forward(app: &mut App, attach: bool) {
+    let Some(h) = app.current_header.clone() else {
+        app.status = "No mail selected".to_string();
+        return;
+    };
+
+    if app.body_fetch.is_some() || app.current_body.trim().is_empty() {
+        app.status = "Mail is still loading".to_string();
+        return;
+    }
+
+    if attach && app.current_raw.is_none() {
+        app.status = "Mail is too large to attach; f forwards its text".to_string();
+        return;
+    }
+
+    reset_compose_new(app);
+    app.compose.subject = make_forward_subject(&h.subject);
+    if attach {
+        app.compose.attached_message = app.current_raw.clone();
+    } else {
+        app.compose.quote = make_forward_body(&h, &app.quote_body);
+    }
+    app.compose.focus = ComposeField::To; // forwarding needs a new recipient
+
+    app.view = View::Compose;
+    app.status = if attach { "Forward as attachment" } else { "Forward" }.to_string();
+}
+
+fn start_reply(app: &mut App, all: bool) {
+    let Some(h) = app.current_header.clone() else {
+        app.status = "No mail selected".to_string();
+        return;
+    };
+
+    if app.body_fetch.is_some() || app.current_body.trim().is_empty() {
+        app.status = "Mail is still loading".to_string();
+        return;
+    }
+
+    let body = app.quote_body.clone();
+    compose_reply(app, &h, &body);
+    if all {
+        let reply_to = if h.reply_to.is_empty() { &h.from } else { &h.reply_to };
+        let (to, cc) = mail::smtp::reply_all_recipients(reply_to, &h.to, &h.cc, &app.config.user.email);
+        app.compose.to = to;
+        app.compose.cc = cc;
+        app.status = match &app.current_delivery {
+            Some(Delivery::Bcc(_)) => "Reply all: you look Bcc'd, so answering everyone shows them you got this".to_string(),
+            _ => "Reply all".to_string(),
+        };
+    }
+}
+
+// The parent's References with its own Message-ID appended, so threading clients
+// can place the reply even when intermediate messages are missing.
+fn make_references(parent_refs: Option<&str>, parent_id: Option<&str>) -> Option<String> {
+    match (parent_refs, parent_id) {
+        (Some(refs), Some(id)) if !refs.split_whitespace().any(|r| r == id) => Some(format!("{refs} {id}")),
+        (Some(refs), _) => Some(refs.to_string()),
+        (None, id) => id.map(str::to_string),
+    }
+}
+
+fn compose_reply(app: &mut App, h: &MessageSummary, body: &str) {
+    reset_compose_new(app);
+    app.compose.to = extract_reply_to(&h.from);
+    app.compose.subject = make_reply_subject(&h.subject);
+
+    app.compose.quote = make_reply_quote(h, body); // quote below, user writes on top
+    app.compose.in_reply_to = app.current_message_id.clone();
+    app.compose.references = make_references(app.current_references.as_deref(), app.current_message_id.as_deref());
+    app.compose.focus = ComposeField::Body;
+
+    app.view = View::Compose;
+    app.status = "Reply".to_string();
+}
+
+// `r` / `f` in the list: fetch the body in the background and go straight to compose.
+fn start_compose_from_list(app: &mut App, what: PendingCompose, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let Some(m) = app.messages.get(app.selected).cloned() else {
+        app.status = "No mail selected".to_string();
+        return;
+    };
+
+    cancel_body_fetch(app);
+    app.body_from_cache = false;
+    app.pending_compose = Some((m.uid, what));
+    app.body_fetch = Some(spawn_fetch_body(app.config.clone(), app.current_folder.clone(), app.uid_validity, m, tx.clone()));
+    app.status = match what {
+        PendingCompose::Reply => "Preparing reply… (Esc cancels)",
+        PendingCompose::Forward => "Preparing forward… (Esc cancels)",
+    }
+    .to_string();
+}
+
+fn apply_quick_filter(app: &mut App, code: KeyCode) {
+    let Some(m) = app.messages.get(app.selected) else {
+        app.status = "No mail selected".to_string();
+        return;
+    };
+
+    let filter = match code {
+        KeyCode::Char('s') => ListFilter::Sender(extract_reply_to(&m.from)),
+        KeyCode::Char('t') => ListFilter::Thread(normalize_subject(&m.subject)),
+        _ => {
+            app.status = "Filter canceled".to_string();
+            return;
+        }
+    };
+
+    app.status = format!("Filter {} (Esc clears)", filter.label());
+    app.filter = Some(filter);
+    app.saved_search = None;
+    app.clamp_selection();
+}
+
+// Drops a message locally, keeping the cursor on the same neighbourhood of the list.
+// The row is remembered so a failed server-side delete/archive can put it back.
+fn remove_message(app: &mut App, uid: u32) {
+    let Some(i) = app.messages.iter().position(|m| m.uid == uid) else {
+        return;
+    };
+    let m = app.messages.remove(i);
+    app.removing.push((app.current_folder.clone(), i, m));
+    if app.selected > i {
+        app.selected -= 1;
+    }
+    app.selected = app.selected.min(app.messages.len().saturating_sub(1));
+    app.clamp_selection();
+}
+
+fn restore_message(app: &mut App, mailbox: &str, uid: u32) {
+    let Some(pos) = app.removing.iter().position(|(mb, _, m)| mb == mailbox && m.uid == uid) else {
+        return;
+    };
+    let (_, i, m) = app.removing.remove(pos);
+    // a refresh may already have brought it back
+    if mailbox != app.current_folder || app.messages.iter().any(|x| x.uid == uid) {
+        return;
+    }
+    let i = i.min(app.messages.len());
+    app.messages.insert(i, m);
+    if app.selected >= i && app.messages.len() > 1 {
+        app.selected += 1;
+    }
+    app.clamp_selection();
+}
+
+fn spawn_remove(app: &mut App, uid: u32, archive: bool, tx: &mpsc::UnboundedSender<AppMsg>) {
+    remove_message(app, uid);
+    app.marked.remove(&uid);
+    if archive {
+        spawn_archive(app.config.clone(), app.current_folder.clone(), uid, tx.clone());
+    } else {
+        spawn_delete(app.config.clone(), app.current_folder.clone(), vec![uid], tx.clone());
+    }
+}
+
+// Marked messages when there are any, otherwise the one under the cursor.
+fn delete_targets(app: &App) -> Vec<u32> {
+    if app.marked.is_empty() {
+        app.messages.get(app.selected).map(|m| vec![m.uid]).unwrap_or_default()
+    } else {
+        app.messages.iter().map(|m| m.uid).filter(|uid| app.marked.contains(uid)).collect()
+    }
+}
+
+fn needs_delete_confirm(app: &App, count: usize) -> bool {
+    match app.config.list.confirm_delete {
+        ConfirmDelete::Always => true,
+        ConfirmDelete::Never => false,
+        ConfirmDelete::Above => count > app.config.list.confirm_delete_above,
+    }
+}
+
+fn ask_delete(app: &mut App, uids: Vec<u32>, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if uids.is_empty() {
+        return;
+    }
+    if let Some(why) = not_permitted(app, 't') {
+        app.status = format!("Can't delete: {why}");
+        return;
+    }
+    if !needs_delete_confirm(app, uids.len()) {
+        delete_now(app, uids, tx);
+        return;
+    }
+
+    app.status = match uids.as_slice() {
+        [uid] => {
+            let subject = app
+                .messages
+                .iter()
+                .chain(app.current_header.iter())
+                .find(|m| m.uid == *uid)
+                .map(|m| m.subject.as_str())
+                .filter(|s| !s.is_empty())
+                .unwrap_or("(no subject)");
+            format!("Delete '{subject}'? y/n")
+        }
+        _ => format!("Delete {} messages? y/n", uids.len()),
+    };
+    app.confirm_delete = Some(uids);
+}
+
+fn delete_now(app: &mut App, uids: Vec<u32>, tx: &mpsc::UnboundedSender<AppMsg>) {
+    for &uid in &uids {
+        remove_message(app, uid);
+        app.marked.remove(&uid);
+    }
+    spawn_delete(app.config.clone(), app.current_folder.clone(), uids, tx.clone());
+    if app.view == View::Mail {
+        cancel_body_fetch(app);
+        app.view = View::List;
+    }
+}
+
+// The year of a summary's Date header, as already parsed by mail::parse_date.
+fn date_year(timestamp: i64) -> Option<i32> {
+    use chrono::Datelike;
+    chrono::DateTime::from_timestamp(timestamp, 0).map(|d| d.year())
+}
+
+// `A`: groups the targets by the year of their Date header, then asks the server how
+// folders are named below the root before asking for confirmation.
+fn plan_archive_by_year(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let mut years: std::collections::BTreeMap<i32, Vec<u32>> = Default::default();
+    let mut undated = 0;
+    for uid in delete_targets(app) {
+        match app.messages.iter().find(|m| m.uid == uid).and_then(|m| m.timestamp).and_then(date_year) {
+            Some(year) => years.entry(year).or_default().push(uid),
+            None => undated += 1,
+        }
+    }
+    if years.is_empty() {
+        app.status = if undated > 0 { "No readable Date to archive by" } else { "Nothing to archive" }.to_string();
+        return;
+    }
+
+    let root = app.config.folders.year_archive_root().to_string();
+    let (config, mailbox) = (app.config.clone(), app.current_folder.clone());
+    app.status = format!("Looking up {root}...");
+    spawn_worker(tx.clone(), "Archive by year", AppMsg::Status, move |tx| {
+        let msg = match mail::imap::folder_root(&config.imap, &root) {
+            Ok((root, delim)) => {
+                let moves = years.into_iter().rev().map(|(year, uids)| (format!("{root}{delim}{year}"), uids)).collect();
+                AppMsg::List(ListEvent::ArchivePlan(ArchivePlan { mailbox, moves, undated }))
+            }
+            Err(e) => AppMsg::Status(format!("Archive by year error: {e}")),
+        };
+        let _ = tx.send(msg);
+    });
+}
+
+fn archive_by_year_now(app: &mut App, plan: ArchivePlan, tx: &mpsc::UnboundedSender<AppMsg>) {
+    for (_, uids) in &plan.moves {
+        for &uid in uids {
+            remove_message(app, uid);
+            app.marked.remove(&uid);
+        }
+    }
+    app.status = "Archiving...".to_string();
+
+    let config = app.config.clone();
+    let all: Vec<u32> = plan.moves.iter().flat_map(|(_, uids)| uids.clone()).collect();
+    let mb = plan.mailbox.clone();
+    let on_panic = |error| AppMsg::List(ListEvent::RemoveFailed { mailbox: mb, uids: all, error });
+    spawn_worker(tx.clone(), "Archive by year", on_panic, move |tx| {
+        let ArchivePlan { mailbox, moves, .. } = plan;
+        match mail::imap::archive_grouped(&config.imap, &mailbox, &moves) {
+            Ok(results) => {
+                for ((dest, uids), res) in moves.into_iter().zip(results) {
+                    let ev = match res {
+                        Ok(()) => ListEvent::Removed { mailbox: mailbox.clone(), uids, status: format!("Archived to {dest}") },
+                        Err(e) => ListEvent::RemoveFailed { mailbox: mailbox.clone(), uids, error: format!("IMAP archive error ({dest}): {e}") },
+                    };
+                    let _ = tx.send(AppMsg::List(ev));
+                }
+            }
+            Err(e) => {
+                let uids = moves.into_iter().flat_map(|(_, uids)| uids).collect();
+                let _ = tx.send(AppMsg::List(ListEvent::RemoveFailed { mailbox, uids, error: format!("IMAP archive error: {e}") }));
+            }
+        }
+    });
+}
+
+// Returns true when the key was consumed by a pending archive-by-year confirmation.
+fn handle_archive_confirm(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) -> bool {
+    let Some(plan) = app.confirm_archive.take() else {
+        return false;
+    };
+    if code == KeyCode::Char('y') {
+        archive_by_year_now(app, plan, tx);
+    } else {
+        app.status = "Archive canceled".to_string();
+    }
+    true
+}
+
+// Returns true when the key was consumed by a pending delete confirmation.
+fn handle_delete_confirm(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) -> bool {
+    let Some(uids) = app.confirm_delete.take() else {
+        return false;
+    };
+    if code == KeyCode::Char('y') {
+        delete_now(app, uids, tx);
+    } else {
+        app.status = "Delete canceled".to_string();
+    }
+    true
+}
+
+fn select_folder(app: &mut App, name: String, tx: &mpsc::UnboundedSender<AppMsg>) {
+    reset_folder(app, name);
+    refresh_list(app, tx);
+}
+
+fn reset_folder(app: &mut App, name: String) {
+    app.current_folder = name;
+    app.messages.clear();
+    app.marked.clear();
+    app.uid_validity = None;
+    app.cached_validity = None;
+    app.last_uid = 0;
+    app.selected = 0;
+    app.filter = None;
+    app.search = None;
+    app.saved_search = None;
+    show_cached_list(app);
+}
+
+// The rows kept on disk for the folder, shown until the server's list replaces them.
+// `uid_validity` stays unknown, so the next refresh loads the whole list, and changes
+// to the mail wait until it has (see `not_permitted`).
+fn show_cached_list(app: &mut App) {
+    if let Some((uid_validity, list)) = cache::load_list(&app.config, &app.current_folder) {
+        app.cached_validity = Some(uid_validity);
+        app.messages = list;
+        app.sort_messages();
+        app.selected = app.visible_indices().first().copied().unwrap_or(0);
+    }
+}
+
+// Keeps the open folder's rows on disk, unless they are search results.
+fn save_list_cache(app: &App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let Some(uid_validity) = app.uid_validity.filter(|_| app.search.is_none()) else { return };
+    let (config, mailbox, list) = (app.config.clone(), app.current_folder.clone(), app.messages.clone());
+    spawn_worker(tx.clone(), "Saving the cache", AppMsg::Status, move |_| {
+        // only a quicker start is lost when this fails
+        let _ = cache::save_list(&config, &mailbox, uid_validity, &list);
+    });
+}
+
+// Folder, search, filter and order all at once; the list reloads from scratch.
+fn apply_saved_search(app: &mut App, i: usize, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let Some(s) = app.config.saved_searches.get(i).cloned() else {
+        app.status = format!("No saved search {} (~ lists them)", i + 1);
+        return;
+    };
+    reset_folder(app, s.folder.clone());
+    app.filter = if !s.from.is_empty() {
+        Some(ListFilter::Sender(s.from.clone()))
+    } else if !s.thread.is_empty() {
+        Some(ListFilter::Thread(s.thread.clone()))
+    } else {
+        None
+    };
+    app.newest_first = s.newest_first;
+    app.saved_search = Some(s.name.clone());
+    app.view = View::List;
+    if s.query.is_empty() {
+        refresh_list(app, tx);
+    } else {
+        spawn_search(app.config.clone(), s.folder, s.query, previews(app), tx.clone());
+    }
+}
+
+// What the list shows now, as a saved search called `name`.
+fn current_view(app: &App, name: String) -> SavedSearch {
+    let (from, thread) = match &app.filter {
+        Some(ListFilter::Sender(addr)) => (addr.clone(), String::new()),
+        Some(ListFilter::Thread(subject)) => (String::new(), subject.clone()),
+        None => (String::new(), String::new()),
+    };
+    SavedSearch {
+        name,
+        folder: app.current_folder.clone(),
+        query: app.search.clone().unwrap_or_default(),
+        from,
+        thread,
+        newest_first: app.newest_first,
+    }
+}
+
+fn open_saved_searches(app: &mut App) {
+    app.saved_selected = app.saved_selected.min(app.config.saved_searches.len().saturating_sub(1));
+    app.view = View::Searches;
+    app.status = format!("{} saved search(es)", app.config.saved_searches.len());
+}
+
+fn save_config_status(app: &mut App, done: String) {
+    app.status = match app.config.save_to(&app.config_path) {
+        Ok(()) => done,
+        Err(e) => format!("Save error: {e}"),
+    };
+}
+
+fn handle_saved_search_keys(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if let Some((which, input)) = app.saved_name_input.as_mut() {
+        match code {
+            KeyCode::Esc => {
+                app.saved_name_input = None;
+                app.status = "Canceled".to_string();
+            }
+            KeyCode::Enter if !input.trim().is_empty() => {
+                let name = input.trim().to_string();
+                let done = match *which {
+                    Some(i) => {
+                        app.config.saved_searches[i].name = name.clone();
+                        format!("Renamed to {name:?}")
+                    }
+                    None => {
+                        app.config.saved_searches.push(current_view(app, name.clone()));
+                        app.saved_selected = app.config.saved_searches.len() - 1;
+                        app.saved_search = Some(name.clone());
+                        format!("Saved {name:?}")
+                    }
+                };
+                app.saved_name_input = None;
+                save_config_status(app, done);
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+        return;
+    }
+
+    let count = app.config.saved_searches.len();
+    let selected = (app.saved_selected < count).then_some(app.saved_selected);
+    match code {
+        KeyCode::Esc => {
+            app.view = View::List;
+            app.status = "Back".to_string();
+        }
+        KeyCode::Char('j') | KeyCode::Down if count > 0 => app.saved_selected = (app.saved_selected + 1).min(count - 1),
+        KeyCode::Char('k') | KeyCode::Up => app.saved_selected = clamp_dec(app.saved_selected),
+        KeyCode::Char('a') => {
+            app.saved_name_input = Some((None, String::new()));
+            app.status = "Name for the current view?".to_string();
+        }
+        KeyCode::Enter if selected.is_some() => apply_saved_search(app, app.saved_selected, tx),
+        KeyCode::Char('r') if selected.is_some() => {
+            let name = app.config.saved_searches[app.saved_selected].name.clone();
+            app.saved_name_input = Some((selected, name));
+            app.status = "New name?".to_string();
+        }
+        KeyCode::Char('u') if selected.is_some() => {
+            let name = app.config.saved_searches[app.saved_selected].name.clone();
+            app.config.saved_searches[app.saved_selected] = current_view(app, name.clone());
+            app.saved_search = Some(name.clone());
+            save_config_status(app, format!("{name:?} now shows the current view"));
+        }
+        KeyCode::Char('d') if selected.is_some() => {
+            let s = app.config.saved_searches.remove(app.saved_selected);
+            if app.saved_search.as_deref() == Some(s.name.as_str()) {
+                app.saved_search = None;
+            }
+            app.saved_selected = app.saved_selected.min(count.saturating_sub(2));
+            save_config_status(app, format!("Deleted {:?}", s.name));
+        }
+        _ => {}
+    }
+}
+
+fn open_groups(app: &mut App) {
+    app.group_selected = app.group_selected.min(app.config.groups.len().saturating_sub(1));
+    app.view = View::Groups;
+    app.status = format!("{} group(s)", app.config.groups.len());
+}
+
+fn handle_group_keys(app: &mut App, code: KeyCode) {
+    if let Some((editing, input)) = app.group_input.as_mut() {
+        match code {
+            KeyCode::Esc => {
+                app.group_input = None;
+                app.status = "Canceled".to_string();
+            }
+            KeyCode::Enter => match mail::smtp::parse_group(input) {
+                Ok((name, members)) => {
+                    if let Some(old) = editing.take().filter(|old| *old != name) {
+                        app.config.groups.remove(&old);
+                    }
+                    app.group_input = None;
+                    let done = format!("Saved group {name:?} ({} members)", members.len());
+                    app.config.groups.insert(name.clone(), members);
+                    app.group_selected = app.config.groups.keys().position(|n| *n == name).unwrap_or(0);
+                    save_config_status(app, done);
+                }
+                Err(e) => app.status = e.to_string(),
+            },
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+        return;
+    }
+
+    let count = app.config.groups.len();
+    let selected = app.config.groups.iter().nth(app.group_selected).map(|(n, m)| (n.clone(), m.join(", ")));
+    match code {
+        KeyCode::Esc => {
+            app.view = View::List;
+            app.status = "Back".to_string();
+        }
+        KeyCode::Char('j') | KeyCode::Down if count > 0 => app.group_selected = (app.group_selected + 1).min(count - 1),
+        KeyCode::Char('k') | KeyCode::Up => app.group_selected = clamp_dec(app.group_selected),
+        KeyCode::Char('a') => {
+            app.group_input = Some((None, String::new()));
+            app.status = "New group".to_string();
+        }
+        KeyCode::Enter | KeyCode::Char('e') => {
+            if let Some((name, members)) = selected {
+                app.group_input = Some((Some(name.clone()), format!("{name} = {members}")));
+                app.status = format!("Editing {name:?}");
+            }
+        }
+        KeyCode::Char('d') => {
+            if let Some((name, _)) = selected {
+                app.config.groups.remove(&name);
+                app.group_selected = app.group_selected.min(count.saturating_sub(2));
+                save_config_status(app, format!("Deleted group {name:?}"));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn cycle_folder(app: &mut App, forward: bool, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if app.folders.is_empty() {
+        app.status = "Loading folders...".to_string();
+        spawn_list_folders(app.config.clone(), tx.clone());
+        return;
+    }
+
+    let n = app.folders.len();
+    let pos = app.folders.iter().position(|f| f.name == app.current_folder);
+    let next = match (pos, forward) {
+        (Some(i), true) => (i + 1) % n,
+        (Some(i), false) => (i + n - 1) % n,
+        (None, _) => 0,
+    };
+
+    select_folder(app, app.folders[next].name.clone(), tx);
+}
+
+fn open_folder_picker(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if app.folders.is_empty() {
+        spawn_list_folders(app.config.clone(), tx.clone());
+    }
+    app.folder_selected = app
+        .folders
+        .iter()
+        .position(|f| f.name == app.current_folder)
+        .unwrap_or(0);
+    app.view = View::Folders;
+    app.status = "Folders".to_string();
+}
+
+fn handle_folder_keys(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    match code {
+        KeyCode::Esc => {
+            app.view = View::List;
+            app.status = "Back".to_string();
+        }
+        KeyCode::Char('j') | KeyCode::Down if !app.folders.is_empty() => {
+            app.folder_selected = (app.folder_selected + 1).min(app.folders.len() - 1);
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.folder_selected = clamp_dec(app.folder_selected);
+        }
+        KeyCode::Enter => {
+            if let Some(f) = app.folders.get(app.folder_selected) {
+                let name = f.name.clone();
+                app.view = View::List;
+                select_folder(app, name, tx);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn handle_list_keys(app: &mut App, code: KeyCode, mods: KeyModifiers, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if handle_delete_confirm(app, code, tx) || handle_archive_confirm(app, code, tx) {
+        return;
+    }
+
+    if app.filter_pending {
+        app.filter_pending = false;
+        apply_quick_filter(app, code);
+        return;
+    }
+
+    if app.search_input.is_some() {
+        handle_search_input(app, code, tx);
+        return;
+    }
+    if app.command_input.is_some() {
+        handle_command_input(app, code, tx);
+        return;
+    }
+
+    match code {
+        _ if app.keys.is(Action::ListDown, code, mods) => move_selection(app, true, app.config.list.wrap),
+        _ if app.keys.is(Action::ListUp, code, mods) => move_selection(app, false, app.config.list.wrap),
+        _ if app.keys.is(Action::Open, code, mods) => open_selected(app, tx),
+        _ if app.keys.is(Action::Refresh, code, mods) => refresh_list(app, tx),
+        _ if app.keys.is(Action::Reply, code, mods) => start_compose_from_list(app, PendingCompose::Reply, tx),
+        _ if app.keys.is(Action::Compose, code, mods) => start_compose(app),
+        KeyCode::Char(c @ '1'..='9') => apply_saved_search(app, c as usize - '1' as usize, tx),
+        _ => {
+            if let Some(key) = keys::list_key(code) {
+                press_list_key(app, key, tx);
+            }
+        }
+    }
+}
+
+// The list's own keys, from `keys::LIST_KEYS`, once no remapped action took the key.
+fn press_list_key(app: &mut App, key: ListKey, tx: &mpsc::UnboundedSender<AppMsg>) {
+    match key {
+        ListKey::Down => move_selection(app, true, app.config.list.wrap),
+        ListKey::Up => move_selection(app, false, app.config.list.wrap),
+        ListKey::Forward => start_compose_from_list(app, PendingCompose::Forward, tx),
+        ListKey::Filter => {
+            app.filter_pending = true;
+            app.status = "Filter: s same sender · t same thread".to_string();
+        }
+        ListKey::Cancel if app.pending_compose.is_some() => {
+            app.status = match app.pending_compose {
+                Some((_, PendingCompose::Forward)) => "Forward canceled",
+                _ => "Reply canceled",
+            }
+            .to_string();
+            cancel_body_fetch(app);
+        }
+        ListKey::Cancel if !app.marked.is_empty() => {
+            app.marked.clear();
+            app.status = "Marks cleared".to_string();
+        }
+        ListKey::Cancel if app.filter.is_some() => {
+            app.filter = None;
+            app.saved_search = None;
+            app.status = "Filter cleared".to_string();
+        }
+        ListKey::Cancel if app.search.is_some() => {
+            app.search = None;
+            app.saved_search = None;
+            app.uid_validity = None;
+            spawn_refresh_list(app.config.clone(), app.current_folder.clone(), previews(app), tx.clone());
+        }
+        ListKey::Search => {
+            app.search_input = Some(String::new());
+            app.status.clear();
+        }
+        ListKey::Commands => {
+            app.command_input = Some(String::new());
+            app.status.clear();
+        }
+        ListKey::Reload => {
+            spawn_refresh_list(app.config.clone(), app.current_folder.clone(), previews(app), tx.clone());
+        }
+        ListKey::LoadOlder => load_older(app, tx),
+        ListKey::Seen => {
+            if let Some(why) = not_permitted(app, 's') {
+                app.status = format!("Can't change read state: {why}");
+            } else if let Some(m) = app.messages.get_mut(app.selected) {
+                m.seen = !m.seen;
+                app.status = if m.seen { "Marked read" } else { "Marked unread" }.to_string();
+                spawn_set_seen(app.config.clone(), app.current_folder.clone(), m.uid, m.seen, tx.clone());
+            }
+        }
+        ListKey::Delete => {
+            let uids = delete_targets(app);
+            ask_delete(app, uids, tx);
+        }
+        ListKey::Mark => {
+            if let Some(uid) = app.messages.get(app.selected).map(|m| m.uid) {
+                if !app.marked.remove(&uid) {
+                    app.marked.insert(uid);
+                }
+                app.status = format!("{} marked", app.marked.len());
+                move_selection(app, true, false);
+            }
+        }
+        ListKey::Folders => open_folder_picker(app, tx),
+        ListKey::Density => {
+            app.density = app.density.next();
+            app.status = format!("Density: {}", app.density.name());
+            // the rows were listed without their previews
+            if previews(app) && app.messages.iter().any(|m| m.preview.is_empty()) {
+                match app.search.clone() {
+                    Some(query) => spawn_search(app.config.clone(), app.current_folder.clone(), query, true, tx.clone()),
+                    None => spawn_refresh_list(app.config.clone(), app.current_folder.clone(), true, tx.clone()),
+                }
+            }
+        }
+        ListKey::Sort => {
+            let selected_uid = app.messages.get(app.selected).map(|m| m.uid);
+            app.newest_first = !app.newest_first;
+            app.sort_messages();
+            if let Some(i) = selected_uid.and_then(|uid| app.messages.iter().position(|m| m.uid == uid)) {
+                app.selected = i;
+            }
+            app.clamp_selection();
+            app.status = if app.newest_first { "Newest first" } else { "Oldest first" }.to_string();
+        }
+        ListKey::Drafts => open_drafts(app),
+        ListKey::Outbox => open_outbox(app),
+        ListKey::NextFolder => cycle_folder(app, true, tx),
+        ListKey::PrevFolder => cycle_folder(app, false, tx),
+        ListKey::Archive => {
+            if let Some(why) = feature_unavailable(app, "Archive").or_else(|| not_permitted(app, 't')) {
+                app.status = format!("Archive unavailable: {why}");
+            } else if let Some(uid) = app.messages.get(app.selected).map(|m| m.uid) {
+                spawn_remove(app, uid, true, tx);
+            }
+        }
+        ListKey::ArchiveByYear => {
+            if let Some(why) = feature_unavailable(app, "Archive by year").or_else(|| not_permitted(app, 't')) {
+                app.status = format!("Archive by year unavailable: {why}");
+            } else {
+                plan_archive_by_year(app, tx);
+            }
+        }
+        ListKey::Compare => compare_marked(app, tx),
+        ListKey::SavedSearches => open_saved_searches(app),
+        ListKey::Health => open_health(app, tx),
+        ListKey::Cancel => {}
+    }
+}
+
+fn open_selected(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if let Some(m) = app.messages.get(app.selected).cloned() {
+        // show it read right away; the server flag is set once the body loads
+        if not_permitted(app, 's').is_none() {
+            app.messages[app.selected].seen = true;
+        }
+        cancel_body_fetch(app);
+        app.view = View::Mail;
+        app.current_header = Some(m.clone());
+        app.current_delivery = None;
+        app.current_body = "Loading...".to_string();
+        app.body_rows.get_mut().clear();
+        app.other_body = None;
+        app.attachments.clear();
+        app.attachment_prompt = false;
+        app.status = format!("Fetching body (uid={})...", m.uid);
+        // a copy read before shows at once; the fetch brings attachments and any change
+        let cached = app.uid_validity.or(app.cached_validity).and_then(|v| cache::load_body(&app.config, &app.current_folder, v, m.uid));
+        app.body_from_cache = cached.is_some();
+        if let Some(text) = cached {
+            app.body_scroll = 0;
+            set_body_text(app, text);
+        }
+        app.body_fetch = Some(spawn_fetch_body(app.config.clone(), app.current_folder.clone(), app.uid_validity, m, tx.clone()));
+    }
+}
+
+fn start_compose(app: &mut App) {
+    reset_compose_new(app);
+    app.view = View::Compose;
+    app.status = "Compose".to_string();
+}
+
+// Moves the body by `rows`, stopping at the top and where its last row reaches the bottom.
+fn scroll_body(app: &mut App, rows: i32) {
+    let (cols, lines) = crossterm::terminal::size().unwrap_or((80, 24));
+    let max = crate::ui::max_scroll(app, cols, lines) as i64;
+    app.body_scroll = (app.body_scroll.min(max as usize) as i64 + rows as i64).clamp(0, max) as usize;
+}
+
+// The visible body height, what PageUp/PageDown move by.
+fn body_page(app: &App) -> i32 {
+    let lines = crossterm::terminal::size().map(|(_, h)| h).unwrap_or(24);
+    crate::ui::body_height(app, lines).max(1) as i32
+}
+
+fn handle_mail_keys(app: &mut App, code: KeyCode, mods: KeyModifiers, tx: &mpsc::UnboundedSender<AppMsg>) {
+    if handle_delete_confirm(app, code, tx) {
+        return;
+    }
+
+    if app.attachment_prompt {
+        app.attachment_prompt = false;
+        match code {
+            KeyCode::Char(c @ '1'..='9') if (c as usize - '1' as usize) < app.attachments.len() => {
+                let index = c as usize - '1' as usize;
+                app.attachment_selected = index;
+                save_selected_attachment(app, index, tx);
+            }
+            _ => app.status = "Save canceled".to_string(),
+        }
+        return;
+    }
+
+    match code {
+        _ if app.keys.is(Action::Reply, code, mods) => start_reply(app, false),
+        _ if app.keys.is(Action::Compose, code, mods) => start_compose(app),
+        KeyCode::Esc => {
+            app.view = View::List;
+            app.status = if cancel_body_fetch(app) {
+                app.current_body.clear();
+                app.body_rows.get_mut().clear();
+                "Fetch canceled".to_string()
+            } else {
+                "Back".to_string()
+            };
+            if app.config.list.refresh_on_return {
+                refresh_list(app, tx);
+            }
+        }
+        KeyCode::Tab if !app.attachments.is_empty() => {
+            app.attachment_selected = (app.attachment_selected + 1) % app.attachments.len();
+        }
+        KeyCode::Char('w') if app.body_fetch.is_some() => app.status = "Mail is still loading".to_string(),
+        KeyCode::Char('w') if app.current_header.is_some() => match default_export_path(app) {
+            Ok(path) => {
+                app.export_input = Some(path.display().to_string());
+                app.status = "Export to".to_string();
+            }
+            Err(e) => app.status = format!("Export error: {e}"),
+        },
+        KeyCode::Char('f') if mods.contains(KeyModifiers::CONTROL) => scroll_body(app, body_page(app)),
+        KeyCode::Char('b') if mods.contains(KeyModifiers::CONTROL) => scroll_body(app, -body_page(app)),
+        KeyCode::Char('f') => start_forward(app, false),
+        KeyCode::Char('F') => start_forward(app, true),
+        KeyCode::Char('x') => match app.other_body.as_mut() {
+            Some(other) => {
+                std::mem::swap(&mut app.current_body, other);
+                app.body_count = TextCount::of(&app.current_body);
+                app.body_rows.get_mut().clear();
+                app.body_scroll = 0;
+                app.status = if app.current_body.contains(DISCLAIMER_MARKER) {
+                    "Disclaimers hidden".to_string()
+                } else {
+                    "Disclaimers shown".to_string()
+                };
+            }
+            None => app.status = "No hidden disclaimers".to_string(),
+        },
+        KeyCode::Char('s') => save_selected_attachment(app, app.attachment_selected, tx),
+        KeyCode::Char('a') => match app.attachments.len() {
+            0 => app.status = "No attachments".to_string(),
+            1 => save_selected_attachment(app, 0, tx),
+            n => {
+                app.attachment_prompt = true;
+                app.status = format!("Save which attachment? (1-{}, Esc cancels)", n.min(9));
+            }
+        },
+        KeyCode::Char('j') | KeyCode::Down => scroll_body(app, 1),
+        KeyCode::Char('k') | KeyCode::Up => scroll_body(app, -1),
+        KeyCode::PageDown => scroll_body(app, body_page(app)),
+        KeyCode::PageUp => scroll_body(app, -body_page(app)),
+        KeyCode::Home => app.body_scroll = 0,
+        KeyCode::End => scroll_body(app, i32::MAX),
+        KeyCode::Char('R') => start_reply(app, true),
+        KeyCode::Char('z') => {
+            let cols = crossterm::terminal::size().map(|(w, _)| w).unwrap_or(80);
+            let before = crate::ui::body_width(app, cols);
+            app.focus_mode = !app.focus_mode;
+            let after = crate::ui::body_width(app, cols);
+            app.body_scroll = crate::ui::remap_scroll(&app.current_body, app.body_scroll, before, after);
+        }
+        KeyCode::Char('i') => open_selected_image(app, tx),
+        KeyCode::Char('d') => {
+            if let Some(uid) = app.current_header.as_ref().map(|h| h.uid) {
+                ask_delete(app, vec![uid], tx);
+            }
+        }
+        KeyCode::Char('E') => {
+            if let Some(why) = feature_unavailable(app, "Archive").or_else(|| not_permitted(app, 't')) {
+                app.status = format!("Archive unavailable: {why}");
+            } else if let Some(uid) = app.current_header.as_ref().map(|h| h.uid) {
+                spawn_remove(app, uid, true, tx);
+                cancel_body_fetch(app);
+                app.view = View::List;
+            }
+        }
+        KeyCode::Char('o') => {
+            // optional: refresh list while reading
+            refresh_list(app, tx);
+            app.status = "Refreshing...".to_string();
+        }
+        _ => {}
     }
-    if out.is_empty() {
-        out.push_str("> ");
+}
+
+fn send_compose(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    app.sending_draft = if app.config.compose.delete_draft_on_send {
+        app.compose.draft_path.clone()
+    } else {
+        None
+    };
+
+    spawn_send_mail(app.config.clone(), compose_message(&app.compose), tx.clone());
+}
+
+fn compose_message(c: &ComposeState) -> OutgoingMessage {
+    OutgoingMessage {
+        to: c.to.clone(),
+        cc: c.cc.clone(),
+        bcc: c.bcc.clone(),
+        reply_to: c.reply_to.clone(),
+        subject: c.subject.clone(),
+        body: compose_full_body(&c.body, &c.quote),
+        undisclosed: c.undisclosed,
+        high_priority: c.high_priority,
+        in_reply_to: c.in_reply_to.clone(),
+        references: c.references.clone(),
+        attached_message: c.attached_message.clone(),
+        attachments: c.attachments.clone(),
     }
-    out
 }
 
-fn make_reply_quote(header: &MessageSummary, body: &str) -> String {
-    let from = if header.from.is_empty() { "(unknown)" } else { header.from.as_str() };
-    let date = if header.date.is_empty() { "(unknown date)" } else { header.date.as_str() };
-    let intro = format!("On {date}, {from} wrote:");
-    let quoted = quote_lines(body);
-    format!("{intro}\n{quoted}")
+fn compose_draft(c: &ComposeState) -> Draft {
+    Draft {
+        to: c.to.clone(),
+        cc: c.cc.clone(),
+        bcc: c.bcc.clone(),
+        reply_to: c.reply_to.clone(),
+        subject: c.subject.clone(),
+        body: c.body.clone(),
+        quote: c.quote.clone(),
+        high_priority: c.high_priority,
+        in_reply_to: c.in_reply_to.clone(),
+        references: c.references.clone(),
+        attachments: c.attachments.clone(),
+    }
 }
 
-fn start_reply(app: &mut App) {
-    let Some(h) = app.current_header.clone() else {
-        app.status = "No mail selected".to_string();
-        return;
+// Saves locally, then, with `[folders] drafts` set, replaces the server copy as well.
+fn save_draft(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) -> bool {
+    let draft = compose_draft(&app.compose);
+    let path = match drafts::save(&draft, app.compose.draft_path.as_deref()) {
+        Ok(path) => path,
+        Err(e) => {
+            app.status = format!("Draft error: {e}");
+            return false;
+        }
     };
+    app.status = if app.compose.attached_message.is_some() {
+        "Draft saved (without the attached original)"
+    } else {
+        "Draft saved"
+    }
+    .to_string();
 
-    if app.current_body.trim().is_empty() || app.current_body.trim() == "Loading..." {
-        app.status = "Mail is still loading".to_string();
-        return;
+    let folder = app.config.folders.drafts.trim().to_string();
+    if !folder.is_empty() {
+        let id = draft_message_id(&path, &app.config.user.email);
+        let (config, msg) = (app.config.clone(), compose_message(&app.compose));
+        spawn_worker(tx.clone(), "Saving the draft", AppMsg::Status, move |tx| {
+            let res = mail::smtp::draft(&config.user, &msg, &id)
+                .and_then(|raw| mail::imap::save_draft(&config.imap, &folder, &id, &raw));
+            let _ = tx.send(AppMsg::Status(match res {
+                Ok(()) => format!("Draft saved, also to {folder}"),
+                Err(e) => format!("Draft saved locally; copying it to {folder} failed: {e}"),
+            }));
+        });
     }
+    app.compose.draft_path = Some(path);
+    true
+}
 
-    app.compose.to = extract_reply_to(&h.from);
-    app.compose.subject = make_reply_subject(&h.subject);
+// The server copy of a draft is found again by this, derived from the local file name.
+fn draft_message_id(path: &std::path::Path, email: &str) -> String {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let domain = email.rsplit_once('@').map_or("zenmail.invalid", |(_, d)| d.trim_end_matches('>'));
+    format!("<zenmail-draft-{stem}@{domain}>")
+}
 
-    app.compose.body.clear(); // user writes reply here (top)
-    app.compose.quote = make_reply_quote(&h, &app.current_body); // quote below
-    app.compose.focus = ComposeField::Body;
+// Deletes the draft file and, in the background, its copy in `[folders] drafts`.
+fn forget_draft(app: &App, path: &std::path::Path, tx: &mpsc::UnboundedSender<AppMsg>) -> Result<()> {
+    drafts::delete(path)?;
+    let folder = app.config.folders.drafts.trim().to_string();
+    if !folder.is_empty() {
+        let id = draft_message_id(path, &app.config.user.email);
+        let config = app.config.clone();
+        spawn_worker(tx.clone(), "Removing the draft", AppMsg::Status, move |tx| {
+            if let Err(e) = mail::imap::remove_draft(&config.imap, &folder, &id) {
+                let _ = tx.send(AppMsg::Status(format!("Removing the draft from {folder} failed: {e}")));
+            }
+        });
+    }
+    Ok(())
+}
 
-    app.view = View::Compose;
-    app.status = "Reply".to_string();
+// Anything typed that Esc would throw away.
+fn compose_has_content(c: &ComposeState) -> bool {
+    [&c.to, &c.cc, &c.bcc, &c.reply_to, &c.subject, &c.body].iter().any(|s| !s.trim().is_empty()) || !c.attachments.is_empty()
 }
 
-fn handle_list_keys(app: &mut App, code: KeyCode, _mods: KeyModifiers, tx: &mpsc::UnboundedSender<AppMsg>) {
+fn handle_discard_confirm(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    app.compose.confirm_discard = false;
     match code {
-        KeyCode::Char('j') | KeyCode::Down => {
-            if !app.messages.is_empty() {
-                app.selected = (app.selected + 1).min(app.messages.len() - 1);
+        KeyCode::Char('d') => {
+            app.view = View::List;
+            app.status = "Compose canceled".to_string();
+        }
+        KeyCode::Char('s') => {
+            if save_draft(app, tx) {
+                app.view = View::List;
             }
         }
+        _ => app.status = "Compose".to_string(),
+    }
+}
+
+fn open_drafts(app: &mut App) {
+    match drafts::list() {
+        Ok(list) => {
+            app.drafts = list;
+            app.draft_selected = 0;
+            app.view = View::Drafts;
+            app.status = format!("{} draft(s)", app.drafts.len());
+        }
+        Err(e) => app.status = format!("Draft error: {e}"),
+    }
+}
+
+fn resume_draft(app: &mut App, path: Option<PathBuf>, draft: Draft) {
+    reset_compose_new(app);
+    app.compose.to = draft.to;
+    app.compose.cc = draft.cc;
+    app.compose.bcc = draft.bcc;
+    app.compose.reply_to = draft.reply_to;
+    app.compose.subject = draft.subject;
+    app.compose.body = draft.body;
+    app.compose.quote = draft.quote;
+    app.compose.high_priority = draft.high_priority;
+    app.compose.in_reply_to = draft.in_reply_to;
+    app.compose.references = draft.references;
+    app.compose.attachment_sizes = draft.attachments.iter()
+        .filter_map(|p| Some((p.clone(), std::fs::metadata(p).ok()?.len())))
+        .collect();
+    app.compose.attachments = draft.attachments;
+    app.compose.draft_path = path;
+    app.compose.focus = ComposeField::Body; // carry on writing where it was left
+    app.view = View::Compose;
+    app.status = "Draft".to_string();
+}
+
+fn handle_auth_keys(app: &mut App, code: KeyCode, mods: KeyModifiers, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let remember = mods.contains(KeyModifiers::CONTROL) && code == KeyCode::Char('k');
+    match code {
+        KeyCode::Esc => {
+            app.auth_input.clear();
+            app.view = View::List;
+            app.status = "Login still failing (o to try again)".to_string();
+        }
+        _ if (code == KeyCode::Enter || remember) && !app.auth_checking && !app.auth_input.is_empty() => {
+            // only for this session; it goes ahead of a failing password_cmd or file too
+            app.config.imap.session_password = Some(std::mem::take(&mut app.auth_input));
+            app.auth_checking = true;
+            app.auth_remember = remember;
+            spawn_check_login(app.config.clone(), tx.clone());
+        }
+        _ if remember => {}
+        KeyCode::Backspace => {
+            app.auth_input.pop();
+        }
+        KeyCode::Char(c) => app.auth_input.push(c),
+        _ => {}
+    }
+}
+
+fn handle_capabilities_keys(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    match code {
+        KeyCode::Esc => {
+            app.view = View::List;
+            app.status = "Back".to_string();
+        }
+        KeyCode::Char('o') => {
+            refresh_capabilities(app, tx);
+            app.status = "Asking the server...".to_string();
+        }
+        _ => {}
+    }
+}
+
+fn handle_draft_keys(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    match code {
+        KeyCode::Esc => {
+            app.view = View::List;
+            app.status = "Back".to_string();
+        }
+        KeyCode::Char('j') | KeyCode::Down if !app.drafts.is_empty() => {
+            app.draft_selected = (app.draft_selected + 1).min(app.drafts.len() - 1);
+        }
         KeyCode::Char('k') | KeyCode::Up => {
-            app.selected = clamp_dec(app.selected);
+            app.draft_selected = clamp_dec(app.draft_selected);
         }
-        KeyCode::Enter => {
-            if let Some(m) = app.messages.get(app.selected).cloned() {
-                app.view = View::Mail;
-                app.current_header = Some(m.clone());
-                app.current_body = "Loading...".to_string();
-                spawn_fetch_body(app.config.clone(), m, tx.clone());
+        KeyCode::Enter if app.draft_selected < app.drafts.len() => {
+            let (path, draft) = app.drafts.remove(app.draft_selected);
+            resume_draft(app, Some(path), draft);
+        }
+        KeyCode::Char('d') if app.draft_selected < app.drafts.len() => {
+            let (path, _) = app.drafts.remove(app.draft_selected);
+            app.status = match forget_draft(app, &path, tx) {
+                Ok(_) => "Draft deleted".to_string(),
+                Err(e) => format!("Draft error: {e}"),
+            };
+            app.draft_selected = app.draft_selected.min(app.drafts.len().saturating_sub(1));
+        }
+        _ => {}
+    }
+}
+
+fn handle_guard_confirm(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    app.compose.confirm_guard = false;
+    if code == KeyCode::Char('y') {
+        confirm_bulk_or_send(app, tx);
+    } else {
+        app.compose.send_at = None;
+        app.status = "Send canceled".to_string();
+    }
+}
+
+fn handle_bulk_confirm(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    app.compose.confirm_bulk = false;
+    match code {
+        KeyCode::Char('y') => send_or_queue(app, tx),
+        KeyCode::Char('b') => {
+            app.compose.undisclosed = true;
+            send_or_queue(app, tx);
+        }
+        _ => {
+            app.compose.send_at = None;
+            app.status = "Send canceled".to_string();
+        }
+    }
+}
+
+// The address history behind completion: senders as of their mail's date.
+fn remember_senders(app: &mut App, list: &[MessageSummary]) {
+    let mut changed = false;
+    for m in list {
+        changed |= contacts::remember(&mut app.contacts, &m.from, m.timestamp.unwrap_or(0));
+    }
+    if changed {
+        let _ = contacts::save(&app.contacts); // only a convenience; not worth an error
+    }
+}
+
+fn send_or_queue(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let now = Local::now().timestamp();
+    let c = &app.compose;
+    let mut changed = false;
+    for addr in [&c.to, &c.cc, &c.bcc].into_iter().flat_map(|f| mail::smtp::split_addresses(f)) {
+        changed |= contacts::remember(&mut app.contacts, addr, now);
+    }
+    if changed {
+        let _ = contacts::save(&app.contacts);
+    }
+    match app.compose.send_at.take() {
+        Some(at) => schedule_compose(app, at, tx),
+        None => send_compose(app, tx),
+    }
+}
+
+fn schedule_compose(app: &mut App, at: i64, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let scheduled = outbox::Scheduled { at, undisclosed: app.compose.undisclosed, mail: compose_draft(&app.compose) };
+    if let Err(e) = outbox::add(&scheduled) {
+        app.status = format!("Outbox error: {e}");
+        return;
+    }
+    app.status = format!("Scheduled for {} (S shows the outbox)", scheduled.local_time());
+    if app.config.compose.delete_draft_on_send {
+        if let Some(path) = app.compose.draft_path.take() {
+            if let Err(e) = forget_draft(app, &path, tx) {
+                app.status = format!("Scheduled, but removing the draft failed: {e}");
             }
         }
-        KeyCode::Char('o') => {
-            spawn_refresh_list(app.config.clone(), tx.clone());
+    }
+    reload_outbox(app);
+    app.view = View::List;
+}
+
+fn handle_schedule_keys(app: &mut App, code: KeyCode) {
+    let Some(input) = app.compose.schedule_input.as_mut() else { return };
+    match code {
+        KeyCode::Esc => {
+            app.compose.schedule_input = None;
+            app.status = "Compose".to_string();
+        }
+        KeyCode::Tab => {
+            let next = outbox::PRESETS.iter().position(|p| p == input).map_or(0, |i| (i + 1) % outbox::PRESETS.len());
+            *input = outbox::PRESETS[next].to_string();
+        }
+        KeyCode::Backspace => {
+            input.pop();
         }
-        KeyCode::Char('c') => {
-            reset_compose_new(app);
-            app.view = View::Compose;
+        KeyCode::Char(c) => input.push(c),
+        KeyCode::Enter => match outbox::parse_when(input, Local::now()) {
+            Ok(at) => {
+                app.compose.schedule_input = None;
+                app.compose.send_at = Some(at.timestamp());
+                try_send(app);
+            }
+            Err(e) => app.status = e.to_string(),
+        },
+        _ => {}
+    }
+}
+
+fn handle_attach_keys(app: &mut App, code: KeyCode) {
+    let Some(input) = app.compose.attach_input.as_mut() else { return };
+    match code {
+        KeyCode::Esc => {
+            app.compose.attach_input = None;
             app.status = "Compose".to_string();
         }
+        KeyCode::Tab => {
+            let (completed, matches) = complete_path(input);
+            *input = completed;
+            app.status = match matches.len() {
+                0 => "No match".to_string(),
+                1 => String::new(),
+                n if n <= 6 => matches.join("  "),
+                n => format!("{}  … ({n} matches)", matches[..6].join("  ")),
+            };
+        }
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        KeyCode::Enter => {
+            let input = input.trim().to_string();
+            match attachable(&input, app.config.compose.max_attachment_mb) {
+                Ok((path, size)) => {
+                    app.status = format!("Attached {}", path.display());
+                    app.compose.attachment_sizes.insert(path.clone(), size);
+                    app.compose.attachments.push(path);
+                    app.compose.attach_input = None;
+                }
+                Err(e) => app.status = e.to_string(),
+            }
+        }
         _ => {}
     }
 }
 
-fn handle_mail_keys(app: &mut App, code: KeyCode, _mods: KeyModifiers, tx: &mpsc::UnboundedSender<AppMsg>) {
+// The file at `input`, if it is a readable regular file within the size limit. Only
+// its size is looked at here, and returned; the contents are read when the mail is sent.
+fn attachable(input: &str, max_mb: u64) -> Result<(PathBuf, u64)> {
+    if input.is_empty() {
+        return Err(anyhow!("No file given"));
+    }
+    let path = expand_home(input)?;
+    let meta = std::fs::metadata(&path).map_err(|e| anyhow!("{input}: {e}"))?;
+    if !meta.is_file() {
+        return Err(anyhow!("{input} is not a file"));
+    }
+    let max = max_mb * 1024 * 1024;
+    if meta.len() > max {
+        return Err(anyhow!(
+            "{input} is {:.1} MB; the limit is {max_mb} MB ([compose] max_attachment_mb)",
+            meta.len() as f64 / (1024.0 * 1024.0)
+        ));
+    }
+    Ok((path, meta.len()))
+}
+
+// Completes the last path component of `input` as far as the entries of its
+// directory agree, adding `/` to a directory. Also returns the matching names.
+fn complete_path(input: &str) -> (String, Vec<String>) {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(i) => input.split_at(i + 1),
+        None => ("", input),
+    };
+    let Ok(entries) = expand_home(if dir.is_empty() { "." } else { dir }).and_then(|d| Ok(std::fs::read_dir(d)?)) else {
+        return (input.to_string(), vec![]);
+    };
+    let mut matches: Vec<(String, bool)> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            let hidden_ok = prefix.starts_with('.') || !name.starts_with('.');
+            (name.starts_with(prefix) && hidden_ok).then(|| (name, e.path().is_dir()))
+        })
+        .collect();
+    matches.sort();
+
+    let completed = match matches.as_slice() {
+        [] => prefix.to_string(),
+        [(name, is_dir)] => format!("{name}{}", if *is_dir { "/" } else { "" }),
+        [(first, _), rest @ ..] => {
+            let mut common = first.clone();
+            for (name, _) in rest {
+                while !name.starts_with(&common) {
+                    common.pop();
+                }
+            }
+            common
+        }
+    };
+    (format!("{dir}{completed}"), matches.into_iter().map(|(n, _)| n).collect())
+}
+
+// `=`: fetches both marked messages and diffs their text, older against newer.
+fn compare_marked(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let mut pair: Vec<MessageSummary> = app.messages.iter().filter(|m| app.marked.contains(&m.uid)).cloned().collect();
+    pair.sort_by_key(|m| (m.timestamp, m.uid));
+    let Ok([old, new]) = <[MessageSummary; 2]>::try_from(pair) else {
+        app.status = "Mark exactly two messages (Space) to compare them".to_string();
+        return;
+    };
+
+    let config = app.config.clone();
+    let mailbox = app.current_folder.clone();
+    app.status = "Comparing...".to_string();
+    spawn_worker(tx.clone(), "Comparing", AppMsg::Status, move |tx| {
+        let max_bytes = config.view.max_message_mb * 1024 * 1024;
+        let fetch = |uid| mail::imap::fetch_body_plain(&config.imap, &mailbox, uid, max_bytes, &AtomicBool::new(false), |_| {});
+        let msg = match fetch(old.uid).and_then(|a| Ok((a, fetch(new.uid)?))) {
+            Ok((a, b)) => {
+                let lines = diff::lines(&a.text, &b.text);
+                AppMsg::List(ListEvent::Compared(Box::new(DiffState { old, new, lines, scroll: 0 })))
+            }
+            Err(e) => AppMsg::Status(format!("Compare failed: {e}")),
+        };
+        let _ = tx.send(msg);
+    });
+}
+
+fn handle_diff_keys(app: &mut App, code: KeyCode) {
+    let Some(d) = app.diff.as_mut() else { return };
+    let lines = crossterm::terminal::size().map(|(_, h)| h).unwrap_or(24);
+    let page = crate::ui::diff_height(lines).max(1) as i64;
+    let max = (d.lines.len() as i64 - page).clamp(0, u16::MAX as i64);
+    let by = match code {
+        KeyCode::Esc => {
+            app.diff = None;
+            app.view = View::List;
+            app.status = "Back".to_string();
+            return;
+        }
+        KeyCode::Char('j') | KeyCode::Down => 1,
+        KeyCode::Char('k') | KeyCode::Up => -1,
+        KeyCode::PageDown => page,
+        KeyCode::PageUp => -page,
+        KeyCode::Home => -max,
+        KeyCode::End => max,
+        _ => return,
+    };
+    d.scroll = (d.scroll as i64 + by).clamp(0, max) as u16;
+}
+
+fn handle_outbox_keys(app: &mut App, code: KeyCode) {
+    let selected = app.outbox.get(app.outbox_selected);
+    let inflight = selected.is_some_and(|e| app.outbox_inflight.contains(&e.path));
     match code {
         KeyCode::Esc => {
             app.view = View::List;
             app.status = "Back".to_string();
         }
-        KeyCode::Char('j') | KeyCode::Down => {
-            app.body_scroll = app.body_scroll.saturating_add(1);
+        KeyCode::Char('j') | KeyCode::Down if !app.outbox.is_empty() => {
+            app.outbox_selected = (app.outbox_selected + 1).min(app.outbox.len() - 1);
         }
         KeyCode::Char('k') | KeyCode::Up => {
-            app.body_scroll = app.body_scroll.saturating_sub(1);
+            app.outbox_selected = clamp_dec(app.outbox_selected);
         }
-        KeyCode::Char('c') => {
-            reset_compose_new(app);
-            app.view = View::Compose;
-            app.status = "Compose".to_string();
+        KeyCode::Enter | KeyCode::Char('d') if inflight => {
+            app.status = "That mail is being sent right now".to_string();
         }
-        KeyCode::Char('r') => {
-            start_reply(app);
+        KeyCode::Enter | KeyCode::Char('d') if selected.is_some() => {
+            let entry = app.outbox.remove(app.outbox_selected);
+            if let Err(e) = outbox::delete(&entry.path) {
+                app.status = format!("Outbox error: {e}");
+                reload_outbox(app);
+                return;
+            }
+            if code == KeyCode::Enter {
+                resume_draft(app, None, entry.mail.mail);
+                app.compose.undisclosed = entry.mail.undisclosed;
+                app.status = "Unscheduled; Ctrl+L schedules it again".to_string();
+            } else {
+                app.status = "Scheduled mail canceled".to_string();
+            }
+            reload_outbox(app);
         }
-        KeyCode::Char('o') => {
-            // optional: refresh list while reading
-            spawn_refresh_list(app.config.clone(), tx.clone());
-            app.status = "Refreshing...".to_string();
+        _ => {}
+    }
+}
+
+fn open_outbox(app: &mut App) {
+    reload_outbox(app);
+    app.view = View::Outbox;
+    app.status = format!("{} scheduled", app.outbox.len());
+}
+
+// Expands group names in one recipient field and drops addresses the fields above
+// it already have (To, then Cc, then Bcc); on an error the field is left as typed.
+fn expand_field_groups(app: &mut App, field: ComposeField) -> bool {
+    let c = &mut app.compose;
+    let (name, text, earlier) = match field {
+        ComposeField::To => ("To", &mut c.to, vec![]),
+        ComposeField::Cc => ("Cc", &mut c.cc, vec![c.to.as_str()]),
+        ComposeField::Bcc => ("Bcc", &mut c.bcc, vec![c.to.as_str(), c.cc.as_str()]),
+        ComposeField::ReplyTo | ComposeField::Subject | ComposeField::Body => return true,
+    };
+    match mail::smtp::expand_groups(&app.config.groups, text) {
+        Ok(expanded) => {
+            *text = mail::smtp::drop_listed(&expanded, &earlier);
+            true
+        }
+        Err(e) => {
+            app.status = format!("{name}: {e}");
+            false
+        }
+    }
+}
+
+fn next_compose_field(f: ComposeField) -> ComposeField {
+    match f {
+        ComposeField::To => ComposeField::Cc,
+        ComposeField::Cc => ComposeField::Bcc,
+        ComposeField::Bcc => ComposeField::ReplyTo,
+        ComposeField::ReplyTo => ComposeField::Subject,
+        ComposeField::Subject => ComposeField::Body,
+        ComposeField::Body => ComposeField::To,
+    }
+}
+
+fn compose_text(c: &mut ComposeState) -> &mut String {
+    match c.focus {
+        ComposeField::To => &mut c.to,
+        ComposeField::Cc => &mut c.cc,
+        ComposeField::Bcc => &mut c.bcc,
+        ComposeField::ReplyTo => &mut c.reply_to,
+        ComposeField::Subject => &mut c.subject,
+        ComposeField::Body => &mut c.body,
+    }
+}
+
+// Checks the recipients and subject, then sends, or queues with `send_at` set.
+fn try_send(app: &mut App) {
+    let fields = [ComposeField::To, ComposeField::Cc, ComposeField::Bcc];
+    if !fields.iter().all(|&f| expand_field_groups(app, f)) {
+        app.compose.send_at = None;
+        return;
+    }
+    let c = &app.compose;
+    let count = [&c.to, &c.cc, &c.bcc].iter().map(|f| mail::smtp::split_addresses(f).count()).sum::<usize>();
+    let problem = if count == 0 {
+        Some("No recipients")
+    } else if c.subject.trim().is_empty() {
+        Some("Subject is empty")
+    } else {
+        None
+    };
+    if let Some(p) = problem {
+        app.compose.send_at = None;
+        app.status = p.to_string();
+        return;
+    }
+    // caught here rather than as an SMTP error after connecting
+    let invalid = [
+        (ComposeField::To, &c.to),
+        (ComposeField::Cc, &c.cc),
+        (ComposeField::Bcc, &c.bcc),
+        (ComposeField::ReplyTo, &c.reply_to),
+    ]
+    .into_iter()
+    .find_map(|(field, text)| mail::smtp::validate_addresses(text).err().map(|addr| (field, addr)));
+    if let Some((field, addr)) = invalid {
+        app.compose.send_at = None;
+        app.compose.focus = field;
+        app.compose.cursor = usize::MAX;
+        app.status = format!("Invalid address: {addr}");
+        return;
+    }
+
+    app.compose.preview = Some(0);
+    app.status = "Send this? y send · n keep editing".to_string();
+}
+
+/// What the send preview points out: a mentioned attachment that is missing.
+pub fn send_warnings(app: &App) -> Vec<String> {
+    let c = &app.compose;
+    let mut out = Vec::new();
+
+    if c.attachments.is_empty() && c.attached_message.is_none() {
+        let text = c.body.lines().filter(|l| !l.trim_start().starts_with('>')).collect::<Vec<_>>().join("\n").to_lowercase();
+        let mentioned = app.config.compose.attach_keywords.iter().find(|k| !k.is_empty() && text.contains(&k.to_lowercase()));
+        if let Some(k) = mentioned {
+            out.push(format!("The text mentions {k:?} but nothing is attached"));
+        }
+    }
+    out
+}
+
+fn handle_preview_keys(app: &mut App, code: KeyCode, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let Some(scroll) = app.compose.preview else { return };
+    match code {
+        KeyCode::Char('y') => {
+            app.compose.preview = None;
+            send_previewed(app, tx);
+        }
+        KeyCode::Char('n') | KeyCode::Esc => {
+            app.compose.preview = None;
+            app.compose.send_at = None;
+            app.status = "Not sent".to_string();
         }
+        KeyCode::Char('j') | KeyCode::Down => app.compose.preview = Some(scroll.saturating_add(1)),
+        KeyCode::Char('k') | KeyCode::Up => app.compose.preview = Some(scroll.saturating_sub(1)),
         _ => {}
     }
-}
+}
+
+fn send_previewed(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let c = &app.compose;
+    match guard::check(&app.config.send_guard, &c.body, &c.quote, &[&c.to, &c.cc, &c.bcc]) {
+        Ok(None) => {}
+        Ok(Some(hit)) => {
+            app.compose.confirm_guard = true;
+            app.status = format!("Matches {:?} and goes to {}. Send anyway? y send · n cancel", hit.pattern, hit.recipient);
+            return;
+        }
+        Err(e) => {
+            app.compose.send_at = None;
+            app.status = format!("Not sent: {e}");
+            return;
+        }
+    }
+    confirm_bulk_or_send(app, tx);
+}
+
+// The recipients who see each other: To and Cc. Bcc ones are never disclosed.
+fn disclosed_count(c: &ComposeState) -> usize {
+    [&c.to, &c.cc].iter().map(|f| mail::smtp::split_addresses(f).count()).sum()
+}
+
+fn confirm_bulk_or_send(app: &mut App, tx: &mpsc::UnboundedSender<AppMsg>) {
+    let count = disclosed_count(&app.compose);
+    if count > app.config.compose.max_recipients && !app.compose.undisclosed {
+        app.compose.confirm_bulk = true;
+        app.status = format!(
+            "Send to {count} recipients? y send · b send as undisclosed Bcc · n cancel"
+        );
+        return;
+    }
+
+    send_or_queue(app, tx);
+}
+
+fn handle_compose_keys(
+    app: &mut App,
+    code: KeyCode,
+    mods: KeyModifiers,
+    tx: &mpsc::UnboundedSender<AppMsg>,
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+) {
+    if app.compose.preview.is_some() {
+        handle_preview_keys(app, code, tx);
+        return;
+    }
+    if app.compose.confirm_guard {
+        handle_guard_confirm(app, code, tx);
+        return;
+    }
+    if app.compose.confirm_bulk {
+        handle_bulk_confirm(app, code, tx);
+        return;
+    }
+    if app.compose.confirm_discard {
+        handle_discard_confirm(app, code, tx);
+        return;
+    }
+
+    if app.compose.schedule_input.is_some() {
+        handle_schedule_keys(app, code);
+        return;
+    }
+    if app.compose.attach_input.is_some() {
+        handle_attach_keys(app, code);
+        return;
+    }
+
+    if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('a')) {
+        app.compose.attach_input = Some(String::new());
+        app.status = "Attach which file? Tab completes".to_string();
+        return;
+    }
+
+    if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('r')) {
+        app.status = match app.compose.attachments.pop() {
+            Some(p) => format!("Removed {}", p.display()),
+            None => "No attachments".to_string(),
+        };
+        return;
+    }
 
-fn handle_compose_keys(app: &mut App, code: KeyCode, mods: KeyModifiers, tx: &mpsc::UnboundedSender<AppMsg>) {
     if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('s')) {
-        if app.compose.to.trim().is_empty() {
-            app.status = "To is empty".to_string();
-            return;
-        }
-        if app.compose.subject.trim().is_empty() {
-            app.status = "Subject is empty".to_string();
+        try_send(app);
+        return;
+    }
+
+    if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('l')) {
+        if app.compose.attached_message.is_some() {
+            app.status = "The attached original can't wait in the outbox; send it now".to_string();
             return;
         }
+        app.compose.schedule_input = Some(String::new());
+        app.status = "Send when? Tab cycles presets".to_string();
+        return;
+    }
 
-        let full_body = compose_full_body(&app.compose);
+    if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('d')) {
+        save_draft(app, tx);
+        return;
+    }
 
-        spawn_send_mail(
-            app.config.clone(),
-            app.compose.to.clone(),
-            app.compose.subject.clone(),
-            full_body,
-            tx.clone(),
-        );
+    if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('e')) {
+        app.status = match edit_body_in_editor(&mut app.compose) {
+            Ok(()) => "Body updated from the editor".to_string(),
+            Err(e) => format!("Editor error: {e} (body unchanged)"),
+        };
+        let _ = terminal.clear();
+        return;
+    }
+
+    if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('p')) {
+        app.compose.high_priority = !app.compose.high_priority;
+        app.status = if app.compose.high_priority { "Priority: high" } else { "Priority: normal" }.to_string();
+        return;
+    }
+
+    if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('t')) {
+        app.status = if inline_quote(&mut app.compose) {
+            "Quote moved into the body: Ctrl+↑/↓ jump between quoted paragraphs, Ctrl+O splits one".to_string()
+        } else {
+            "No quote to answer inline".to_string()
+        };
+        return;
+    }
+
+    if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('o')) && app.compose.focus == ComposeField::Body {
+        let pos = app.compose.cursor;
+        match edit::split_quote(&mut app.compose.body, pos) {
+            Some(at) => app.compose.cursor = at,
+            None => app.status = "Not on a quoted line (Ctrl+T brings the quote into the body)".to_string(),
+        }
+        return;
+    }
+
+    if handle_suggestion_keys(app, code) {
         return;
     }
 
+    let body = app.compose.focus == ComposeField::Body;
     match code {
+        KeyCode::Esc if compose_has_content(&app.compose) => {
+            app.compose.confirm_discard = true;
+            app.status = "Discard, save draft, or keep editing? (d/s/Esc)".to_string();
+        }
         KeyCode::Esc => {
             app.view = View::List;
             app.status = "Compose canceled".to_string();
         }
-        KeyCode::Tab => {
-            app.compose.focus = match app.compose.focus {
-                ComposeField::To => ComposeField::Subject,
-                ComposeField::Subject => ComposeField::Body,
-                ComposeField::Body => ComposeField::To,
-            };
-        }
-        KeyCode::Backspace => match app.compose.focus {
-            ComposeField::To => {
-                app.compose.to.pop();
-            }
-            ComposeField::Subject => {
-                app.compose.subject.pop();
-            }
-            ComposeField::Body => {
-                app.compose.body.pop();
-            }
-        },
-        KeyCode::Enter => {
-            if app.compose.focus == ComposeField::Body {
-                app.compose.body.push('\n');
-            } else {
-                app.compose.focus = match app.compose.focus {
-                    ComposeField::To => ComposeField::Subject,
-                    ComposeField::Subject => ComposeField::Body,
-                    ComposeField::Body => ComposeField::Body,
-                };
-            }
-        }
-        KeyCode::Char(ch) => match app.compose.focus {
-            ComposeField::To => app.compose.to.push(ch),
-            ComposeField::Subject => app.compose.subject.push(ch),
-            ComposeField::Body => app.compose.body.push(ch),
-        },
+        KeyCode::Tab | KeyCode::Enter if !expand_field_groups(app, app.compose.focus) => {}
+        KeyCode::Tab => focus_next_compose_field(&mut app.compose),
+        KeyCode::Enter if app.compose.focus == ComposeField::Body => compose_edit(&mut app.compose, |t, p| edit::insert(t, p, '\n')),
+        KeyCode::Enter => focus_next_compose_field(&mut app.compose),
+        KeyCode::Left if mods.contains(KeyModifiers::CONTROL) => compose_move(&mut app.compose, edit::word_left),
+        KeyCode::Right if mods.contains(KeyModifiers::CONTROL) => compose_move(&mut app.compose, edit::word_right),
+        KeyCode::Left => compose_move(&mut app.compose, edit::left),
+        KeyCode::Right => compose_move(&mut app.compose, edit::right),
+        KeyCode::Up if body && mods.contains(KeyModifiers::CONTROL) => compose_move(&mut app.compose, edit::prev_quote),
+        KeyCode::Down if body && mods.contains(KeyModifiers::CONTROL) => compose_move(&mut app.compose, edit::next_quote),
+        KeyCode::Up => compose_move(&mut app.compose, edit::up),
+        KeyCode::Down => compose_move(&mut app.compose, edit::down),
+        KeyCode::Home => compose_move(&mut app.compose, edit::line_start),
+        KeyCode::End => compose_move(&mut app.compose, edit::line_end),
+        KeyCode::Backspace => compose_edit(&mut app.compose, edit::backspace),
+        KeyCode::Delete => compose_edit(&mut app.compose, edit::delete),
+        KeyCode::Char('k') if mods.contains(KeyModifiers::CONTROL) => compose_edit(&mut app.compose, edit::kill_line),
+        KeyCode::Char(ch) => compose_edit(&mut app.compose, |t, p| edit::insert(t, p, ch)),
         _ => {}
     }
 }
 
+// The address being typed in To / Cc / Bcc / Reply-To: from the last separator to the cursor.
+pub fn completion_token(c: &ComposeState) -> Option<(usize, &str)> {
+    let text = match c.focus {
+        ComposeField::To => &c.to,
+        ComposeField::Cc => &c.cc,
+        ComposeField::Bcc => &c.bcc,
+        ComposeField::ReplyTo => &c.reply_to,
+        _ => return None,
+    };
+    let before = &text[..edit::clamp(text, c.cursor)];
+    let start = before.rfind([',', ';']).map_or(0, |i| i + 1);
+    let start = start + (before.len() - start - before[start..].trim_start().len());
+    Some((start, &before[start..]))
+}
+
+/// Completions for the address being typed, most likely first.
+pub fn suggestions(app: &App) -> Vec<&Contact> {
+    match completion_token(&app.compose) {
+        Some((_, typed)) => contacts::matching(&app.contacts, typed, 5),
+        None => vec![],
+    }
+}
+
+// Up/Down pick a completion and Tab/Enter take it; false when the key isn't for them.
+fn handle_suggestion_keys(app: &mut App, code: KeyCode) -> bool {
+    let found: Vec<String> = suggestions(app).iter().map(|c| c.display()).collect();
+    if found.is_empty() {
+        return false;
+    }
+    let c = &mut app.compose;
+    let picked = c.suggestion.min(found.len() - 1);
+    match code {
+        KeyCode::Down => c.suggestion = (picked + 1) % found.len(),
+        KeyCode::Up => c.suggestion = (picked + found.len() - 1) % found.len(),
+        KeyCode::Tab | KeyCode::Enter => {
+            let Some((start, typed)) = completion_token(c) else { return false };
+            let end = start + typed.len();
+            let text = compose_text(c);
+            let entry = format!("{}, ", found[picked]);
+            text.replace_range(start..end, &entry);
+            c.cursor = start + entry.len();
+            c.suggestion = 0;
+        }
+        _ => return false,
+    }
+    true
+}
+
+fn focus_next_compose_field(c: &mut ComposeState) {
+    c.focus = next_compose_field(c.focus);
+    c.cursor = usize::MAX;
+}
+
+// Applies an edit at the cursor of the focused field.
+fn compose_edit(c: &mut ComposeState, f: impl FnOnce(&mut String, usize) -> usize) {
+    let pos = c.cursor;
+    c.cursor = f(compose_text(c), pos);
+    c.suggestion = 0;
+}
+
+fn compose_move(c: &mut ComposeState, f: impl FnOnce(&str, usize) -> usize) {
+    let pos = c.cursor;
+    c.cursor = f(compose_text(c), pos);
+}
+
+// Ctrl+T: the read-only quote becomes part of the body, so it can be answered between
+// its paragraphs; the cursor goes to its first line. False when there is no quote.
+fn inline_quote(c: &mut ComposeState) -> bool {
+    if c.quote.trim().is_empty() {
+        return false;
+    }
+    let quote = std::mem::take(&mut c.quote);
+    let body = c.body.trim_end();
+    let start = if body.is_empty() { 0 } else { body.len() + 2 };
+    c.body = compose_full_body(&c.body, &quote);
+    c.focus = ComposeField::Body;
+    c.cursor = start;
+    true
+}
+
 fn next_field(f: ConfigField) -> ConfigField {
     use ConfigField::*;
     match f {
@@ -516,12 +4242,16 @@ fn next_field(f: ConfigField) -> ConfigField {
         ImapPort => ImapUser,
         ImapUser => ImapPass,
         ImapPass => ImapStarttls,
-        ImapStarttls => SmtpHost,
+        ImapStarttls => ImapTlsVerify,
+        ImapTlsVerify => ImapCaFile,
+        ImapCaFile => SmtpHost,
         SmtpHost => SmtpPort,
         SmtpPort => SmtpUser,
         SmtpUser => SmtpPass,
         SmtpPass => SmtpStarttls,
-        SmtpStarttls => UserName,
+        SmtpStarttls => SmtpTlsVerify,
+        SmtpTlsVerify => SmtpCaFile,
+        SmtpCaFile => UserName,
         UserName => UserEmail,
         UserEmail => ImapHost,
     }
@@ -535,12 +4265,16 @@ fn prev_field(f: ConfigField) -> ConfigField {
         ImapUser => ImapPort,
         ImapPass => ImapUser,
         ImapStarttls => ImapPass,
-        SmtpHost => ImapStarttls,
+        ImapTlsVerify => ImapStarttls,
+        ImapCaFile => ImapTlsVerify,
+        SmtpHost => ImapCaFile,
         SmtpPort => SmtpHost,
         SmtpUser => SmtpPort,
         SmtpPass => SmtpUser,
         SmtpStarttls => SmtpPass,
-        UserName => SmtpStarttls,
+        SmtpTlsVerify => SmtpStarttls,
+        SmtpCaFile => SmtpTlsVerify,
+        UserName => SmtpCaFile,
         UserEmail => UserName,
     }
 }
@@ -549,25 +4283,82 @@ fn field_is_port(f: ConfigField) -> bool {
     matches!(f, ConfigField::ImapPort | ConfigField::SmtpPort)
 }
 
+// Ctrl+K on a password field: what was typed there goes to the keyring, and
+// config.toml keeps only `password_source = "keyring"`.
+fn store_in_keyring(app: &mut App) {
+    let (mail, typed) = match app.cfg_edit.focus {
+        ConfigField::ImapPass => (&mut app.config.imap, &mut app.cfg_edit.imap_pass),
+        ConfigField::SmtpPass => (&mut app.config.smtp, &mut app.cfg_edit.smtp_pass),
+        _ => {
+            app.status = "Ctrl+K works on a password field".to_string();
+            return;
+        }
+    };
+    if typed.is_empty() {
+        app.status = "Type the password first, then Ctrl+K".to_string();
+        return;
+    }
+    if let Err(e) = mail.move_to_keyring(typed) {
+        app.status = format!("{e}");
+        return;
+    }
+    typed.clear();
+    app.status = match app.config.save_to(&app.config_path) {
+        Ok(()) => "Password stored in the keyring and removed from config.toml".to_string(),
+        Err(e) => format!("Stored in the keyring, but saving config.toml failed: {e}"),
+    };
+}
+
+// Ctrl+K on the login prompt, once the password worked: it moves from the session
+// into the keyring, and config.toml keeps only `password_source = "keyring"`.
+fn keep_login_in_keyring(app: &mut App) {
+    let Some(password) = app.config.imap.session_password.take() else { return };
+    if let Err(e) = app.config.imap.move_to_keyring(&password) {
+        app.config.imap.session_password = Some(password);
+        app.status = format!("Logged in, but not stored: {e}");
+        return;
+    }
+    app.status = match app.config.save_to(&app.config_path) {
+        Ok(()) => "Logged in; password stored in the keyring and removed from config.toml".to_string(),
+        Err(e) => format!("Logged in and stored in the keyring, but saving config.toml failed: {e}"),
+    };
+}
+
+// The focused password field isn't what logs in: password_cmd or password_file is.
+fn password_elsewhere(app: &App) -> bool {
+    match app.cfg_edit.focus {
+        ConfigField::ImapPass => app.config.imap.password_source().is_some(),
+        ConfigField::SmtpPass => app.config.smtp.password_source().is_some(),
+        _ => false,
+    }
+}
+
 fn field_is_toggle(f: ConfigField) -> bool {
-    matches!(f, ConfigField::ImapStarttls | ConfigField::SmtpStarttls)
+    matches!(
+        f,
+        ConfigField::ImapStarttls | ConfigField::ImapTlsVerify | ConfigField::SmtpStarttls | ConfigField::SmtpTlsVerify
+    )
 }
 
 fn apply_cfg_edit(app: &mut App) -> Result<()> {
     let imap_port: u16 = app.cfg_edit.imap_port.parse()?;
     let smtp_port: u16 = app.cfg_edit.smtp_port.parse()?;
+    // before the hosts change, since a localhost host alters what no setting means
+    let (imap_verified, smtp_verified) = (app.config.imap.verify_certs(), app.config.smtp.verify_certs());
 
     app.config.imap.host = app.cfg_edit.imap_host.clone();
     app.config.imap.port = imap_port;
     app.config.imap.username = app.cfg_edit.imap_user.clone();
     app.config.imap.password = app.cfg_edit.imap_pass.clone();
     app.config.imap.starttls = app.cfg_edit.imap_starttls;
+    apply_tls_edit(&mut app.config.imap, imap_verified, app.cfg_edit.imap_tls_verify, &app.cfg_edit.imap_ca_file);
 
     app.config.smtp.host = app.cfg_edit.smtp_host.clone();
     app.config.smtp.port = smtp_port;
     app.config.smtp.username = app.cfg_edit.smtp_user.clone();
     app.config.smtp.password = app.cfg_edit.smtp_pass.clone();
     app.config.smtp.starttls = app.cfg_edit.smtp_starttls;
+    apply_tls_edit(&mut app.config.smtp, smtp_verified, app.cfg_edit.smtp_tls_verify, &app.cfg_edit.smtp_ca_file);
 
     app.config.user.name = app.cfg_edit.user_name.clone();
     app.config.user.email = app.cfg_edit.user_email.clone();
@@ -575,6 +4366,18 @@ fn apply_cfg_edit(app: &mut App) -> Result<()> {
     Ok(())
 }
 
+// `tls_verify` is only written out once the toggle differs from what the loaded
+// config meant (`verified`), so an untouched localhost account keeps its old
+// behavior and its notice, and an untouched toggle moved to another host gets
+// that host's default.
+fn apply_tls_edit(mail: &mut MailConfig, verified: bool, verify: bool, ca_file: &str) {
+    if verify != verified {
+        mail.tls_verify = Some(verify);
+    }
+    let ca_file = ca_file.trim();
+    mail.tls_ca_file = (!ca_file.is_empty()).then(|| ca_file.to_string());
+}
+
 fn open_in_editor(path: &std::path::Path) -> Result<()> {
     use crossterm::cursor::{Hide, MoveTo, Show};
     use crossterm::terminal::{Clear, ClearType};
@@ -605,6 +4408,66 @@ fn open_in_editor(path: &std::path::Path) -> Result<()> {
     Ok(())
 }
 
+const QUOTE_MARKER: &str = "-- zenmail: write above this line, or delete it to answer inside the quote --";
+
+// Ctrl+E: the body goes through $EDITOR, with the quote after a marker line. Left in
+// place, the quote below it is kept as it was; deleted, the whole file becomes the
+// body so replies can be interleaved, and the quote is no longer added apart. The
+// file sits in a private directory that is removed again either way.
+fn edit_body_in_editor(c: &mut ComposeState) -> Result<()> {
+    let dir = private_temp_dir("zenmail-edit")?;
+    let result = edit_body_file(c, &dir.join("body.txt"));
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+// A new directory only this user can enter, under a random name so nobody can
+// create it first; a name that is taken is simply passed over.
+fn private_temp_dir(prefix: &str) -> Result<PathBuf> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    for _ in 0..16 {
+        let dir = std::env::temp_dir().join(format!("{prefix}-{:016x}", fastrand::u64(..)));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(anyhow!("could not create a temporary directory in {}", std::env::temp_dir().display()))
+}
+
+fn edit_body_file(c: &mut ComposeState, path: &std::path::Path) -> Result<()> {
+    let mut text = c.body.clone();
+    if !c.quote.is_empty() {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&format!("{QUOTE_MARKER}\n{}", c.quote));
+    }
+    std::fs::write(path, text)?;
+
+    open_in_editor(path)?;
+
+    let edited = std::fs::read_to_string(path)?;
+    let mut body = match edited.find(QUOTE_MARKER) {
+        Some(i) if !c.quote.is_empty() => edited[..i].to_string(),
+        _ => {
+            c.quote.clear();
+            edited
+        }
+    };
+    // editors end the file with a newline the body didn't have
+    if body.ends_with('\n') && !c.body.ends_with('\n') {
+        body.pop();
+    }
+    c.body = body;
+    c.focus = ComposeField::Body;
+    c.cursor = usize::MAX;
+    Ok(())
+}
+
 fn reload_config_from_file(app: &mut App) -> Result<()> {
     let data = std::fs::read_to_string(&app.config_path)?;
     let cfg: Config = toml::from_str(&data)?;
@@ -629,21 +4492,33 @@ fn handle_config_keys(
                 }
                 app.status = "Saved config.toml".to_string();
                 app.view = app.return_view;
-                spawn_refresh_list(app.config.clone(), tx.clone());
+                app.auth_failed = false;
+                refresh_list(app, tx);
+                restart_idle(app, tx);
+                refresh_capabilities(app, tx);
             }
             Err(e) => app.status = format!("Config invalid: {e}"),
         }
         return;
     }
+    if mods.contains(KeyModifiers::CONTROL) && matches!(code, KeyCode::Char('k')) {
+        store_in_keyring(app);
+        return;
+    }
 
     match code {
         KeyCode::Esc => {
             app.view = app.return_view;
             app.status = "Back".to_string();
         }
+        // `e` falls through to the editor below, where such a password is changed
+        KeyCode::Backspace | KeyCode::Char(_) if password_elsewhere(app) && code != KeyCode::Char('e') => {
+            app.status = "This password isn't kept in config.toml as typed; change it in the editor (e)".to_string();
+        }
         KeyCode::Tab => app.cfg_edit.focus = next_field(app.cfg_edit.focus),
         KeyCode::BackTab => app.cfg_edit.focus = prev_field(app.cfg_edit.focus),
-        KeyCode::Char('e') => {
+        // paths are full of `e`s
+        KeyCode::Char('e') if !matches!(app.cfg_edit.focus, ConfigField::ImapCaFile | ConfigField::SmtpCaFile) => {
             if let Err(e) = open_in_editor(&app.config_path) {
                 app.status = format!("Editor error: {e}");
                 return;
@@ -653,31 +4528,34 @@ fn handle_config_keys(
             match reload_config_from_file(app) {
                 Ok(_) => {
                     app.status = "Reloaded config".to_string();
-                    spawn_refresh_list(app.config.clone(), tx.clone());
+                    app.auth_failed = false;
+                    refresh_list(app, tx);
+                    restart_idle(app, tx);
+                    refresh_capabilities(app, tx);
                 }
                 Err(e) => app.status = format!("Reload failed: {e}"),
             }
         }
-        KeyCode::Char(' ') => {
-            if field_is_toggle(app.cfg_edit.focus) {
-                match app.cfg_edit.focus {
-                    ConfigField::ImapStarttls => app.cfg_edit.imap_starttls = !app.cfg_edit.imap_starttls,
-                    ConfigField::SmtpStarttls => app.cfg_edit.smtp_starttls = !app.cfg_edit.smtp_starttls,
-                    _ => {}
-                }
-            }
-        }
+        KeyCode::Char(' ') => match app.cfg_edit.focus {
+            ConfigField::ImapStarttls => app.cfg_edit.imap_starttls = !app.cfg_edit.imap_starttls,
+            ConfigField::SmtpStarttls => app.cfg_edit.smtp_starttls = !app.cfg_edit.smtp_starttls,
+            ConfigField::ImapTlsVerify => app.cfg_edit.imap_tls_verify = !app.cfg_edit.imap_tls_verify,
+            ConfigField::SmtpTlsVerify => app.cfg_edit.smtp_tls_verify = !app.cfg_edit.smtp_tls_verify,
+            _ => {}
+        },
         KeyCode::Backspace => {
             match app.cfg_edit.focus {
                 ConfigField::ImapHost => { app.cfg_edit.imap_host.pop(); }
                 ConfigField::ImapPort => { app.cfg_edit.imap_port.pop(); }
                 ConfigField::ImapUser => { app.cfg_edit.imap_user.pop(); }
                 ConfigField::ImapPass => { app.cfg_edit.imap_pass.pop(); }
+                ConfigField::ImapCaFile => { app.cfg_edit.imap_ca_file.pop(); }
 
                 ConfigField::SmtpHost => { app.cfg_edit.smtp_host.pop(); }
                 ConfigField::SmtpPort => { app.cfg_edit.smtp_port.pop(); }
                 ConfigField::SmtpUser => { app.cfg_edit.smtp_user.pop(); }
                 ConfigField::SmtpPass => { app.cfg_edit.smtp_pass.pop(); }
+                ConfigField::SmtpCaFile => { app.cfg_edit.smtp_ca_file.pop(); }
 
                 ConfigField::UserName => { app.cfg_edit.user_name.pop(); }
                 ConfigField::UserEmail => { app.cfg_edit.user_email.pop(); }
@@ -698,10 +4576,12 @@ fn handle_config_keys(
                 ConfigField::ImapPort => app.cfg_edit.imap_port.push(ch),
                 ConfigField::ImapUser => app.cfg_edit.imap_user.push(ch),
                 ConfigField::ImapPass => app.cfg_edit.imap_pass.push(ch),
+                ConfigField::ImapCaFile => app.cfg_edit.imap_ca_file.push(ch),
                 ConfigField::SmtpHost => app.cfg_edit.smtp_host.push(ch),
                 ConfigField::SmtpPort => app.cfg_edit.smtp_port.push(ch),
                 ConfigField::SmtpUser => app.cfg_edit.smtp_user.push(ch),
                 ConfigField::SmtpPass => app.cfg_edit.smtp_pass.push(ch),
+                ConfigField::SmtpCaFile => app.cfg_edit.smtp_ca_file.push(ch),
                 ConfigField::UserName => app.cfg_edit.user_name.push(ch),
                 ConfigField::UserEmail => app.cfg_edit.user_email.push(ch),
                 _ => {}
@@ -710,3 +4590,693 @@ fn handle_config_keys(
         _ => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The tests play the server: what a worker would have sent back is handed to the
+    // same event handlers the main loop uses.
+    fn app() -> App {
+        let config = Config::for_tests();
+        let (keys, _) = KeyMap::load(&config.keys);
+        App::new(config, Config::path().unwrap(), keys, false)
+    }
+
+    fn summary(uid: u32, from: &str, subject: &str) -> MessageSummary {
+        MessageSummary {
+            uid,
+            from: from.into(),
+            to: "me@example.org".into(),
+            cc: String::new(),
+            reply_to: from.into(),
+            date: String::new(),
+            timestamp: Some(1_700_000_000 + uid as i64),
+            subject: subject.into(),
+            high_priority: false,
+            seen: false,
+            preview: String::new(),
+        }
+    }
+
+    // The screen as text, one string per row.
+    fn screen(app: &App) -> Vec<String> {
+        screen_sized(app, 100, 12)
+    }
+
+    fn screen_sized(app: &App, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| crate::ui::draw(f, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer.get(x, y).symbol()).collect())
+            .collect()
+    }
+
+    fn saved(name: &str, folder: &str, query: &str, from: &str, newest_first: bool) -> SavedSearch {
+        SavedSearch {
+            name: name.into(),
+            folder: folder.into(),
+            query: query.into(),
+            from: from.into(),
+            thread: String::new(),
+            newest_first,
+        }
+    }
+
+    #[test]
+    fn saved_searches_survive_a_config_round_trip() {
+        let mut config = Config::for_tests();
+        config.saved_searches =
+            vec![saved("Invoices", "Invoices", "has:attachment", "", false), saved("Boss", "INBOX", "", "boss@corp.com", true)];
+        let text = toml::to_string(&config).unwrap();
+        let back: Config = toml::from_str(&text).unwrap();
+        let fields = |s: &SavedSearch| (s.name.clone(), s.folder.clone(), s.query.clone(), s.from.clone(), s.thread.clone(), s.newest_first);
+        assert_eq!(back.saved_searches.iter().map(fields).collect::<Vec<_>>(), config.saved_searches.iter().map(fields).collect::<Vec<_>>());
+        // empty parts stay out of the file
+        assert!(!text.contains("thread ="));
+    }
+
+    #[tokio::test]
+    async fn applying_a_saved_search_sets_every_part_at_once() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        app.config.saved_searches =
+            vec![saved("Invoices", "Invoices", "has:attachment", "", false), saved("Boss", "INBOX", "", "boss@corp.com", true)];
+        app.filter = Some(ListFilter::Thread("old".into()));
+
+        apply_saved_search(&mut app, 0, &tx);
+        assert_eq!(app.current_folder, "Invoices");
+        assert!(!app.newest_first);
+        assert!(app.filter.is_none());
+        assert_eq!(app.saved_search.as_deref(), Some("Invoices"));
+        assert!(matches!(rx.try_recv(), Ok(AppMsg::Status(s)) if s == "Searching Invoices..."));
+
+        let list = vec![summary(7, "shop@x.com", "Invoice 7"), summary(9, "shop@x.com", "Invoice 9")];
+        on_list_event(&mut app, ListEvent::Searched { mailbox: "Invoices".into(), query: "has:attachment".into(), total: 2, list }, &tx);
+        assert_eq!(app.messages.iter().map(|m| m.uid).collect::<Vec<_>>(), [7, 9]);
+        assert!(screen(&app)[0].contains("Invoices · Invoices · search \"has:attachment\""));
+
+        // the second one lists the whole folder, filtered by sender
+        apply_saved_search(&mut app, 1, &tx);
+        assert_eq!(app.current_folder, "INBOX");
+        assert!(app.search.is_none());
+        assert!(app.filter == Some(ListFilter::Sender("boss@corp.com".into())));
+        assert!(app.newest_first);
+
+        apply_saved_search(&mut app, 5, &tx);
+        assert_eq!(app.status, "No saved search 6 (~ lists them)");
+        assert_eq!(app.saved_search.as_deref(), Some("Boss"));
+    }
+
+    #[tokio::test]
+    async fn a_panicking_worker_leaves_the_app_usable() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        app.refreshing = Some("INBOX".into());
+        app.loading_more = true;
+
+        // a bug in envelope parsing, say
+        spawn_worker(tx.clone(), "Fetching the list", |e| AppMsg::List(ListEvent::Crashed(e)), |_| {
+            panic!("envelope without a date");
+        });
+        let Some(AppMsg::List(ev)) = rx.recv().await else { panic!("no crash report") };
+        on_list_event(&mut app, ev, &tx);
+
+        assert_eq!(app.status, format!("Fetching the list crashed: envelope without a date (details in {PANIC_LOG})"));
+        assert!(app.refreshing.is_none());
+        assert!(!app.loading_more);
+        assert!(app.health.errors.back().is_some_and(|(_, e)| e.contains("envelope without a date")));
+
+        // and the next refresh goes out rather than waiting on the dead one
+        refresh_list(&mut app, &tx);
+        assert_eq!(app.refreshing.as_deref(), Some("INBOX"));
+    }
+
+    #[test]
+    fn panics_with_any_payload_are_reported() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        catch_panic(&tx, "Search", AppMsg::Status, |_| std::panic::panic_any(42u8));
+        assert!(matches!(rx.try_recv(), Ok(AppMsg::Status(s)) if s.starts_with("Search crashed: unknown panic")));
+        catch_panic(&tx, "Search", AppMsg::Status, |tx| {
+            let _ = tx.send(AppMsg::Status("done".into()));
+        });
+        assert!(matches!(rx.try_recv(), Ok(AppMsg::Status(s)) if s == "done"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn shutdown_flushes_every_store() {
+        let mut app = app();
+        app.config.cache.max_mb = 10;
+        let stop = Arc::new(AtomicBool::new(false));
+        app.idle_stop = Some(stop.clone());
+
+        // a folder listed by the server, with a flag changed since
+        app.current_folder = "Shutdown test".into();
+        app.uid_validity = Some(5);
+        app.messages = vec![summary(1, "a@x.com", "One"), summary(2, "b@x.com", "Two")];
+        app.messages[1].seen = true;
+
+        // a mail half written, with Styles opened over it
+        app.compose.to = "bob@x.com".into();
+        app.compose.subject = "Half written at shutdown".into();
+        app.compose.body = "Dear Bob,".into();
+        app.return_view = View::Compose;
+        app.view = View::Styles;
+
+        shutdown(&mut app);
+
+        assert!(stop.load(Ordering::Relaxed));
+        let (_, cached) = cache::load_list(&app.config, "Shutdown test").expect("list cache written");
+        assert_eq!(cached.iter().map(|m| (m.uid, m.seen)).collect::<Vec<_>>(), [(1, false), (2, true)]);
+        let drafts = drafts::list().unwrap();
+        let (path, draft) = drafts.iter().find(|(_, d)| d.subject == "Half written at shutdown").expect("draft saved");
+        assert_eq!((draft.to.as_str(), draft.body.as_str()), ("bob@x.com", "Dear Bob,"));
+        drafts::delete(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn health_counts_drafts_on_disk_and_shows_the_quota() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        app.compose.subject = "Counted by health".into();
+        let path = drafts::save(&compose_draft(&app.compose), None).unwrap();
+
+        // the drafts view was never opened, so `app.drafts` is empty
+        open_health(&mut app, &tx);
+        let kept = app.health.drafts.expect("drafts readable");
+        assert!(kept >= 1);
+        assert!(screen(&app).iter().any(|row| row.contains(&format!("Drafts      {kept} kept locally"))));
+        drafts::delete(&path).unwrap();
+
+        app.server_caps = Some(vec!["IMAP4rev1".into(), "QUOTA".into()]);
+        assert!(screen(&app).iter().any(|row| row.contains("Quota       not known yet")));
+        on_net_event(&mut app, NetEvent::Quota(Some((10 * 1024, 40 * 1024))), &tx);
+        assert!(screen(&app).iter().any(|row| row.contains("Quota       25% · 10.0 MiB of 40.0 MiB")));
+    }
+
+    #[test]
+    fn shutdown_skips_what_isnt_there() {
+        let mut app = app();
+        app.config.cache.max_mb = 10;
+        app.current_folder = "Shutdown search".into();
+        app.uid_validity = Some(5);
+        app.search = Some("invoice".into());
+        app.messages = vec![summary(1, "a@x.com", "One")];
+        // an empty compose isn't worth a draft
+        app.view = View::Compose;
+
+        let before = drafts::list().unwrap().len();
+        shutdown(&mut app);
+        // search results aren't the folder's rows
+        assert!(cache::load_list(&app.config, "Shutdown search").is_none());
+        assert_eq!(drafts::list().unwrap().len(), before);
+    }
+
+    #[test]
+    fn shutdown_steps_report_failures_and_give_up_in_time() {
+        let steps: Vec<ShutdownStep> = vec![
+            ("saving a", Box::new(|| Ok(()))),
+            ("saving b", Box::new(|| Err(anyhow!("disk full")))),
+            ("saving c", Box::new(|| {
+                std::thread::sleep(Duration::from_secs(5));
+                Ok(())
+            })),
+            ("saving d", Box::new(|| Ok(()))),
+        ];
+        let started = Instant::now();
+        let problems = run_steps(steps, Duration::from_secs(1));
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(
+            problems,
+            ["saving b failed: disk full", "saving c didn't finish within 1s", "saving d didn't finish within 1s"]
+        );
+        assert!(run_steps(vec![("saving a", Box::new(|| Ok(())))], Duration::from_secs(1)).is_empty());
+    }
+
+    fn list(poll_secs: u64, poll_max_secs: u64) -> ListConfig {
+        ListConfig { poll_secs, poll_max_secs, adaptive_poll: true, ..ListConfig::default() }
+    }
+
+    fn secs(a: Adaptive) -> u64 {
+        a.interval.as_secs()
+    }
+
+    #[test]
+    fn empty_polls_double_the_interval() {
+        let cfg = list(60, 900);
+        let a = Adaptive::new(&cfg);
+        assert_eq!(secs(a), 60);
+        let a = a.polled(&cfg, 0);
+        assert_eq!(secs(a), 120);
+        assert_eq!(secs(a.polled(&cfg, 0).polled(&cfg, 0)), 480);
+    }
+
+    #[test]
+    fn new_mail_resets_the_interval() {
+        let cfg = list(60, 900);
+        let a = Adaptive::new(&cfg).polled(&cfg, 0).polled(&cfg, 0);
+        assert_eq!(secs(a), 240);
+        assert_eq!(a.polled(&cfg, 1), Adaptive::new(&cfg));
+        assert_eq!(a.polled(&cfg, 12), Adaptive::new(&cfg));
+    }
+
+    #[test]
+    fn growth_stops_at_poll_max_secs() {
+        let cfg = list(60, 300);
+        let mut a = Adaptive::new(&cfg);
+        for _ in 0..10 {
+            a = a.polled(&cfg, 0);
+        }
+        assert_eq!(secs(a), 300);
+
+        // a cap below poll_secs leaves the interval at poll_secs
+        let cfg = list(60, 30);
+        assert_eq!(secs(Adaptive::new(&cfg).polled(&cfg, 0)), 60);
+    }
+
+    #[test]
+    fn a_refresh_by_hand_starts_over() {
+        let cfg = list(60, 900);
+        let mut poller = Poller::new();
+        poller.refreshed(&cfg, true, 0);
+        poller.refreshed(&cfg, true, 0);
+        assert_eq!(poller.base(&cfg), Duration::from_secs(240));
+        poller.refreshed(&cfg, false, 0);
+        assert_eq!(poller.base(&cfg), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn the_send_preview_shows_undisclosed_recipients() {
+        let mut app = app();
+        app.view = View::Compose;
+        app.compose.to = "a@x.com, b@y.com".into();
+        app.compose.subject = "Hello".into();
+        app.compose.preview = Some(0);
+        let rows = screen(&app);
+        assert!(rows.iter().any(|row| row.starts_with("│To: a@x.com, b@y.com ")), "{rows:#?}");
+
+        app.compose.undisclosed = true;
+        let rows = screen(&app);
+        assert!(rows.iter().any(|row| row.starts_with("│To: undisclosed-recipients:; (Bcc: a@x.com, b@y.com)")), "{rows:#?}");
+
+        app.compose.cc = "c@x.com".into();
+        let rows = screen(&app);
+        assert!(rows.iter().any(|row| row.starts_with("│To: undisclosed-recipients:; (Bcc: a@x.com, b@y.com, c@x.com)")), "{rows:#?}");
+        assert!(!rows.iter().any(|row| row.starts_with("│Cc:")), "{rows:#?}");
+    }
+
+    #[test]
+    fn a_100k_character_line_is_clamped() {
+        let blob = "QUJD".repeat(25_000);
+        let body = clamp_long_lines(format!("Hi,\n{blob}\nbye"));
+        let lines: Vec<_> = body.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], format!("{}… [{} more characters]", &blob[..MAX_LINE_CHARS], 100_000 - MAX_LINE_CHARS));
+        assert_eq!((lines[0], lines[2]), ("Hi,", "bye"));
+
+        // multi-byte characters are cut on a character boundary
+        let wide = clamp_long_lines("가".repeat(100_000));
+        assert!(wide.starts_with(&"가".repeat(MAX_LINE_CHARS)));
+        assert!(wide.ends_with(&format!("… [{} more characters]", 100_000 - MAX_LINE_CHARS)));
+
+        let short = "a".repeat(MAX_LINE_CHARS);
+        assert_eq!(clamp_long_lines(short.clone()), short);
+    }
+
+    #[test]
+    fn replies_quote_long_lines_whole() {
+        let mut app = app();
+        app.current_header = Some(summary(1, "Bob <bob@x.com>", "Key"));
+        let key = "QUJD".repeat(2_000);
+        set_body_text(&mut app, format!("The key:\n{key}"));
+        assert!(app.current_body.contains("more characters]"));
+        start_reply(&mut app, false);
+        assert!(app.compose.quote.contains(&format!("> {key}")), "{}", &app.compose.quote[..200]);
+        assert!(!app.compose.quote.contains("more characters]"));
+    }
+
+    #[test]
+    fn a_100k_character_line_renders_quickly() {
+        let mut app = app();
+        app.messages = vec![summary(1, "a@x.com", "blob")];
+        app.view = View::Mail;
+        set_body_text(&mut app, "QUJD".repeat(25_000));
+        let start = Instant::now();
+        for _ in 0..20 {
+            screen(&app);
+        }
+        assert!(start.elapsed() < Duration::from_secs(2), "{:?}", start.elapsed());
+        assert!(screen(&app).iter().any(|row| row.contains("QUJDQUJD")));
+    }
+
+    #[test]
+    fn replies_quote_the_disclaimer_that_was_hidden() {
+        let mut app = app();
+        app.config.view.disclaimer_patterns = vec!["(?s)CONFIDENTIAL.*".into()];
+        app.current_header = Some(summary(1, "Bob <bob@x.com>", "Plans"));
+        set_body_text(&mut app, "See you at 3.\n\nCONFIDENTIAL: for the addressee only.".into());
+        assert!(app.current_body.contains(DISCLAIMER_MARKER));
+
+        for forward in [false, true] {
+            if forward {
+                start_forward(&mut app, false);
+            } else {
+                start_reply(&mut app, false);
+            }
+            assert!(!app.compose.quote.contains(DISCLAIMER_MARKER), "{}", app.compose.quote);
+            assert!(app.compose.quote.contains("CONFIDENTIAL: for the addressee only."), "{}", app.compose.quote);
+        }
+    }
+
+    fn attachment(filename: &str, content_type: &str) -> Attachment {
+        Attachment { section: "2".into(), encoding: "base64".into(), filename: filename.into(), content_type: content_type.into(), size: 10 }
+    }
+
+    #[tokio::test]
+    async fn i_opens_only_the_selected_image() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        app.current_header = Some(summary(1, "a@x.com", "Photos"));
+        open_selected_image(&mut app, &tx);
+        assert_eq!(app.status, "No images in this mail");
+
+        app.attachments = vec![attachment("report.pdf", "application/pdf"), attachment("cat.jpg", "image/jpeg")];
+        open_selected_image(&mut app, &tx);
+        assert_eq!(app.status, "report.pdf is not an image; Tab selects another attachment");
+        assert!(rx.try_recv().is_err());
+
+        app.attachment_selected = 1;
+        open_selected_image(&mut app, &tx);
+        match rx.recv().await {
+            Some(AppMsg::Status(s)) => assert_eq!(s, "Fetching cat.jpg..."),
+            _ => panic!("no status"),
+        }
+    }
+
+    #[tokio::test]
+    async fn previews_are_fetched_only_when_shown() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        app.density = Density::Normal;
+        app.messages = vec![summary(1, "a@x.com", "Hi")];
+        assert!(!previews(&app));
+
+        handle_list_keys(&mut app, KeyCode::Char('v'), KeyModifiers::NONE, &tx);
+        assert!(previews(&app));
+        match rx.recv().await {
+            Some(AppMsg::Status(s)) => assert_eq!(s, "Fetching INBOX..."),
+            _ => panic!("no refetch"),
+        }
+    }
+
+    #[test]
+    fn attachment_sizes_are_read_once_not_every_frame() {
+        let path = std::env::temp_dir().join(format!("zenmail-size-{}.txt", std::process::id()));
+        std::fs::write(&path, vec![b'x'; 2048]).unwrap();
+        let mut app = app();
+        app.view = View::Compose;
+        app.compose.attach_input = Some(path.display().to_string());
+        handle_attach_keys(&mut app, KeyCode::Enter);
+        std::fs::remove_file(&path).unwrap();
+
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let rows = screen_sized(&app, 100, 16);
+        assert!(rows.iter().any(|row| row.contains(&format!("{name} (2.0 KiB)"))), "{rows:?}");
+    }
+
+    #[test]
+    fn archive_years_come_from_the_date_header() {
+        let year = |date: &str| mail::parse_date(date).and_then(date_year);
+        assert_eq!(year("Tue, 3 Mar 2020 10:00:00 +0000"), Some(2020));
+        assert_eq!(year("3 Mar 2019 10:00:00 GMT"), Some(2019));
+    }
+
+    #[test]
+    fn editor_directories_are_new_and_private() {
+        let a = private_temp_dir("zenmail-test").unwrap();
+        let b = private_temp_dir("zenmail-test").unwrap();
+        assert_ne!(a, b);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&a).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        std::fs::remove_dir(a).unwrap();
+        std::fs::remove_dir(b).unwrap();
+    }
+
+    #[test]
+    fn an_untouched_tls_toggle_follows_the_new_host() {
+        let mut app = app();
+        app.config.imap.host = "localhost".to_string();
+        app.config.imap.tls_verify = None;
+        app.cfg_edit = ConfigEditState::from_config(&app.config);
+        assert!(!app.cfg_edit.imap_tls_verify);
+
+        app.cfg_edit.imap_host = "mail.lan".to_string();
+        apply_cfg_edit(&mut app).unwrap();
+        assert_eq!(app.config.imap.tls_verify, None);
+        assert!(app.config.imap.verify_certs());
+
+        // toggled, it is written out
+        app.config.imap.host = "localhost".to_string();
+        app.cfg_edit = ConfigEditState::from_config(&app.config);
+        app.cfg_edit.imap_tls_verify = true;
+        apply_cfg_edit(&mut app).unwrap();
+        assert_eq!(app.config.imap.tls_verify, Some(true));
+    }
+
+    #[tokio::test]
+    async fn f_in_the_list_forwards_once_the_body_arrives() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        let mut m = summary(7, "Ann <ann@x.com>", "Plans");
+        m.seen = true;
+        app.messages = vec![m.clone()];
+
+        handle_list_keys(&mut app, KeyCode::Char('f'), KeyModifiers::NONE, &tx);
+        assert!(app.pending_compose == Some((7, PendingCompose::Forward)));
+        assert!(app.view == View::List);
+
+        let body = Body {
+            text: "See you at noon.".to_string(),
+            was_html: false,
+            attachments: vec![],
+            message_id: None,
+            references: None,
+            raw: None,
+            delivered_to: vec![],
+            mailing_list: false,
+        };
+        on_body_event(&mut app, BodyEvent::Loaded { header: Box::new(m), body }, &tx);
+        assert!(app.view == View::Compose);
+        assert_eq!(app.compose.subject, "Fwd: Plans");
+        assert!(app.compose.quote.contains("See you at noon."));
+        assert!(app.compose.to.is_empty());
+    }
+
+    #[tokio::test]
+    async fn esc_cancels_a_pending_forward() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        app.messages = vec![summary(7, "ann@x.com", "Plans")];
+        handle_list_keys(&mut app, KeyCode::Char('f'), KeyModifiers::NONE, &tx);
+        handle_list_keys(&mut app, KeyCode::Esc, KeyModifiers::NONE, &tx);
+        assert!(app.pending_compose.is_none() && app.body_fetch.is_none());
+        assert_eq!(app.status, "Forward canceled");
+    }
+
+    #[test]
+    fn profile_is_toggled_from_the_command_line() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        let run = |app: &mut App, command: &str| {
+            handle_list_keys(app, KeyCode::Char(':'), KeyModifiers::NONE, &tx);
+            for c in command.chars() {
+                handle_list_keys(app, KeyCode::Char(c), KeyModifiers::NONE, &tx);
+            }
+            handle_list_keys(app, KeyCode::Enter, KeyModifiers::NONE, &tx);
+        };
+
+        run(&mut app, "profile");
+        assert!(app.profile.is_some());
+        assert!(app.command_input.is_none());
+
+        run(&mut app, "bogus");
+        assert_eq!(app.status, "Unknown command :bogus (try :profile, :capabilities or :groups)");
+        assert!(app.profile.is_some());
+    }
+
+    #[test]
+    fn capabilities_open_from_the_command_line() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        app.server_caps = Some(Default::default());
+        handle_list_keys(&mut app, KeyCode::Char(':'), KeyModifiers::NONE, &tx);
+        for c in "capabilities".chars() {
+            handle_list_keys(&mut app, KeyCode::Char(c), KeyModifiers::NONE, &tx);
+        }
+        handle_list_keys(&mut app, KeyCode::Enter, KeyModifiers::NONE, &tx);
+        assert!(app.view == View::Capabilities);
+    }
+
+    #[test]
+    fn compose_shows_and_sends_a_reply_to() {
+        let mut app = app();
+        app.config.user.reply_to = Some("team@example.org".into());
+        app.view = View::Compose;
+        assert!(screen(&app).iter().any(|row| row.contains("Reply-To: (team@example.org)")), "{:?}", screen(&app));
+
+        app.compose.focus = ComposeField::ReplyTo;
+        app.compose.reply_to = "me@home.example".into();
+        assert!(screen(&app).iter().any(|row| row.contains("Reply-To: me@home.example")));
+        assert_eq!(compose_message(&app.compose).reply_to, "me@home.example");
+        assert_eq!(compose_draft(&app.compose).reply_to, "me@home.example");
+
+        app.compose.preview = Some(0);
+        assert!(screen(&app).iter().any(|row| row.contains("Reply-To: me@home.example")));
+    }
+
+    #[test]
+    fn an_inline_reply_keeps_quoted_lines_styled_apart_from_mine() {
+        let mut app = app();
+        app.view = View::Compose;
+        app.compose.body = "Hi,".into();
+        app.compose.quote = "> Lunch at noon?\n> Or later.".into();
+        assert!(inline_quote(&mut app.compose));
+        assert!(app.compose.quote.is_empty());
+        assert_eq!(app.compose.body, "Hi,\n\n> Lunch at noon?\n> Or later.");
+        assert_eq!(app.compose.cursor, app.compose.body.find('>').unwrap());
+        assert!(!inline_quote(&mut app.compose));
+
+        let pos = edit::line_end(&app.compose.body, app.compose.cursor);
+        app.compose.cursor = edit::split_quote(&mut app.compose.body, pos).unwrap();
+        for ch in "Noon works.".chars() {
+            compose_edit(&mut app.compose, |t, p| edit::insert(t, p, ch));
+        }
+        assert_eq!(compose_full_body(&app.compose.body, &app.compose.quote), "Hi,\n\n> Lunch at noon?\n\nNoon works.\n\n> Or later.");
+
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(100, 24)).unwrap();
+        terminal.draw(|f| crate::ui::draw(f, &app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let row = |text: &str| {
+            (0..buffer.area.height)
+                .find(|&y| (0..buffer.area.width).map(|x| buffer.get(x, y).symbol()).collect::<String>().contains(text))
+                .unwrap()
+        };
+        let fg = |y: u16| buffer.get(2, y).fg;
+        let quoted = crate::ui::theme::of(&app, crate::ui::theme::Role::Quote1).fg.unwrap();
+        assert_eq!(fg(row("> Lunch at noon?")), quoted);
+        assert_eq!(fg(row("> Or later.")), quoted);
+        assert_ne!(fg(row("Noon works.")), quoted);
+    }
+
+    #[test]
+    fn a_body_longer_than_65535_rows_scrolls_to_its_end() {
+        let mut app = app();
+        app.view = View::Mail;
+        set_body_text(&mut app, (1..=100_000).map(|i| format!("line {i}\n")).collect());
+        scroll_body(&mut app, i32::MAX);
+        assert!(app.body_scroll > u16::MAX as usize, "{}", app.body_scroll);
+        // taller than the terminal `scroll_body` measured, so the offset is at least the end
+        let rows = screen_sized(&app, 100, 200);
+        assert!(rows.iter().any(|row| row.contains("line 100000")));
+        assert!(!rows.iter().any(|row| row.contains("line 99000 ")));
+    }
+
+    #[test]
+    fn only_disclosed_recipients_count_toward_a_bulk_send() {
+        let mut c = app().compose;
+        c.to = "a@x.com".into();
+        c.bcc = (0..50).map(|i| format!("r{i}@x.com")).collect::<Vec<_>>().join(", ");
+        assert_eq!(disclosed_count(&c), 1);
+        c.cc = "b@x.com; c@x.com".into();
+        assert_eq!(disclosed_count(&c), 3);
+    }
+
+    #[tokio::test]
+    async fn the_login_prompt_keeps_a_password_only_when_asked() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        app.view = View::Auth;
+        assert!(screen(&app).iter().any(|row| row.contains("Ctrl+K retry, keep in keyring")), "{:?}", screen(&app));
+
+        // nothing typed yet: Ctrl+K is neither a letter nor a retry
+        handle_auth_keys(&mut app, KeyCode::Char('k'), KeyModifiers::CONTROL, &tx);
+        assert!(app.auth_input.is_empty() && !app.auth_checking);
+
+        for c in "s3cret".chars() {
+            handle_auth_keys(&mut app, KeyCode::Char(c), KeyModifiers::NONE, &tx);
+        }
+        handle_auth_keys(&mut app, KeyCode::Char('k'), KeyModifiers::CONTROL, &tx);
+        assert!(app.auth_checking && app.auth_remember);
+        assert_eq!(app.config.imap.session_password.as_deref(), Some("s3cret"));
+
+        // a rejected password is not kept anywhere
+        on_net_event(&mut app, NetEvent::AuthChecked(Err("login rejected".into())), &tx);
+        assert!(!app.auth_remember);
+        assert!(app.config.imap.password_source == crate::config::PasswordSource::Config);
+
+        app.auth_input = "other".into();
+        handle_auth_keys(&mut app, KeyCode::Enter, KeyModifiers::NONE, &tx);
+        assert!(app.auth_checking && !app.auth_remember);
+    }
+
+    #[test]
+    fn groups_expand_without_repeating_an_address_in_another_field() {
+        let mut app = app();
+        app.config.groups.insert("team".into(), vec!["a@x.com".into(), "B <b@x.com>".into()]);
+        app.compose.to = "A <A@x.com>".into();
+        app.compose.cc = "team".into();
+        app.compose.bcc = "b@x.com, team, c@x.com".into();
+        for field in [ComposeField::To, ComposeField::Cc, ComposeField::Bcc] {
+            assert!(expand_field_groups(&mut app, field));
+        }
+        assert_eq!(app.compose.cc, "B <b@x.com>");
+        assert_eq!(app.compose.bcc, "c@x.com");
+
+        app.compose.cc = "nobody".into();
+        assert!(!expand_field_groups(&mut app, ComposeField::Cc));
+        assert_eq!(app.compose.cc, "nobody");
+        assert_eq!(app.status, "Cc: unknown group \"nobody\"");
+    }
+
+    #[test]
+    fn groups_are_added_edited_and_deleted_in_the_group_list() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut app = app();
+        app.command_input = Some("groups".into());
+        handle_command_input(&mut app, KeyCode::Enter, &tx);
+        assert!(app.view == View::Groups);
+        assert!(screen(&app).iter().any(|row| row.contains("No groups yet")));
+
+        handle_group_keys(&mut app, KeyCode::Char('a'));
+        for c in "team = a@x.com, b@x.com".chars() {
+            handle_group_keys(&mut app, KeyCode::Char(c));
+        }
+        handle_group_keys(&mut app, KeyCode::Enter);
+        assert_eq!(app.config.groups["team"], ["a@x.com", "b@x.com"]);
+        assert!(screen(&app).iter().any(|row| row.contains("a@x.com, b@x.com")));
+
+        // Renaming while editing replaces the old entry; a bad entry keeps the input open.
+        handle_group_keys(&mut app, KeyCode::Enter);
+        for _ in "team".len().."team = a@x.com, b@x.com".len() {
+            handle_group_keys(&mut app, KeyCode::Backspace);
+        }
+        handle_group_keys(&mut app, KeyCode::Enter);
+        assert!(app.group_input.is_some());
+        for c in "s = c@x.com".chars() {
+            handle_group_keys(&mut app, KeyCode::Char(c));
+        }
+        handle_group_keys(&mut app, KeyCode::Enter);
+        assert!(app.group_input.is_none());
+        assert_eq!(app.config.groups.keys().collect::<Vec<_>>(), ["teams"]);
+
+        handle_group_keys(&mut app, KeyCode::Char('d'));
+        assert!(app.config.groups.is_empty());
+        handle_group_keys(&mut app, KeyCode::Esc);
+        assert!(app.view == View::List);
+    }
+}
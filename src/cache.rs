@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+use crate::config::{Config, MailConfig};
+use crate::mail::MessageSummary;
+
+// Mail kept on disk so a folder shows up before the server answers, and read mail can
+// be read again offline. Under `cache/` next to config.toml:
+//
+//   <login@host>/<mailbox>/<UIDVALIDITY>/list.toml   the folder's rows
+//   <login@host>/<mailbox>/<UIDVALIDITY>/<UID>.txt   the text of each opened mail
+//
+// A new UIDVALIDITY makes the old UIDs meaningless, so its directory is dropped. Past
+// `[cache] max_mb`, the mail opened longest ago goes first.
+
+// a file name keeps letters, digits and `@._-`; anything else becomes %XX
+fn escape(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'@' | b'.' | b'_' | b'-' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+pub fn dir() -> Result<PathBuf> {
+    let cfg = Config::path()?;
+    let parent = cfg.parent().ok_or_else(|| anyhow!("no config dir"))?;
+    Ok(parent.join("cache"))
+}
+
+fn folder_dir(imap: &MailConfig, mailbox: &str) -> Result<PathBuf> {
+    Ok(dir()?.join(escape(&format!("{}@{}", imap.username, imap.host))).join(escape(mailbox)))
+}
+
+// Writes and evictions, which may come from several workers at once. Holds the bytes
+// kept under `dir()`, counted by one walk the first time it's needed and kept up to
+// date after that.
+static WRITING: LazyLock<Mutex<Option<u64>>> = LazyLock::new(Mutex::default);
+
+#[derive(Deserialize, Serialize)]
+struct ListFile {
+    uid_validity: u32,
+    #[serde(default)]
+    messages: Vec<MessageSummary>,
+}
+
+/// The rows last seen in `mailbox`, if any were kept, with the UIDVALIDITY they were
+/// seen under.
+pub fn load_list(config: &Config, mailbox: &str) -> Option<(u32, Vec<MessageSummary>)> {
+    if config.cache.max_mb == 0 {
+        return None;
+    }
+    let folder = folder_dir(&config.imap, mailbox).ok()?;
+    // only one UIDVALIDITY directory is ever left behind by `save_list`
+    let validity = fs::read_dir(folder).ok()?.flatten().find(|e| e.path().join("list.toml").exists())?;
+    let data = fs::read_to_string(validity.path().join("list.toml")).ok()?;
+    toml::from_str::<ListFile>(&data).ok().map(|f| (f.uid_validity, f.messages))
+}
+
+/// Keeps `messages` as the rows of `mailbox`, dropping whatever was kept under another
+/// UIDVALIDITY.
+pub fn save_list(config: &Config, mailbox: &str, uid_validity: u32, messages: &[MessageSummary]) -> Result<()> {
+    if config.cache.max_mb == 0 {
+        return Ok(());
+    }
+    let mut used = WRITING.lock().unwrap_or_else(|e| e.into_inner());
+    let folder = folder_dir(&config.imap, mailbox)?;
+    let dir = folder.join(uid_validity.to_string());
+    create_private(&dir)?;
+    for stale in fs::read_dir(&folder)?.flatten().filter(|e| e.path() != dir) {
+        if fs::remove_dir_all(stale.path()).is_ok() {
+            // whole folders of mail went; cheaper to count again than to sum them
+            *used = None;
+        }
+    }
+
+    let file = ListFile { uid_validity, messages: messages.to_vec() };
+    let data = toml::to_string(&file)?;
+    let path = dir.join("list.toml");
+    let old = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    // written aside and renamed, so a crash mid-write keeps the old file
+    let tmp = dir.join("list.toml.tmp");
+    fs::write(&tmp, &data)?;
+    fs::rename(tmp, path)?;
+    if let Some(used) = used.as_mut() {
+        *used = (*used + data.len() as u64).saturating_sub(old);
+    }
+    Ok(())
+}
+
+/// The text of a mail opened before, marked as just used.
+pub fn load_body(config: &Config, mailbox: &str, uid_validity: u32, uid: u32) -> Option<String> {
+    if config.cache.max_mb == 0 {
+        return None;
+    }
+    let path = folder_dir(&config.imap, mailbox).ok()?.join(uid_validity.to_string()).join(format!("{uid}.txt"));
+    let text = fs::read_to_string(&path).ok()?;
+    if let Ok(f) = fs::File::options().write(true).open(&path) {
+        let _ = f.set_modified(SystemTime::now());
+    }
+    Some(text)
+}
+
+pub fn save_body(config: &Config, mailbox: &str, uid_validity: u32, uid: u32, text: &str) -> Result<()> {
+    if config.cache.max_mb == 0 {
+        return Ok(());
+    }
+    let mut used = WRITING.lock().unwrap_or_else(|e| e.into_inner());
+    let validity = folder_dir(&config.imap, mailbox)?.join(uid_validity.to_string());
+    create_private(&validity)?;
+    let path = validity.join(format!("{uid}.txt"));
+    let old = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    fs::write(path, text)?;
+    let total = match *used {
+        Some(n) => (n + text.len() as u64).saturating_sub(old),
+        None => size(&dir()?)?,
+    };
+    *used = Some(evict(total, config.cache.max_mb * 1024 * 1024)?);
+    Ok(())
+}
+
+fn size(dir: &Path) -> Result<u64> {
+    let mut total = 0;
+    walk(dir, &mut |_, meta| total += meta.len())?;
+    Ok(total)
+}
+
+// Past `max_bytes`, mail texts are deleted, least recently opened first, until a
+// tenth of the room is free again. The row lists are small and stay. Returns the
+// bytes left; only a cache over the cap is walked.
+fn evict(mut total: u64, max_bytes: u64) -> Result<u64> {
+    if total <= max_bytes {
+        return Ok(total);
+    }
+    let mut bodies = vec![];
+    total = 0;
+    walk(&dir()?, &mut |path, meta| {
+        total += meta.len();
+        if path.extension().is_some_and(|e| e == "txt") {
+            bodies.push((meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), meta.len(), path.to_path_buf()));
+        }
+    })?;
+    bodies.sort();
+    for (_, len, path) in bodies {
+        if total <= max_bytes / 10 * 9 {
+            break;
+        }
+        if fs::remove_file(path).is_ok() {
+            total -= len;
+        }
+    }
+    Ok(total)
+}
+
+fn walk(dir: &Path, visit: &mut impl FnMut(&Path, &fs::Metadata)) -> Result<()> {
+    for entry in fs::read_dir(dir)?.flatten() {
+        let Ok(meta) = entry.metadata() else { continue };
+        if meta.is_dir() {
+            walk(&entry.path(), visit)?;
+        } else {
+            visit(&entry.path(), &meta);
+        }
+    }
+    Ok(())
+}
+
+// Mail is private: the directories are for this user only.
+fn create_private(dir: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)?;
+    Ok(())
+}
+
+/// `zenmail --clear-cache`: removes everything kept; true if there was anything.
+pub fn clear() -> Result<bool> {
+    let dir = dir()?;
+    *WRITING.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    if !dir.exists() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(&dir).map_err(|e| anyhow!("{}: {e}", dir.display()))?;
+    Ok(true)
+}
@@ -1,12 +1,58 @@
 use serde::{Deserialize, Serialize};
-use anyhow::Result;
-use std::{fs, path::{Path, PathBuf}};
+use anyhow::{anyhow, Result};
+
+use crate::keyring;
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}, process::{Command, Stdio}, sync::Mutex};
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Config {
+    /// Look for a newer release on GitHub once a day (off unless set).
+    #[serde(default)]
+    pub check_updates: bool,
     pub imap: MailConfig,
     pub smtp: MailConfig,
     pub user: UserConfig,
+    #[serde(default)]
+    pub folders: FolderConfig,
+    #[serde(default)]
+    pub compose: ComposeConfig,
+    #[serde(default)]
+    pub send_guard: SendGuardConfig,
+    #[serde(default)]
+    pub list: ListConfig,
+    #[serde(default)]
+    pub view: ViewConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// Named recipient groups, e.g. `team = ["a@x", "b@x"]`, expanded in To, Cc and Bcc;
+    /// edited in `:groups`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Named views of the mail, kept from the `~` picker; 1–9 open the first nine.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub saved_searches: Vec<SavedSearch>,
+    /// Remapped keys: list_down, list_up, open, compose, reply, refresh, quit, config or
+    /// help to a key such as "n", "Down" or "Ctrl+r".
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, String>,
+}
+
+/// A folder with a server search, a filter and an order, opened as one.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct SavedSearch {
+    pub name: String,
+    pub folder: String,
+    /// As typed after `/`; empty lists the whole folder.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub query: String,
+    /// Only mail from this address, like `F s`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub from: String,
+    /// Only this thread, like `F t`: the subject without `Re:` / `Fwd:`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub thread: String,
+    #[serde(default = "default_true")]
+    pub newest_first: bool,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -16,20 +62,568 @@ pub struct MailConfig {
     pub username: String,
     pub password: String,
     pub starttls: bool,
+    /// Name the TLS certificate is verified against (and sent as SNI) when `host`
+    /// is an IP or a load balancer; defaults to `host`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_server_name: Option<String>,
+    /// `false` accepts any certificate (a self-signed one on the LAN); unset means
+    /// verify, except on localhost where it used to be skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_verify: Option<bool>,
+    /// PEM file of CA certificates trusted in addition to the system's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_ca_file: Option<String>,
+    /// Seconds to wait for the server to accept the connection or answer a command.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Shell command whose output is the password (`pass show mail`, a keychain
+    /// helper, ...); `password` can then stay empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_cmd: Option<String>,
+    /// File holding the password, e.g. one written by a secrets manager.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_file: Option<String>,
+    /// `keyring`: the password is in the system keyring (stored there with Ctrl+K in
+    /// the config screen), not in this file.
+    #[serde(default, skip_serializing_if = "PasswordSource::is_config")]
+    pub password_source: PasswordSource,
+    /// `oauth2`: log in through XOAUTH2 with a token from its `oauth2` table instead of a password.
+    #[serde(default, skip_serializing_if = "AuthMode::is_password")]
+    pub auth: AuthMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth2: Option<OAuthConfig>,
+    /// Typed at the login prompt: used ahead of everything else, never saved.
+    #[serde(skip)]
+    pub session_password: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordSource {
+    #[default]
+    Config, // password_cmd, password_file or password
+    Keyring,
+}
+
+impl PasswordSource {
+    fn is_config(&self) -> bool {
+        *self == PasswordSource::Config
+    }
+}
+
+/// What `cmd` prints, run via `sh -c`, without trailing whitespace; `setting` names
+/// it in errors. Failing or printing nothing is an error.
+pub fn secret_from_command(cmd: &str, setting: &str) -> Result<String> {
+    let out = Command::new("sh").arg("-c").arg(cmd).stdin(Stdio::null()).output()?;
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        return Err(anyhow!("{setting} failed ({}): {}", out.status, err.trim()));
+    }
+    let secret = String::from_utf8(out.stdout)?.trim_end().to_string();
+    if secret.is_empty() {
+        return Err(anyhow!("{setting} printed nothing"));
+    }
+    Ok(secret)
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    #[default]
+    Password,
+    Oauth2,
+}
+
+impl AuthMode {
+    fn is_password(&self) -> bool {
+        *self == AuthMode::Password
+    }
+}
+
+/// `[imap.oauth2]` / `[smtp.oauth2]`. The endpoints and scope default to Google's or
+/// Microsoft's when `host` is theirs.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct OAuthConfig {
+    /// Command printing a current access token (an external OAuth helper); when set,
+    /// nothing else here is needed.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub token_cmd: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub client_secret: String,
+    /// Written by `zenmail --oauth-setup`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub refresh_token: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub token_url: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub auth_url: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub scope: String,
+}
+
+// password_cmd output, kept for the session so gpg & co. are not asked on every connect
+static PASSWORD_CACHE: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+impl MailConfig {
+    /// The keyring's entry with `password_source = "keyring"`, else the output of
+    /// `password_cmd`, else the contents of `password_file`, else the plaintext
+    /// `password`. Command output and file contents lose trailing
+    /// whitespace (the newline `echo` and editors add); `password` is used exactly as
+    /// written. A failing command, a missing file or an empty result is an error
+    /// rather than an empty login.
+    pub fn password(&self) -> Result<String> {
+        if let Some(pw) = &self.session_password {
+            return Ok(pw.clone());
+        }
+        if self.password_source == PasswordSource::Keyring {
+            return self.password_from_keyring();
+        }
+        let cmd = match self.password_cmd.as_deref().map(str::trim) {
+            Some(cmd) if !cmd.is_empty() => cmd,
+            _ => return self.password_from_file(),
+        };
+        if let Some(pw) = PASSWORD_CACHE.lock().unwrap().get(cmd) {
+            return Ok(pw.clone());
+        }
+
+        let pw = secret_from_command(cmd, "password_cmd")?;
+        PASSWORD_CACHE.lock().unwrap().insert(cmd.to_string(), pw.clone());
+        Ok(pw)
+    }
+
+    fn keyring_account(&self) -> String {
+        keyring::account(&self.username, &self.host)
+    }
+
+    fn password_from_keyring(&self) -> Result<String> {
+        let account = self.keyring_account();
+        let key = format!("keyring:{account}");
+        if let Some(pw) = PASSWORD_CACHE.lock().unwrap().get(&key) {
+            return Ok(pw.clone());
+        }
+        let pw = keyring::get(&account)?;
+        PASSWORD_CACHE.lock().unwrap().insert(key, pw.clone());
+        Ok(pw)
+    }
+
+    /// Puts `password` into the keyring and switches to it; `password` is cleared.
+    pub fn move_to_keyring(&mut self, password: &str) -> Result<()> {
+        let account = self.keyring_account();
+        keyring::set(&account, password)?;
+        PASSWORD_CACHE.lock().unwrap().insert(format!("keyring:{account}"), password.to_string());
+        self.password_source = PasswordSource::Keyring;
+        self.password.clear();
+        Ok(())
+    }
+
+    fn password_from_file(&self) -> Result<String> {
+        let path = match self.password_file.as_deref().map(str::trim) {
+            Some(p) if !p.is_empty() => p,
+            _ => return Ok(self.password.clone()),
+        };
+        let full = expand_home(path)?;
+        let text = fs::read_to_string(&full).map_err(|e| anyhow!("password_file {path}: {e}"))?;
+        let pw = text.trim_end().to_string();
+        if pw.is_empty() {
+            return Err(anyhow!("password_file {path} is empty"));
+        }
+        Ok(pw)
+    }
+
+    /// The login and every password this account is known to use, without running
+    /// `password_cmd`: only output it already printed this session counts.
+    pub fn secrets(&self) -> Vec<String> {
+        let mut out = vec![self.username.clone(), self.password.clone()];
+        out.extend(self.session_password.clone());
+        out.extend(crate::oauth::secrets(self));
+        out.extend(PASSWORD_CACHE.lock().unwrap().get(&format!("keyring:{}", self.keyring_account())).cloned());
+        if let Some(cmd) = self.password_cmd.as_deref() {
+            out.extend(PASSWORD_CACHE.lock().unwrap().get(cmd.trim()).cloned());
+        }
+        if self.password_file.is_some() {
+            out.extend(self.password_from_file().ok());
+        }
+        out
+    }
+
+    /// Where the password comes from when it isn't `password`, for the config view.
+    pub fn password_source(&self) -> Option<&'static str> {
+        let set = |v: &Option<String>| v.as_deref().is_some_and(|s| !s.trim().is_empty());
+        if self.auth == AuthMode::Oauth2 {
+            Some("(OAuth2)")
+        } else if self.password_source == PasswordSource::Keyring {
+            Some("(in keyring)")
+        } else if set(&self.password_cmd) {
+            Some("(from command)")
+        } else if set(&self.password_file) {
+            Some("(from file)")
+        } else {
+            None
+        }
+    }
+
+    /// Whether the server's certificate and name are checked.
+    pub fn verify_certs(&self) -> bool {
+        self.tls_verify.unwrap_or(!self.legacy_localhost())
+    }
+
+    /// `host` is localhost and `tls_verify` unset: certificates go unchecked as they
+    /// always did there, until the config says so itself.
+    pub fn legacy_localhost(&self) -> bool {
+        self.tls_verify.is_none() && (self.host == "127.0.0.1" || self.host == "localhost")
+    }
+
+    /// The certificates in `tls_ca_file`, one PEM block each.
+    pub fn ca_certs(&self) -> Result<Vec<Vec<u8>>> {
+        let Some(path) = self.tls_ca_file.as_deref().map(str::trim).filter(|p| !p.is_empty()) else {
+            return Ok(vec![]);
+        };
+        let pem = std::fs::read_to_string(expand_home(path)?).map_err(|e| anyhow!("tls_ca_file {path}: {e}"))?;
+        const END: &str = "-----END CERTIFICATE-----";
+        let certs: Vec<Vec<u8>> = pem
+            .split_inclusive(END)
+            .filter(|block| block.contains(END))
+            .filter_map(|block| block.find("-----BEGIN CERTIFICATE-----").map(|at| block.as_bytes()[at..].to_vec()))
+            .collect();
+        if certs.is_empty() {
+            return Err(anyhow!("tls_ca_file {path}: no PEM certificate in it"));
+        }
+        Ok(certs)
+    }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeout_secs.max(1))
+    }
+
+    pub fn server_name(&self) -> &str {
+        match self.tls_server_name.as_deref().map(str::trim) {
+            Some(name) if !name.is_empty() => name,
+            _ => &self.host,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct UserConfig {
     pub name: String,
     pub email: String,
+    /// SMTP envelope sender (Return-Path) when bounces should go elsewhere than `email`.
+    #[serde(default)]
+    pub envelope_from: Option<String>,
+    /// Where replies to sent mail should go, when not to `email`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    /// Other addresses that reach me, for telling mail sent to one of them (or Bcc'd)
+    /// apart in the Mail view.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct FolderConfig {
+    #[serde(default = "default_archive")]
+    pub archive: String,
+    /// `A` files mail under `<archive_root>/<year>`; empty means `archive`.
+    #[serde(default)]
+    pub archive_root: String,
+    /// Deleted mail is moved here; without it, deletion expunges immediately.
+    #[serde(default)]
+    pub trash: Option<String>,
+    /// Sent mail is appended here; empty disables saving a copy.
+    #[serde(default = "default_sent")]
+    pub sent: String,
+    /// Saved drafts are also kept here, flagged \Draft; empty keeps them local only.
+    #[serde(default)]
+    pub drafts: String,
+}
+
+fn default_timeout_secs() -> u64 {
+    15
+}
+
+fn default_archive() -> String {
+    "Archive".to_string()
+}
+
+fn default_sent() -> String {
+    "Sent".to_string()
+}
+
+impl Default for FolderConfig {
+    fn default() -> Self {
+        Self { archive: default_archive(), archive_root: String::new(), trash: None, sent: default_sent(), drafts: String::new() }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ComposeConfig {
+    /// Sending to more recipients than this asks for confirmation first.
+    #[serde(default = "default_max_recipients")]
+    pub max_recipients: usize,
+    /// Remove a resumed draft once it has been sent.
+    #[serde(default = "default_true")]
+    pub delete_draft_on_send: bool,
+    /// Larger files are refused as attachments before they are read.
+    #[serde(default = "default_max_attachment_mb")]
+    pub max_attachment_mb: u64,
+    /// The send preview warns when the text mentions one of these but nothing is attached.
+    #[serde(default = "default_attach_keywords")]
+    pub attach_keywords: Vec<String>,
+}
+
+fn default_attach_keywords() -> Vec<String> {
+    vec!["attach".to_string()]
+}
+
+/// Asks before mail matching one of `patterns` goes to anyone outside `internal_domains`.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct SendGuardConfig {
+    /// Case-insensitive regexes searched in the text and the quote, not the signature.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+    /// Recipients here (or in a subdomain) don't trigger the guard.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub internal_domains: Vec<String>,
+}
+
+fn default_max_attachment_mb() -> u64 {
+    25
+}
+
+fn default_max_recipients() -> usize {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for ComposeConfig {
+    fn default() -> Self {
+        Self {
+            max_recipients: default_max_recipients(),
+            delete_draft_on_send: true,
+            max_attachment_mb: default_max_attachment_mb(),
+            attach_keywords: default_attach_keywords(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ListConfig {
+    /// Show high-priority messages above the rest.
+    #[serde(default)]
+    pub priority_first: bool,
+    /// Refetch the list when going back from the Mail view.
+    #[serde(default)]
+    pub refresh_on_return: bool,
+    #[serde(default)]
+    pub confirm_delete: ConfirmDelete,
+    /// With `confirm_delete = "above"`, ask only when deleting more than this many.
+    #[serde(default = "default_confirm_delete_above")]
+    pub confirm_delete_above: usize,
+    /// Check for new mail every this many seconds; 0 turns polling off.
+    #[serde(default)]
+    pub poll_secs: u64,
+    /// Without a keypress for this long (or with the terminal unfocused), the poll
+    /// interval doubles after every poll...
+    #[serde(default = "default_idle_after_secs")]
+    pub idle_after_secs: u64,
+    /// ...up to this many seconds.
+    #[serde(default = "default_idle_poll_max_secs")]
+    pub idle_poll_max_secs: u64,
+    /// Also while in use, polls that keep finding nothing come further apart, from
+    /// `poll_secs` up to `poll_max_secs`; new mail or a refresh by hand resets it.
+    #[serde(default)]
+    pub adaptive_poll: bool,
+    #[serde(default = "default_poll_max_secs")]
+    pub poll_max_secs: u64,
+    /// Keep a connection in IMAP IDLE so the server pushes new INBOX mail.
+    #[serde(default)]
+    pub idle: bool,
+    /// `j` on the last message goes to the first, `k` on the first to the last.
+    #[serde(default)]
+    pub wrap: bool,
+    /// Rows per message at startup; `v` cycles through them.
+    #[serde(default)]
+    pub density: Density,
+    /// List order at startup; `s` flips it.
+    #[serde(default = "default_true")]
+    pub newest_first: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Density {
+    Compact, // one line: flags, date, sender, subject
+    #[default]
+    Normal,  // subject, then sender and date
+    Relaxed, // and a line of the text
+}
+
+impl Density {
+    pub fn next(self) -> Self {
+        match self {
+            Density::Compact => Density::Normal,
+            Density::Normal => Density::Relaxed,
+            Density::Relaxed => Density::Compact,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Density::Compact => "compact",
+            Density::Normal => "normal",
+            Density::Relaxed => "relaxed",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfirmDelete {
+    #[default]
+    Always,
+    Never,
+    Above,
+}
+
+fn default_confirm_delete_above() -> usize {
+    1
+}
+
+fn default_idle_after_secs() -> u64 {
+    300
+}
+
+fn default_idle_poll_max_secs() -> u64 {
+    1800
+}
+
+fn default_poll_max_secs() -> u64 {
+    900
+}
+
+impl FolderConfig {
+    /// The folder `A` archives below.
+    pub fn year_archive_root(&self) -> &str {
+        match self.archive_root.trim() {
+            "" => self.archive.trim(),
+            root => root,
+        }
+    }
+}
+
+impl Default for ListConfig {
+    fn default() -> Self {
+        Self {
+            priority_first: false,
+            refresh_on_return: false,
+            idle: false,
+            wrap: false,
+            density: Density::Normal,
+            newest_first: true,
+            confirm_delete: ConfirmDelete::Always,
+            confirm_delete_above: default_confirm_delete_above(),
+            poll_secs: 0,
+            idle_after_secs: default_idle_after_secs(),
+            idle_poll_max_secs: default_idle_poll_max_secs(),
+            adaptive_poll: false,
+            poll_max_secs: default_poll_max_secs(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ViewConfig {
+    /// Maximum body width in focus mode.
+    #[serde(default = "default_focus_width")]
+    pub focus_width: u16,
+    /// Larger messages are never loaded whole: only their text part is shown.
+    #[serde(default = "default_max_message_mb")]
+    pub max_message_mb: usize,
+    /// Regexes for legal disclaimers and the like; matched text is hidden behind a marker.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disclaimer_patterns: Vec<String>,
+    /// Where attachments are saved; defaults to the system downloads folder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_dir: Option<String>,
+    #[serde(default)]
+    pub theme: Theme,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CacheConfig {
+    /// Room for mail kept on disk for quick starts and offline reading; 0 keeps none.
+    #[serde(default = "default_cache_mb")]
+    pub max_mb: u64,
+}
+
+fn default_cache_mb() -> u64 {
+    200
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { max_mb: default_cache_mb() }
+    }
+}
+
+/// Built-in color themes; see `ui::theme` for what each one looks like.
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Default, // Okabe–Ito colors, safe for the common color-vision deficiencies
+    Mono,    // no colors, only bold / dim / reverse
+}
+
+fn default_max_message_mb() -> usize {
+    25
+}
+
+fn default_focus_width() -> u16 {
+    80
+}
+
+impl Default for ViewConfig {
+    fn default() -> Self {
+        Self {
+            focus_width: default_focus_width(),
+            max_message_mb: default_max_message_mb(),
+            disclaimer_patterns: vec![],
+            download_dir: None,
+            theme: Theme::Default,
+        }
+    }
+}
+
+/// `~/...` relative to the home directory; other paths as given.
+pub fn expand_home(path: &str) -> Result<PathBuf> {
+    let home = || dirs::home_dir().ok_or_else(|| anyhow!("no home dir"));
+    match path.strip_prefix("~/") {
+        Some(rest) => Ok(home()?.join(rest)),
+        None if path == "~" => home(),
+        None => Ok(PathBuf::from(path)),
+    }
 }
 
 impl Config {
+    #[cfg(not(test))]
     pub fn path() -> Result<PathBuf> {
         let dir = dirs::config_dir().ok_or_else(|| anyhow::anyhow!("no config dir"))?;
         Ok(dir.join("zenmail").join("config.toml"))
     }
 
+    /// Under test, everything kept next to config.toml (drafts, cache, outbox,
+    /// addresses, logs) goes to a scratch directory instead of the real one.
+    #[cfg(test)]
+    pub fn path() -> Result<PathBuf> {
+        Ok(scratch_dir().join("zenmail").join("config.toml"))
+    }
+
     pub fn load_or_create() -> Result<(Self, bool, PathBuf)> {
         let path = Self::path()?;
         if !path.exists() {
@@ -47,7 +641,14 @@ impl Config {
     }
 
     pub fn save_to(&self, path: &Path) -> Result<()> {
-        let toml = toml::to_string_pretty(self)?;
+        // a password from a command or file never reaches config.toml
+        let mut out = self.clone();
+        for m in [&mut out.imap, &mut out.smtp] {
+            if m.password_source().is_some() {
+                m.password.clear();
+            }
+        }
+        let toml = toml::to_string_pretty(&out)?;
         fs::write(path, toml)?;
         Ok(())
     }
@@ -71,4 +672,103 @@ starttls = true
 [user]
 name = "Your Name"
 email = "you@email.ml"
+
+[folders]
+archive = "Archive"
+sent = "Sent"
+
+[compose]
+max_recipients = 10
 "#;
+
+// One directory per test run, removed when the run exits.
+#[cfg(test)]
+fn scratch_dir() -> &'static Path {
+    static DIR: std::sync::LazyLock<PathBuf> = std::sync::LazyLock::new(|| {
+        extern "C" {
+            fn atexit(callback: extern "C" fn()) -> std::os::raw::c_int;
+        }
+        extern "C" fn remove() {
+            let _ = fs::remove_dir_all(&*DIR);
+        }
+        // SAFETY: `remove` is a plain function, valid for as long as the process runs
+        unsafe { atexit(remove) };
+        std::env::temp_dir().join(format!("zenmail-test-{}-{:08x}", std::process::id(), fastrand::u32(..)))
+    });
+    &DIR
+}
+
+#[cfg(test)]
+impl Config {
+    /// A whole config for tests. The servers are on a port nothing listens on, so
+    /// anything that reaches for the network fails at once, and the config directory
+    /// (drafts, cache, log) is a scratch one for the test run.
+    pub fn for_tests() -> Config {
+        let mut config: Config = toml::from_str(DEFAULT_CONFIG).expect("default config parses");
+        for m in [&mut config.imap, &mut config.smtp] {
+            m.port = 1;
+            m.timeout_secs = 1;
+        }
+        config.cache.max_mb = 0;
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `contents` in a file of its own, named after the test using it.
+    fn password_file(name: &str, contents: &str) -> MailConfig {
+        let dir = scratch_dir().join("password-files");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        let mut cfg = Config::for_tests().imap;
+        cfg.password = "unused".into();
+        cfg.password_file = Some(path.to_string_lossy().into_owned());
+        cfg
+    }
+
+    #[test]
+    fn password_file_loses_its_trailing_newline() {
+        assert_eq!(password_file("newline", " s3cr:t \"pw\"#\n").password().unwrap(), " s3cr:t \"pw\"#");
+        assert_eq!(password_file("crlf", "hunter2\r\n\r\n").password().unwrap(), "hunter2");
+        assert_eq!(password_file("tabs", "hunter2 \t\n").password().unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn empty_or_missing_password_file_is_an_error() {
+        let err = password_file("empty", "\n").password().unwrap_err().to_string();
+        assert!(err.ends_with("is empty"), "{err}");
+        let mut cfg = password_file("missing", "");
+        cfg.password_file = Some("/nonexistent/zenmail-password".into());
+        assert!(cfg.password().unwrap_err().to_string().starts_with("password_file /nonexistent/zenmail-password: "));
+    }
+
+    #[test]
+    fn inline_password_is_used_as_written() {
+        let mut cfg = Config::for_tests().imap;
+        cfg.password = " pass word\n".into();
+        assert_eq!(cfg.password().unwrap(), " pass word\n");
+    }
+
+    #[test]
+    fn password_cmd_output_loses_its_trailing_newline() {
+        let mut cfg = Config::for_tests().imap;
+        cfg.password_cmd = Some("printf ' p@ss w0rd!\\n\\n'".into());
+        assert_eq!(cfg.password().unwrap(), " p@ss w0rd!");
+        assert!(secret_from_command("exit 3", "password_cmd").is_err());
+        assert_eq!(secret_from_command("echo", "password_cmd").unwrap_err().to_string(), "password_cmd printed nothing");
+    }
+
+    #[test]
+    fn tests_keep_their_files_out_of_the_real_config_dir() {
+        let path = Config::path().unwrap();
+        assert!(path.starts_with(std::env::temp_dir()), "{}", path.display());
+        assert!(dirs::config_dir().is_none_or(|real| !path.starts_with(real)), "{}", path.display());
+        assert!(crate::drafts::dir().unwrap().starts_with(scratch_dir()));
+        assert!(crate::outbox::dir().unwrap().starts_with(scratch_dir()));
+        assert!(crate::cache::dir().unwrap().starts_with(scratch_dir()));
+    }
+}
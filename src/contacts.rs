@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result};
+use std::{fs, path::PathBuf};
+
+use crate::config::Config;
+
+// Beyond this the least recently used addresses are forgotten.
+const MAX_CONTACTS: usize = 2000;
+
+/// An address seen on received mail or used on sent mail, for completion in To / Cc.
+#[derive(Clone)]
+pub struct Contact {
+    pub name: String, // as it appeared, quotes included; may be empty
+    pub address: String,
+    pub last_used: i64, // Unix seconds
+}
+
+impl Contact {
+    /// What completion puts into the field.
+    pub fn display(&self) -> String {
+        if self.name.is_empty() {
+            self.address.clone()
+        } else {
+            format!("{} <{}>", self.name, self.address)
+        }
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    let cfg = Config::path()?;
+    let parent = cfg.parent().ok_or_else(|| anyhow!("no config dir"))?;
+    Ok(parent.join("addresses.tsv"))
+}
+
+/// The saved addresses; none when the file is missing or unreadable. One per line:
+/// name, address and last use, separated by tabs.
+pub fn load() -> Vec<Contact> {
+    let Ok(data) = path().and_then(|p| Ok(fs::read_to_string(p)?)) else {
+        return vec![];
+    };
+    data.lines()
+        .filter_map(|line| {
+            let mut cols = line.split('\t');
+            let (name, address, last_used) = (cols.next()?, cols.next()?, cols.next()?);
+            Some(Contact { name: name.to_string(), address: address.to_string(), last_used: last_used.parse().ok()? })
+        })
+        .collect()
+}
+
+pub fn save(contacts: &[Contact]) -> Result<()> {
+    let path = path()?;
+    let mut out = String::new();
+    for c in contacts {
+        out.push_str(&format!("{}\t{}\t{}\n", c.name, c.address, c.last_used));
+    }
+    // written aside and renamed, so a crash mid-write keeps the old file
+    let tmp = path.with_extension("tsv.tmp");
+    fs::write(&tmp, out)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// `"Doe, John" <j@x>` → (`"Doe, John"`, `j@x`); a bare address has no name.
+pub fn split(entry: &str) -> (String, String) {
+    let entry = entry.trim();
+    match entry.rsplit_once('<') {
+        Some((name, rest)) => (name.trim().to_string(), rest.trim_end_matches('>').trim().to_string()),
+        None => (String::new(), entry.to_string()),
+    }
+}
+
+/// Adds `entry` or moves it forward; false when nothing changed. Addresses are
+/// compared case-insensitively, and the newest name wins.
+pub fn remember(contacts: &mut Vec<Contact>, entry: &str, at: i64) -> bool {
+    let (name, address) = split(entry);
+    let clean = |s: &str| s.replace(['\t', '\n', '\r'], " ");
+    let (name, address) = (clean(&name), clean(&address));
+    if !address.contains('@') {
+        return false;
+    }
+
+    match contacts.iter_mut().find(|c| c.address.eq_ignore_ascii_case(&address)) {
+        Some(c) if at <= c.last_used => false,
+        Some(c) => {
+            c.last_used = at;
+            if !name.is_empty() {
+                c.name = name;
+            }
+            true
+        }
+        None => {
+            contacts.push(Contact { name, address, last_used: at });
+            if contacts.len() > MAX_CONTACTS {
+                contacts.sort_by_key(|c| std::cmp::Reverse(c.last_used));
+                contacts.truncate(MAX_CONTACTS);
+            }
+            true
+        }
+    }
+}
+
+/// Up to `limit` contacts whose name or address contains `typed` (ignoring case):
+/// those starting with it first, then the most recently used.
+pub fn matching<'a>(contacts: &'a [Contact], typed: &str, limit: usize) -> Vec<&'a Contact> {
+    let typed = typed.trim().to_lowercase();
+    if typed.is_empty() || contacts.iter().any(|c| c.address.to_lowercase() == typed) {
+        return vec![]; // nothing typed, or already complete
+    }
+    let mut found: Vec<(bool, &Contact)> = contacts
+        .iter()
+        .filter_map(|c| {
+            let (name, address) = (c.name.to_lowercase(), c.address.to_lowercase());
+            let prefix = address.starts_with(&typed) || name.trim_start_matches('"').starts_with(&typed);
+            (prefix || name.contains(&typed) || address.contains(&typed)).then_some((prefix, c))
+        })
+        .collect();
+    found.sort_by_key(|(prefix, c)| (!prefix, std::cmp::Reverse(c.last_used)));
+    found.into_iter().take(limit).map(|(_, c)| c).collect()
+}
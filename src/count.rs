@@ -0,0 +1,73 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+const WORDS_PER_MINUTE: usize = 230;
+
+/// Word and character counts of the text a person actually wrote.
+#[derive(Clone, Copy, Default)]
+pub struct TextCount {
+    pub words: usize,
+    pub chars: usize,
+}
+
+impl TextCount {
+    /// Counts `text`, skipping `>` quoted lines. Words follow UAX #29, so CJK text
+    /// (which has no spaces) is counted per word rather than as one long run.
+    pub fn of(text: &str) -> Self {
+        let mut count = TextCount::default();
+        for line in text.lines().filter(|l| !l.trim_start().starts_with('>')) {
+            count.words += line.unicode_words().count();
+            count.chars += line.chars().count();
+        }
+        count
+    }
+
+    /// Whole minutes at an average reading pace, at least one.
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE).max(1)
+    }
+
+    /// e.g. `412 words · 2391 chars`
+    pub fn label(&self) -> String {
+        format!("{} words · {} chars", self.words, self.chars)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counted(text: &str) -> (usize, usize) {
+        let c = TextCount::of(text);
+        (c.words, c.chars)
+    }
+
+    #[test]
+    fn words_without_spaces_are_still_words() {
+        // whitespace splitting would see one word in each of these
+        assert_eq!(counted("我们今天去公园散步。"), (9, 10));
+        assert_eq!(counted("今日は良い天気ですね"), (10, 10));
+        // katakana runs stay together, as do Hangul words between spaces
+        assert_eq!(counted("カタカナ テスト"), (2, 8));
+        assert_eq!(counted("안녕하세요 여러분, 반갑습니다!"), (3, 17));
+    }
+
+    #[test]
+    fn punctuation_is_not_a_word() {
+        assert_eq!(counted("Hello, world! It's 3.5 km."), (5, 26));
+        assert_eq!(counted("— … !"), (0, 5));
+        assert_eq!(counted(""), (0, 0));
+    }
+
+    #[test]
+    fn quoted_lines_are_left_out() {
+        let reply = "Sounds good.\n\nOn Monday, Bob wrote:\n> Shall we meet?\n  >> 会议在三点\nSee you";
+        assert_eq!(counted(reply), (8, 40));
+    }
+
+    #[test]
+    fn reading_time_rounds_up() {
+        let minutes = |words| TextCount { words, chars: 0 }.reading_minutes();
+        assert_eq!((minutes(0), minutes(1), minutes(230), minutes(231)), (1, 1, 1, 2));
+        assert_eq!(TextCount { words: 412, chars: 2391 }.label(), "412 words · 2391 chars");
+    }
+}
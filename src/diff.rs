@@ -0,0 +1,120 @@
+// Line diff of two texts: longest common subsequence over the lines that differ,
+// after the shared start and end are set aside.
+
+// Beyond this many line pairs in the differing middle the table gets too big;
+// the middle is then shown as all removed, then all added.
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+/// `new` compared against `old`, line by line, in reading order.
+pub fn lines(old: &str, new: &str) -> Vec<(Change, String)> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut out: Vec<(Change, String)> = a[..prefix].iter().map(|l| (Change::Same, l.to_string())).collect();
+    out.extend(middle(mid_a, mid_b));
+    out.extend(a[a.len() - suffix..].iter().map(|l| (Change::Same, l.to_string())));
+    out
+}
+
+fn middle(a: &[&str], b: &[&str]) -> Vec<(Change, String)> {
+    let removed = |l: &&str| (Change::Removed, l.to_string());
+    let added = |l: &&str| (Change::Added, l.to_string());
+    if a.is_empty() || b.is_empty() || a.len() * b.len() > MAX_CELLS {
+        return a.iter().map(removed).chain(b.iter().map(added)).collect();
+    }
+
+    // common[i][j]: LCS length of a[i..] and b[j..]
+    let w = b.len() + 1;
+    let mut common = vec![0u32; (a.len() + 1) * w];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i * w + j] = if a[i] == b[j] {
+                common[(i + 1) * w + j + 1] + 1
+            } else {
+                common[(i + 1) * w + j].max(common[i * w + j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            out.push((Change::Same, a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if common[(i + 1) * w + j] >= common[i * w + j + 1] {
+            out.push(removed(&a[i]));
+            i += 1;
+        } else {
+            out.push(added(&b[j]));
+            j += 1;
+        }
+    }
+    out.extend(a[i..].iter().map(removed));
+    out.extend(b[j..].iter().map(added));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Change::*;
+
+    fn check(old: &str, new: &str, expected: &[(Change, &str)]) {
+        let got = lines(old, new);
+        let got: Vec<(Change, &str)> = got.iter().map(|(c, l)| (*c, l.as_str())).collect();
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn empty_inputs() {
+        check("", "", &[]);
+        check("", "a\nb", &[(Added, "a"), (Added, "b")]);
+        check("a\nb", "", &[(Removed, "a"), (Removed, "b")]);
+    }
+
+    #[test]
+    fn identical_inputs_are_all_same() {
+        check("a\nb\nc", "a\nb\nc", &[(Same, "a"), (Same, "b"), (Same, "c")]);
+    }
+
+    #[test]
+    fn runs_of_inserts_and_deletes() {
+        let old = "Build #41\nstatus: failed\nstep: test\nduration: 3m\nbye";
+        let new = "Build #42\nstatus: passed\nstep: test\nartifact: a.tar\nartifact: b.tar\nduration: 3m\nbye";
+        check(
+            old,
+            new,
+            &[
+                (Removed, "Build #41"),
+                (Removed, "status: failed"),
+                (Added, "Build #42"),
+                (Added, "status: passed"),
+                (Same, "step: test"),
+                (Added, "artifact: a.tar"),
+                (Added, "artifact: b.tar"),
+                (Same, "duration: 3m"),
+                (Same, "bye"),
+            ]
+        );
+        check("a\nx\ny\nb", "a\nb", &[(Same, "a"), (Removed, "x"), (Removed, "y"), (Same, "b")]);
+    }
+
+    #[test]
+    fn keeps_the_longest_common_lines() {
+        // `b c` in common beats matching the lone `a`
+        check("a\nb\nc", "b\nc\na", &[(Removed, "a"), (Same, "b"), (Same, "c"), (Added, "a")]);
+    }
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+use std::{fs, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::config::Config;
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Draft {
+    pub to: String,
+    #[serde(default)]
+    pub cc: String,
+    #[serde(default)]
+    pub bcc: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reply_to: String,
+    pub subject: String,
+    pub body: String,
+    #[serde(default)]
+    pub quote: String,
+    #[serde(default)]
+    pub high_priority: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub references: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<PathBuf>, // read when the mail is sent
+}
+
+pub fn dir() -> Result<PathBuf> {
+    let cfg = Config::path()?;
+    let parent = cfg.parent().ok_or_else(|| anyhow::anyhow!("no config dir"))?;
+    Ok(parent.join("drafts"))
+}
+
+/// Writes the draft to `path`, or to a new timestamped file when `path` is `None`.
+pub fn save(draft: &Draft, path: Option<&Path>) -> Result<PathBuf> {
+    let path = match path {
+        Some(p) => p.to_path_buf(),
+        None => {
+            let dir = dir()?;
+            fs::create_dir_all(&dir)?;
+            let ts = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+            dir.join(format!("{ts}.toml"))
+        }
+    };
+    fs::write(&path, toml::to_string_pretty(draft)?)?;
+    Ok(path)
+}
+
+/// All readable drafts, newest first.
+pub fn list() -> Result<Vec<(PathBuf, Draft)>> {
+    let dir = dir()?;
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut out = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "toml") {
+            continue;
+        }
+        let Ok(data) = fs::read_to_string(&path) else { continue };
+        if let Ok(d) = toml::from_str::<Draft>(&data) {
+            out.push((path, d));
+        }
+    }
+    out.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(out)
+}
+
+pub fn delete(path: &Path) -> Result<()> {
+    fs::remove_file(path)?;
+    Ok(())
+}
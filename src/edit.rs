@@ -0,0 +1,262 @@
+// Cursor editing of a text field. The cursor is a byte offset into the text; any
+// offset past the end means "at the end", so a field can be replaced wholesale
+// without fixing up the cursor.
+
+/// `pos` moved onto the text: at most its length, and on a char boundary.
+pub fn clamp(text: &str, pos: usize) -> usize {
+    let mut pos = pos.min(text.len());
+    while !text.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    pos
+}
+
+pub fn insert(text: &mut String, pos: usize, ch: char) -> usize {
+    let pos = clamp(text, pos);
+    text.insert(pos, ch);
+    pos + ch.len_utf8()
+}
+
+pub fn backspace(text: &mut String, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    let prev = left(text, pos);
+    text.replace_range(prev..pos, "");
+    prev
+}
+
+pub fn delete(text: &mut String, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    let next = right(text, pos);
+    text.replace_range(pos..next, "");
+    pos
+}
+
+/// Ctrl+K: removes the rest of the line, or the line break when already at its end.
+pub fn kill_line(text: &mut String, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    let end = line_end(text, pos);
+    let end = if end == pos { right(text, pos) } else { end };
+    text.replace_range(pos..end, "");
+    pos
+}
+
+pub fn left(text: &str, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    text[..pos].chars().next_back().map_or(pos, |c| pos - c.len_utf8())
+}
+
+pub fn right(text: &str, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    text[pos..].chars().next().map_or(pos, |c| pos + c.len_utf8())
+}
+
+/// The start of the word before the cursor (Ctrl+Left).
+pub fn word_left(text: &str, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    let before = text[..pos].trim_end_matches(|c: char| !c.is_alphanumeric());
+    before.trim_end_matches(char::is_alphanumeric).len()
+}
+
+/// The end of the word after the cursor (Ctrl+Right).
+pub fn word_right(text: &str, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    let after = text[pos..].trim_start_matches(|c: char| !c.is_alphanumeric());
+    text.len() - after.trim_start_matches(char::is_alphanumeric).len()
+}
+
+pub fn line_start(text: &str, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    text[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+pub fn line_end(text: &str, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    text[pos..].find('\n').map_or(text.len(), |i| pos + i)
+}
+
+/// The same column (in chars) on the line above, or its end when it is shorter.
+pub fn up(text: &str, pos: usize) -> usize {
+    let start = line_start(text, pos);
+    if start == 0 {
+        return 0;
+    }
+    let col = text[start..clamp(text, pos)].chars().count();
+    at_column(text, line_start(text, start - 1), col)
+}
+
+/// The same column (in chars) on the line below, or its end when it is shorter.
+pub fn down(text: &str, pos: usize) -> usize {
+    let end = line_end(text, pos);
+    if end == text.len() {
+        return end;
+    }
+    let col = text[line_start(text, pos)..clamp(text, pos)].chars().count();
+    at_column(text, end + 1, col)
+}
+
+fn at_column(text: &str, start: usize, col: usize) -> usize {
+    let end = line_end(text, start);
+    text[start..end].char_indices().nth(col).map_or(end, |(i, _)| start + i)
+}
+
+/// How many times `line` is quoted: `>` is 1, `> >` or `>>` is 2.
+pub fn quote_depth(line: &str) -> usize {
+    line.chars().take_while(|c| *c == '>' || *c == ' ').filter(|c| *c == '>').count()
+}
+
+// A quoted line with something after its `>` marks.
+fn quoted_text(line: &str) -> bool {
+    quote_depth(line) > 0 && !line.trim_start_matches(['>', ' ']).trim().is_empty()
+}
+
+// Where each quoted paragraph starts: a quoted line with text whose line above isn't one.
+fn quote_paragraphs(text: &str) -> impl Iterator<Item = usize> + '_ {
+    let mut above = false;
+    let mut at = 0;
+    text.split('\n').filter_map(move |line| {
+        let start = at;
+        at += line.len() + 1;
+        let quoted = quoted_text(line);
+        let first = quoted && !above;
+        above = quoted;
+        first.then_some(start)
+    })
+}
+
+/// Ctrl+Down: the start of the next quoted paragraph, or `pos` when there is none.
+pub fn next_quote(text: &str, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    let line = line_start(text, pos);
+    quote_paragraphs(text).find(|&start| start > line).unwrap_or(pos)
+}
+
+/// Ctrl+Up: the start of the quoted paragraph before the cursor, or `pos` when there is none.
+pub fn prev_quote(text: &str, pos: usize) -> usize {
+    let pos = clamp(text, pos);
+    quote_paragraphs(text).take_while(|&start| start < pos).last().unwrap_or(pos)
+}
+
+/// Ctrl+O on a quoted line: breaks the quote at the cursor and puts the cursor on an
+/// empty line between the two halves, with a blank line on either side. The second
+/// half keeps the line's `>` marks. `None` when the line isn't quoted.
+pub fn split_quote(text: &mut String, pos: usize) -> Option<usize> {
+    let pos = clamp(text, pos);
+    let (start, end) = (line_start(text, pos), line_end(text, pos));
+    let line = &text[start..end];
+    if quote_depth(line) == 0 {
+        return None;
+    }
+    let marks = line.len() - line.trim_start_matches(['>', ' ']).len();
+    let prefix = format!("{} ", line[..marks].trim_end());
+    let pos = pos.max(start + marks);
+    let head = text[start + marks..pos].trim_end();
+    let tail = text[pos..end].trim_start();
+
+    let (from, mut out) = if head.is_empty() {
+        // at the start of the line: the whole line goes below
+        let blank_above = start == 0 || text[..start - 1].ends_with('\n');
+        (start, if blank_above { String::new() } else { "\n".to_string() })
+    } else {
+        (start + marks + head.len(), "\n\n".to_string())
+    };
+    let cursor = from + out.len();
+    if !tail.is_empty() {
+        out.push_str(&format!("\n\n{prefix}{tail}"));
+    } else if end < text.len() {
+        out.push('\n');
+    }
+    text.replace_range(from..end, &out);
+    Some(cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPLY: &str = "Thanks!\n\n> First point,\n> still first.\n>\n> Second point.\n>> older\n\n> Third.";
+
+    #[test]
+    fn quote_depth_counts_marks_with_or_without_spaces() {
+        assert_eq!(quote_depth("plain"), 0);
+        assert_eq!(quote_depth("> one"), 1);
+        assert_eq!(quote_depth(">> two"), 2);
+        assert_eq!(quote_depth("> > two"), 2);
+        assert_eq!(quote_depth(">"), 1);
+        assert_eq!(quote_depth("a > b"), 0);
+    }
+
+    #[test]
+    fn next_quote_visits_each_quoted_paragraph() {
+        let starts: Vec<usize> = ["> First", "> Second", "> Third"].iter().map(|s| REPLY.find(s).unwrap()).collect();
+        let mut pos = 0;
+        for &start in &starts {
+            pos = next_quote(REPLY, pos);
+            assert_eq!(pos, start);
+        }
+        // no further paragraph: the cursor stays
+        assert_eq!(next_quote(REPLY, pos), pos);
+        // from inside a paragraph, the next one
+        assert_eq!(next_quote(REPLY, REPLY.find("still").unwrap()), starts[1]);
+    }
+
+    #[test]
+    fn prev_quote_goes_back_to_paragraph_starts() {
+        let second = REPLY.find("> Second").unwrap();
+        let first = REPLY.find("> First").unwrap();
+        assert_eq!(prev_quote(REPLY, REPLY.find("older").unwrap()), second);
+        assert_eq!(prev_quote(REPLY, second), first);
+        assert_eq!(prev_quote(REPLY, first), first);
+        assert_eq!(prev_quote(REPLY, 0), 0);
+        assert_eq!(prev_quote(REPLY, usize::MAX), REPLY.find("> Third").unwrap());
+    }
+
+    #[test]
+    fn quote_navigation_without_a_quote_stays_put() {
+        assert_eq!(next_quote("no quote here\nat all", 3), 3);
+        assert_eq!(prev_quote("no quote here\nat all", 3), 3);
+        assert_eq!(next_quote("", 0), 0);
+    }
+
+    #[test]
+    fn split_quote_mid_line_keeps_the_marks_on_the_rest() {
+        let mut text = "> one two\n> three".to_string();
+        let at = split_quote(&mut text, "> one".len()).unwrap();
+        assert_eq!(text, "> one\n\n\n\n> two\n> three");
+        assert_eq!(at, "> one\n\n".len());
+        text.insert_str(at, "Agreed.");
+        assert_eq!(text, "> one\n\nAgreed.\n\n> two\n> three");
+    }
+
+    #[test]
+    fn split_quote_at_line_end_opens_a_line_before_the_next() {
+        let mut text = ">> deep\n>> more".to_string();
+        let at = split_quote(&mut text, ">> deep".len()).unwrap();
+        text.insert_str(at, "me");
+        assert_eq!(text, ">> deep\n\nme\n\n>> more");
+
+        let mut last = "> end".to_string();
+        let at = split_quote(&mut last, usize::MAX).unwrap();
+        last.insert_str(at, "me");
+        assert_eq!(last, "> end\n\nme");
+    }
+
+    #[test]
+    fn split_quote_at_line_start_answers_above_it() {
+        let mut text = "> one\n> two".to_string();
+        let at = split_quote(&mut text, "> one\n".len()).unwrap();
+        text.insert_str(at, "me");
+        assert_eq!(text, "> one\n\nme\n\n> two");
+
+        let mut first = "> one".to_string();
+        let at = split_quote(&mut first, 0).unwrap();
+        first.insert_str(at, "me");
+        assert_eq!(first, "me\n\n> one");
+    }
+
+    #[test]
+    fn split_quote_refuses_unquoted_lines() {
+        let mut text = "mine\n> theirs".to_string();
+        assert_eq!(split_quote(&mut text, 2), None);
+        assert_eq!(text, "mine\n> theirs");
+    }
+}
@@ -0,0 +1,105 @@
+use crate::config::{AuthMode, Config};
+use crate::mail::Folder;
+
+/// Whether an optional feature works with the current account, and why not.
+pub enum State {
+    On,
+    Fallback(String), // works, but in a weaker way
+    Off(String),
+}
+
+pub struct Feature {
+    pub name: &'static str,
+    pub keys: &'static str,
+    pub state: State,
+}
+
+// Server extensions zenmail does not implement; listed so their absence is not a mystery.
+const NOT_IMPLEMENTED: [(&str, &str); 4] = [
+    ("Quota display", "QUOTA"),
+    ("Incremental flag sync", "CONDSTORE"),
+    ("Compression", "COMPRESS=DEFLATE"),
+    ("Special-use folders", "SPECIAL-USE"),
+];
+
+/// The feature matrix for `config` against the server's capabilities, `None` until
+/// they are known.
+pub fn matrix(config: &Config, caps: Option<&[String]>, folders: &[Folder]) -> Vec<Feature> {
+    let has = |cap: &str| caps.map(|c| c.iter().any(|x| x.eq_ignore_ascii_case(cap)));
+    let server = |cap: &str, fallback: Option<&str>| match (has(cap), fallback) {
+        (None, _) => State::Off("server capabilities not known yet".to_string()),
+        (Some(true), _) => State::On,
+        (Some(false), Some(f)) => State::Fallback(format!("server lacks {cap}; {f}")),
+        (Some(false), None) => State::Off(format!("server lacks {cap}")),
+    };
+    let folder = |key: &str, name: &str| {
+        if name.is_empty() {
+            State::Off(format!("[folders] {key} is not set"))
+        } else if !folders.is_empty() && !folders.iter().any(|f| f.name == name) {
+            State::Off(format!("no folder named {name:?} on the server"))
+        } else {
+            State::On
+        }
+    };
+
+    let mut out = vec![
+        Feature {
+            name: "Push (IDLE)",
+            keys: "",
+            state: if config.list.idle { server("IDLE", None) } else { State::Off("disabled in config ([list] idle)".to_string()) },
+        },
+        Feature {
+            name: "Background polling",
+            keys: "",
+            state: if config.list.poll_secs > 0 { State::On } else { State::Off("disabled in config ([list] poll_secs = 0)".to_string()) },
+        },
+        Feature {
+            name: "OAuth login",
+            keys: "",
+            state: match config.imap.auth {
+                AuthMode::Oauth2 => server("AUTH=XOAUTH2", None),
+                AuthMode::Password => State::Off("[imap] auth is \"password\"".to_string()),
+            },
+        },
+        Feature { name: "Archive", keys: "E", state: folder("archive", &config.folders.archive) },
+        Feature {
+            name: "Archive by year",
+            keys: "A",
+            // its folders are created on demand, so only the setting matters
+            state: if config.folders.year_archive_root().is_empty() {
+                State::Off("[folders] archive_root and archive are not set".to_string())
+            } else {
+                State::On
+            },
+        },
+        Feature {
+            name: "Delete to trash",
+            keys: "d",
+            state: match config.folders.trash.as_deref() {
+                Some(t) => folder("trash", t),
+                None => State::Fallback("[folders] trash is not set; mail is expunged".to_string()),
+            },
+        },
+        Feature { name: "Save sent mail", keys: "", state: folder("sent", &config.folders.sent) },
+        Feature { name: "Drafts on the server", keys: "Ctrl+D", state: folder("drafts", &config.folders.drafts) },
+        Feature { name: "Server-side move", keys: "E d", state: server("MOVE", Some("COPY + EXPUNGE is used")) },
+        Feature {
+            name: "Expunge only chosen mail",
+            keys: "E d",
+            state: server("UIDPLUS", Some("EXPUNGE also removes other mail flagged \\Deleted")),
+        },
+    ];
+    for (name, cap) in NOT_IMPLEMENTED {
+        let offered = if has(cap) == Some(true) { " (the server offers it)" } else { "" };
+        out.push(Feature { name, keys: "", state: State::Off(format!("not supported by zenmail{offered}")) });
+    }
+    out
+}
+
+/// Why the feature called `name` cannot be used, or `None` when it can.
+pub fn unavailable(config: &Config, caps: Option<&[String]>, folders: &[Folder], name: &str) -> Option<String> {
+    matrix(config, caps, folders).into_iter().find(|f| f.name == name).and_then(|f| match f.state {
+        State::Off(why) => Some(why),
+        _ => None,
+    })
+}
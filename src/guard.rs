@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Result};
+use regex::RegexBuilder;
+
+use crate::config::SendGuardConfig;
+use crate::mail::smtp::split_addresses;
+
+/// A `[send_guard]` pattern found in mail going outside the internal domains.
+pub struct Hit {
+    pub pattern: String,
+    pub recipient: String, // the first external recipient
+}
+
+/// Looks for the guarded patterns (case-insensitively) in the text and the quote
+/// when any recipient is outside `internal_domains`. A signature, everything after
+/// a `-- ` line, is not searched.
+pub fn check(cfg: &SendGuardConfig, body: &str, quote: &str, recipients: &[&str]) -> Result<Option<Hit>> {
+    if cfg.patterns.is_empty() {
+        return Ok(None);
+    }
+    let external = recipients
+        .iter()
+        .flat_map(|field| split_addresses(field))
+        .find(|addr| !is_internal(addr, &cfg.internal_domains));
+    let Some(recipient) = external else { return Ok(None) };
+
+    let text = without_signature(body);
+    for p in &cfg.patterns {
+        let re = RegexBuilder::new(p)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| anyhow!("bad [send_guard] pattern {p:?}: {e}"))?;
+        if re.is_match(text) || re.is_match(quote) {
+            return Ok(Some(Hit { pattern: p.clone(), recipient: recipient.to_string() }));
+        }
+    }
+    Ok(None)
+}
+
+// `example.com` covers `mail.example.com` too, but not `badexample.com`.
+fn is_internal(addr: &str, domains: &[String]) -> bool {
+    let addr = addr.rsplit_once('<').map_or(addr, |(_, a)| a).trim_end_matches('>').trim();
+    let Some((_, domain)) = addr.rsplit_once('@') else { return false };
+    let domain = domain.to_lowercase();
+    domains.iter().any(|d| {
+        let d = d.trim().trim_start_matches('.').to_lowercase();
+        !d.is_empty() && (domain == d || domain.strip_suffix(&d).is_some_and(|sub| sub.ends_with('.')))
+    })
+}
+
+fn without_signature(body: &str) -> &str {
+    let mut at = 0;
+    for line in body.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']) == "-- " {
+            return &body[..at];
+        }
+        at += line.len();
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(patterns: &[&str], internal: &[&str]) -> SendGuardConfig {
+        SendGuardConfig {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            internal_domains: internal.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    fn hit(cfg: &SendGuardConfig, body: &str, quote: &str, to: &str) -> Option<(String, String)> {
+        check(cfg, body, quote, &[to, ""]).unwrap().map(|h| (h.pattern, h.recipient))
+    }
+
+    #[test]
+    fn matches_in_the_quote_alone() {
+        let c = cfg(&["CONFIDENTIAL"], &["corp.com"]);
+        let found = hit(&c, "Sounds good.", "> CONFIDENTIAL: Q3 numbers", "Partner <bob@partner.org>");
+        assert_eq!(found, Some(("CONFIDENTIAL".into(), "Partner <bob@partner.org>".into())));
+    }
+
+    #[test]
+    fn matching_ignores_case() {
+        let c = cfg(&["internal only", r"project\s+falcon"], &[]);
+        assert!(hit(&c, "This is Internal Only, sorry", "", "bob@partner.org").is_some());
+        let found = hit(&c, "about PROJECT   Falcon", "", "bob@partner.org");
+        assert_eq!(found.map(|(p, _)| p), Some(r"project\s+falcon".to_string()));
+    }
+
+    #[test]
+    fn internal_recipients_and_subdomains_pass() {
+        let c = cfg(&["CONFIDENTIAL"], &["corp.com", ".Example.org"]);
+        assert_eq!(hit(&c, "CONFIDENTIAL", "", "amy@corp.com, Ben <ben@eu.mail.CORP.com>"), None);
+        assert_eq!(hit(&c, "CONFIDENTIAL", "", "cat@example.org"), None);
+        // the first one outside is named, even after internal ones
+        let found = hit(&c, "CONFIDENTIAL", "", "amy@corp.com, dan@notcorp.com");
+        assert_eq!(found.map(|(_, r)| r), Some("dan@notcorp.com".to_string()));
+    }
+
+    #[test]
+    fn is_internal_needs_a_dot_boundary() {
+        let domains = vec!["example.com".to_string()];
+        assert!(is_internal("a@example.com", &domains));
+        assert!(is_internal("A <a@Mail.Example.com>", &domains));
+        assert!(!is_internal("a@badexample.com", &domains));
+        assert!(!is_internal("a@example.com.evil.net", &domains));
+        assert!(!is_internal("no-at-sign", &domains));
+        assert!(!is_internal("a@example.com", &["".to_string()]));
+    }
+
+    #[test]
+    fn the_signature_is_not_searched() {
+        let c = cfg(&["confidential"], &[]);
+        let body = "See you Monday.\n-- \nAnn\nThis mail may be confidential.\n";
+        assert_eq!(hit(&c, body, "", "bob@partner.org"), None);
+        assert_eq!(without_signature(body), "See you Monday.\n");
+        assert_eq!(without_signature("a\r\n-- \r\nsig"), "a\r\n");
+        // `--` without the space is just text
+        assert_eq!(without_signature("a\n--\nb"), "a\n--\nb");
+    }
+
+    #[test]
+    fn nothing_to_guard() {
+        assert_eq!(hit(&cfg(&[], &[]), "CONFIDENTIAL", "", "bob@partner.org"), None);
+        assert!(check(&cfg(&["("], &[]), "x", "", &["bob@partner.org"]).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, VecDeque};
+
+// What the health screen (`H`) shows beyond the live state: when each folder last
+// synced, the last few errors, and what was counted when it was last opened.
+
+const ERRORS_KEPT: usize = 5;
+
+#[derive(Default)]
+pub struct AccountHealth {
+    pub synced: BTreeMap<String, DateTime<Local>>, // folder → last list load or check that worked
+    pub errors: VecDeque<(DateTime<Local>, String)>, // newest last
+    pub drafts: Option<usize>,                       // kept locally; None if they can't be read
+    pub quota: Option<(u64, u64)>,                   // INBOX storage (used, limit) in KiB, when the server says
+}
+
+impl AccountHealth {
+    pub fn synced(&mut self, folder: &str) {
+        self.synced.insert(folder.to_string(), Local::now());
+    }
+
+    /// Remembers `error`, prefixed with what failed, dropping the oldest beyond a few.
+    pub fn error(&mut self, what: &str, error: &str) {
+        if self.errors.len() == ERRORS_KEPT {
+            self.errors.pop_front();
+        }
+        self.errors.push_back((Local::now(), format!("{what}: {error}")));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_five_errors() {
+        let mut h = AccountHealth::default();
+        for i in 1..=7 {
+            h.error("refresh", &format!("timeout {i}"));
+        }
+        let kept: Vec<&str> = h.errors.iter().map(|(_, e)| e.as_str()).collect();
+        assert_eq!(kept, ["refresh: timeout 3", "refresh: timeout 4", "refresh: timeout 5", "refresh: timeout 6", "refresh: timeout 7"]);
+        assert!(h.errors.iter().zip(h.errors.iter().skip(1)).all(|(a, b)| a.0 <= b.0));
+    }
+
+    #[test]
+    fn synced_keeps_one_time_per_folder() {
+        let mut h = AccountHealth::default();
+        h.synced("INBOX");
+        let first = h.synced["INBOX"];
+        h.synced("Archive");
+        h.synced("INBOX");
+        assert_eq!(h.synced.keys().collect::<Vec<_>>(), ["Archive", "INBOX"]);
+        assert!(h.synced["INBOX"] >= first);
+        assert!(h.errors.is_empty());
+    }
+}
@@ -0,0 +1,38 @@
+use anyhow::{anyhow, Result};
+use native_tls::TlsConnector;
+use std::{io::{Read, Write}, net::{TcpStream, ToSocketAddrs}, time::Duration};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_RESPONSE: u64 = 1024 * 1024;
+
+/// Sends `request` (request line and headers, optionally a body) to `host` on port
+/// 443 and returns the reply's status code and body. Meant for HTTP/1.0 requests,
+/// so the reply is neither chunked nor kept open.
+pub fn send(host: &str, request: &str) -> Result<(u16, String)> {
+    let addr = (host, 443).to_socket_addrs()?.next().ok_or_else(|| anyhow!("{host}: no address"))?;
+    let tcp = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    tcp.set_read_timeout(Some(TIMEOUT))?;
+    tcp.set_write_timeout(Some(TIMEOUT))?;
+    let mut tls = TlsConnector::new()?.connect(host, tcp)?;
+
+    tls.write_all(request.as_bytes())?;
+    let mut resp = String::new();
+    tls.take(MAX_RESPONSE).read_to_string(&mut resp)?;
+
+    let (head, body) = resp.split_once("\r\n\r\n").ok_or_else(|| anyhow!("malformed reply"))?;
+    let status = head.lines().next().unwrap_or("");
+    let code = status
+        .split_whitespace()
+        .nth(1)
+        .and_then(|c| c.parse().ok())
+        .ok_or_else(|| anyhow!("{host}: bad status line {status:?}"))?;
+    Ok((code, body.to_string()))
+}
+
+/// The string value of `"name": "..."` in a JSON reply; enough for the flat
+/// objects read here.
+pub fn json_field(body: &str, name: &str) -> Option<String> {
+    regex::Regex::new(&format!(r#""{name}"\s*:\s*"([^"]*)""#))
+        .ok()
+        .and_then(|re| re.captures(body).map(|c| c[1].to_string()))
+}
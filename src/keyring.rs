@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Passwords kept by the desktop's secret store, through its command-line tool:
+// `secret-tool` (libsecret: GNOME Keyring, KWallet) or macOS's `security`. Windows has
+// no such tool, so there it's an error that points at the other password settings.
+
+const SERVICE: &str = "zenmail";
+
+/// The key a password is stored under: login and server, so two accounts with the same
+/// login on different servers stay apart.
+pub fn account(username: &str, host: &str) -> String {
+    format!("{username}@{host}")
+}
+
+fn unsupported() -> Result<()> {
+    if cfg!(windows) {
+        return Err(anyhow!("the keyring isn't supported on Windows; use password_cmd or password_file instead"));
+    }
+    Ok(())
+}
+
+pub fn get(account: &str) -> Result<String> {
+    unsupported()?;
+    let out = if cfg!(target_os = "macos") {
+        run(Command::new("security").args(["find-generic-password", "-s", SERVICE, "-a", account, "-w"]), None)?
+    } else {
+        run(Command::new("secret-tool").args(["lookup", "service", SERVICE, "account", account]), None)?
+    };
+    let pw = out.trim_end_matches(['\r', '\n']).to_string();
+    if pw.is_empty() {
+        return Err(anyhow!("the keyring has no password for {account}; store one with Ctrl+K in the config screen"));
+    }
+    Ok(pw)
+}
+
+pub fn set(account: &str, password: &str) -> Result<()> {
+    unsupported()?;
+    if cfg!(target_os = "macos") {
+        // `-w` last with no value makes `security` ask for the password (twice) on its
+        // input, so it never shows up in the process list
+        let input = format!("{password}\n{password}\n");
+        run(
+            Command::new("security").args(["add-generic-password", "-U", "-s", SERVICE, "-a", account, "-w"]),
+            Some(&input),
+        )?;
+    } else {
+        let label = format!("zenmail {account}");
+        run(
+            Command::new("secret-tool").args(["store", "--label", &label, "service", SERVICE, "account", account]),
+            Some(password),
+        )?;
+    }
+    Ok(())
+}
+
+fn run(cmd: &mut Command, input: Option<&str>) -> Result<String> {
+    let tool = cmd.get_program().to_string_lossy().to_string();
+    let mut child = cmd
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("keyring unavailable ({tool}: {e}); use password_cmd or password instead"))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let out = child.wait_with_output()?;
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        let err = if err.trim().is_empty() { "nothing found".into() } else { err.trim().to_string() };
+        return Err(anyhow!("keyring: {tool} failed ({err}); is a secret service running?"));
+    }
+    Ok(String::from_utf8(out.stdout)?)
+}
@@ -0,0 +1,339 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+use std::collections::BTreeMap;
+
+// The remappable keys: `[keys]` in config.toml binds an action name to a key string
+// like "n", "Down", "Enter" or "Ctrl+r". Unset actions keep their usual key.
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    ListDown,
+    ListUp,
+    Open,
+    Compose,
+    Reply,
+    Refresh,
+    Quit,
+    Config,
+    Help,
+}
+
+impl Action {
+    const ALL: [Action; 9] = [
+        Action::ListDown,
+        Action::ListUp,
+        Action::Open,
+        Action::Compose,
+        Action::Reply,
+        Action::Refresh,
+        Action::Quit,
+        Action::Config,
+        Action::Help,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Action::ListDown => "list_down",
+            Action::ListUp => "list_up",
+            Action::Open => "open",
+            Action::Compose => "compose",
+            Action::Reply => "reply",
+            Action::Refresh => "refresh",
+            Action::Quit => "quit",
+            Action::Config => "config",
+            Action::Help => "help",
+        }
+    }
+
+    fn default_key(self) -> Key {
+        let code = match self {
+            Action::ListDown => KeyCode::Char('j'),
+            Action::ListUp => KeyCode::Char('k'),
+            Action::Open => KeyCode::Enter,
+            Action::Compose => KeyCode::Char('c'),
+            Action::Reply => KeyCode::Char('r'),
+            Action::Refresh => KeyCode::Char('o'),
+            Action::Quit => KeyCode::Char('q'),
+            Action::Config => KeyCode::Char('g'),
+            Action::Help => KeyCode::Char('?'),
+        };
+        Key { code, mods: KeyModifiers::NONE }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Key {
+    code: KeyCode,
+    mods: KeyModifiers, // only CONTROL and ALT; Shift is in the character itself
+}
+
+const MODS: KeyModifiers = KeyModifiers::CONTROL.union(KeyModifiers::ALT);
+
+/// What one of the list's own keys does; `handle_list_keys` dispatches on these.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ListKey {
+    Up,
+    Down,
+    Cancel,
+    Mark,
+    Search,
+    Commands,
+    Compare,
+    SavedSearches,
+    PrevFolder,
+    NextFolder,
+    ArchiveByYear,
+    Drafts,
+    Archive,
+    Filter,
+    Health,
+    Reload,
+    Outbox,
+    Folders,
+    Delete,
+    Forward,
+    LoadOlder,
+    Sort,
+    Seen,
+    Density,
+}
+
+/// The keys the list handles itself. The remappable actions are matched before
+/// them, so an action bound to one hides it, which `KeyMap::load` warns about.
+pub const LIST_KEYS: [(KeyCode, ListKey, &str); 24] = [
+    (KeyCode::Up, ListKey::Up, "move up"),
+    (KeyCode::Down, ListKey::Down, "move down"),
+    (KeyCode::Esc, ListKey::Cancel, "cancel / clear"),
+    (KeyCode::Char(' '), ListKey::Mark, "mark"),
+    (KeyCode::Char('/'), ListKey::Search, "search"),
+    (KeyCode::Char(':'), ListKey::Commands, "commands"),
+    (KeyCode::Char('='), ListKey::Compare, "compare"),
+    (KeyCode::Char('~'), ListKey::SavedSearches, "saved searches"),
+    (KeyCode::Char('['), ListKey::PrevFolder, "previous folder"),
+    (KeyCode::Char(']'), ListKey::NextFolder, "next folder"),
+    (KeyCode::Char('A'), ListKey::ArchiveByYear, "archive by year"),
+    (KeyCode::Char('D'), ListKey::Drafts, "drafts"),
+    (KeyCode::Char('E'), ListKey::Archive, "archive"),
+    (KeyCode::Char('F'), ListKey::Filter, "filter"),
+    (KeyCode::Char('H'), ListKey::Health, "account health"),
+    (KeyCode::Char('O'), ListKey::Reload, "reload"),
+    (KeyCode::Char('S'), ListKey::Outbox, "outbox"),
+    (KeyCode::Char('b'), ListKey::Folders, "folders"),
+    (KeyCode::Char('d'), ListKey::Delete, "delete"),
+    (KeyCode::Char('f'), ListKey::Forward, "forward"),
+    (KeyCode::Char('m'), ListKey::LoadOlder, "load older"),
+    (KeyCode::Char('s'), ListKey::Sort, "sort"),
+    (KeyCode::Char('u'), ListKey::Seen, "read / unread"),
+    (KeyCode::Char('v'), ListKey::Density, "density"),
+];
+
+// The F keys every view handles.
+const GLOBAL_KEYS: [(KeyCode, &str); 2] = [(KeyCode::F(1), "help"), (KeyCode::F(11), "styles")];
+
+/// The list's own key for `code`; modifiers don't change it.
+pub fn list_key(code: KeyCode) -> Option<ListKey> {
+    LIST_KEYS.iter().find(|(k, _, _)| *k == code).map(|&(_, key, _)| key)
+}
+
+// What `key` does on its own, if anything; 1 to 9 pick a saved search.
+fn fixed(key: Key) -> Option<String> {
+    if key.mods != KeyModifiers::NONE {
+        return None;
+    }
+    match key.code {
+        KeyCode::Char(c @ '1'..='9') => Some(format!("saved search {c}")),
+        code => LIST_KEYS
+            .iter()
+            .map(|&(k, _, what)| (k, what))
+            .chain(GLOBAL_KEYS)
+            .find(|(k, _)| *k == code)
+            .map(|(_, what)| what.to_string()),
+    }
+}
+
+pub struct KeyMap {
+    keys: Vec<(Action, Key)>,
+}
+
+impl KeyMap {
+    /// The bindings from `[keys]`, with defaults for everything unset, and what was
+    /// wrong with the entries that couldn't be used.
+    pub fn load(table: &BTreeMap<String, String>) -> (Self, Vec<String>) {
+        let mut keys: Vec<(Action, Key)> = Action::ALL.iter().map(|&a| (a, a.default_key())).collect();
+        let mut warnings = vec![];
+        for (name, value) in table {
+            let Some(action) = Action::ALL.iter().copied().find(|a| a.name() == name) else {
+                warnings.push(format!("[keys] {name}: no such action"));
+                continue;
+            };
+            match parse(value) {
+                Some(key) => {
+                    if let Some(slot) = keys.iter_mut().find(|(a, _)| *a == action) {
+                        slot.1 = key;
+                    }
+                }
+                None => warnings.push(format!("[keys] {name} = {value:?}: not a key, keeping {}", label(action.default_key()))),
+            }
+        }
+        for (i, (a, key)) in keys.iter().enumerate() {
+            if let Some((b, _)) = keys[..i].iter().find(|(_, k)| k == key) {
+                warnings.push(format!("[keys] {} and {} are both {}", b.name(), a.name(), label(*key)));
+            }
+            if let Some(what) = fixed(*key).filter(|_| *key != a.default_key()) {
+                warnings.push(format!("[keys] {} = {:?} takes {} from {what}", a.name(), label(*key), label(*key)));
+            }
+        }
+        (KeyMap { keys }, warnings)
+    }
+
+    /// Whether the pressed key is the one bound to `action`.
+    pub fn is(&self, action: Action, code: KeyCode, mods: KeyModifiers) -> bool {
+        let pressed = Key { code, mods: mods & MODS };
+        self.keys.iter().any(|(a, k)| *a == action && *k == pressed)
+    }
+
+    /// The key bound to `action`, as written in help lines.
+    pub fn label(&self, action: Action) -> String {
+        self.keys.iter().find(|(a, _)| *a == action).map(|(_, k)| label(*k)).unwrap_or_default()
+    }
+}
+
+const NAMED: [(&str, KeyCode); 13] = [
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+];
+
+// "n", "N", "Space", "Down", "F5", "Ctrl+n", "Alt+Shift+n"; names are not case-sensitive
+// but single characters are.
+fn parse(s: &str) -> Option<Key> {
+    let mut mods = KeyModifiers::NONE;
+    let mut shift = false;
+    let mut rest = s.trim();
+    while let Some((prefix, tail)) = rest.split_once('+').filter(|(p, t)| !p.is_empty() && !t.is_empty()) {
+        match prefix.to_lowercase().as_str() {
+            "ctrl" | "control" => mods |= KeyModifiers::CONTROL,
+            "alt" => mods |= KeyModifiers::ALT,
+            "shift" => shift = true,
+            _ => return None,
+        }
+        rest = tail;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if shift => KeyCode::Char(c.to_uppercase().next()?),
+        (Some(c), None) => KeyCode::Char(c),
+        _ if rest.eq_ignore_ascii_case("space") => KeyCode::Char(' '),
+        _ => match NAMED.iter().find(|(n, _)| n.eq_ignore_ascii_case(rest)) {
+            Some((_, code)) => *code,
+            None => KeyCode::F(rest.strip_prefix(['F', 'f'])?.parse().ok().filter(|n| (1..=12).contains(n))?),
+        },
+    };
+    Some(Key { code, mods })
+}
+
+fn label(key: Key) -> String {
+    let name = match key.code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        code => NAMED.iter().find(|(_, c)| *c == code).map(|(n, _)| n.to_string()).unwrap_or_default(),
+    };
+    let ctrl = if key.mods.contains(KeyModifiers::CONTROL) { "Ctrl+" } else { "" };
+    let alt = if key.mods.contains(KeyModifiers::ALT) { "Alt+" } else { "" };
+    format!("{ctrl}{alt}{name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, mods: KeyModifiers) -> Option<Key> {
+        Some(Key { code, mods })
+    }
+
+    fn load(pairs: &[(&str, &str)]) -> (KeyMap, Vec<String>) {
+        KeyMap::load(&pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn parse_reads_characters_names_and_modifiers() {
+        assert!(parse("n") == key(KeyCode::Char('n'), KeyModifiers::NONE));
+        assert!(parse("N") == key(KeyCode::Char('N'), KeyModifiers::NONE));
+        assert!(parse(" Space ") == key(KeyCode::Char(' '), KeyModifiers::NONE));
+        assert!(parse("down") == key(KeyCode::Down, KeyModifiers::NONE));
+        assert!(parse("PAGEUP") == key(KeyCode::PageUp, KeyModifiers::NONE));
+        assert!(parse("F5") == key(KeyCode::F(5), KeyModifiers::NONE));
+        assert!(parse("Ctrl+r") == key(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        assert!(parse("control+Enter") == key(KeyCode::Enter, KeyModifiers::CONTROL));
+        assert!(parse("Alt+Shift+n") == key(KeyCode::Char('N'), KeyModifiers::ALT));
+        assert!(parse("+") == key(KeyCode::Char('+'), KeyModifiers::NONE));
+        assert!(parse("Ctrl++") == key(KeyCode::Char('+'), KeyModifiers::CONTROL));
+    }
+
+    #[test]
+    fn parse_refuses_what_is_not_a_key() {
+        for s in ["", "nn", "Hyper+n", "F0", "F13", "Fx", "Ctrl+"] {
+            assert!(parse(s).is_none(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn labels_read_back_as_the_same_key() {
+        for s in ["n", "Space", "Down", "F5", "Ctrl+r", "Ctrl+Alt+Enter"] {
+            let k = parse(s).unwrap();
+            assert_eq!(label(k), s);
+            assert!(parse(&label(k)) == Some(k));
+        }
+    }
+
+    #[test]
+    fn unset_actions_keep_their_keys() {
+        let (keys, warnings) = load(&[("open", "l"), ("reply", "Ctrl+r")]);
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert!(keys.is(Action::Open, KeyCode::Char('l'), KeyModifiers::NONE));
+        assert!(!keys.is(Action::Open, KeyCode::Enter, KeyModifiers::NONE));
+        assert!(keys.is(Action::Reply, KeyCode::Char('r'), KeyModifiers::CONTROL | KeyModifiers::SHIFT));
+        assert!(keys.is(Action::ListDown, KeyCode::Char('j'), KeyModifiers::NONE));
+        assert_eq!(keys.label(Action::Reply), "Ctrl+r");
+    }
+
+    #[test]
+    fn bad_entries_are_reported_and_skipped() {
+        let (keys, warnings) = load(&[("jump", "x"), ("quit", "Hyper+q")]);
+        assert_eq!(warnings, ["[keys] jump: no such action", "[keys] quit = \"Hyper+q\": not a key, keeping q"]);
+        assert!(keys.is(Action::Quit, KeyCode::Char('q'), KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn clashes_are_reported() {
+        let (_, warnings) = load(&[("open", "r")]);
+        assert_eq!(warnings, ["[keys] open and reply are both r"]);
+        let (_, warnings) = load(&[("open", "d"), ("help", "7"), ("config", "Ctrl+d")]);
+        assert_eq!(warnings, [
+            "[keys] open = \"d\" takes d from delete",
+            "[keys] help = \"7\" takes 7 from saved search 7",
+        ]);
+    }
+
+    #[test]
+    fn every_list_key_is_found_and_guarded() {
+        for (i, &(code, key, what)) in LIST_KEYS.iter().enumerate() {
+            assert!(LIST_KEYS[..i].iter().all(|(c, k, _)| *c != code && *k != key), "{what} is listed twice");
+            assert_eq!(list_key(code), Some(key));
+            assert_eq!(fixed(Key { code, mods: KeyModifiers::NONE }).as_deref(), Some(what));
+        }
+        assert_eq!(list_key(KeyCode::Char('x')), None);
+        assert_eq!(fixed(Key { code: KeyCode::F(11), mods: KeyModifiers::NONE }).as_deref(), Some("styles"));
+    }
+}
@@ -0,0 +1,124 @@
+use html2text::render::TextDecorator;
+use std::io::Cursor;
+
+const WIDTH: usize = 80;
+
+// Plain text with links written inline as `text (url)` instead of numbered footnotes,
+// so a link stays next to what it labels when the mail is quoted in a reply.
+#[derive(Clone, Default)]
+struct InlineLinks {
+    urls: Vec<String>, // open <a> elements, innermost last
+}
+
+impl TextDecorator for InlineLinks {
+    type Annotation = ();
+
+    fn decorate_link_start(&mut self, url: &str) -> (String, Self::Annotation) {
+        self.urls.push(url.to_string());
+        (String::new(), ())
+    }
+
+    fn decorate_link_end(&mut self) -> String {
+        match self.urls.pop() {
+            Some(url) if !url.is_empty() && !url.starts_with('#') => format!(" ({url})"),
+            _ => String::new(),
+        }
+    }
+
+    fn decorate_em_start(&self) -> (String, Self::Annotation) {
+        (String::new(), ())
+    }
+
+    fn decorate_em_end(&self) -> String {
+        String::new()
+    }
+
+    fn decorate_strong_start(&self) -> (String, Self::Annotation) {
+        (String::new(), ())
+    }
+
+    fn decorate_strong_end(&self) -> String {
+        String::new()
+    }
+
+    fn decorate_strikeout_start(&self) -> (String, Self::Annotation) {
+        (String::new(), ())
+    }
+
+    fn decorate_strikeout_end(&self) -> String {
+        String::new()
+    }
+
+    fn decorate_code_start(&self) -> (String, Self::Annotation) {
+        (String::new(), ())
+    }
+
+    fn decorate_code_end(&self) -> String {
+        String::new()
+    }
+
+    fn decorate_preformat_first(&self) -> Self::Annotation {}
+    fn decorate_preformat_cont(&self) -> Self::Annotation {}
+
+    fn decorate_image(&mut self, _src: &str, title: &str) -> (String, Self::Annotation) {
+        // tracking pixels and spacer images have no alt text; drop them
+        if title.trim().is_empty() {
+            (String::new(), ())
+        } else {
+            (format!("[{title}]"), ())
+        }
+    }
+
+    fn header_prefix(&self, _level: usize) -> String {
+        String::new()
+    }
+
+    fn quote_prefix(&self) -> String {
+        "> ".to_string()
+    }
+
+    fn unordered_item_prefix(&self) -> String {
+        "• ".to_string()
+    }
+
+    fn ordered_item_prefix(&self, i: i64) -> String {
+        format!("{i}. ")
+    }
+
+    fn make_subblock_decorator(&self) -> Self {
+        InlineLinks::default()
+    }
+}
+
+// Layout tables and nested divs leave lines of padding; keep at most one blank line
+// in a row and no trailing spaces.
+fn tidy(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            blank += 1;
+            if blank > 1 {
+                continue;
+            }
+        } else {
+            blank = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+/// Converts an HTML body to readable plain text, or `None` when nothing is left.
+pub fn to_text(html: &str) -> Option<String> {
+    if html.trim().is_empty() {
+        return None;
+    }
+    let text = html2text::config::with_decorator(InlineLinks::default())
+        .link_footnotes(false)
+        .no_table_borders()
+        .string_from_read(Cursor::new(html.as_bytes()), WIDTH)
+        .ok()?;
+    Some(tidy(&text)).filter(|t| !t.is_empty())
+}
@@ -1,19 +1,43 @@
 use anyhow::{Result, anyhow};
 use native_tls::TlsConnector;
-use std::net::TcpStream;
-use std::io::Cursor;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::io::{BufRead, BufWriter, Read, Write};
+use std::fs::File;
+use std::collections::HashSet;
+use std::path::Path;
+use base64::{Engine, alphabet};
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use imap::extensions::idle::{SetReadTimeout, WaitOutcome};
+use imap_proto::types::{BodyParams, BodyStructure, ContentEncoding, SectionPath};
+use mailparse::MailHeaderMap;
+use std::time::{Duration, Instant};
+use std::sync::{LazyLock, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::config::MailConfig;
-use crate::mail::MessageSummary;
+use crate::config::{AuthMode, MailConfig};
+use crate::mail::{html, Attachment, Body, Folder, MessageSummary};
 
-fn is_localhost(host: &str) -> bool {
-    host == "127.0.0.1" || host == "localhost"
+type Session = imap::Session<native_tls::TlsStream<TcpStream>>;
+
+/// The server turned the login down; retrying with the same password is pointless.
+#[derive(Debug)]
+pub struct AuthError(pub String);
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "login rejected: {}", self.0)
+    }
 }
 
+impl std::error::Error for AuthError {}
+
 fn tls_connector_for(cfg: &MailConfig) -> Result<TlsConnector> {
     let mut b = TlsConnector::builder();
 
-    if is_localhost(&cfg.host) {
+    for pem in cfg.ca_certs()? {
+        b.add_root_certificate(native_tls::Certificate::from_pem(&pem)?);
+    }
+    if !cfg.verify_certs() {
         b.danger_accept_invalid_certs(true);
         b.danger_accept_invalid_hostnames(true);
     }
@@ -21,30 +45,365 @@ fn tls_connector_for(cfg: &MailConfig) -> Result<TlsConnector> {
     Ok(b.build()?)
 }
 
-fn connect(cfg: &MailConfig) -> Result<imap::Session<native_tls::TlsStream<TcpStream>>> {
+fn connect(cfg: &MailConfig) -> Result<Session> {
+    connect_over(cfg, |tls| tls)
+}
+
+// Logs in over the TLS stream as `wrap` dresses it; IDLE uses its own stream type.
+fn connect_over<S: Read + Write>(
+    cfg: &MailConfig,
+    wrap: impl FnOnce(native_tls::TlsStream<TcpStream>) -> S,
+) -> Result<imap::Session<S>> {
     let tls = tls_connector_for(cfg)?;
 
+    let tcp = open_tcp(cfg)?;
+
     // STARTTLS / TLS 선택
     let client = if cfg.starttls {
-        imap::connect_starttls((cfg.host.as_str(), cfg.port), &cfg.host, &tls)?
+        // by hand rather than `Client::secure`, which can only hand back a plain TlsStream
+        starttls(&tcp).map_err(|e| explain(e, cfg))?;
+        let stream = tls.connect(cfg.server_name(), tcp).map_err(|e| anyhow!("TLS handshake with {}: {e}", cfg.host))?;
+        imap::Client::new(wrap(stream))
     } else {
-        imap::connect((cfg.host.as_str(), cfg.port), &cfg.host, &tls)?
+        let stream = tls.connect(cfg.server_name(), tcp).map_err(|e| anyhow!("TLS handshake with {}: {e}", cfg.host))?;
+        let mut client = imap::Client::new(wrap(stream));
+        client.read_greeting().map_err(|e| explain(e.into(), cfg))?;
+        client
     };
 
+    if cfg.auth == AuthMode::Oauth2 {
+        return oauth_login(client, cfg);
+    }
+    // only NO is a refused login; BAD is a protocol error a new password won't fix
     let session = client
-        .login(&cfg.username, &cfg.password)
-        .map_err(|e| e.0)?;
+        .login(&cfg.username, &cfg.password()?)
+        .map_err(|e| match e.0 {
+            imap::error::Error::No(msg) => anyhow::Error::new(AuthError(msg)),
+            other => other.into(),
+        })?;
 
     Ok(session)
 }
 
+// The greeting, then STARTTLS and its OK, read line by line so nothing of the
+// TLS handshake that follows is consumed.
+fn starttls(tcp: &TcpStream) -> Result<()> {
+    let mut lines = std::io::BufReader::new(tcp);
+    let mut line = String::new();
+    lines.read_line(&mut line)?;
+    let mut out = tcp;
+    out.write_all(b"a0 STARTTLS\r\n")?;
+    loop {
+        line.clear();
+        if lines.read_line(&mut line)? == 0 {
+            return Err(anyhow!("connection closed during STARTTLS"));
+        }
+        if let Some(status) = line.strip_prefix("a0 ") {
+            if status.starts_with("OK") {
+                return Ok(());
+            }
+            return Err(anyhow!("STARTTLS refused: {}", status.trim_end()));
+        }
+    }
+}
+
+// A TCP connection to the server that gives up after `timeout_secs`, both while
+// connecting and on every read or write afterwards, so a stalled FETCH errors out.
+fn open_tcp(cfg: &MailConfig) -> Result<TcpStream> {
+    let timeout = cfg.timeout();
+    let mut last = None;
+    for addr in (cfg.host.as_str(), cfg.port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(tcp) => {
+                tcp.set_read_timeout(Some(timeout))?;
+                tcp.set_write_timeout(Some(timeout))?;
+                return Ok(tcp);
+            }
+            Err(e) => last = Some(e),
+        }
+    }
+    match last {
+        Some(e) if timed_out(&e) => Err(anyhow!("IMAP timeout after {}s connecting to {}:{}", cfg.timeout_secs, cfg.host, cfg.port)),
+        Some(e) => Err(anyhow!("connecting to {}:{}: {e}", cfg.host, cfg.port)),
+        None => Err(anyhow!("{}: no address", cfg.host)),
+    }
+}
+
+fn timed_out(e: &std::io::Error) -> bool {
+    matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock)
+}
+
+/// Says "IMAP timeout after 15s waiting for host:port" for a read or write that ran
+/// into the socket timeout; any other error is passed through.
+pub fn explain(e: anyhow::Error, cfg: &MailConfig) -> anyhow::Error {
+    let io = match e.downcast_ref::<imap::error::Error>() {
+        Some(imap::error::Error::Io(io)) => Some(io),
+        _ => e.downcast_ref::<std::io::Error>(),
+    };
+    match io {
+        Some(io) if timed_out(io) => anyhow!("IMAP timeout after {}s waiting for {}:{}", cfg.timeout_secs, cfg.host, cfg.port),
+        _ => e,
+    }
+}
+
+struct XOAuth2 {
+    response: String,
+    sent: std::cell::Cell<bool>,
+}
+
+impl imap::Authenticator for XOAuth2 {
+    type Response = String;
+    // The first challenge gets the token. A second one carries the server's error
+    // report, and an empty answer lets it finish with NO.
+    fn process(&self, _challenge: &[u8]) -> String {
+        if self.sent.replace(true) { String::new() } else { self.response.clone() }
+    }
+}
+
+// AUTHENTICATE XOAUTH2. A rejected token is dropped and a fresh one tried once, since
+// the provider may have revoked it before it ran out. A second NO isn't an AuthError:
+// a password prompt wouldn't help.
+fn oauth_login<S: Read + Write>(mut client: imap::Client<S>, cfg: &MailConfig) -> Result<imap::Session<S>> {
+    for retry in [false, true] {
+        let response = crate::oauth::xoauth2(&cfg.username, &crate::oauth::access_token(cfg, "imap")?);
+        let auth = XOAuth2 { response, sent: Default::default() };
+        match client.authenticate("XOAUTH2", &auth) {
+            Ok(session) => return Ok(session),
+            Err((imap::error::Error::No(msg) | imap::error::Error::Bad(msg), c)) => {
+                crate::oauth::forget_token(cfg);
+                if retry {
+                    return Err(anyhow!("OAuth2 login rejected: {msg} (run `zenmail --oauth-setup` for a new token)"));
+                }
+                client = c;
+            }
+            Err((e, _)) => return Err(e.into()),
+        }
+    }
+    unreachable!("the second attempt returns")
+}
+
+// Logged-in sessions are kept between operations instead of logging out, so opening
+// a message costs a FETCH rather than a TLS handshake and LOGIN. An operation takes
+// one from the pool (or connects) and puts it back once it succeeded; after an error
+// it is dropped, and the next operation connects afresh.
+struct Pooled {
+    key: String,
+    sess: Session,
+    selected: Option<String>,
+    since: Instant, // back in the pool
+}
+
+static POOL: LazyLock<Mutex<Vec<Pooled>>> = LazyLock::new(Mutex::default);
+
+// Idle sessions kept; more only pile up after operations ran side by side.
+const POOL_SIZE: usize = 3;
+// One idle longer than this gets a NOOP before reuse, to find connections that died...
+const CHECK_AFTER: Duration = Duration::from_secs(60);
+// ...and one idle longer than this is taken as logged out by the server (RFC 3501
+// allows that after 30 minutes).
+const MAX_IDLE: Duration = Duration::from_secs(25 * 60);
+
+fn pool() -> std::sync::MutexGuard<'static, Vec<Pooled>> {
+    POOL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn pool_key(cfg: &MailConfig) -> String {
+    format!("{}:{}:{}:{}", cfg.host, cfg.port, cfg.username, cfg.starttls)
+}
+
+struct Conn {
+    key: String,
+    sess: Session,
+    selected: Option<String>, // mailbox the server has selected, if any
+}
+
+impl Conn {
+    fn open(cfg: &MailConfig) -> Result<Conn> {
+        let key = pool_key(cfg);
+        loop {
+            let found = {
+                let mut pool = pool();
+                pool.iter().rposition(|p| p.key == key).map(|i| pool.remove(i))
+            };
+            let Some(mut p) = found else { break };
+            let idle = p.since.elapsed();
+            if idle < CHECK_AFTER || (idle < MAX_IDLE && p.sess.noop().is_ok()) {
+                return Ok(Conn { key, sess: p.sess, selected: p.selected });
+            }
+        }
+        Ok(Conn { key, sess: connect(cfg)?, selected: None })
+    }
+
+    // SELECT only when another mailbox is selected. Listing and search use
+    // `select_fresh` instead, for the current UIDVALIDITY and message count.
+    fn select(&mut self, mailbox: &str) -> Result<()> {
+        if self.selected.as_deref() != Some(mailbox) {
+            self.select_fresh(mailbox)?;
+        }
+        Ok(())
+    }
+
+    fn select_fresh(&mut self, mailbox: &str) -> Result<imap::types::Mailbox> {
+        self.selected = None; // a failed SELECT leaves nothing selected
+        let mbox = self.sess.select(mailbox)?;
+        self.selected = Some(mailbox.to_string());
+        Ok(mbox)
+    }
+
+    /// Back to the pool for the next operation.
+    fn done(self) {
+        // untagged EXISTS / FETCH replies nobody reads would pile up otherwise
+        while self.sess.unsolicited_responses.try_recv().is_ok() {}
+        let extra = {
+            let mut pool = pool();
+            pool.push(Pooled { key: self.key, sess: self.sess, selected: self.selected, since: Instant::now() });
+            (pool.len() > POOL_SIZE).then(|| pool.remove(0))
+        };
+        if let Some(mut p) = extra {
+            let _ = p.sess.logout();
+        }
+    }
+}
+
+impl std::ops::Deref for Conn {
+    type Target = Session;
+    fn deref(&self) -> &Session {
+        &self.sess
+    }
+}
+
+impl std::ops::DerefMut for Conn {
+    fn deref_mut(&mut self) -> &mut Session {
+        &mut self.sess
+    }
+}
+
+/// Logs out of every kept session; for quitting.
+pub fn close_sessions() {
+    let sessions = std::mem::take(&mut *pool());
+    for mut p in sessions {
+        let _ = p.sess.logout();
+    }
+}
+
+// The IDLE is ended and re-issued this often, so a stop request is seen within a few
+// seconds; that also keeps well inside the 30 minutes after which servers may drop
+// an IDLE (RFC 2177).
+const IDLE_STEP: Duration = Duration::from_secs(5);
+
+/// Waits in IDLE on `mailbox`, calling `changed` whenever the server reports
+/// activity there, until `stop` is set. `Ok(false)` when the server has no IDLE.
+pub fn idle(cfg: &MailConfig, mailbox: &str, stop: &AtomicBool, mut changed: impl FnMut()) -> Result<bool> {
+    let mut sess = connect_over(cfg, |tls| IdleStream { inner: tls, timeout: cfg.timeout() })?;
+    if !sess.capabilities()?.has_str("IDLE") {
+        let _ = sess.logout();
+        return Ok(false);
+    }
+    sess.select(mailbox)?;
+
+    while !stop.load(Ordering::Relaxed) {
+        if idle_step(&mut sess, IDLE_STEP)? {
+            changed();
+        }
+    }
+    let _ = sess.logout();
+    Ok(true)
+}
+
+// One IDLE of at most `step`; true when the mailbox changed meanwhile.
+fn idle_step<S: Read + Write + SetReadTimeout>(sess: &mut imap::Session<S>, step: Duration) -> Result<bool> {
+    let outcome = sess.idle()?.wait_with_timeout(step)?;
+    // what arrived between one IDLE's DONE and the next lands here instead
+    let missed = sess.unsolicited_responses.try_iter().count() > 0;
+    Ok(missed || matches!(outcome, WaitOutcome::MailboxChanged))
+}
+
+// The IDLE connection's stream. imap's timed wait clears the read timeout as it
+// returns, and then reads the answer to DONE; this puts `timeout_secs` back
+// instead, so no read on a dead link blocks for good.
+struct IdleStream<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+impl<S: Read> Read for IdleStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<S: Write> Write for IdleStream<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: SetReadTimeout> SetReadTimeout for IdleStream<S> {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> imap::error::Result<()> {
+        self.inner.set_read_timeout(Some(timeout.unwrap_or(self.timeout)))
+    }
+}
+
+/// Logs in and out again, to try a newly entered password.
+pub fn check_login(cfg: &MailConfig) -> Result<()> {
+    let mut sess = connect(cfg)?;
+    let _ = sess.logout();
+    Ok(())
+}
+
+/// What the server advertises once logged in (`IMAP4rev1`, `IDLE`, `AUTH=PLAIN`, ...).
+pub fn capabilities(cfg: &MailConfig) -> Result<Vec<String>> {
+    use imap_proto::types::Capability;
+
+    let mut sess = Conn::open(cfg)?;
+    let caps = sess
+        .capabilities()?
+        .iter()
+        .map(|c| match c {
+            Capability::Imap4rev1 => "IMAP4rev1".to_string(),
+            Capability::Auth(a) => format!("AUTH={a}"),
+            Capability::Atom(a) => a.to_string(),
+        })
+        .collect();
+    sess.done();
+    Ok(caps)
+}
+
+/// Cheap reachability check: a plain TCP connect to the server, no TLS or login.
+pub fn probe(cfg: &MailConfig) -> bool {
+    let Ok(addrs) = (cfg.host.as_str(), cfg.port).to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|a| TcpStream::connect_timeout(&a, Duration::from_secs(3)).is_ok())
+}
+
+/// Decodes RFC 2047 encoded-words (`=?UTF-8?B?...?=`, `=?EUC-KR?Q?...?=`, ...) by
+/// letting mailparse treat the value as a header; falls back to lossy UTF-8.
+fn decode_header_value(raw: &[u8]) -> String {
+    let mut line = b"X: ".to_vec();
+    line.extend_from_slice(raw);
+    match mailparse::parse_header(&line) {
+        Ok((h, _)) => h.get_value().trim().to_string(),
+        Err(_) => String::from_utf8_lossy(raw).trim().to_string(),
+    }
+}
+
 fn bytes_opt_to_string(v: Option<&[u8]>) -> String {
-    v.map(|b| String::from_utf8_lossy(b).trim().to_string())
-        .unwrap_or_default()
+    v.map(decode_header_value).unwrap_or_default()
 }
 
 fn addr_to_string(name: Option<&[u8]>, mailbox: Option<&[u8]>, host: Option<&[u8]>) -> String {
-    let name = name.map(|b| String::from_utf8_lossy(b).trim().to_string()).unwrap_or_default();
+    let mut name = bytes_opt_to_string(name);
+    // `Doe, John` has to stay one recipient when the list is split on commas again
+    if name.contains([',', ';', '"']) {
+        name = format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+    }
     let mailbox = mailbox.map(|b| String::from_utf8_lossy(b).to_string());
     let host = host.map(|b| String::from_utf8_lossy(b).to_string());
 
@@ -55,31 +414,403 @@ fn addr_to_string(name: Option<&[u8]>, mailbox: Option<&[u8]>, host: Option<&[u8
     }
 }
 
-pub fn fetch_summaries(cfg: &MailConfig, limit: usize) -> Result<Vec<MessageSummary>> {
-    let mut sess = connect(cfg)?;
-    sess.select("INBOX")?;
+/// A set of UIDs, written in IMAP sequence-set syntax with runs collapsed:
+/// 1, 2, 3, 7, 9, 10 → `1:3,7,9:10`. Input order and duplicates don't matter.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UidSet {
+    ranges: Vec<(u32, u32)>, // inclusive, sorted, neither overlapping nor touching
+}
 
-    let mut uids: Vec<u32> = sess.uid_search("ALL")?.into_iter().collect();
-    if uids.is_empty() {
-        let _ = sess.logout();
-        return Ok(vec![]);
+// Room for a few hundred ranges while staying far below the line lengths servers
+// accept (RFC 7162 recommends allowing at least 8192 octets).
+const MAX_SET_LEN: usize = 1000;
+
+impl FromIterator<u32> for UidSet {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        let mut uids: Vec<u32> = iter.into_iter().collect();
+        uids.sort_unstable();
+        uids.dedup();
+        UidSet::from_ranges(uids.into_iter().map(|u| (u, u)))
+    }
+}
+
+impl UidSet {
+    // Merges ranges, sorted by start, that overlap or touch.
+    fn from_ranges(ranges: impl Iterator<Item = (u32, u32)>) -> Self {
+        let mut out: Vec<(u32, u32)> = Vec::new();
+        for (start, end) in ranges {
+            match out.last_mut() {
+                Some(last) if last.1.checked_add(1).is_none_or(|next| start <= next) => last.1 = last.1.max(end),
+                _ => out.push((start, end)),
+            }
+        }
+        UidSet { ranges: out }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|&(a, b)| (b - a) as usize + 1).sum()
+    }
+
+    pub fn contains(&self, uid: u32) -> bool {
+        let i = self.ranges.partition_point(|&(_, end)| end < uid);
+        self.ranges.get(i).is_some_and(|&(start, _)| start <= uid)
+    }
+
+    /// Splits the set into pieces whose text is at most `max_len` bytes, for one
+    /// command per piece. A piece always holds at least one range.
+    pub fn chunks(&self, max_len: usize) -> Vec<UidSet> {
+        let mut out = Vec::new();
+        let mut cur = UidSet::default();
+        let mut len = 0;
+        for &r in &self.ranges {
+            let piece = range_len(r);
+            if !cur.is_empty() && len + 1 + piece > max_len {
+                out.push(std::mem::take(&mut cur));
+                len = 0;
+            }
+            len += piece + usize::from(!cur.is_empty());
+            cur.ranges.push(r);
+        }
+        if !cur.is_empty() {
+            out.push(cur);
+        }
+        out
+    }
+}
+
+// Length of `a` or `a:b` as written.
+fn range_len((a, b): (u32, u32)) -> usize {
+    let digits = |n: u32| n.to_string().len();
+    if a == b { digits(a) } else { digits(a) + 1 + digits(b) }
+}
+
+impl std::fmt::Display for UidSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, &(a, b)) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            if a == b {
+                write!(f, "{a}")?;
+            } else {
+                write!(f, "{a}:{b}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads a set as servers send it (COPYUID, VANISHED, ...): `a` or `a:b` parts,
+/// comma separated, a range's ends in either order. `*` has no meaning outside a
+/// mailbox, so it is refused.
+impl std::str::FromStr for UidSet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let uid = |n: &str| match n.parse::<u32>() {
+            Ok(u) if u > 0 => Ok(u),
+            _ => Err(anyhow!("bad UID {n:?} in set {s:?}")),
+        };
+        let mut ranges = Vec::new();
+        for part in s.split(',').filter(|_| !s.is_empty()) {
+            let (a, b) = match part.split_once(':') {
+                Some((a, b)) => (uid(a)?, uid(b)?),
+                None => (uid(part)?, uid(part)?),
+            };
+            ranges.push((a.min(b), a.max(b)));
+        }
+        ranges.sort_unstable();
+        Ok(UidSet::from_ranges(ranges.into_iter()))
+    }
+}
+
+/// Interprets Importance / X-Priority / Priority headers; true for anything above normal.
+fn is_high_priority(header: &[u8]) -> bool {
+    let Ok((headers, _)) = mailparse::parse_headers(header) else {
+        return false;
+    };
+
+    headers.iter().any(|h| {
+        let v = h.get_value().trim().to_ascii_lowercase();
+        match h.get_key().to_ascii_lowercase().as_str() {
+            "importance" => v == "high",
+            "x-priority" => v.starts_with('1') || v.starts_with('2'),
+            "priority" => v == "urgent",
+            _ => false,
+        }
+    })
+}
+
+/// Decodes an IMAP modified UTF-7 mailbox name (RFC 3501 §5.1.3), e.g.
+/// `&ZeVnLIqe-` → `日本語`. Malformed input is returned unchanged.
+fn decode_mutf7(name: &str) -> String {
+    fn b64(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b',' => Some(63),
+            _ => None,
+        }
+    }
+
+    let mut out = String::new();
+    let mut rest = name;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('-') else {
+            return name.to_string();
+        };
+        let encoded = &after[..end];
+        if encoded.is_empty() {
+            out.push('&');
+        } else {
+            let mut units = Vec::new();
+            let (mut acc, mut bits) = (0u32, 0);
+            for c in encoded.bytes() {
+                let Some(v) = b64(c) else {
+                    return name.to_string();
+                };
+                acc = (acc << 6) | v;
+                bits += 6;
+                if bits >= 16 {
+                    bits -= 16;
+                    units.push((acc >> bits) as u16);
+                    acc &= (1 << bits) - 1;
+                }
+            }
+            match String::from_utf16(&units) {
+                Ok(s) => out.push_str(&s),
+                Err(_) => return name.to_string(),
+            }
+        }
+        rest = &after[end + 1..];
     }
+    out.push_str(rest);
+    out
+}
+
+pub fn list_mailboxes(cfg: &MailConfig) -> Result<Vec<Folder>> {
+    let mut sess = Conn::open(cfg)?;
+
+    let names = sess.list(Some(""), Some("*"))?;
+    let mut out: Vec<Folder> = names
+        .iter()
+        .filter(|n| !n.attributes().contains(&imap::types::NameAttribute::NoSelect))
+        .map(|n| {
+            let name = n.name().to_string();
+            let (depth, leaf) = match n.delimiter().filter(|d| !d.is_empty()) {
+                Some(d) => (name.matches(d).count(), name.rsplit(d).next().unwrap_or(&name)),
+                None => (0, name.as_str()),
+            };
+            Folder { display: decode_mutf7(leaf), depth, name: name.clone(), rights: None }
+        })
+        .collect();
+    out.sort_by_key(|f| (!f.name.eq_ignore_ascii_case("INBOX"), f.name.to_lowercase()));
+
+    sess.done();
+    Ok(out)
+}
+
+/// What ACL grants on `mailbox`, asked for when it's opened rather than for every
+/// folder in the list. None on servers without ACL, and for a folder the server won't
+/// tell about, which is then treated as writable; STORE still says no.
+pub fn folder_rights(cfg: &MailConfig, mailbox: &str) -> Result<Option<String>> {
+    let mut sess = Conn::open(cfg)?;
+    let rights = if sess.capabilities()?.has_str("ACL") {
+        sess.run_command_and_read_response(format!("MYRIGHTS {}", quote(mailbox)))
+            .ok()
+            .and_then(|resp| my_rights(&resp))
+    } else {
+        None
+    };
+    sess.done();
+    Ok(rights)
+}
+
+/// INBOX's storage quota as (used, limit) in KiB, from GETQUOTAROOT. None on servers
+/// without QUOTA, and when no quota root limits INBOX's storage.
+pub fn inbox_quota(cfg: &MailConfig) -> Result<Option<(u64, u64)>> {
+    let mut sess = Conn::open(cfg)?;
+    let quota = if sess.capabilities()?.has_str("QUOTA") {
+        storage_quota(&sess.run_command_and_read_response("GETQUOTAROOT INBOX")?)
+    } else {
+        None
+    };
+    sess.done();
+    Ok(quota)
+}
 
+/// `limit` messages of `mailbox` after skipping the newest `offset`, oldest first,
+/// with the mailbox's UIDVALIDITY so later refreshes can ask for just what arrived since.
+/// The start of each body is only fetched for `previews`.
+pub fn fetch_summaries(
+    cfg: &MailConfig,
+    mailbox: &str,
+    offset: usize,
+    limit: usize,
+    previews: bool,
+) -> Result<(u32, Vec<MessageSummary>)> {
+    let mut sess = Conn::open(cfg)?;
+    let uid_validity = sess.select_fresh(mailbox)?.uid_validity.unwrap_or(0);
+
+    let mut uids: Vec<u32> = sess.uid_search("UNDELETED")?.into_iter().collect();
     uids.sort_unstable();
 
-    let mut picked: Vec<u32> = uids.into_iter().rev().take(limit).collect();
+    let mut picked: Vec<u32> = uids.into_iter().rev().skip(offset).take(limit).collect();
     picked.reverse();
 
-    let mut out = Vec::with_capacity(picked.len());
+    let out = fetch_summaries_for(&mut *sess, &picked, previews)?;
 
-    for uid in picked {
-        let fetches = sess.uid_fetch(uid.to_string(), "ENVELOPE")?;
-        let f = fetches.iter().next().ok_or_else(|| anyhow!("no fetch result"))?;
-        let env = f.envelope().ok_or_else(|| anyhow!("no envelope"))?;
+    sess.done();
+    Ok((uid_validity, out))
+}
+
+// Splits on whitespace outside double quotes, so `subject:"weekly sync"` is one term.
+fn search_terms(input: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut cur = String::new();
+    let mut quoted = false;
+    for c in input.chars().filter(|c| !c.is_control()) {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !cur.is_empty() {
+                    terms.push(std::mem::take(&mut cur));
+                }
+            }
+            c => cur.push(c),
+        }
+    }
+    if !cur.is_empty() {
+        terms.push(cur);
+    }
+    terms
+}
+
+// An IMAP quoted string; the user's quotes and backslashes must not end it early.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Turns what was typed into UID SEARCH keys: `from:`, `to:`, `subject:` and `body:`
+/// pick a field, bare words match anywhere in the message (TEXT). Terms are ANDed,
+/// and messages flagged \Deleted are left out. Empty when there is nothing to search for.
+pub fn search_query(input: &str) -> String {
+    let keys: Vec<String> = search_terms(input)
+        .iter()
+        .filter_map(|term| {
+            let (key, value) = match term.split_once(':') {
+                Some(("from", v)) => ("FROM", v),
+                Some(("to", v)) => ("TO", v),
+                Some(("subject", v)) => ("SUBJECT", v),
+                Some(("body", v)) => ("BODY", v),
+                _ => ("TEXT", term.as_str()),
+            };
+            (!value.is_empty()).then(|| format!("{key} {}", quote(value)))
+        })
+        .collect();
+    if keys.is_empty() {
+        return String::new();
+    }
+    let query = format!("UNDELETED {}", keys.join(" "));
+    if query.is_ascii() { query } else { format!("CHARSET UTF-8 {query}") }
+}
+
+/// Messages in `mailbox` matching `input` (see `search_query`): the newest `limit`
+/// of them, oldest first, and how many matched in all.
+pub fn search_messages(
+    cfg: &MailConfig,
+    mailbox: &str,
+    input: &str,
+    limit: usize,
+    previews: bool,
+) -> Result<(usize, Vec<MessageSummary>)> {
+    let query = search_query(input);
+    if query.is_empty() {
+        return Err(anyhow!("nothing to search for"));
+    }
+
+    let mut sess = Conn::open(cfg)?;
+    sess.select_fresh(mailbox)?;
+    let mut uids: Vec<u32> = sess.uid_search(&query)?.into_iter().collect();
+    uids.sort_unstable();
+    let total = uids.len();
+    let picked = &uids[total.saturating_sub(limit)..];
+
+    let out = fetch_summaries_for(&mut *sess, picked, previews)?;
+
+    sess.done();
+    Ok((total, out))
+}
+
+/// Messages with a UID above `last_uid`, or `None` when UIDVALIDITY no longer
+/// matches and the caller has to reload from scratch.
+pub fn fetch_summaries_since(
+    cfg: &MailConfig,
+    mailbox: &str,
+    last_uid: u32,
+    uid_validity: u32,
+    previews: bool,
+) -> Result<Option<Vec<MessageSummary>>> {
+    let mut sess = Conn::open(cfg)?;
+    if sess.select_fresh(mailbox)?.uid_validity.unwrap_or(0) != uid_validity {
+        sess.done();
+        return Ok(None);
+    }
+
+    // `n:*` always matches the highest UID, even when it is below n
+    let mut uids: Vec<u32> = sess
+        .uid_search(format!("UID {}:* UNDELETED", last_uid.saturating_add(1)))?
+        .into_iter()
+        .filter(|&uid| uid > last_uid)
+        .collect();
+    uids.sort_unstable();
+
+    let out = fetch_summaries_for(&mut *sess, &uids, previews)?;
+
+    sess.done();
+    Ok(Some(out))
+}
+
+// The priority headers are always needed for the `!` mark; the content headers and
+// the first KB of the body only for a preview.
+const SUMMARY_ITEMS: &str = "(UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (IMPORTANCE X-PRIORITY PRIORITY)])";
+const SUMMARY_ITEMS_WITH_PREVIEW: &str = "(UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (IMPORTANCE X-PRIORITY PRIORITY CONTENT-TYPE CONTENT-TRANSFER-ENCODING)] BODY.PEEK[TEXT]<0.1024>)";
+
+fn fetch_summaries_for<T: Read + Write>(sess: &mut imap::Session<T>, uids: &[u32], previews: bool) -> Result<Vec<MessageSummary>> {
+    if uids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let items = if previews { SUMMARY_ITEMS_WITH_PREVIEW } else { SUMMARY_ITEMS };
+    let set: UidSet = uids.iter().copied().collect();
+    let mut out = Vec::with_capacity(set.len());
+    for chunk in set.chunks(MAX_SET_LEN) {
+        let fetches = sess.uid_fetch(chunk.to_string(), items)?;
+        summaries_from(&fetches, &chunk, &mut out);
+    }
+
+    out.sort_unstable_by_key(|m| m.uid);
+    Ok(out)
+}
+
+fn summaries_from(fetches: &[imap::types::Fetch], asked: &UidSet, out: &mut Vec<MessageSummary>) {
+    // UIDs expunged between SEARCH and FETCH are simply absent from the response;
+    // unsolicited FETCHes (flag changes elsewhere) are skipped.
+    for f in fetches.iter() {
+        let Some(uid) = f.uid.filter(|&uid| asked.contains(uid)) else { continue };
+        let Some(env) = f.envelope() else { continue };
 
         let from = if let Some(froms) = &env.from {
-            if let Some(a) = froms.get(0) {
-                addr_to_string(a.name.as_deref(), a.mailbox.as_deref(), a.host.as_deref())
+            if let Some(a) = froms.first() {
+                addr_to_string(a.name, a.mailbox, a.host)
             } else {
                 String::new()
             }
@@ -87,89 +818,1261 @@ pub fn fetch_summaries(cfg: &MailConfig, limit: usize) -> Result<Vec<MessageSumm
             String::new()
         };
 
-        let date = bytes_opt_to_string(env.date.as_deref());
-        let subject = bytes_opt_to_string(env.subject.as_deref());
+        let list = |addrs: &Option<Vec<imap_proto::types::Address>>| {
+            addrs
+                .iter()
+                .flatten()
+                .map(|a| addr_to_string(a.name, a.mailbox, a.host))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let to = list(&env.to);
+        let cc = list(&env.cc);
+        let reply_to = list(&env.reply_to);
+
+        let date = bytes_opt_to_string(env.date);
+        let timestamp = super::parse_date(&date);
+        let subject = bytes_opt_to_string(env.subject);
+        let high_priority = f.header().is_some_and(is_high_priority);
+        let seen = f.flags().contains(&imap::types::Flag::Seen);
+        let preview = match (f.header(), f.text()) {
+            (Some(header), Some(text)) => preview_of(header, text),
+            _ => String::new(),
+        };
 
-        out.push(MessageSummary { uid, from, date, subject });
+        out.push(MessageSummary { uid, from, to, cc, reply_to, date, timestamp, subject, high_priority, seen, preview });
     }
+}
 
-    let _ = sess.logout();
-    Ok(out)
+const PREVIEW_CHARS: usize = 200;
+
+// The first text of a message from its content headers and the first bytes of its
+// body, quotes skipped and whitespace collapsed. Best effort: a part cut off in the
+// middle of its base64 usually gives nothing.
+fn preview_of(header: &[u8], text: &[u8]) -> String {
+    let raw = [header, text].concat();
+    let Ok(parsed) = mailparse::parse_mail(&raw) else {
+        return String::new();
+    };
+    let text = extract_text(&parsed, false).or_else(|| extract_text(&parsed, true)).unwrap_or_default();
+    let words = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.starts_with('>'))
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ");
+    words.chars().take(PREVIEW_CHARS).collect()
 }
 
-pub fn fetch_body_plain(cfg: &MailConfig, uid: u32) -> Result<String> {
-    let mut sess = connect(cfg)?;
-    sess.select("INBOX")?;
+// Flags \Deleted on these UIDs and, with UIDPLUS, expunges just them. Without it they
+// stay flagged: a plain EXPUNGE would also take whatever else is flagged in the
+// mailbox. The list leaves flagged messages out either way.
+fn expunge_uids<T: Read + Write>(sess: &mut imap::Session<T>, uids: &UidSet) -> Result<()> {
+    let uidplus = sess.capabilities()?.has_str("UIDPLUS");
+    for chunk in uids.chunks(MAX_SET_LEN) {
+        let chunk = chunk.to_string();
+        sess.uid_store(&chunk, "+FLAGS.SILENT (\\Deleted)")?;
+        if uidplus {
+            sess.uid_expunge(&chunk)?;
+        }
+    }
+    Ok(())
+}
 
-    let fetches = sess.uid_fetch(uid.to_string(), "BODY.PEEK[]")?;
-    let f = fetches.iter().next().ok_or_else(|| anyhow!("no fetch result"))?;
-    let raw = f.body().ok_or_else(|| anyhow!("no body"))?;
+// MOVE when the server supports it, COPY + \Deleted + EXPUNGE otherwise.
+fn move_uids(sess: &mut Session, uids: &UidSet, dest: &str) -> Result<()> {
+    if sess.capabilities()?.has_str("MOVE") {
+        for chunk in uids.chunks(MAX_SET_LEN) {
+            sess.uid_mv(chunk.to_string(), dest)?;
+        }
+    } else {
+        for chunk in uids.chunks(MAX_SET_LEN) {
+            sess.uid_copy(chunk.to_string(), dest)?;
+        }
+        expunge_uids(sess, uids)?;
+    }
+    Ok(())
+}
 
-    let text = extract_body_with_html_fallback(raw)?;
+/// Deletes the messages: moved to `trash` when one is configured (and we are not
+/// already in it), expunged otherwise.
+pub fn delete_message(cfg: &MailConfig, mailbox: &str, uids: &[u32], trash: Option<&str>) -> Result<()> {
+    if let Some(t) = trash.filter(|t| !t.is_empty() && *t != mailbox) {
+        return move_message(cfg, mailbox, uids, t);
+    }
 
-    let _ = sess.logout();
-    Ok(text)
+    let mut sess = Conn::open(cfg)?;
+    sess.select(mailbox)?;
+    expunge_uids(&mut sess, &uids.iter().copied().collect())?;
+
+    sess.done();
+    Ok(())
 }
 
-fn extract_text_plain(m: &mailparse::ParsedMail) -> String {
-    if !m.subparts.is_empty() {
+/// Stores an already-sent message in `mailbox`, flagged \Seen and dated now.
+pub fn append_to_sent(cfg: &MailConfig, mailbox: &str, raw: &[u8]) -> Result<()> {
+    let mut sess = Conn::open(cfg)?;
+    sess.append_with_flags_and_date(mailbox, raw, &[imap::types::Flag::Seen], chrono::Local::now().fixed_offset())?;
+
+    sess.done();
+    Ok(())
+}
+
+/// Stores a draft in `mailbox`, flagged \Draft and \Seen, in place of any earlier copy
+/// with the same Message-ID.
+pub fn save_draft(cfg: &MailConfig, mailbox: &str, message_id: &str, raw: &[u8]) -> Result<()> {
+    use imap::types::Flag;
+
+    let mut sess = Conn::open(cfg)?;
+    sess.select(mailbox)?;
+    expunge_message_id(&mut sess, message_id)?;
+    sess.append_with_flags_and_date(mailbox, raw, &[Flag::Draft, Flag::Seen], chrono::Local::now().fixed_offset())?;
+
+    sess.done();
+    Ok(())
+}
+
+/// Removes the draft with this Message-ID from `mailbox`, if it is there.
+pub fn remove_draft(cfg: &MailConfig, mailbox: &str, message_id: &str) -> Result<()> {
+    let mut sess = Conn::open(cfg)?;
+    sess.select(mailbox)?;
+    expunge_message_id(&mut sess, message_id)?;
+
+    sess.done();
+    Ok(())
+}
+
+fn expunge_message_id(sess: &mut Session, message_id: &str) -> Result<()> {
+    let uids: UidSet = sess.uid_search(format!("HEADER Message-ID {}", quote(message_id)))?.into_iter().collect();
+    if !uids.is_empty() {
+        expunge_uids(sess, &uids)?;
+    }
+    Ok(())
+}
+
+pub fn move_message(cfg: &MailConfig, mailbox: &str, uids: &[u32], dest: &str) -> Result<()> {
+    let mut sess = Conn::open(cfg)?;
+    sess.select(mailbox)?;
+    move_uids(&mut sess, &uids.iter().copied().collect(), dest)?;
+
+    sess.done();
+    Ok(())
+}
+
+fn store_flags(cfg: &MailConfig, mailbox: &str, uid: u32, query: &str) -> Result<()> {
+    let mut sess = Conn::open(cfg)?;
+    sess.select(mailbox)?;
+    sess.uid_store(uid.to_string(), query)?;
+    sess.done();
+    Ok(())
+}
+
+pub fn mark_seen(cfg: &MailConfig, mailbox: &str, uid: u32) -> Result<()> {
+    store_flags(cfg, mailbox, uid, "+FLAGS.SILENT (\\Seen)")
+}
+
+pub fn mark_unseen(cfg: &MailConfig, mailbox: &str, uid: u32) -> Result<()> {
+    store_flags(cfg, mailbox, uid, "-FLAGS.SILENT (\\Seen)")
+}
+
+/// Marks the message seen and moves it from `mailbox` to the archive folder.
+pub fn archive_message(cfg: &MailConfig, mailbox: &str, uid: u32, archive: &str) -> Result<()> {
+    let mut sess = Conn::open(cfg)?;
+    sess.select(mailbox)?;
+
+    sess.uid_store(uid.to_string(), "+FLAGS (\\Seen)")?;
+    move_uids(&mut sess, &UidSet::from_iter([uid]), archive)?;
+
+    sess.done();
+    Ok(())
+}
+
+/// `root` as a full mailbox name, with the personal namespace prefix added when the
+/// server has one (`Archive` → `INBOX.Archive`), and the hierarchy delimiter.
+pub fn folder_root(cfg: &MailConfig, root: &str) -> Result<(String, String)> {
+    let mut sess = Conn::open(cfg)?;
+    let (prefix, mut delim) = if sess.capabilities()?.has_str("NAMESPACE") {
+        personal_namespace(&sess.run_command_and_read_response("NAMESPACE")?)
+    } else {
+        (String::new(), None)
+    };
+    if delim.is_none() {
+        // LIST "" "" answers with just the delimiter
+        delim = sess.list(Some(""), Some(""))?.iter().find_map(|n| n.delimiter().map(str::to_string));
+    }
+    sess.done();
+
+    let delim = delim.filter(|d| !d.is_empty()).ok_or_else(|| anyhow!("the server has no folder hierarchy"))?;
+    let root = root.trim_end_matches(delim.as_str());
+    let full = if prefix.is_empty() || root.starts_with(&prefix) || root.eq_ignore_ascii_case("INBOX") {
+        root.to_string()
+    } else {
+        format!("{prefix}{root}")
+    };
+    Ok((full, delim))
+}
+
+static MY_RIGHTS: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r#"\* MYRIGHTS (?:"(?:[^"\\]|\\.)*"|[^\s"]+) ([a-z0-9]*)"#).expect("valid regex")
+});
+
+// `* MYRIGHTS "Shared/Support" lr` → `lr`.
+fn my_rights(resp: &[u8]) -> Option<String> {
+    let resp = String::from_utf8_lossy(resp);
+    MY_RIGHTS.captures(&resp).map(|c| c[1].to_string())
+}
+
+static STORAGE_QUOTA: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r#"(?i)\* QUOTA (?:"(?:[^"\\]|\\.)*"|[^\s"]+) \([^)]*?\bSTORAGE (\d+) (\d+)"#).expect("valid regex")
+});
+
+// `* QUOTA "" (STORAGE 10 512)` → (10, 512), in KiB; the first root that limits storage.
+fn storage_quota(resp: &[u8]) -> Option<(u64, u64)> {
+    let resp = String::from_utf8_lossy(resp);
+    let c = STORAGE_QUOTA.captures(&resp)?;
+    Some((c[1].parse().ok()?, c[2].parse().ok()?))
+}
+
+static NAMESPACE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r#"\* NAMESPACE \(\("((?:[^"\\]|\\.)*)" (?:"((?:[^"\\]|\\.)*)"|NIL)"#).expect("valid regex")
+});
+
+// `* NAMESPACE (("INBOX." ".")) NIL NIL` → (`INBOX.`, `.`): the first personal namespace.
+fn personal_namespace(resp: &[u8]) -> (String, Option<String>) {
+    let resp = String::from_utf8_lossy(resp);
+    // quoted strings escape only `"` and `\`
+    let unquote = |s: &str| {
         let mut out = String::new();
-        for sp in &m.subparts {
-            let t = extract_text_plain(sp);
-            if !t.trim().is_empty() {
-                if !out.is_empty() { out.push_str("\n\n"); }
-                out.push_str(&t);
-            }
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            out.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
         }
-        return out;
+        out
+    };
+    match NAMESPACE.captures(&resp) {
+        Some(c) => (unquote(&c[1]), c.get(2).map(|d| unquote(d.as_str()))),
+        None => (String::new(), None),
     }
+}
 
-    let ctype = m.ctype.mimetype.to_lowercase();
-    if ctype == "text/plain" {
-        if let Ok(body) = m.get_body() {
-            return body;
+/// Marks each group of `mailbox` seen and moves it to its folder, creating folders
+/// that don't exist yet. One result per group, in order; the error is for connecting.
+pub fn archive_grouped(cfg: &MailConfig, mailbox: &str, moves: &[(String, Vec<u32>)]) -> Result<Vec<Result<()>>> {
+    let mut sess = Conn::open(cfg)?;
+    sess.select(mailbox)?;
+    let existing: HashSet<String> = sess.list(Some(""), Some("*"))?.iter().map(|n| n.name().to_string()).collect();
+
+    let mut out = Vec::with_capacity(moves.len());
+    for (dest, uids) in moves {
+        let res = (|| -> Result<()> {
+            if !existing.contains(dest) {
+                sess.create(dest)?;
+                let _ = sess.subscribe(dest);
+            }
+            let set: UidSet = uids.iter().copied().collect();
+            for chunk in set.chunks(MAX_SET_LEN) {
+                sess.uid_store(chunk.to_string(), "+FLAGS.SILENT (\\Seen)")?;
+            }
+            move_uids(&mut sess, &set, dest)
+        })();
+        out.push(res);
+    }
+
+    sess.done();
+    Ok(out)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FetchPhase {
+    Connecting,
+    Selecting,
+    Fetching,
+    Parsing,
+}
+
+impl FetchPhase {
+    pub fn label(self) -> &'static str {
+        match self {
+            FetchPhase::Connecting => "connecting",
+            FetchPhase::Selecting => "selecting",
+            FetchPhase::Fetching => "fetching",
+            FetchPhase::Parsing => "parsing",
         }
     }
+}
+
+#[derive(Clone, Copy)]
+pub struct FetchProgress {
+    pub phase: FetchPhase,
+    pub bytes: usize,
+    pub total: Option<usize>,
+}
+
+// Partial fetch size; small enough that progress updates stay live on slow links.
+const BODY_CHUNK: usize = 64 * 1024;
+
+fn check_canceled(cancel: &AtomicBool) -> Result<()> {
+    if cancel.load(Ordering::Relaxed) {
+        return Err(anyhow!("canceled"));
+    }
+    Ok(())
+}
+
+pub fn fetch_body_plain(
+    cfg: &MailConfig,
+    mailbox: &str,
+    uid: u32,
+    max_bytes: usize,
+    cancel: &AtomicBool,
+    progress: impl Fn(FetchProgress),
+) -> Result<Body> {
+    let mut p = FetchProgress { phase: FetchPhase::Connecting, bytes: 0, total: None };
+    progress(p);
+    let mut sess = Conn::open(cfg)?;
+    check_canceled(cancel)?;
+
+    p.phase = FetchPhase::Selecting;
+    progress(p);
+    sess.select(mailbox)?;
+    check_canceled(cancel)?;
+
+    p.phase = FetchPhase::Fetching;
+    let sizes = sess.uid_fetch(uid.to_string(), "RFC822.SIZE")?;
+    let size = sizes.iter().next().and_then(|f| f.size).map(|n| n as usize);
+
+    if let Some(size) = size.filter(|&s| s > max_bytes) {
+        let out = fetch_large_preview(&mut sess, uid, size, max_bytes, cancel, &progress)?;
+        sess.done();
+        return Ok(out);
+    }
+
+    p.total = size;
+    progress(p);
+
+    let mut raw: Vec<u8> = Vec::with_capacity(size.unwrap_or(0));
+    fetch_chunked(&mut sess, uid, "", cancel, |chunk| {
+        raw.extend_from_slice(chunk);
+        p.bytes = raw.len();
+        progress(p);
+        Ok(())
+    })?;
+    if raw.is_empty() {
+        return Err(anyhow!("no body"));
+    }
+
+    sess.done();
+
+    p.phase = FetchPhase::Parsing;
+    progress(p);
+    let parsed = mailparse::parse_mail(&raw)?;
+    let mut attachments = Vec::new();
+    list_attachments(&parsed, "", &mut attachments);
+    let (text, was_html) = extract_body_with_html_fallback(&parsed, &raw);
+    let (message_id, references) = thread_ids(&parsed.headers);
+    let (delivered_to, mailing_list) = delivery_headers(&parsed.headers);
+    Ok(Body { text, was_html, attachments, message_id, references, raw: Some(raw), delivered_to, mailing_list })
+}
 
-    String::new()
+// Message-ID and References with folding undone, for threading a reply.
+fn thread_ids(headers: &[mailparse::MailHeader]) -> (Option<String>, Option<String>) {
+    let get = |name| {
+        headers
+            .get_first_value(name)
+            .map(|v| v.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|v| !v.is_empty())
+    };
+    (get("Message-ID"), get("References"))
+}
+
+// Where the receiving server says it delivered the mail, and whether it came
+// through a mailing list.
+fn delivery_headers(headers: &[mailparse::MailHeader]) -> (Vec<String>, bool) {
+    let delivered = ["Delivered-To", "X-Original-To"]
+        .iter()
+        .flat_map(|name| headers.get_all_values(name))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+    (delivered, headers.get_first_value("List-Id").is_some())
 }
 
-fn find_mime_part<'a>(m: &'a mailparse::ParsedMail, mime: &str) -> Option<&'a mailparse::ParsedMail<'a>> {
-    if m.ctype.mimetype.eq_ignore_ascii_case(mime) {
-        return Some(m);
+// The bytes of `BODY[section]` in a fetch response; the empty section is the whole message.
+fn section_data<'a>(f: &'a imap::types::Fetch, section: &str) -> Option<&'a [u8]> {
+    if section.is_empty() {
+        return f.body();
     }
-    for sp in &m.subparts {
-        if let Some(p) = find_mime_part(sp, mime) {
-            return Some(p);
+    let path: Vec<u32> = section.split('.').map(|n| n.parse().ok()).collect::<Option<_>>()?;
+    f.section(&SectionPath::Part(path, None))
+}
+
+/// Streams `BODY[section]` in `BODY_CHUNK` pieces so no more than one chunk is held
+/// at a time; `sink` sees each piece in order.
+fn fetch_chunked(
+    sess: &mut Session,
+    uid: u32,
+    section: &str,
+    cancel: &AtomicBool,
+    mut sink: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<usize> {
+    let mut offset = 0;
+    loop {
+        check_canceled(cancel)?;
+
+        let query = format!("BODY.PEEK[{section}]<{offset}.{BODY_CHUNK}>");
+        let fetches = sess.uid_fetch(uid.to_string(), query)?;
+        let f = fetches.iter().next().ok_or_else(|| anyhow!("no fetch result"))?;
+        let chunk = section_data(f, section).unwrap_or_default();
+
+        sink(chunk)?;
+        offset += chunk.len();
+
+        if chunk.len() < BODY_CHUNK {
+            return Ok(offset);
         }
     }
-    None
 }
 
-fn extract_body_with_html_fallback(raw: &[u8]) -> Result<String> {
-    let parsed = mailparse::parse_mail(raw)?;
+// A displayable leaf found in BODYSTRUCTURE.
+struct TextPart {
+    section: String,
+    mime: String,
+    charset: String,
+    encoding: String,
+    octets: usize,
+}
+
+#[derive(Default)]
+struct StructureScan {
+    plain: Option<TextPart>,
+    html: Option<TextPart>,
+    attachments: Vec<Attachment>,
+}
+
+fn param<'a>(params: &BodyParams<'a>, key: &str) -> Option<&'a str> {
+    params.iter().flatten().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| *v)
+}
+
+fn encoding_name(e: &ContentEncoding) -> String {
+    match e {
+        ContentEncoding::SevenBit => "7bit".to_string(),
+        ContentEncoding::EightBit => "8bit".to_string(),
+        ContentEncoding::Binary => "binary".to_string(),
+        ContentEncoding::Base64 => "base64".to_string(),
+        ContentEncoding::QuotedPrintable => "quoted-printable".to_string(),
+        ContentEncoding::Other(o) => o.to_lowercase(),
+    }
+}
 
-    if let Some(p) = find_mime_part(&parsed, "text/plain") {
-        if let Ok(s) = p.get_body() {
-            if !s.trim().is_empty() {
-                return Ok(s);
+// Numbers parts the way IMAP does: children of a multipart are 1, 2, ... below the
+// parent's number, and a single-part message's body is part 1.
+fn scan_structure(bs: &BodyStructure, section: &str, scan: &mut StructureScan) {
+    let (common, other) = match bs {
+        BodyStructure::Multipart { bodies, .. } => {
+            for (i, body) in bodies.iter().enumerate() {
+                let child = if section.is_empty() { format!("{}", i + 1) } else { format!("{section}.{}", i + 1) };
+                scan_structure(body, &child, scan);
             }
+            return;
+        }
+        BodyStructure::Basic { common, other, .. }
+        | BodyStructure::Text { common, other, .. }
+        | BodyStructure::Message { common, other, .. } => (common, other),
+    };
+
+    let section = if section.is_empty() { "1".to_string() } else { section.to_string() };
+    let mime = format!("{}/{}", common.ty.ty, common.ty.subtype).to_lowercase();
+    let encoding = encoding_name(&other.transfer_encoding);
+    let octets = other.octets as usize;
+
+    let disposition = common.disposition.as_ref();
+    let filename = disposition
+        .and_then(|d| param(&d.params, "filename"))
+        .or_else(|| param(&common.ty.params, "name"))
+        .map(|n| decode_header_value(n.as_bytes()))
+        .filter(|n| !n.trim().is_empty());
+    // inline images are listed too, so `i` can open them, and so is any inline part
+    // the sender named; those are still shown in the body as well
+    let attached = disposition.is_some_and(|d| d.ty.eq_ignore_ascii_case("attachment"));
+    let named_inline = disposition.is_some_and(|d| d.ty.eq_ignore_ascii_case("inline") && param(&d.params, "filename").is_some());
+    if attached || named_inline || mime.starts_with("image/") {
+        let filename = filename.clone().unwrap_or_else(|| default_filename(&section, &mime));
+        let size = if encoding == "base64" { octets / 4 * 3 } else { octets };
+        let att = Attachment { section: section.clone(), encoding: encoding.clone(), filename, content_type: mime.clone(), size };
+        scan.attachments.push(att);
+    }
+    if attached {
+        return;
+    }
+
+    let slot = match mime.as_str() {
+        "text/plain" => &mut scan.plain,
+        "text/html" => &mut scan.html,
+        _ => return,
+    };
+    if slot.is_none() {
+        let charset = param(&common.ty.params, "charset").unwrap_or("us-ascii").to_string();
+        *slot = Some(TextPart { section, mime, charset, encoding, octets });
+    }
+}
+
+// Decodes a lone part body by giving it back the headers BODYSTRUCTURE described.
+fn decode_part(data: &[u8], mime: &str, charset: &str, encoding: &str) -> Result<String> {
+    let mut wrapped = format!(
+        "Content-Type: {mime}; charset=\"{charset}\"\r\nContent-Transfer-Encoding: {encoding}\r\n\r\n"
+    )
+    .into_bytes();
+    wrapped.extend_from_slice(data);
+    body_text(&mailparse::parse_mail(&wrapped)?)
+}
+
+// The part's text, transfer encoding undone, in its declared charset. An unknown
+// charset is read as UTF-8, lossily. So is 8-bit text labeled US-ASCII (the default
+// when none is given) as long as it is valid UTF-8; other 8-bit bytes in it are
+// taken as windows-1252, like browsers do.
+fn body_text(m: &mailparse::ParsedMail) -> Result<String> {
+    let raw = m.get_body_raw()?;
+    let label = m.ctype.charset.trim();
+    let ascii = label.eq_ignore_ascii_case("us-ascii") || label.eq_ignore_ascii_case("ascii");
+    let text = match charset::Charset::for_label(label.as_bytes()) {
+        Some(_) if ascii && std::str::from_utf8(&raw).is_ok() => String::from_utf8(raw)?,
+        Some(cs) => cs.decode(&raw).0.into_owned(),
+        None => String::from_utf8_lossy(&raw).into_owned(),
+    };
+    Ok(text)
+}
+
+// For mail over the size cap: read BODYSTRUCTURE, fetch only the text part (plain,
+// else HTML) when that fits, and list the rest as attachments to stream to disk.
+fn fetch_large_preview(
+    sess: &mut Session,
+    uid: u32,
+    size: usize,
+    max_bytes: usize,
+    cancel: &AtomicBool,
+    progress: &impl Fn(FetchProgress),
+) -> Result<Body> {
+    let fetches = sess.uid_fetch(uid.to_string(), "(BODYSTRUCTURE BODY.PEEK[HEADER])")?;
+    let f = fetches.iter().next().ok_or_else(|| anyhow!("no fetch result"))?;
+    let bs = f.bodystructure().ok_or_else(|| anyhow!("no BODYSTRUCTURE"))?;
+    let ((message_id, references), (delivered_to, mailing_list)) = match f.header().map(mailparse::parse_headers) {
+        Some(Ok((headers, _))) => (thread_ids(&headers), delivery_headers(&headers)),
+        _ => ((None, None), (vec![], false)),
+    };
+    let mut scan = StructureScan::default();
+    scan_structure(bs, "", &mut scan);
+
+    let mb = size / (1024 * 1024);
+    let mut text = String::new();
+    let mut was_html = false;
+    if let Some(part) = scan.plain.or(scan.html).filter(|p| p.octets <= max_bytes) {
+        let mut p = FetchProgress { phase: FetchPhase::Fetching, bytes: 0, total: Some(part.octets) };
+        progress(p);
+        let mut data = Vec::with_capacity(part.octets);
+        fetch_chunked(sess, uid, &part.section, cancel, |chunk| {
+            data.extend_from_slice(chunk);
+            p.bytes = data.len();
+            progress(p);
+            Ok(())
+        })?;
+
+        p.phase = FetchPhase::Parsing;
+        progress(p);
+        text = decode_part(&data, &part.mime, &part.charset, &part.encoding)?;
+        if part.mime == "text/html" {
+            text = html::to_text(&text).unwrap_or_default();
+            was_html = true;
         }
     }
 
-    if let Some(p) = find_mime_part(&parsed, "text/html") {
-        let html = p.get_body().unwrap_or_default();
-        if !html.trim().is_empty() {
-            let text = html2text::from_read(Cursor::new(html.as_bytes()), 80);
-            return Ok(text?);
+    let banner = format!("[message is {mb} MB; only its text is loaded, other parts can be saved from the attachments list]");
+    Ok(Body {
+        text: format!("{banner}\n\n{text}"),
+        was_html,
+        attachments: scan.attachments,
+        message_id,
+        references,
+        raw: None,
+        delivered_to,
+        mailing_list,
+    })
+}
+
+fn part_filename(p: &mailparse::ParsedMail) -> Option<String> {
+    p.get_content_disposition()
+        .params
+        .get("filename")
+        .or_else(|| p.ctype.params.get("name"))
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+}
+
+// For a part the sender gave no name: `image-1.2.png`, `attachment-2`.
+fn default_filename(section: &str, mime: &str) -> String {
+    match mime.strip_prefix("image/") {
+        Some(ext) => format!("image-{section}.{ext}"),
+        None => format!("attachment-{section}"),
+    }
+}
+
+fn is_attachment(p: &mailparse::ParsedMail) -> bool {
+    p.get_content_disposition().disposition == mailparse::DispositionType::Attachment
+}
+
+// `Content-Disposition: inline; filename=...`
+fn is_named_inline(p: &mailparse::ParsedMail) -> bool {
+    let d = p.get_content_disposition();
+    d.disposition == mailparse::DispositionType::Inline && d.params.contains_key("filename")
+}
+
+// Same part numbering as `scan_structure`, over an already parsed message.
+fn list_attachments(m: &mailparse::ParsedMail, section: &str, out: &mut Vec<Attachment>) {
+    if !m.subparts.is_empty() {
+        for (i, sp) in m.subparts.iter().enumerate() {
+            let child = if section.is_empty() { format!("{}", i + 1) } else { format!("{section}.{}", i + 1) };
+            list_attachments(sp, &child, out);
+        }
+        return;
+    }
+    let mime = m.ctype.mimetype.to_lowercase();
+    if !is_attachment(m) && !is_named_inline(m) && !mime.starts_with("image/") {
+        return;
+    }
+
+    let section = if section.is_empty() { "1".to_string() } else { section.to_string() };
+    out.push(Attachment {
+        filename: part_filename(m).unwrap_or_else(|| default_filename(&section, &mime)),
+        encoding: m
+            .headers
+            .get_first_value("Content-Transfer-Encoding")
+            .map(|e| e.trim().to_lowercase())
+            .unwrap_or_else(|| "7bit".to_string()),
+        content_type: mime,
+        size: m.get_body_raw().map(|b| b.len()).unwrap_or(0),
+        section,
+    });
+}
+
+// Decodes base64 as it streams in, carrying incomplete 4-character groups over.
+struct Base64Sink<W: Write> {
+    out: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Base64Sink<W> {
+    fn push(&mut self, chunk: &[u8]) -> Result<()> {
+        self.pending.extend(chunk.iter().filter(|b| !b.is_ascii_whitespace()));
+        let whole = self.pending.len() / 4 * 4;
+        let decoded = BASE64.decode(&self.pending[..whole])?;
+        self.out.write_all(&decoded)?;
+        self.pending.drain(..whole);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        if !self.pending.is_empty() {
+            let decoded = BASE64.decode(&self.pending)?;
+            self.out.write_all(&decoded)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Writes the whole message to `path` exactly as the server has it, streamed so a
+/// large one is never held in memory.
+pub fn export_message(cfg: &MailConfig, mailbox: &str, uid: u32, path: &Path) -> Result<()> {
+    let mut sess = Conn::open(cfg)?;
+    sess.select(mailbox)?;
+
+    let cancel = AtomicBool::new(false);
+    let mut file = BufWriter::new(File::create(path)?);
+    fetch_chunked(&mut sess, uid, "", &cancel, |chunk| Ok(file.write_all(chunk)?))?;
+    file.flush()?;
+
+    sess.done();
+    Ok(())
+}
+
+/// Writes the decoded attachment to `path`, fetching it chunk by chunk so large
+/// attachments never sit in memory whole. Quoted-printable parts cannot be decoded
+/// piecewise and are refused above `max_bytes`.
+pub fn download_attachment(
+    cfg: &MailConfig,
+    mailbox: &str,
+    uid: u32,
+    att: &Attachment,
+    path: &Path,
+    max_bytes: usize,
+) -> Result<()> {
+    let mut sess = Conn::open(cfg)?;
+    sess.select(mailbox)?;
+
+    let cancel = AtomicBool::new(false);
+    let mut file = BufWriter::new(File::create(path)?);
+    match att.encoding.as_str() {
+        "base64" => {
+            let mut b64 = Base64Sink { out: file, pending: Vec::new() };
+            fetch_chunked(&mut sess, uid, &att.section, &cancel, |chunk| b64.push(chunk))?;
+            b64.finish()?;
+        }
+        "quoted-printable" => {
+            let mut data = Vec::new();
+            fetch_chunked(&mut sess, uid, &att.section, &cancel, |chunk| {
+                data.extend_from_slice(chunk);
+                if data.len() > max_bytes {
+                    return Err(anyhow!("quoted-printable attachment is over the size limit"));
+                }
+                Ok(())
+            })?;
+            let mut wrapped = b"Content-Transfer-Encoding: quoted-printable\r\n\r\n".to_vec();
+            wrapped.extend_from_slice(&data);
+            file.write_all(&mailparse::parse_mail(&wrapped)?.get_body_raw()?)?;
+            file.flush()?;
         }
+        _ => {
+            fetch_chunked(&mut sess, uid, &att.section, &cancel, |chunk| Ok(file.write_all(chunk)?))?;
+            file.flush()?;
+        }
+    }
+
+    sess.done();
+    Ok(())
+}
+
+/// Displayable text of a MIME (sub)tree. A multipart/alternative contributes its
+/// best single alternative (plain preferred over HTML), any other multipart every
+/// displayable part in order. Attachments never count. HTML is only converted
+/// when `allow_html` is set.
+fn extract_text(m: &mailparse::ParsedMail, allow_html: bool) -> Option<String> {
+    let ctype = m.ctype.mimetype.to_lowercase();
+
+    if ctype == "multipart/alternative" {
+        let plain = m.subparts.iter().find_map(|sp| extract_text(sp, false));
+        return if allow_html {
+            plain.or_else(|| m.subparts.iter().find_map(|sp| extract_text(sp, true)))
+        } else {
+            plain
+        };
+    }
+
+    if ctype.starts_with("multipart/") {
+        let parts: Vec<String> = m.subparts.iter().filter_map(|sp| extract_text(sp, allow_html)).collect();
+        return (!parts.is_empty()).then(|| parts.join("\n\n"));
+    }
+
+    if is_attachment(m) {
+        return None;
+    }
+    let body = body_text(m).ok()?;
+    match ctype.as_str() {
+        "text/plain" => Some(body).filter(|b| !b.trim().is_empty()),
+        "text/html" if allow_html => html::to_text(&body),
+        _ => None,
+    }
+}
+
+// The text and whether it had to come from HTML.
+fn extract_body_with_html_fallback(parsed: &mailparse::ParsedMail, raw: &[u8]) -> (String, bool) {
+    if let Some(text) = extract_text(parsed, false) {
+        return (text, false);
+    }
+    if let Some(text) = extract_text(parsed, true) {
+        return (text, true);
     }
 
-    if let Ok(s) = parsed.get_body() {
+    if let Ok(s) = body_text(parsed) {
         if !s.trim().is_empty() {
-            return Ok(s);
+            return (s, false);
+        }
+    }
+
+    (String::from_utf8_lossy(raw).to_string(), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    // A server that answers from a script, whatever is asked; what was asked is kept.
+    struct FakeStream {
+        replies: Cursor<Vec<u8>>,
+        sent: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.replies.read(buf)
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.sent.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Logged in (tag a1), with `replies` to come; the commands sent after login.
+    fn fake_session(replies: &str) -> (imap::Session<FakeStream>, Rc<RefCell<Vec<u8>>>) {
+        let sent = Rc::new(RefCell::new(vec![]));
+        let stream = FakeStream { replies: Cursor::new(format!("a1 OK logged in\r\n{replies}").into_bytes()), sent: sent.clone() };
+        let sess = imap::Client::new(stream).login("ann", "pw").map_err(|(e, _)| e).unwrap();
+        sent.borrow_mut().clear();
+        (sess, sent)
+    }
+
+    fn envelope(subject: &str, from: &str) -> String {
+        let (mailbox, host) = from.split_once('@').unwrap();
+        let addr = format!("((NIL NIL \"{mailbox}\" \"{host}\"))");
+        format!("(\"Tue, 14 Nov 2023 10:00:00 +0000\" \"{subject}\" {addr} {addr} {addr} ((NIL NIL \"me\" \"x.org\")) NIL NIL NIL \"<{subject}@x>\")")
+    }
+
+    #[test]
+    fn uid_sets_collapse_runs() {
+        let set: UidSet = [101, 102, 105].into_iter().chain(106..=150).collect();
+        assert_eq!(set.to_string(), "101:102,105:150");
+        assert_eq!(set.len(), 48);
+    }
+
+    #[test]
+    fn uid_set_edge_cases() {
+        let empty: UidSet = std::iter::empty().collect();
+        assert!(empty.is_empty() && !empty.contains(1));
+        assert_eq!(empty.len(), 0);
+        assert_eq!(empty.to_string(), "");
+        assert!(empty.chunks(10).is_empty());
+
+        let one: UidSet = [7].into_iter().collect();
+        assert_eq!((one.to_string(), one.len()), ("7".to_string(), 1));
+
+        let set: UidSet = [10, 9, 1, 3, 2, 7, 3, 10, 1].into_iter().collect();
+        assert_eq!((set.to_string(), set.len()), ("1:3,7,9:10".to_string(), 6));
+        assert!(set.contains(2) && set.contains(9) && !set.contains(8) && !set.contains(11) && !set.contains(0));
+
+        let top: UidSet = [u32::MAX, u32::MAX - 1, 1].into_iter().collect();
+        assert_eq!(top.to_string(), format!("1,{}:{}", u32::MAX - 1, u32::MAX));
+        assert!(top.contains(u32::MAX));
+        let all: UidSet = "1:4294967295".parse().unwrap();
+        assert_eq!(all.len(), u32::MAX as usize);
+    }
+
+    #[test]
+    fn uid_sets_split_below_a_length() {
+        let set: UidSet = (1..=50).map(|n| n * 1000).collect();
+        let chunks = set.chunks(40);
+        assert!(chunks.iter().all(|c| c.to_string().len() <= 40), "{chunks:?}");
+        assert_eq!(chunks.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(","), set.to_string());
+        // a range longer than the limit still goes out, on its own
+        let long: UidSet = "1000000:2000000,5".parse().unwrap();
+        assert_eq!(long.chunks(3).iter().map(|c| c.to_string()).collect::<Vec<_>>(), ["5", "1000000:2000000"]);
+    }
+
+    #[test]
+    fn uid_sets_parse_what_servers_send() {
+        let parse = |s: &str| s.parse::<UidSet>().map(|set| set.to_string());
+        assert_eq!(parse("1:3,7,9:10").unwrap(), "1:3,7,9:10");
+        assert_eq!(parse("10:9,3,2,1").unwrap(), "1:3,9:10");
+        assert_eq!(parse("1:5,3:8,9").unwrap(), "1:9");
+        assert_eq!(parse("").unwrap(), "");
+        for bad in ["*", "1:*", "0", "1,,2", "a", "1:2:3", " 1", "-1", "4294967296"] {
+            assert!(parse(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn uid_sets_round_trip() {
+        let mut rng = fastrand::Rng::with_seed(514);
+        for _ in 0..500 {
+            let spread = [10, 1000, u32::MAX][rng.usize(..3)];
+            let uids: Vec<u32> = (0..rng.usize(0..60)).map(|_| rng.u32(1..=spread)).collect();
+            let set: UidSet = uids.iter().copied().collect();
+
+            let back: UidSet = set.to_string().parse().unwrap();
+            assert_eq!(back, set);
+            let mut distinct = uids.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            assert_eq!(set.len(), distinct.len());
+            assert!(uids.iter().all(|&u| set.contains(u)));
+            for _ in 0..20 {
+                let probe = rng.u32(1..=spread);
+                assert_eq!(set.contains(probe), distinct.binary_search(&probe).is_ok());
+            }
+
+            let max_len = rng.usize(1..40);
+            let rejoined: UidSet = set.chunks(max_len).iter().flat_map(|c| {
+                assert!(c.to_string().len() <= max_len || c.ranges.len() == 1, "{c} over {max_len}");
+                c.to_string().parse::<UidSet>().unwrap().ranges
+            }).flat_map(|(a, b)| a..=b).collect();
+            assert_eq!(rejoined, set);
         }
     }
 
-    Ok(String::from_utf8_lossy(raw).to_string())
+    #[test]
+    fn summaries_come_from_one_fetch() {
+        let replies = format!(
+            "* 3 FETCH (UID 105 FLAGS () ENVELOPE {})\r\n\
+             * 1 FETCH (UID 101 FLAGS (\\Seen) ENVELOPE {})\r\n\
+             * 9 FETCH (UID 200 FLAGS (\\Seen))\r\n\
+             a2 OK fetched\r\n",
+            envelope("third", "c@x.com"),
+            envelope("first", "a@x.com"),
+        );
+        let (mut sess, sent) = fake_session(&replies);
+        // 102 was expunged between SEARCH and FETCH; 200 is someone else's flag change
+        let out = fetch_summaries_for(&mut sess, &[105, 101, 102], false).unwrap();
+
+        let sent = String::from_utf8(sent.borrow().clone()).unwrap();
+        assert_eq!(sent.lines().count(), 1, "{sent}");
+        assert!(sent.starts_with("a2 UID FETCH 101:102,105 (UID FLAGS ENVELOPE "), "{sent}");
+        // no preview shown, so no body text asked for
+        assert!(!sent.contains("TEXT") && !sent.contains("CONTENT-TYPE"), "{sent}");
+        let rows: Vec<_> = out.iter().map(|m| (m.uid, m.subject.as_str(), m.from.as_str(), m.seen)).collect();
+        assert_eq!(rows, [(101, "first", "<a@x.com>", true), (105, "third", "<c@x.com>", false)]);
+        assert_eq!(out[0].to, "<me@x.org>");
+        assert!(out[0].timestamp.is_some());
+    }
+
+    #[test]
+    fn no_uids_no_fetch() {
+        let (mut sess, sent) = fake_session("");
+        assert!(fetch_summaries_for(&mut sess, &[], true).unwrap().is_empty());
+        assert!(sent.borrow().is_empty());
+    }
+
+    #[test]
+    fn storage_quota_from_getquotaroot() {
+        let resp = b"* QUOTAROOT INBOX \"\"\r\n* QUOTA \"\" (STORAGE 10240 1048576)\r\nA1 OK done\r\n";
+        assert_eq!(storage_quota(resp), Some((10240, 1048576)));
+        // other resources come first on some servers, and roots may be atoms
+        assert_eq!(storage_quota(b"* QUOTA user.bob (MESSAGE 12 5000 STORAGE 33 100)\r\n"), Some((33, 100)));
+        assert_eq!(storage_quota(b"* quota \"\" (storage 1 2)\r\n"), Some((1, 2)));
+    }
+
+    #[test]
+    fn storage_quota_missing() {
+        assert_eq!(storage_quota(b"* QUOTAROOT INBOX\r\nA1 OK no quota\r\n"), None);
+        assert_eq!(storage_quota(b"* QUOTA \"\" (MESSAGE 12 5000)\r\n"), None);
+    }
+
+    #[test]
+    fn my_rights_after_a_quoted_name() {
+        assert_eq!(my_rights(b"* MYRIGHTS \"Shared/Support\" lr\r\nA1 OK done\r\n").as_deref(), Some("lr"));
+        assert_eq!(my_rights(b"* MYRIGHTS \"say \\\"hi\\\"\" lrswite\r\n").as_deref(), Some("lrswite"));
+        assert_eq!(my_rights(b"* MYRIGHTS \"With space\" lrs\r\n").as_deref(), Some("lrs"));
+    }
+
+    #[test]
+    fn my_rights_after_an_atom() {
+        assert_eq!(my_rights(b"* MYRIGHTS INBOX lrswipkxtecda\r\n").as_deref(), Some("lrswipkxtecda"));
+        // RFC 2086 servers answer with `d` and `c` rather than RFC 4314's letters
+        assert_eq!(my_rights(b"* MYRIGHTS Archive lrswd\r\n").as_deref(), Some("lrswd"));
+        assert_eq!(my_rights(b"* MYRIGHTS Empty \r\n").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn quoted_printable_latin1_keeps_its_accents() {
+        let raw = b"Content-Type: text/plain; charset=ISO-8859-1\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+Caf=E9 cr=E8me br=FBl=E9e, =E0 bient=F4t!\r\n";
+        let parsed = mailparse::parse_mail(raw).unwrap();
+        assert_eq!(body_text(&parsed).unwrap().trim_end(), "Café crème brûlée, à bientôt!");
+    }
+
+    #[test]
+    fn charsets_apply_inside_multipart() {
+        let raw = b"Content-Type: multipart/mixed; boundary=b\r\n\
+\r\n\
+--b\r\n\
+Content-Type: text/plain; charset=\"iso-8859-1\"\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+Gr=FC=DFe\r\n\
+--b\r\n\
+Content-Type: text/plain; charset=Shift_JIS\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+k/qWe4zq\r\n\
+--b--\r\n";
+        let parsed = mailparse::parse_mail(raw).unwrap();
+        let text = extract_text(&parsed, false).unwrap();
+        assert!(text.contains("Grüße"), "{text:?}");
+        assert!(text.contains("日本語"), "{text:?}");
+    }
+
+    #[test]
+    fn unknown_charsets_fall_back_to_lossy_utf8() {
+        let raw = b"Content-Type: text/plain; charset=x-made-up\r\n\r\nna\xefve caf\xc3\xa9\r\n";
+        let parsed = mailparse::parse_mail(raw).unwrap();
+        assert_eq!(body_text(&parsed).unwrap().trim_end(), "na\u{fffd}ve café");
+    }
+
+    #[test]
+    fn my_rights_missing() {
+        assert_eq!(my_rights(b"A1 NO no such mailbox\r\n"), None);
+        assert_eq!(my_rights(b""), None);
+    }
+
+    #[test]
+    fn encoded_words_in_real_subjects() {
+        let cases = [
+            ("=?UTF-8?B?R3LDvMOfZSBhdXMgS8O2bG4=?=", "Grüße aus Köln"),
+            ("=?ISO-8859-1?Q?Re:_R=E9union_de_lundi?=", "Re: Réunion de lundi"),
+            ("=?EUC-KR?B?vsiz58fPvLy/5A==?=", "안녕하세요"),
+            ("=?ISO-2022-JP?B?GyRCIVo9RU1XIVskKkNOJGkkOxsoQg==?=", "【重要】お知らせ"),
+            ("=?Shift_JIS?B?grGC8YLJgr+CzQ==?=", "こんにちは"),
+            ("[list] =?utf-8?q?caf=C3=A9?= meeting", "[list] café meeting"),
+        ];
+        for (raw, want) in cases {
+            assert_eq!(decode_header_value(raw.as_bytes()), want, "{raw}");
+        }
+    }
+
+    #[test]
+    fn adjacent_encoded_words_join_without_the_space() {
+        let raw = "=?UTF-8?B?R3LDvMOf?= =?UTF-8?Q?e_aus?=\r\n =?ISO-8859-1?Q?_K=F6ln?=";
+        assert_eq!(decode_header_value(raw.as_bytes()), "Grüße aus Köln");
+    }
+
+    #[test]
+    fn undecodable_words_stay_as_they_came() {
+        assert_eq!(decode_header_value(b"=?x-made-up?B?aGVsbG8=?="), "=?x-made-up?B?aGVsbG8=?=");
+        assert_eq!(decode_header_value(b"=?UTF-8?B?not base64?="), "=?UTF-8?B?not base64?=");
+        assert_eq!(decode_header_value(b"plain caf\xc3\xa9"), "plain café");
+    }
+
+    #[test]
+    fn encoded_sender_names() {
+        assert_eq!(addr_to_string(Some(b"=?UTF-8?Q?J=C3=BCrgen?="), Some(b"j"), Some(b"x.de")), "Jürgen <j@x.de>");
+        // a decoded comma must not split the address later
+        assert_eq!(addr_to_string(Some(b"=?UTF-8?Q?M=C3=BCller,_Hans?="), Some(b"h"), Some(b"x.de")), "\"Müller, Hans\" <h@x.de>");
+    }
+
+    fn text_of(raw: &str) -> (String, bool) {
+        let raw = raw.replace('\n', "\r\n");
+        let parsed = mailparse::parse_mail(raw.as_bytes()).unwrap();
+        let (text, html) = extract_body_with_html_fallback(&parsed, raw.as_bytes());
+        (text.trim_end().to_string(), html)
+    }
+
+    #[test]
+    fn alternative_picks_one_part() {
+        let (text, html) = text_of(
+            "Content-Type: multipart/alternative; boundary=alt
+
+--alt
+Content-Type: text/plain; charset=utf-8
+
+Plain words
+--alt
+Content-Type: text/html; charset=utf-8
+
+<p>Plain words</p>
+--alt--
+",
+        );
+        assert_eq!((text.as_str(), html), ("Plain words", false));
+    }
+
+    #[test]
+    fn alternative_without_plain_text_falls_back_to_html() {
+        let (text, html) = text_of(
+            "Content-Type: multipart/alternative; boundary=alt
+
+--alt
+Content-Type: text/html; charset=utf-8
+
+<p>Only <b>HTML</b> here</p>
+--alt--
+",
+        );
+        assert!(html);
+        assert!(text.contains("Only") && text.contains("HTML") && !text.contains("<b>"), "{text:?}");
+    }
+
+    #[test]
+    fn mixed_keeps_its_text_parts_in_order() {
+        let (text, html) = text_of(
+            "Content-Type: multipart/mixed; boundary=mix
+
+--mix
+Content-Type: text/plain; charset=utf-8
+
+First
+--mix
+Content-Type: application/pdf; name=a.pdf
+Content-Disposition: attachment; filename=a.pdf
+Content-Transfer-Encoding: base64
+
+JVBERi0=
+--mix
+Content-Type: text/plain; charset=utf-8
+Content-Disposition: attachment; filename=notes.txt
+
+not shown
+--mix
+Content-Type: text/plain; charset=utf-8
+
+Second
+--mix--
+",
+        );
+        assert_eq!((text.as_str(), html), ("First\n\nSecond", false));
+    }
+
+    #[test]
+    fn nested_multiparts_show_each_text_once() {
+        let (text, _) = text_of(
+            "Content-Type: multipart/mixed; boundary=outer
+
+--outer
+Content-Type: multipart/alternative; boundary=alt
+
+--alt
+Content-Type: text/plain; charset=utf-8
+
+Body text
+--alt
+Content-Type: multipart/related; boundary=rel
+
+--rel
+Content-Type: text/html; charset=utf-8
+
+<p>Body text</p>
+--rel
+Content-Type: image/png
+Content-ID: <logo>
+
+iVBORw0KGgo=
+--rel--
+--alt--
+--outer
+Content-Type: text/plain; charset=utf-8
+
+Footer
+--outer--
+",
+        );
+        assert_eq!(text.matches("Body text").count(), 1, "{text:?}");
+        assert!(text.ends_with("Footer"), "{text:?}");
+    }
+
+    #[test]
+    fn base64_utf8_bodies_are_decoded() {
+        let (text, _) = text_of(
+            "Content-Type: text/plain; charset=UTF-8
+Content-Transfer-Encoding: base64
+
+R3LDvMOfZSDigJQg4oCccXVvdGVk4oCdIOaXpeacrOiqniDinJM=
+",
+        );
+        assert_eq!(text, "Grüße — “quoted” 日本語 ✓");
+    }
+
+    fn expunge_commands(capabilities: &str) -> Vec<String> {
+        let replies = format!("* CAPABILITY IMAP4rev1{capabilities}\r\na2 OK done\r\na3 OK stored\r\na4 OK expunged\r\n");
+        let (mut sess, sent) = fake_session(&replies);
+        expunge_uids(&mut sess, &[3, 1, 2].into_iter().collect()).unwrap();
+        let sent = String::from_utf8(sent.borrow().clone()).unwrap();
+        sent.lines().map(|l| l.split_once(' ').unwrap().1.to_string()).collect()
+    }
+
+    #[test]
+    fn expunge_is_scoped_to_the_uids() {
+        assert_eq!(expunge_commands(" UIDPLUS"), ["CAPABILITY", "UID STORE 1:3 +FLAGS.SILENT (\\Deleted)", "UID EXPUNGE 1:3"]);
+        // a plain EXPUNGE would take other clients' \Deleted messages with it
+        assert_eq!(expunge_commands(""), ["CAPABILITY", "UID STORE 1:3 +FLAGS.SILENT (\\Deleted)"]);
+    }
+
+    #[test]
+    fn searches_leave_deleted_mail_out() {
+        assert_eq!(search_query("from:ann report"), "UNDELETED FROM \"ann\" TEXT \"report\"");
+        assert_eq!(search_query("café"), "CHARSET UTF-8 UNDELETED TEXT \"café\"");
+        assert_eq!(search_query("  from: "), "");
+    }
+
+    #[test]
+    fn inline_images_are_listed_with_the_attachments() {
+        let raw = "Content-Type: multipart/related; boundary=rel\r\n\r\n\
+--rel\r\nContent-Type: text/html\r\n\r\n<img src=\"cid:logo\">\r\n\
+--rel\r\nContent-Type: image/png\r\nContent-ID: <logo>\r\nContent-Transfer-Encoding: base64\r\n\r\niVBORw==\r\n\
+--rel\r\nContent-Type: image/jpeg; name=\"cat.jpg\"\r\nContent-Disposition: inline\r\nContent-Transfer-Encoding: base64\r\n\r\n/9j/\r\n\
+--rel--\r\n";
+        let mut out = vec![];
+        list_attachments(&mailparse::parse_mail(raw.as_bytes()).unwrap(), "", &mut out);
+        let listed: Vec<_> = out.iter().map(|a| (a.section.as_str(), a.filename.as_str(), a.content_type.as_str())).collect();
+        assert_eq!(listed, [("2", "image-2.png", "image/png"), ("3", "cat.jpg", "image/jpeg")]);
+    }
+
+    #[test]
+    fn named_inline_parts_are_listed_and_still_shown() {
+        let raw = "Content-Type: multipart/mixed; boundary=mix\r\n\r\n\
+--mix\r\nContent-Type: text/plain\r\n\r\nSee below.\r\n\
+--mix\r\nContent-Type: text/plain; name=\"notes.txt\"\r\nContent-Disposition: inline; filename=\"notes.txt\"\r\n\r\nThe notes\r\n\
+--mix\r\nContent-Type: application/pdf\r\nContent-Disposition: inline\r\n\r\n%PDF\r\n\
+--mix--\r\n";
+        let parsed = mailparse::parse_mail(raw.as_bytes()).unwrap();
+        let mut out = vec![];
+        list_attachments(&parsed, "", &mut out);
+        let listed: Vec<_> = out.iter().map(|a| (a.section.as_str(), a.filename.as_str())).collect();
+        assert_eq!(listed, [("2", "notes.txt")]);
+        assert!(extract_text(&parsed, false).unwrap().contains("The notes"));
+    }
+
+    // A server that logs in, idles and ends the IDLE on DONE, for `idle_step`.
+    fn idle_server() -> std::net::SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            let mut out = tcp.try_clone().unwrap();
+            let mut lines = std::io::BufReader::new(tcp);
+            out.write_all(b"* OK ready\r\n").unwrap();
+            let mut idle_tag = String::new();
+            let mut line = String::new();
+            while lines.read_line(&mut line).unwrap_or(0) > 0 {
+                let (tag, command) = line.trim_end().split_once(' ').unwrap_or((line.trim_end(), ""));
+                let reply = match command {
+                    "IDLE" => {
+                        idle_tag = tag.to_string();
+                        "+ idling\r\n".to_string()
+                    }
+                    _ if tag == "DONE" => format!("{idle_tag} OK IDLE done\r\n"),
+                    _ => format!("{tag} OK done\r\n"),
+                };
+                out.write_all(reply.as_bytes()).unwrap();
+                line.clear();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn an_idle_step_leaves_the_read_timeout_set() {
+        let tcp = TcpStream::connect(idle_server()).unwrap();
+        let socket = tcp.try_clone().unwrap();
+        let timeout = Duration::from_secs(7);
+        let mut client = imap::Client::new(IdleStream { inner: tcp, timeout });
+        client.read_greeting().unwrap();
+        let mut sess = client.login("user", "pass").map_err(|e| e.0).unwrap();
+
+        assert!(!idle_step(&mut sess, Duration::from_millis(50)).unwrap());
+        // imap's timed wait clears it on the way out; a plain TcpStream would have None here
+        assert_eq!(socket.read_timeout().unwrap(), Some(timeout));
+        assert!(!idle_step(&mut sess, Duration::from_millis(50)).unwrap());
+        assert_eq!(socket.read_timeout().unwrap(), Some(timeout));
+    }
 }
@@ -1,10 +1,217 @@
+pub mod html;
 pub mod imap;
 pub mod smtp;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct MessageSummary {
     pub uid: u32,
     pub from: String,
+    pub to: String, // every To address, comma separated
+    pub cc: String,
+    pub reply_to: String, // the server fills in From when the header is absent
     pub date: String,
+    pub timestamp: Option<i64>, // `date` as Unix seconds; None when it doesn't parse
     pub subject: String,
+    pub high_priority: bool,
+    pub seen: bool,
+    pub preview: String, // the start of the text, on one line; may be empty
+}
+
+/// A Date header as Unix seconds: RFC 2822, or whatever mailparse makes of it.
+pub fn parse_date(date: &str) -> Option<i64> {
+    match chrono::DateTime::parse_from_rfc2822(date.trim()) {
+        Ok(d) => Some(d.timestamp()),
+        Err(_) => mailparse::dateparse(date).ok(),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Folder {
+    pub name: String,    // raw name as used in SELECT
+    pub display: String, // decoded from modified UTF-7
+    pub depth: usize,    // nesting level from the hierarchy delimiter
+    pub rights: Option<String>, // MYRIGHTS letters; None without ACL or until first opened
+}
+
+impl Folder {
+    /// Whether the ACL grants `right`: `s` set \Seen, `w` other flags, `t` delete,
+    /// `e` expunge. Always true on servers without ACL.
+    pub fn may(&self, right: char) -> bool {
+        let Some(rights) = &self.rights else { return true };
+        // RFC 2086's `d` covered what RFC 4314 split into `t` and `e`
+        rights.contains(right) || (matches!(right, 't' | 'e') && rights.contains('d'))
+    }
+
+    /// Readable but nothing in it can be changed, like a shared mailbox with lookup
+    /// and read rights only.
+    pub fn read_only(&self) -> bool {
+        !self.may('s') && !self.may('w') && !self.may('t')
+    }
+}
+
+/// A `Content-Disposition: attachment` part, found while loading the body.
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    pub section: String,  // IMAP part number, e.g. `2` or `1.3`
+    pub encoding: String, // Content-Transfer-Encoding, lowercase
+    pub filename: String,
+    pub content_type: String,
+    pub size: usize, // decoded bytes; estimated from the encoded size for large mail
+}
+
+/// What the Mail view shows for one message.
+#[derive(Clone, Debug)]
+pub struct Body {
+    pub text: String,
+    pub was_html: bool, // no text/plain part; `text` was converted from HTML
+    pub attachments: Vec<Attachment>,
+    pub message_id: Option<String>, // for In-Reply-To / References of a reply
+    pub references: Option<String>,
+    pub raw: Option<Vec<u8>>, // the whole message; None when it was too large to load
+    pub delivered_to: Vec<String>, // Delivered-To and X-Original-To addresses
+    pub mailing_list: bool,        // has a List-Id
+}
+
+/// How mail reached me when my own address isn't among its recipients.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Delivery {
+    Alias(String),       // one of `[user] aliases` is in To / Cc
+    Bcc(Option<String>), // no address of mine in To / Cc; where it was delivered, if known
+}
+
+/// Compares the recipients with my addresses: `None` when `me` is in To / Cc, for
+/// mailing list traffic and for mail I sent.
+pub fn delivery(me: &str, aliases: &[String], h: &MessageSummary, body: &Body) -> Option<Delivery> {
+    use smtp::{bare_address, split_addresses};
+
+    let me = bare_address(me);
+    let aliases: Vec<String> = aliases.iter().map(|a| bare_address(a)).collect();
+    let mine = |a: &str| a == me || aliases.iter().any(|x| x == a);
+    if split_addresses(&h.from).any(|a| mine(&bare_address(a))) {
+        return None;
+    }
+    let recipients: Vec<String> = [&h.to, &h.cc].into_iter().flat_map(|f| split_addresses(f)).map(bare_address).collect();
+    if recipients.contains(&me) {
+        return None;
+    }
+    if let Some(alias) = aliases.iter().find(|a| recipients.contains(a)) {
+        return Some(Delivery::Alias(alias.clone()));
+    }
+    if body.mailing_list {
+        return None;
+    }
+    let delivered: Vec<String> = body.delivered_to.iter().map(|a| bare_address(a)).collect();
+    let to = delivered.iter().find(|a| mine(a)).or(delivered.first()).cloned();
+    Some(Delivery::Bcc(to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(from: &str, to: &str, cc: &str) -> MessageSummary {
+        MessageSummary {
+            uid: 1,
+            from: from.into(),
+            to: to.into(),
+            cc: cc.into(),
+            reply_to: from.into(),
+            date: String::new(),
+            timestamp: None,
+            subject: "Hi".into(),
+            high_priority: false,
+            seen: false,
+            preview: String::new(),
+        }
+    }
+
+    fn body(delivered_to: &[&str], mailing_list: bool) -> Body {
+        Body {
+            text: String::new(),
+            was_html: false,
+            attachments: vec![],
+            message_id: None,
+            references: None,
+            raw: None,
+            delivered_to: delivered_to.iter().map(|a| a.to_string()).collect(),
+            mailing_list,
+        }
+    }
+
+    const ME: &str = "Ann <ann@example.org>";
+
+    fn aliases() -> Vec<String> {
+        vec!["Sales <SALES@example.org>".into(), "ann@old.example".into()]
+    }
+
+    #[test]
+    fn addressed_to_me_is_plain_delivery() {
+        let h = summary("bob@x.com", "Ann <ANN@example.org>, carl@x.com", "");
+        assert_eq!(delivery(ME, &aliases(), &h, &body(&[], false)), None);
+        let h = summary("bob@x.com", "carl@x.com", "ann@example.org");
+        assert_eq!(delivery(ME, &aliases(), &h, &body(&[], false)), None);
+    }
+
+    #[test]
+    fn alias_in_to_or_cc_is_named() {
+        let h = summary("bob@x.com", "sales@example.org", "");
+        assert_eq!(delivery(ME, &aliases(), &h, &body(&[], false)), Some(Delivery::Alias("sales@example.org".into())));
+        let h = summary("bob@x.com", "carl@x.com", "Ann Old <ann@old.example>");
+        assert_eq!(delivery(ME, &aliases(), &h, &body(&[], false)), Some(Delivery::Alias("ann@old.example".into())));
+    }
+
+    #[test]
+    fn missing_from_recipients_is_bcc() {
+        let h = summary("bob@x.com", "carl@x.com", "");
+        assert_eq!(delivery(ME, &aliases(), &h, &body(&[], false)), Some(Delivery::Bcc(None)));
+        // my own Delivered-To wins over another one
+        let b = body(&["relay@x.com", "<Ann@Example.org>"], false);
+        assert_eq!(delivery(ME, &aliases(), &h, &b), Some(Delivery::Bcc(Some("ann@example.org".into()))));
+        let b = body(&["relay@x.com"], false);
+        assert_eq!(delivery(ME, &aliases(), &h, &b), Some(Delivery::Bcc(Some("relay@x.com".into()))));
+    }
+
+    #[test]
+    fn lists_and_my_own_mail_are_left_alone() {
+        let h = summary("bob@x.com", "list@lists.x.com", "");
+        assert_eq!(delivery(ME, &aliases(), &h, &body(&[], true)), None);
+        let h = summary("Ann <ann@example.org>", "carl@x.com", "");
+        assert_eq!(delivery(ME, &aliases(), &h, &body(&[], false)), None);
+        let h = summary("sales@example.org", "carl@x.com", "");
+        assert_eq!(delivery(ME, &aliases(), &h, &body(&[], false)), None);
+    }
+
+    fn folder(rights: Option<&str>) -> Folder {
+        Folder { name: "Shared".into(), display: "Shared".into(), depth: 0, rights: rights.map(String::from) }
+    }
+
+    #[test]
+    fn without_acl_everything_is_allowed() {
+        let f = folder(None);
+        assert!(f.may('s') && f.may('t') && f.may('e'));
+        assert!(!f.read_only());
+    }
+
+    #[test]
+    fn rights_gate_each_action() {
+        let f = folder(Some("lrs"));
+        assert!(f.may('s'));
+        assert!(!f.may('t'));
+        assert!(!f.may('w'));
+        assert!(!f.read_only());
+    }
+
+    #[test]
+    fn rfc2086_d_covers_delete_and_expunge() {
+        let f = folder(Some("lrswd"));
+        assert!(f.may('t') && f.may('e'));
+        assert!(!folder(Some("lrswt")).may('e'));
+    }
+
+    #[test]
+    fn lookup_and_read_only_is_read_only() {
+        assert!(folder(Some("lr")).read_only());
+        assert!(folder(Some("")).read_only());
+        assert!(!folder(Some("lrw")).read_only());
+    }
 }
@@ -1,37 +1,646 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 use lettre::{Message, SmtpTransport, Transport};
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::address::Envelope;
+use lettre::message::{Attachment, Body, Mailbox, MultiPart, SinglePart};
+use lettre::message::header::{ContentDisposition, ContentTransferEncoding, ContentType, HeaderName, HeaderValue};
+use lettre::transport::smtp;
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+use lettre::transport::smtp::client::{Certificate, Tls, TlsParameters};
 
-use crate::config::{MailConfig, UserConfig};
+use crate::config::{AuthMode, MailConfig, UserConfig};
 
-fn is_localhost(host: &str) -> bool {
-    host == "127.0.0.1" || host == "localhost"
+/// Splits a recipient field on `,` / `;` outside double quotes (`"Doe, John" <j@x>`
+/// is one address), skipping empty entries.
+pub fn split_addresses(s: &str) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+    let mut escaped = false;
+    s.split(move |c: char| {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' | ';' => return !quoted,
+            _ => {}
+        }
+        false
+    })
+    .map(str::trim)
+    .filter(|a| !a.is_empty())
 }
 
-pub fn send(cfg: &MailConfig, user: &UserConfig, to: &str, subject: &str, body: &str) -> Result<()> {
-    let email = Message::builder()
-        .from(user.email.parse()?)
-        .to(to.parse()?)
-        .subject(subject)
-        .body(body.to_string())?;
+/// `Name <a@B.x>` → `a@b.x`: the address alone, lowercased, for comparing.
+pub fn bare_address(addr: &str) -> String {
+    let inner = match (addr.rfind('<'), addr.rfind('>')) {
+        (Some(l), Some(r)) if l < r => &addr[l + 1..r],
+        _ => addr,
+    };
+    inner.trim().to_lowercase()
+}
+
+fn push_unique(out: &mut Vec<String>, addr: &str) {
+    let key = bare_address(addr);
+    if !out.iter().any(|a| bare_address(a) == key) {
+        out.push(addr.to_string());
+    }
+}
+
+/// To and Cc for answering everyone: the sender (its Reply-To) in To, the other To
+/// and Cc recipients in Cc, each address once and never `me`. Replying to one's own
+/// mail goes to its original recipients instead.
+pub fn reply_all_recipients(reply_to: &str, to: &str, cc: &str, me: &str) -> (String, String) {
+    let me = bare_address(me);
+    let mut new_to = Vec::new();
+    for addr in split_addresses(reply_to).filter(|a| bare_address(a) != me) {
+        push_unique(&mut new_to, addr);
+    }
+    let others = if new_to.is_empty() { to } else { "" };
+    for addr in split_addresses(others).filter(|a| bare_address(a) != me) {
+        push_unique(&mut new_to, addr);
+    }
+
+    let mut new_cc = Vec::new();
+    let rest = if others.is_empty() { [to, cc] } else { [cc, ""] };
+    for addr in rest.iter().flat_map(|f| split_addresses(f)) {
+        let key = bare_address(addr);
+        if key != me && !new_to.iter().any(|a| bare_address(a) == key) {
+            push_unique(&mut new_cc, addr);
+        }
+    }
+    (new_to.join(", "), new_cc.join(", "))
+}
+
+/// Replaces group names in a recipient field with their members and drops repeated
+/// addresses. A group may list other groups, one level deep; any entry without an
+/// `@` must name a known group.
+pub fn expand_groups(groups: &BTreeMap<String, Vec<String>>, field: &str) -> Result<String> {
+    let mut out = Vec::new();
+    for entry in split_addresses(field) {
+        if entry.contains('@') {
+            push_unique(&mut out, entry);
+            continue;
+        }
+        let members = groups.get(entry).ok_or_else(|| anyhow!("unknown group {entry:?}"))?;
+        for member in members.iter().map(|m| m.trim()) {
+            if member.contains('@') {
+                push_unique(&mut out, member);
+                continue;
+            }
+            let inner = groups
+                .get(member)
+                .ok_or_else(|| anyhow!("unknown group {member:?} in {entry:?}"))?;
+            for addr in inner.iter().map(|a| a.trim()) {
+                if !addr.contains('@') {
+                    return Err(anyhow!("group {member:?} lists {addr:?}; groups nest one level deep"));
+                }
+                push_unique(&mut out, addr);
+            }
+        }
+    }
+    Ok(out.join(", "))
+}
+
+/// `team = a@x, B <b@x>, other-group` as typed in the group list: the name and its
+/// members. Members without an `@` name other groups.
+pub fn parse_group(entry: &str) -> Result<(String, Vec<String>)> {
+    let (name, members) = entry.split_once('=').ok_or_else(|| anyhow!("write it as name = a@x, b@x"))?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(['@', ',', ';', '"']) || name.contains(char::is_whitespace) {
+        return Err(anyhow!("{name:?} can't be a group name: one word, no @"));
+    }
+    let members: Vec<String> = split_addresses(members).map(str::to_string).collect();
+    if members.is_empty() {
+        return Err(anyhow!("group {name:?} has no members"));
+    }
+    if let Some(m) = members.iter().find(|m| !m.contains('@') && m.contains(char::is_whitespace)) {
+        return Err(anyhow!("{m:?} is neither an address nor a group name"));
+    }
+    Ok((name.to_string(), members))
+}
+
+/// `field` without the addresses already in `earlier` fields, so an address that To
+/// and an expanded Cc group both have is listed (and sent) once.
+pub fn drop_listed(field: &str, earlier: &[&str]) -> String {
+    let listed: HashSet<String> = earlier.iter().flat_map(|f| split_addresses(f)).map(bare_address).collect();
+    split_addresses(field).filter(|a| !listed.contains(&bare_address(a))).collect::<Vec<_>>().join(", ")
+}
+
+/// The addresses in a recipient field, or the first entry that isn't one, as typed.
+pub fn validate_addresses(s: &str) -> Result<Vec<Mailbox>, String> {
+    split_addresses(s).map(|a| a.parse().map_err(|_| a.to_string())).collect()
+}
+
+fn parse_recipients(s: &str) -> Result<Vec<Mailbox>> {
+    validate_addresses(s).map_err(|a| anyhow!("invalid address {a:?}"))
+}
+
+/// A mail ready to go out, as put together in Compose.
+pub struct OutgoingMessage {
+    pub to: String,
+    pub cc: String,
+    pub bcc: String, // envelope only, never written to the headers
+    pub reply_to: String, // empty: `user.reply_to`, if set
+    pub subject: String,
+    pub body: String,
+    pub undisclosed: bool, // recipients go on the envelope only
+    pub high_priority: bool,
+    pub in_reply_to: Option<String>, // Message-ID of the mail replied to
+    pub references: Option<String>,
+    pub attached_message: Option<Vec<u8>>, // a forwarded original, sent as message/rfc822
+    pub attachments: Vec<PathBuf>,
+}
+
+/// MIME type by file extension, `application/octet-stream` when unknown.
+pub fn mime_type(path: &Path) -> &'static str {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "ics" => "text/calendar",
+        "patch" | "diff" => "text/x-diff",
+        "pdf" => "application/pdf",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "eml" => "message/rfc822",
+        _ => "application/octet-stream",
+    }
+}
 
-    let creds = Credentials::new(cfg.username.clone(), cfg.password.clone());
+fn file_part(path: &Path) -> Result<SinglePart> {
+    let data = fs::read(path).map_err(|e| anyhow!("{}: {e}", path.display()))?;
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "attachment".to_string());
+    Ok(Attachment::new(name).body(data, ContentType::parse(mime_type(path))?))
+}
+
+// message/rfc822 may only be 7bit, 8bit or binary (RFC 2046 §5.2.1); base64 is the
+// last resort for an original that fits none of them.
+fn rfc822_body(raw: Vec<u8>) -> Body {
+    Body::new_with_encoding(raw, ContentTransferEncoding::EightBit).unwrap_or_else(Body::new)
+}
+
+/// Sends `msg` to every address in its `to`, `cc` and `bcc`. With `undisclosed`, the
+/// To recipients only go on the SMTP envelope and the To header becomes
+/// `undisclosed-recipients:;`.
+/// The envelope sender is `user.envelope_from` when set, the From address otherwise;
+/// the Reply-To header is `msg.reply_to`, else a non-empty `user.reply_to`.
+/// Returns the message exactly as it went out, for saving a copy to Sent.
+pub fn send(cfg: &MailConfig, user: &UserConfig, msg: &OutgoingMessage) -> Result<Vec<u8>> {
+    let email = build(user, msg, None)?;
+    with_transport(cfg, |t| t.send(&email))?;
+    Ok(email.formatted())
+}
+
+/// `msg` as a draft for the Drafts folder: recipients may be missing, Bcc is kept, and
+/// the Message-ID is `message_id`, so a later save can find and replace it.
+pub fn draft(user: &UserConfig, msg: &OutgoingMessage, message_id: &str) -> Result<Vec<u8>> {
+    Ok(build(user, msg, Some(message_id))?.formatted())
+}
 
-    let mut tlsb = TlsParameters::builder(cfg.host.clone());
-    if is_localhost(&cfg.host) {
+// `draft_id` is set when building a draft rather than mail to send.
+fn build(user: &UserConfig, msg: &OutgoingMessage, draft_id: Option<&str>) -> Result<Message> {
+    let from: Mailbox = user.email.parse()?;
+    let to = parse_recipients(&msg.to).map_err(|e| anyhow!("To: {e}"))?;
+    let cc = parse_recipients(&msg.cc).map_err(|e| anyhow!("Cc: {e}"))?;
+    let bcc = parse_recipients(&msg.bcc).map_err(|e| anyhow!("Bcc: {e}"))?;
+    let mut rcpts = Vec::new();
+    for m in to.iter().chain(&cc).chain(&bcc) {
+        if !rcpts.contains(&m.email) {
+            rcpts.push(m.email.clone());
+        }
+    }
+    if rcpts.is_empty() {
+        if draft_id.is_none() {
+            return Err(anyhow!("no recipients"));
+        }
+        // never sent; the builder just wants someone to address it to
+        rcpts.push(from.email.clone());
+    }
+
+    let sender = match user.envelope_from.as_deref().map(str::trim) {
+        Some(addr) if !addr.is_empty() => addr
+            .parse()
+            .map_err(|e| anyhow!("invalid envelope_from {addr:?}: {e}"))?,
+        _ => from.email.clone(),
+    };
+    let envelope = Envelope::new(Some(sender), rcpts)?;
+
+    let mut builder = Message::builder().from(from).subject(&msg.subject).envelope(envelope);
+    if !msg.reply_to.trim().is_empty() {
+        for mailbox in parse_recipients(&msg.reply_to).map_err(|e| anyhow!("Reply-To: {e}"))? {
+            builder = builder.reply_to(mailbox);
+        }
+    } else if let Some(addr) = user.reply_to.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+        let mailbox: Mailbox = addr.parse().map_err(|e| anyhow!("invalid reply_to {addr:?}: {e}"))?;
+        builder = builder.reply_to(mailbox);
+    }
+    if let Some(id) = &msg.in_reply_to {
+        builder = builder.in_reply_to(id.clone());
+    }
+    if let Some(refs) = &msg.references {
+        builder = builder.references(refs.clone());
+    }
+    if msg.undisclosed {
+        builder = builder.raw_header(HeaderValue::new(
+            HeaderName::new_from_ascii_str("To"),
+            "undisclosed-recipients:;".to_string(),
+        ));
+    } else {
+        // undisclosed, To and Cc are on the envelope only, like Bcc
+        for m in to {
+            builder = builder.to(m);
+        }
+        for m in cc {
+            builder = builder.cc(m);
+        }
+    }
+    if let Some(id) = draft_id {
+        builder = builder.message_id(Some(id.to_string())).keep_bcc();
+        for m in bcc {
+            builder = builder.bcc(m);
+        }
+    }
+    if msg.high_priority {
+        builder = builder
+            .raw_header(HeaderValue::new(
+                HeaderName::new_from_ascii_str("X-Priority"),
+                "1 (Highest)".to_string(),
+            ))
+            .raw_header(HeaderValue::new(
+                HeaderName::new_from_ascii_str("Importance"),
+                "high".to_string(),
+            ));
+    }
+    let email = if msg.attached_message.is_none() && msg.attachments.is_empty() {
+        builder.body(msg.body.clone())?
+    } else {
+        let mut parts = MultiPart::mixed().singlepart(SinglePart::plain(msg.body.clone()));
+        if let Some(raw) = &msg.attached_message {
+            parts = parts.singlepart(
+                SinglePart::builder()
+                    .header(ContentType::parse("message/rfc822")?)
+                    .header(ContentDisposition::attachment("forwarded.eml"))
+                    .body(rfc822_body(raw.clone())),
+            );
+        }
+        for path in &msg.attachments {
+            parts = parts.singlepart(file_part(path)?);
+        }
+        builder.multipart(parts)?
+    };
+    Ok(email)
+}
+
+fn transport(cfg: &MailConfig) -> Result<SmtpTransport> {
+    let (secret, mechanisms) = match cfg.auth {
+        AuthMode::Oauth2 => (crate::oauth::access_token(cfg, "smtp")?, vec![Mechanism::Xoauth2]),
+        AuthMode::Password => (cfg.password()?, vec![Mechanism::Plain, Mechanism::Login]),
+    };
+    let creds = Credentials::new(cfg.username.clone(), secret);
+
+    let mut tlsb = TlsParameters::builder(cfg.server_name().to_string());
+    for pem in cfg.ca_certs()? {
+        tlsb = tlsb.add_root_certificate(Certificate::from_pem(&pem)?);
+    }
+    if !cfg.verify_certs() {
         tlsb = tlsb
             .dangerous_accept_invalid_certs(true)
             .dangerous_accept_invalid_hostnames(true);
     }
     let tls = tlsb.build()?;
 
-    let mailer = SmtpTransport::builder_dangerous(&cfg.host)
+    Ok(SmtpTransport::builder_dangerous(&cfg.host)
         .port(cfg.port)
         .credentials(creds)
+        .authentication(mechanisms)
         .tls(Tls::Required(tls))
-        .build();
+        .timeout(Some(cfg.timeout()))
+        .build())
+}
 
-    mailer.send(&email)?;
-    Ok(())
+// Runs `send` over a fresh transport. With OAuth2, a rejected login (535) is tried
+// once more with a new access token; nothing was sent in that case.
+fn with_transport<T>(cfg: &MailConfig, send: impl Fn(&SmtpTransport) -> Result<T, smtp::Error>) -> Result<T> {
+    let res = match send(&transport(cfg)?) {
+        Err(e) if cfg.auth == AuthMode::Oauth2 && e.status().is_some_and(|c| c.to_string() == "535") => {
+            crate::oauth::forget_token(cfg);
+            send(&transport(cfg)?)
+        }
+        res => res,
+    };
+    res.map_err(|e| {
+        if timed_out(&e) {
+            anyhow!("SMTP timeout after {}s talking to {}:{}", cfg.timeout_secs, cfg.host, cfg.port)
+        } else {
+            e.into()
+        }
+    })
+}
+
+// lettre's `is_timeout` only knows TimedOut, but a socket read timeout on Unix comes
+// back as WouldBlock.
+fn timed_out(e: &smtp::Error) -> bool {
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            return matches!(io.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock);
+        }
+        source = err.source();
+    }
+    false
+}
+
+// Every address in the header, group members included.
+fn header_addresses(header: &mailparse::MailHeader) -> Result<Vec<String>> {
+    let list = mailparse::addrparse_header(header).map_err(|e| anyhow!("{}: {e}", header.get_key()))?;
+    let mut out = Vec::new();
+    for addr in list.iter() {
+        match addr {
+            mailparse::MailAddr::Single(s) => out.push(s.addr.clone()),
+            mailparse::MailAddr::Group(g) => out.extend(g.addrs.iter().map(|s| s.addr.clone())),
+        }
+    }
+    Ok(out)
+}
+
+// The message with its Bcc header (and that header's continuation lines) left out,
+// so blind recipients stay blind.
+fn without_bcc(raw: &[u8]) -> Vec<u8> {
+    let body_at = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|i| i + 2)
+        .or_else(|| raw.windows(2).position(|w| w == b"\n\n").map(|i| i + 1))
+        .unwrap_or(raw.len());
+    let (head, body) = raw.split_at(body_at);
+
+    let mut out = Vec::with_capacity(raw.len());
+    let mut skipping = false;
+    for line in head.split_inclusive(|&b| b == b'\n') {
+        let continuation = line.first().is_some_and(|b| *b == b' ' || *b == b'\t');
+        if !continuation {
+            skipping = line.len() >= 4 && line[..4].eq_ignore_ascii_case(b"bcc:");
+        }
+        if !skipping {
+            out.extend_from_slice(line);
+        }
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+/// Sends a ready-made message (an `.eml` file) with its headers as they are, except
+/// Bcc, which is removed. Recipients come from To, Cc and Bcc; the envelope sender is
+/// `user.envelope_from` when set, the From address otherwise. Returns the recipients.
+pub fn send_raw(cfg: &MailConfig, user: &UserConfig, raw: &[u8]) -> Result<Vec<String>> {
+    let envelope = raw_envelope(user, raw)?;
+    with_transport(cfg, |t| t.send_raw(&envelope, &without_bcc(raw)))?;
+    Ok(envelope.to().iter().map(|a| a.to_string()).collect())
+}
+
+// The envelope `send_raw` uses for `raw`, checking it is a message worth sending.
+fn raw_envelope(user: &UserConfig, raw: &[u8]) -> Result<Envelope> {
+    use mailparse::MailHeaderMap;
+
+    let (headers, _) = mailparse::parse_headers(raw).map_err(|e| anyhow!("not a mail message: {e}"))?;
+    mailparse::parse_mail(raw).map_err(|e| anyhow!("not a mail message: {e}"))?;
+
+    let from = headers.get_first_header("From").ok_or_else(|| anyhow!("no From header"))?;
+    let from = header_addresses(from)?.into_iter().next().ok_or_else(|| anyhow!("From header has no address"))?;
+    if headers.get_first_header("Date").is_none() {
+        return Err(anyhow!("no Date header"));
+    }
+
+    let mut rcpts: Vec<lettre::Address> = Vec::new();
+    for name in ["To", "Cc", "Bcc"] {
+        for header in headers.get_all_headers(name) {
+            for addr in header_addresses(header)? {
+                let addr: lettre::Address = addr.parse().map_err(|e| anyhow!("{name}: {addr:?}: {e}"))?;
+                if !rcpts.contains(&addr) {
+                    rcpts.push(addr);
+                }
+            }
+        }
+    }
+    if rcpts.is_empty() {
+        return Err(anyhow!("no recipients in To, Cc or Bcc"));
+    }
+
+    let sender = match user.envelope_from.as_deref().map(str::trim) {
+        Some(addr) if !addr.is_empty() => addr.parse().map_err(|e| anyhow!("invalid envelope_from {addr:?}: {e}"))?,
+        _ => from.parse().map_err(|e| anyhow!("From: {from:?}: {e}"))?,
+    };
+    Ok(Envelope::new(Some(sender), rcpts)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> UserConfig {
+        UserConfig {
+            name: "Ann".into(),
+            email: "ann@example.org".into(),
+            envelope_from: None,
+            reply_to: None,
+            aliases: vec![],
+        }
+    }
+
+    fn msg(to: &str, cc: &str, bcc: &str) -> OutgoingMessage {
+        OutgoingMessage {
+            to: to.into(),
+            cc: cc.into(),
+            bcc: bcc.into(),
+            reply_to: String::new(),
+            subject: "Hello".into(),
+            body: "Hi all".into(),
+            undisclosed: false,
+            high_priority: false,
+            in_reply_to: None,
+            references: None,
+            attached_message: None,
+            attachments: vec![],
+        }
+    }
+
+    // The header block as text, and the envelope recipients.
+    fn built(user: &UserConfig, msg: &OutgoingMessage) -> (String, Vec<String>) {
+        let email = build(user, msg, None).unwrap();
+        let text = String::from_utf8(email.formatted()).unwrap();
+        let head = text.split("\r\n\r\n").next().unwrap().to_string();
+        (head, email.envelope().to().iter().map(|a| a.to_string()).collect())
+    }
+
+    fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+        head.lines().find_map(|l| l.strip_prefix(name)?.strip_prefix(": "))
+    }
+
+    #[test]
+    fn undisclosed_keeps_recipients_off_the_headers() {
+        let mut m = msg("a@x.com, B <b@y.com>", "c@x.com", "d@z.com");
+        m.undisclosed = true;
+        let (head, envelope) = built(&user(), &m);
+        assert_eq!(header(&head, "To"), Some("undisclosed-recipients:;"));
+        assert_eq!(header(&head, "Cc"), None);
+        assert!(!head.contains("a@x.com") && !head.contains("b@y.com") && !head.contains("c@x.com") && !head.contains("d@z.com"), "{head}");
+        assert_eq!(envelope, ["a@x.com", "b@y.com", "c@x.com", "d@z.com"]);
+
+        // the group syntax is one an ordinary parser reads as an empty group
+        let raw = format!("{head}\r\n\r\n");
+        let (headers, _) = mailparse::parse_headers(raw.as_bytes()).unwrap();
+        let to = headers.iter().find(|h| h.get_key() == "To").unwrap();
+        assert!(header_addresses(to).unwrap().is_empty());
+    }
+
+    #[test]
+    fn disclosed_mail_names_its_recipients() {
+        let (head, envelope) = built(&user(), &msg("a@x.com, B <b@y.com>", "", "d@z.com, a@x.com"));
+        assert_eq!(header(&head, "To"), Some("a@x.com, B <b@y.com>"));
+        assert_eq!(header(&head, "Bcc"), None);
+        // a recipient named twice gets one copy
+        assert_eq!(envelope, ["a@x.com", "b@y.com", "d@z.com"]);
+    }
+
+    #[test]
+    fn envelope_sender_defaults_to_from() {
+        let email = build(&user(), &msg("a@x.com", "", ""), None).unwrap();
+        assert_eq!(email.envelope().from().map(|a| a.to_string()).as_deref(), Some("ann@example.org"));
+    }
+
+    #[test]
+    fn envelope_from_replaces_only_the_envelope_sender() {
+        let mut user = user();
+        user.envelope_from = Some(" bounces@example.org ".into());
+        let email = build(&user, &msg("a@x.com", "", ""), None).unwrap();
+        assert_eq!(email.envelope().from().map(|a| a.to_string()).as_deref(), Some("bounces@example.org"));
+        let (head, _) = built(&user, &msg("a@x.com", "", ""));
+        assert_eq!(header(&head, "From"), Some("ann@example.org"));
+        assert!(!head.contains("bounces@"), "{head}");
+
+        user.envelope_from = Some("not an address".into());
+        assert!(build(&user, &msg("a@x.com", "", ""), None).is_err());
+    }
+
+    #[test]
+    fn raw_messages_get_the_same_envelope_sender() {
+        let raw = b"From: Ann <ann@example.org>\r\nTo: a@x.com\r\nBcc: d@z.com\r\nDate: Tue, 14 Nov 2023 10:00:00 +0000\r\n\r\nHi\r\n";
+        let envelope = raw_envelope(&user(), raw).unwrap();
+        assert_eq!(envelope.from().map(|a| a.to_string()).as_deref(), Some("ann@example.org"));
+        assert_eq!(envelope.to().iter().map(|a| a.to_string()).collect::<Vec<_>>(), ["a@x.com", "d@z.com"]);
+
+        let mut user = user();
+        user.envelope_from = Some("bounces@example.org".into());
+        let envelope = raw_envelope(&user, raw).unwrap();
+        assert_eq!(envelope.from().map(|a| a.to_string()).as_deref(), Some("bounces@example.org"));
+    }
+
+    fn groups() -> BTreeMap<String, Vec<String>> {
+        let group = |name: &str, members: &[&str]| (name.to_string(), members.iter().map(|m| m.to_string()).collect());
+        BTreeMap::from([
+            group("team-backend", &["a@x.com", "B <b@x.com>", " c@x.com "]),
+            group("team-web", &["c@x.com", "d@x.com"]),
+            group("everyone", &["team-backend", "team-web", "boss@x.com"]),
+            group("too-deep", &["everyone"]),
+            group("broken", &["nobody"]),
+        ])
+    }
+
+    #[test]
+    fn groups_expand_in_place() {
+        assert_eq!(expand_groups(&groups(), "z@y.com, team-backend").unwrap(), "z@y.com, a@x.com, B <b@x.com>, c@x.com");
+        assert_eq!(expand_groups(&groups(), "").unwrap(), "");
+        assert_eq!(expand_groups(&groups(), "a@x.com").unwrap(), "a@x.com");
+    }
+
+    #[test]
+    fn nested_groups_expand_one_level_without_repeats() {
+        assert_eq!(
+            expand_groups(&groups(), "everyone").unwrap(),
+            "a@x.com, B <b@x.com>, c@x.com, d@x.com, boss@x.com"
+        );
+        // an address typed by hand and then again through a group, in other spellings
+        assert_eq!(expand_groups(&groups(), "Bee <b@x.com>, team-web, team-backend").unwrap(), "Bee <b@x.com>, c@x.com, d@x.com, a@x.com");
+        assert!(expand_groups(&groups(), "too-deep").unwrap_err().to_string().contains("one level deep"));
+    }
+
+    #[test]
+    fn groups_are_parsed_as_typed_in_the_group_list() {
+        let (name, members) = parse_group(" team = a@x.com; \"Bee, B\" <b@x.com>, team-web ").unwrap();
+        assert_eq!(name, "team");
+        assert_eq!(members, ["a@x.com", "\"Bee, B\" <b@x.com>", "team-web"]);
+        for bad in ["team a@x.com", " = a@x.com", "a@x.com = b@x.com", "my team = a@x.com", "team = ", "team = two words"] {
+            assert!(parse_group(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn addresses_are_listed_once_across_fields() {
+        let to = expand_groups(&groups(), "a@x.com").unwrap();
+        let cc = expand_groups(&groups(), "team-backend").unwrap();
+        let cc = drop_listed(&cc, &[&to]);
+        assert_eq!(cc, "B <b@x.com>, c@x.com");
+        let bcc = drop_listed("C <C@X.com>; d@x.com, A@x.com", &[&to, &cc]);
+        assert_eq!(bcc, "d@x.com");
+        assert_eq!(drop_listed("a@x.com", &[]), "a@x.com");
+        assert_eq!(drop_listed("", &[&to]), "");
+    }
+
+    #[test]
+    fn unknown_groups_are_named_in_the_error() {
+        assert_eq!(expand_groups(&groups(), "a@x.com, team-fronted").unwrap_err().to_string(), "unknown group \"team-fronted\"");
+        assert_eq!(expand_groups(&groups(), "broken").unwrap_err().to_string(), "unknown group \"nobody\" in \"broken\"");
+    }
+
+    #[test]
+    fn reply_to_header_when_configured() {
+        let (head, _) = built(&user(), &msg("a@x.com", "", ""));
+        assert_eq!(header(&head, "Reply-To"), None);
+
+        let mut user = user();
+        user.reply_to = Some("  ".into());
+        let (head, _) = built(&user, &msg("a@x.com", "", ""));
+        assert_eq!(header(&head, "Reply-To"), None);
+
+        user.reply_to = Some("Team <team@example.org>".into());
+        let (head, _) = built(&user, &msg("a@x.com", "", ""));
+        assert_eq!(header(&head, "Reply-To"), Some("Team <team@example.org>"));
+        assert_eq!(header(&head, "From"), Some("ann@example.org"));
+
+        user.reply_to = Some("team at example".into());
+        assert!(build(&user, &msg("a@x.com", "", ""), None).is_err());
+    }
+
+    #[test]
+    fn the_compose_reply_to_wins_over_the_configured_one() {
+        let mut user = user();
+        user.reply_to = Some("team@example.org".into());
+        let mut m = msg("a@x.com", "", "");
+        m.reply_to = "Ann Home <ann@home.example>, desk@example.org".into();
+        let (head, _) = built(&user, &m);
+        assert_eq!(header(&head, "Reply-To"), Some("\"Ann Home\" <ann@home.example>, desk@example.org"));
+
+        m.reply_to = "not an address".into();
+        assert!(build(&user, &m, None).is_err());
+    }
 }
@@ -1,17 +1,110 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use crossterm::{
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, enable_raw_mode, disable_raw_mode},
 };
-use std::io::stdout;
+use std::io::{stdin, stdout, IsTerminal};
 
 mod app;
+mod cache;
 mod config;
+mod contacts;
+mod drafts;
+mod edit;
+mod outbox;
+mod count;
+mod diff;
+mod features;
+mod guard;
+mod health;
+mod https;
+mod keyring;
+mod keys;
+mod oauth;
+mod update;
+mod profile;
+mod redact;
 mod ui;
 mod mail;
 
+const USAGE: &str = "usage: zenmail                   open the mail client
+       zenmail send --raw FILE   send a ready-made message (.eml) as it is
+       zenmail --oauth-setup     get an OAuth2 refresh token for auth = \"oauth2\"
+       zenmail --clear-cache     delete the mail kept on disk";
+
+// Error text can carry logins and passwords, so it goes through `redact` like the
+// status line does. The config is read again for that when the caller has none.
+fn fail(e: &anyhow::Error, config: Option<&config::Config>) -> ! {
+    let text = match config {
+        Some(config) => redact::clean(&format!("{e:#}"), config),
+        None => match config::Config::load_or_create() {
+            Ok((config, _, _)) => redact::clean(&format!("{e:#}"), &config),
+            Err(_) => format!("{e:#}"),
+        },
+    };
+    eprintln!("zenmail: {text}");
+    std::process::exit(1);
+}
+
+// `zenmail send --raw FILE`: sends the file over SMTP and files a copy in Sent.
+fn send_raw(config: &config::Config, path: &str) -> Result<()> {
+    let raw = std::fs::read(path).map_err(|e| anyhow!("{path}: {e}"))?;
+    let rcpts = mail::smtp::send_raw(&config.smtp, &config.user, &raw).map_err(|e| anyhow!("{path} not sent: {e}"))?;
+    println!("Sent {path} to {}", rcpts.join(", "));
+
+    let sent = config.folders.sent.trim();
+    if !sent.is_empty() {
+        if let Err(e) = mail::imap::append_to_sent(&config.imap, sent, &raw) {
+            eprintln!("warning: saving to {sent} failed: {}", redact::clean(&e.to_string(), config));
+        }
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => {}
+        ["--oauth-setup"] => {
+            let (mut config, _, path) = config::Config::load_or_create().unwrap_or_else(|e| fail(&e, None));
+            if let Err(e) = oauth::setup(&mut config, &path) {
+                fail(&e, Some(&config));
+            }
+            return Ok(());
+        }
+        ["--clear-cache"] => {
+            match cache::clear() {
+                Ok(true) => println!("Cache cleared"),
+                Ok(false) => println!("Nothing cached"),
+                Err(e) => {
+                    eprintln!("zenmail: {e}");
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        ["send", "--raw", path] => {
+            let (config, created, config_path) = config::Config::load_or_create().unwrap_or_else(|e| fail(&e, None));
+            if created {
+                fail(&anyhow!("fill in {} first", config_path.display()), Some(&config));
+            }
+            if let Err(e) = send_raw(&config, path) {
+                fail(&e, Some(&config));
+            }
+            return Ok(());
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    }
+
+    if !stdin().is_terminal() || !stdout().is_terminal() {
+        eprintln!("zenmail requires an interactive terminal");
+        std::process::exit(1);
+    }
+
     enable_raw_mode()?;
     execute!(stdout(), EnterAlternateScreen)?;
 
@@ -20,5 +113,8 @@ async fn main() -> Result<()> {
     disable_raw_mode()?;
     execute!(stdout(), LeaveAlternateScreen)?;
 
-    result
+    if let Err(e) = result {
+        fail(&e, None);
+    }
+    Ok(())
 }
@@ -0,0 +1,318 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::{secret_from_command, AuthMode, Config, MailConfig, OAuthConfig};
+use crate::https;
+
+// OAuth2 for `auth = "oauth2"`: a refresh token (from `zenmail --oauth-setup`) is
+// traded for short-lived access tokens, which log in through XOAUTH2. With `token_cmd`,
+// an external helper hands out the access tokens instead.
+
+// Where the browser is sent back to after consent. Nothing listens there: the code
+// is copied from the address bar of the page that fails to load.
+const REDIRECT_URI: &str = "http://localhost";
+
+// Access tokens are renewed this long before they run out.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+// How long a `token_cmd` token is reused; its real lifetime is unknown, and one the
+// server rejects earlier is dropped anyway.
+const COMMAND_TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+
+struct Endpoints {
+    auth_url: &'static str,
+    token_url: &'static str,
+    scope: &'static str,
+}
+
+const GOOGLE: Endpoints = Endpoints {
+    auth_url: "https://accounts.google.com/o/oauth2/v2/auth",
+    token_url: "https://oauth2.googleapis.com/token",
+    scope: "https://mail.google.com/",
+};
+
+const MICROSOFT: Endpoints = Endpoints {
+    auth_url: "https://login.microsoftonline.com/common/oauth2/v2.0/authorize",
+    token_url: "https://login.microsoftonline.com/common/oauth2/v2.0/token",
+    scope: "https://outlook.office.com/IMAP.AccessAsUser.All https://outlook.office.com/SMTP.Send offline_access",
+};
+
+// token cache key → access token and when it stops being used
+static TOKENS: LazyLock<Mutex<BTreeMap<String, (String, Instant)>>> = LazyLock::new(Mutex::default);
+
+fn tokens() -> std::sync::MutexGuard<'static, BTreeMap<String, (String, Instant)>> {
+    TOKENS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// `section` is `imap` or `smtp`, the table `cfg` was read from; errors name it.
+fn settings<'a>(cfg: &'a MailConfig, section: &str) -> Result<&'a OAuthConfig> {
+    cfg.oauth2.as_ref().ok_or_else(|| anyhow!("auth = \"oauth2\" needs a [{section}.oauth2] table for {}", cfg.host))
+}
+
+// The provider's endpoints, known for Gmail and Microsoft 365 from the server name.
+fn known(host: &str) -> Option<&'static Endpoints> {
+    let host = host.trim_end_matches('.').to_lowercase();
+    let under = |domain: &str| host == domain || host.ends_with(&format!(".{domain}"));
+    if under("gmail.com") || under("googlemail.com") {
+        Some(&GOOGLE)
+    } else if under("outlook.com") || under("office365.com") {
+        Some(&MICROSOFT)
+    } else {
+        None
+    }
+}
+
+// `set` when given, else the known provider's value.
+fn endpoint(cfg: &MailConfig, section: &str, set: &str, name: &str, pick: fn(&Endpoints) -> &'static str) -> Result<String> {
+    if !set.trim().is_empty() {
+        return Ok(set.trim().to_string());
+    }
+    known(&cfg.host)
+        .map(|e| pick(e).to_string())
+        .ok_or_else(|| anyhow!("set {name} under [{section}.oauth2] for {}", cfg.host))
+}
+
+fn cache_key(o: &OAuthConfig) -> String {
+    if o.token_cmd.trim().is_empty() {
+        format!("{}\t{}", o.client_id, o.refresh_token)
+    } else {
+        format!("cmd\t{}", o.token_cmd.trim())
+    }
+}
+
+/// A current access token: the cached one, else one from `token_cmd` or the refresh
+/// token. `section` is `imap` or `smtp`, for error messages.
+pub fn access_token(cfg: &MailConfig, section: &str) -> Result<String> {
+    let o = settings(cfg, section)?;
+    let key = cache_key(o);
+    if let Some((token, until)) = tokens().get(&key) {
+        if Instant::now() < *until {
+            return Ok(token.clone());
+        }
+    }
+
+    let (token, lifetime) = if o.token_cmd.trim().is_empty() {
+        refresh(cfg, section, o)?
+    } else {
+        (secret_from_command(o.token_cmd.trim(), "token_cmd")?, COMMAND_TOKEN_TTL)
+    };
+    tokens().insert(key, (token.clone(), Instant::now() + lifetime));
+    Ok(token)
+}
+
+// A new access token for the refresh token, and how long to use it.
+fn refresh(cfg: &MailConfig, section: &str, o: &OAuthConfig) -> Result<(String, Duration)> {
+    if o.refresh_token.trim().is_empty() {
+        return Err(anyhow!("no OAuth2 refresh token for {}; run `zenmail --oauth-setup` or set token_cmd", cfg.host));
+    }
+    let token_url = endpoint(cfg, section, &o.token_url, "token_url", |e| e.token_url)?;
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", o.refresh_token.trim()),
+        ("client_id", o.client_id.as_str()),
+    ];
+    if !o.client_secret.is_empty() {
+        form.push(("client_secret", o.client_secret.as_str()));
+    }
+    let body = post_form(&token_url, &form).map_err(|e| anyhow!("OAuth2 token refresh failed: {e}"))?;
+    let token = https::json_field(&body, "access_token").ok_or_else(|| anyhow!("OAuth2: no access_token in the reply"))?;
+    let secs = regex::Regex::new(r#""expires_in"\s*:\s*"?(\d+)"#)
+        .ok()
+        .and_then(|re| re.captures(&body).and_then(|c| c[1].parse().ok()))
+        .unwrap_or(3600);
+    Ok((token, Duration::from_secs(secs).saturating_sub(EXPIRY_MARGIN)))
+}
+
+/// Drops the cached access token after the server turned it down.
+pub fn forget_token(cfg: &MailConfig) {
+    if let Some(o) = &cfg.oauth2 {
+        tokens().remove(&cache_key(o));
+    }
+}
+
+/// The refresh token, client secret and cached access token, for redaction.
+pub fn secrets(cfg: &MailConfig) -> Vec<String> {
+    let Some(o) = &cfg.oauth2 else { return vec![] };
+    let mut out = vec![o.refresh_token.clone(), o.client_secret.clone()];
+    out.extend(tokens().get(&cache_key(o)).map(|(t, _)| t.clone()));
+    out
+}
+
+/// The SASL XOAUTH2 initial response (before base64).
+pub fn xoauth2(user: &str, token: &str) -> String {
+    format!("user={user}\x01auth=Bearer {token}\x01\x01")
+}
+
+// POSTs an application/x-www-form-urlencoded body; the reply body on 200.
+fn post_form(url: &str, form: &[(&str, &str)]) -> Result<String> {
+    let rest = url.strip_prefix("https://").ok_or_else(|| anyhow!("{url}: only https:// endpoints are supported"))?;
+    let (host, path) = rest.split_once('/').map_or((rest, "/".to_string()), |(h, p)| (h, format!("/{p}")));
+    let body = form.iter().map(|(k, v)| format!("{k}={}", encode(v))).collect::<Vec<_>>().join("&");
+    let (status, reply) = https::send(
+        host,
+        &format!(
+            "POST {path} HTTP/1.0\r\nHost: {host}\r\nUser-Agent: zenmail/{}\r\nAccept: application/json\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{body}",
+            env!("CARGO_PKG_VERSION"),
+            body.len()
+        ),
+    )?;
+    if status != 200 {
+        let why = https::json_field(&reply, "error_description").or_else(|| https::json_field(&reply, "error"));
+        return Err(anyhow!("{host}: HTTP {status}{}", why.map(|w| format!(" ({w})")).unwrap_or_default()));
+    }
+    Ok(reply)
+}
+
+// Percent-encoding for form values and query strings.
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b'+', _) => {
+                out.push(b' ');
+                i += 1;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// The code out of what was pasted: the whole address the browser ended up at, or
+// just the code.
+fn pasted_code(input: &str) -> Option<String> {
+    let input = input.trim();
+    let query = match input.split_once('?') {
+        Some((_, q)) => q,
+        None if input.contains('=') => input,
+        None => return (!input.is_empty()).then(|| input.to_string()),
+    };
+    query.split(['&', '#']).find_map(|kv| kv.strip_prefix("code=")).map(decode)
+}
+
+/// `zenmail --oauth-setup`: prints the consent URL, takes the pasted code and stores
+/// the refresh token it buys in every `auth = "oauth2"` account with this client.
+pub fn setup(config: &mut Config, config_path: &Path) -> Result<()> {
+    let (section, account) = [("imap", &config.imap), ("smtp", &config.smtp)]
+        .into_iter()
+        .find(|(_, m)| m.auth == AuthMode::Oauth2)
+        .ok_or_else(|| anyhow!("neither [imap] nor [smtp] has auth = \"oauth2\""))?;
+    let account = account.clone();
+    let o = settings(&account, section)?;
+    if o.client_id.trim().is_empty() {
+        return Err(anyhow!("set client_id under [{section}.oauth2] first (or use token_cmd instead)"));
+    }
+    let auth_url = endpoint(&account, section, &o.auth_url, "auth_url", |e| e.auth_url)?;
+    let token_url = endpoint(&account, section, &o.token_url, "token_url", |e| e.token_url)?;
+    let scope = endpoint(&account, section, &o.scope, "scope", |e| e.scope)?;
+
+    println!("Open this address, allow access, and paste the address you end up at (it won't load):\n");
+    println!(
+        "{auth_url}?client_id={}&redirect_uri={}&response_type=code&scope={}&access_type=offline&prompt=consent&login_hint={}\n",
+        encode(&o.client_id),
+        encode(REDIRECT_URI),
+        encode(&scope),
+        encode(&account.username)
+    );
+    print!("> ");
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let code = pasted_code(&line).ok_or_else(|| anyhow!("no code in what was pasted"))?;
+
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("client_id", o.client_id.as_str()),
+        ("redirect_uri", REDIRECT_URI),
+    ];
+    if !o.client_secret.is_empty() {
+        form.push(("client_secret", o.client_secret.as_str()));
+    }
+    let body = post_form(&token_url, &form)?;
+    let refresh = https::json_field(&body, "refresh_token")
+        .ok_or_else(|| anyhow!("the provider sent no refresh token; revoke zenmail's access and try again"))?;
+
+    let mut stored = vec![];
+    for (name, m) in [("[imap]", &mut config.imap), ("[smtp]", &mut config.smtp)] {
+        match m.oauth2.as_mut() {
+            Some(t) if m.auth == AuthMode::Oauth2 && t.client_id == o.client_id => {
+                t.refresh_token = refresh.clone();
+                stored.push(name);
+            }
+            _ => {}
+        }
+    }
+    config.save_to(config_path)?;
+    println!("Refresh token saved for {} in {}", stored.join(" and "), config_path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn providers_are_known_by_whole_domain() {
+        assert_eq!(known("imap.gmail.com").map(|e| e.token_url), Some(GOOGLE.token_url));
+        assert_eq!(known("SMTP.GoogleMail.com.").map(|e| e.token_url), Some(GOOGLE.token_url));
+        assert_eq!(known("outlook.office365.com").map(|e| e.token_url), Some(MICROSOFT.token_url));
+        assert_eq!(known("outlook.com").map(|e| e.token_url), Some(MICROSOFT.token_url));
+        assert!(known("imap.notgmail.com").is_none());
+        assert!(known("evil-outlook.com").is_none());
+        assert!(known("gmail.com.example.org").is_none());
+    }
+
+    #[test]
+    fn encode_leaves_only_unreserved_characters() {
+        assert_eq!(encode("AZaz09-._~"), "AZaz09-._~");
+        assert_eq!(encode("a b&c=d/e"), "a%20b%26c%3Dd%2Fe");
+        assert_eq!(encode("https://mail.google.com/"), "https%3A%2F%2Fmail.google.com%2F");
+        assert_eq!(encode("é"), "%C3%A9");
+        assert_eq!(encode(""), "");
+    }
+
+    #[test]
+    fn decode_undoes_percent_and_plus() {
+        assert_eq!(decode("4%2F0Adx+y"), "4/0Adx y");
+        assert_eq!(decode("%C3%A9"), "é");
+        assert_eq!(decode(&encode("x y/z?&=é")), "x y/z?&=é");
+        // a stray or short escape is kept as it is
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn the_code_comes_from_an_address_a_query_or_alone() {
+        let code = Some("4/0AdQt8q".to_string());
+        assert_eq!(pasted_code("http://localhost/?state=x&code=4%2F0AdQt8q&scope=mail\n"), code);
+        assert_eq!(pasted_code("http://localhost/?code=4%2F0AdQt8q#frag"), code);
+        assert_eq!(pasted_code("code=4%2F0AdQt8q&scope=mail"), code);
+        assert_eq!(pasted_code("  4/0AdQt8q  "), code);
+        assert_eq!(pasted_code("http://localhost/?error=access_denied"), None);
+        assert_eq!(pasted_code("   "), None);
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+use std::{fs, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::config::Config;
+use crate::drafts::Draft;
+
+/// Offered by Tab in the send-later prompt.
+pub const PRESETS: [&str; 4] = ["tomorrow 09:00", "in 1h", "in 3h", "mon 09:00"];
+
+const DEFAULT_TIME: &str = "09:00";
+
+/// A mail waiting in the outbox for its send time.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Scheduled {
+    /// Unix seconds: an instant, so time zones, DST and clock changes can only make it
+    /// late, never fire it twice.
+    pub at: i64,
+    #[serde(default)]
+    pub undisclosed: bool,
+    #[serde(flatten)]
+    pub mail: Draft,
+}
+
+impl Scheduled {
+    pub fn due(&self) -> bool {
+        self.at <= now()
+    }
+
+    pub fn local_time(&self) -> String {
+        match Local.timestamp_opt(self.at, 0).single() {
+            Some(t) => t.format("%a %Y-%m-%d %H:%M").to_string(),
+            None => format!("@{}", self.at),
+        }
+    }
+}
+
+pub struct Entry {
+    pub path: PathBuf,
+    pub mail: Scheduled,
+    pub claimed: bool, // being sent, or a send was cut off before it finished
+}
+
+fn now() -> i64 {
+    Local::now().timestamp()
+}
+
+pub fn dir() -> Result<PathBuf> {
+    let cfg = Config::path()?;
+    let parent = cfg.parent().ok_or_else(|| anyhow!("no config dir"))?;
+    Ok(parent.join("outbox"))
+}
+
+/// Writes a new outbox file for `mail`.
+pub fn add(mail: &Scheduled) -> Result<PathBuf> {
+    let dir = dir()?;
+    fs::create_dir_all(&dir)?;
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let path = dir.join(format!("{ts}.toml"));
+    fs::write(&path, toml::to_string_pretty(mail)?)?;
+    Ok(path)
+}
+
+/// Everything in the outbox, soonest first.
+pub fn list() -> Result<Vec<Entry>> {
+    let dir = dir()?;
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut out = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let claimed = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => false,
+            Some("sending") => true,
+            _ => continue,
+        };
+        let Ok(data) = fs::read_to_string(&path) else { continue };
+        if let Ok(mail) = toml::from_str::<Scheduled>(&data) {
+            out.push(Entry { path, mail, claimed });
+        }
+    }
+    out.sort_by_key(|e| e.mail.at);
+    Ok(out)
+}
+
+/// Renames the file to `.sending` before its mail goes out, so a second run of the
+/// scheduler (or a second zenmail) can't pick it up as well. Returns the new path.
+pub fn claim(path: &Path) -> Result<PathBuf> {
+    let claimed = path.with_extension("sending");
+    fs::rename(path, &claimed)?;
+    Ok(claimed)
+}
+
+/// Puts a claimed mail back in the queue, to be tried again at `at`.
+pub fn release(path: &Path, mut mail: Scheduled, at: i64) -> Result<()> {
+    mail.at = at;
+    fs::write(path, toml::to_string_pretty(&mail)?)?;
+    fs::rename(path, path.with_extension("toml"))?;
+    Ok(())
+}
+
+pub fn delete(path: &Path) -> Result<()> {
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Reads a send time in local time:
+/// - `in 2h`, `in 30m`, `in 1d`
+/// - `21:30` (the next time the clock shows it)
+/// - `tomorrow`, `fri`, `today 18:00`, `mon 08:30` (a day alone means 09:00)
+/// - `2026-03-01 09:00`
+/// - any of the above with an offset, `09:00 +09:00`, to mean a time somewhere else
+pub fn parse_when(input: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let input = input.trim().to_ascii_lowercase();
+    let bad = || anyhow!("can't read {input:?} as a time (try \"tomorrow 09:00\", \"in 2h\" or \"2026-03-01 09:00\")");
+
+    if let Some(span) = input.strip_prefix("in ").or_else(|| input.strip_prefix('+')) {
+        let span = span.trim();
+        let (n, unit) = span.split_at(span.find(|c: char| !c.is_ascii_digit()).ok_or_else(bad)?);
+        let n: i64 = n.parse().map_err(|_| bad())?;
+        let d = match unit.trim() {
+            "m" | "min" | "mins" | "minutes" => Duration::minutes(n),
+            "h" | "hour" | "hours" => Duration::hours(n),
+            "d" | "day" | "days" => Duration::days(n),
+            _ => return Err(bad()),
+        };
+        return Ok(now + d);
+    }
+
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let offset = match words.last() {
+        Some(w) if w.starts_with(['+', '-']) && w.len() > 1 => {
+            let w = w.replace(':', "");
+            let sign = if w.starts_with('-') { -1 } else { 1 };
+            let digits = &w[1..];
+            let (h, m) = digits.split_at(digits.len().min(2));
+            let secs = h.parse::<i32>().map_err(|_| bad())? * 3600 + m.parse::<i32>().unwrap_or(0) * 60;
+            words.pop();
+            Some(chrono::FixedOffset::east_opt(sign * secs).ok_or_else(bad)?)
+        }
+        _ => None,
+    };
+    let today = match offset {
+        Some(o) => now.with_timezone(&o).date_naive(),
+        None => now.date_naive(),
+    };
+
+    let (date, time) = match words.as_slice() {
+        [t] if t.contains(':') => (None, *t),
+        [day] => (Some(*day), DEFAULT_TIME),
+        [day, t] => (Some(*day), *t),
+        _ => return Err(bad()),
+    };
+    let time = NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| bad())?;
+    let date = match date {
+        None => None,
+        Some("today") => Some(today),
+        Some("tomorrow") => today.succ_opt(),
+        Some(d) => match d.parse::<Weekday>() {
+            // the next one, a week out when it is today
+            Ok(wd) => Some(today + Duration::days(((wd.num_days_from_monday() + 6 - today.weekday().num_days_from_monday()) % 7 + 1) as i64)),
+            Err(_) => Some(NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| bad())?),
+        },
+    };
+
+    let resolve = |date: NaiveDate| -> Result<DateTime<Local>> {
+        let naive = date.and_time(time);
+        match offset {
+            Some(o) => o.from_local_datetime(&naive).single().map(|t| t.with_timezone(&Local)).ok_or_else(bad),
+            // in a DST fall-back hour the first of the two instants is taken
+            None => Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .ok_or_else(|| anyhow!("{naive} does not exist here (the clocks skip it)")),
+        }
+    };
+
+    let at = match date {
+        Some(d) => resolve(d)?,
+        None => {
+            let t = resolve(today)?;
+            if t > now { t } else { resolve(today.succ_opt().ok_or_else(bad)?)? }
+        }
+    };
+    if at <= now {
+        return Err(anyhow!("{} is in the past", at.format("%Y-%m-%d %H:%M")));
+    }
+    Ok(at)
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// Frame timings collected while profiling is on (`:profile`).
+#[derive(Default)]
+pub struct Profiler {
+    frames: HashMap<&'static str, Vec<Duration>>,
+    keys: Vec<Duration>, // keypress to the end of the next draw
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let i = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[i]
+}
+
+fn stats_line(name: &str, samples: &[Duration]) -> Option<(Duration, String)> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    let p95 = percentile(&sorted, 0.95);
+    let line = format!(
+        "{name}: n={} p50={:.2}ms p95={:.2}ms max={:.2}ms",
+        sorted.len(),
+        ms(percentile(&sorted, 0.5)),
+        ms(p95),
+        ms(sorted[sorted.len() - 1]),
+    );
+    Some((p95, line))
+}
+
+impl Profiler {
+    pub fn record_frame(&mut self, view: &'static str, d: Duration) {
+        self.frames.entry(view).or_default().push(d);
+    }
+
+    pub fn record_key(&mut self, d: Duration) {
+        self.keys.push(d);
+    }
+
+    /// One-line keypress latency summary for the status bar.
+    pub fn summary(&self) -> String {
+        match stats_line("keypress→draw", &self.keys) {
+            Some((_, line)) => line,
+            None => "no keypresses recorded".to_string(),
+        }
+    }
+
+    /// Keypress latency followed by per-view draw times, worst p95 first.
+    pub fn report(&self) -> Vec<String> {
+        let mut views: Vec<_> = self
+            .frames
+            .iter()
+            .filter_map(|(name, samples)| stats_line(&format!("draw {name}"), samples))
+            .collect();
+        views.sort_by_key(|v| std::cmp::Reverse(v.0));
+
+        let mut out = vec![self.summary()];
+        out.extend(views.into_iter().map(|(_, line)| line));
+        out
+    }
+
+    /// Appends the report to `profile.log` next to the config file.
+    pub fn write_report(&self) -> Result<PathBuf> {
+        let cfg = Config::path()?;
+        let path = cfg.parent().ok_or_else(|| anyhow::anyhow!("no config dir"))?.join("profile.log");
+        let mut f = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(f, "--- zenmail profile ---")?;
+        for line in self.report() {
+            writeln!(f, "{line}")?;
+        }
+        Ok(path)
+    }
+}
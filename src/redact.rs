@@ -0,0 +1,136 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::config::Config;
+
+// Shorter secrets would blank out ordinary words.
+const MIN_SECRET: usize = 3;
+// Unbroken base64 at least this long is cut down to its first few characters.
+const MAX_BLOB: usize = 32;
+const BLOB_KEEP: usize = 6;
+
+// `AUTH PLAIN <base64>`, `AUTHENTICATE XOAUTH2 <token>` as echoed in server replies.
+static AUTH_ARG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(AUTH(?:ENTICATE)?\s+[A-Z0-9_-]+\s+)\S+").unwrap());
+
+/// `text` with the configured logins and passwords replaced by `***`, the argument of
+/// an AUTH command hidden and long base64 runs shortened. Error text from the IMAP
+/// and SMTP libraries can carry any of these, and it ends up on screen.
+pub fn clean(text: &str, config: &Config) -> String {
+    let mut secrets: Vec<String> = [config.imap.secrets(), config.smtp.secrets()]
+        .concat()
+        .into_iter()
+        .filter(|s| s.chars().count() >= MIN_SECRET)
+        .collect();
+    // a password containing the username must go first, whole
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+
+    let mut out = text.to_string();
+    for s in &secrets {
+        if out.contains(s.as_str()) {
+            out = out.replace(s.as_str(), "***");
+        }
+    }
+
+    let out = AUTH_ARG.replace_all(&out, "${1}***");
+    let mut cleaned = String::with_capacity(out.len());
+    for word in out.split_inclusive(char::is_whitespace) {
+        cleaned.push_str(&shorten_blob(word));
+    }
+    cleaned
+}
+
+// A whole word (give or take quotes and brackets) of base64 mixing cases and digits;
+// a path or a long English word doesn't qualify.
+fn shorten_blob(word: &str) -> String {
+    let core = word.trim_end().trim_matches(|c: char| "()[]<>\"',;:".contains(c));
+    let blob = core.trim_end_matches('=');
+    let is_blob = blob.len() >= MAX_BLOB
+        && blob.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+        && blob.contains(|c: char| c.is_ascii_digit())
+        && blob.contains(|c: char| c.is_ascii_uppercase())
+        && blob.contains(|c: char| c.is_ascii_lowercase());
+    if is_blob {
+        word.replacen(core, &format!("{}…", &core[..BLOB_KEEP]), 1)
+    } else {
+        word.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            [imap]
+            host = "imap.example.org"
+            port = 993
+            username = "ann@example.org"
+            password = "hunter2-ann@example.org"
+            starttls = false
+
+            [smtp]
+            host = "smtp.example.org"
+            port = 587
+            username = "ann"
+            password = "s3cr3t!"
+            starttls = true
+
+            [user]
+            name = "Ann"
+            email = "ann@example.org"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn auth_failure_hides_login_and_password() {
+        let e = "IMAP error: No Response: [AUTHENTICATIONFAILED] LOGIN ann@example.org hunter2-ann@example.org failed";
+        let out = clean(e, &config());
+        assert_eq!(out, "IMAP error: No Response: [AUTHENTICATIONFAILED] LOGIN *** *** failed");
+    }
+
+    #[test]
+    fn password_containing_the_login_goes_whole() {
+        let out = clean("tried hunter2-ann@example.org", &config());
+        assert_eq!(out, "tried ***");
+    }
+
+    #[test]
+    fn smtp_transcript_tail_hides_the_auth_argument() {
+        let e = "permanent error (535): 5.7.8 Authentication failed; last command: AUTH PLAIN AGFubgBzM2NyM3Qh";
+        let out = clean(e, &config());
+        assert_eq!(out, "permanent error (535): 5.7.8 Authentication failed; last command: AUTH PLAIN ***");
+        let out = clean("> AUTHENTICATE XOAUTH2 dXNlcj1hbm5AZXhhbXBsZS5vcmcBYXV0aD1CZWFyZXIgeWEyOQEB", &config());
+        assert_eq!(out, "> AUTHENTICATE XOAUTH2 ***");
+    }
+
+    #[test]
+    fn tls_failure_keeps_the_host_but_shortens_blobs() {
+        let e = "TLS error: certificate for imap.example.org (serial \"MIIDdzCCAl+gAwIBAgIEAgAAuTANBgkqhkiG9w0BAQUFADBa\") not trusted";
+        let out = clean(e, &config());
+        assert_eq!(out, "TLS error: certificate for imap.example.org (serial \"MIIDdz…\") not trusted");
+    }
+
+    #[test]
+    fn short_secrets_and_ordinary_words_stay() {
+        let mut config = config();
+        config.smtp.username = "an".into();
+        let out = clean("an answer from /var/mail/incomprehensibilities", &config);
+        assert_eq!(out, "an answer from /var/mail/incomprehensibilities");
+    }
+
+    #[test]
+    fn shorten_blob_needs_mixed_base64() {
+        let blob = "QWxhZGRpbjpvcGVuIHNlc2FtZQ0KQWxhZGRpbjpvcGVu==";
+        assert_eq!(shorten_blob(blob), "QWxhZG…");
+        assert_eq!(shorten_blob(&format!("<{blob}>, ")), "<QWxhZG…>, ");
+        // too short, no digits, or one case only
+        assert_eq!(shorten_blob("QWxhZGRpbjpvcGVu"), "QWxhZGRpbjpvcGVu");
+        assert_eq!(shorten_blob("abcdefghijklmnopqrstuvwxyzABCDEFGHIJ"), "abcdefghijklmnopqrstuvwxyzABCDEFGHIJ");
+        assert_eq!(shorten_blob("0123456789abcdef0123456789abcdef0123"), "0123456789abcdef0123456789abcdef0123");
+    }
+}
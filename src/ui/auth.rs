@@ -0,0 +1,28 @@
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, Paragraph},
+};
+
+use super::help;
+use crate::app::{App, View};
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(6), Constraint::Min(0), Constraint::Length(2)])
+        .split(f.size());
+
+    let imap = &app.config.imap;
+    let prompt = Paragraph::new(format!(
+        "The server rejected the login.\nUser:     {} @ {}\nPassword: {}",
+        imap.username,
+        imap.host,
+        "*".repeat(app.auth_input.chars().count()),
+    ))
+    .block(Block::default().borders(Borders::ALL).title("Login"));
+    f.render_widget(prompt, chunks[0]);
+
+    let help = Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Auth)));
+    f.render_widget(help, chunks[2]);
+}
@@ -0,0 +1,69 @@
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    text::{Line, Span},
+};
+
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{App, View};
+use crate::features::{self, State};
+
+// 90 → "1m 30s"
+fn duration(secs: u64) -> String {
+    match (secs / 60, secs % 60) {
+        (0, s) => format!("{s}s"),
+        (m, 0) => format!("{m}m"),
+        (m, s) => format!("{m}m {s}s"),
+    }
+}
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+
+    let matrix = features::matrix(&app.config, app.server_caps.as_deref(), &app.folders);
+    let width = matrix.iter().map(|feat| feat.name.chars().count()).max().unwrap_or(0);
+
+    let mut lines: Vec<Line> = matrix
+        .iter()
+        .map(|feat| {
+            let (mark, role, why) = match &feat.state {
+                State::On => ("on ", Role::Success, String::new()),
+                State::Fallback(why) => ("~  ", Role::Warning, why.clone()),
+                State::Off(why) => ("off", Role::Muted, why.clone()),
+            };
+            let keys = if feat.keys.is_empty() { String::new() } else { format!("  [{}]", feat.keys) };
+            Line::from(vec![
+                Span::styled(mark, theme::of(app, role)),
+                Span::raw(format!("  {:width$}{keys}  ", feat.name)),
+                Span::styled(why, theme::of(app, Role::Muted)),
+            ])
+        })
+        .collect();
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(match &app.server_caps {
+        Some(caps) => format!("Server: {}", caps.join(" ")),
+        None => "Server: capabilities not known yet".to_string(),
+    }));
+    let list = &app.config.list;
+    if list.poll_secs > 0 {
+        let mut polling = format!("Polling: every {}", duration(app.poll.interval().as_secs().max(list.poll_secs)));
+        if list.adaptive_poll {
+            polling += &format!(" (adaptive, {} to {})", duration(list.poll_secs), duration(list.poll_max_secs.max(list.poll_secs)));
+        }
+        lines.push(Line::from(polling));
+    }
+
+    let body = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(format!("Capabilities · {}", app.config.imap.host)))
+        .wrap(Wrap { trim: false });
+    f.render_widget(body, chunks[0]);
+
+    let help = Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Capabilities)));
+    f.render_widget(help, chunks[1]);
+}
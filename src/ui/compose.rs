@@ -1,53 +1,308 @@
 use ratatui::{
     Frame,
-    layout::{Layout, Direction, Constraint},
-    widgets::{Block, Borders, Paragraph, Wrap},
-    style::{Style, Modifier},
+    layout::{Layout, Direction, Constraint, Rect},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    style::Style,
+    text::{Line, Span, Text},
 };
+use chrono::{Local, TimeZone};
 
-use crate::app::{App, ComposeField};
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{self, App, ComposeField, View};
+use crate::count::TextCount;
+use crate::edit;
+use super::view::{human_size, wrap_segments};
+use unicode_width::UnicodeWidthStr;
+
+// Only the rows that fit are handed to the Paragraph. The quoted history of a long
+// thread can be thousands of lines that never scroll into view, and wrapping all of
+// it on every keypress is what made typing lag on slow machines.
+// The body is wrapped here rather than by the Paragraph so the cursor's row and
+// column are known; the view scrolls to keep that row on screen.
+fn visible_body(app: &App, width: u16, height: u16) -> (Text<'static>, Option<(u16, u16)>) {
+    let (width, height) = (width.max(1) as usize, height as usize);
+    let body = app.compose.body.as_str();
+    let cursor = edit::clamp(body, app.compose.cursor);
+
+    let mut rows = Vec::new();
+    let mut at = None;
+    let mut line_start = 0;
+    for line in body.split('\n') {
+        let depth = edit::quote_depth(line);
+        let segments = wrap_segments(line, width);
+        for (i, &(start, end)) in segments.iter().enumerate() {
+            let last = i + 1 == segments.len();
+            let here = line_start + start..=line_start + end;
+            if at.is_none() && here.contains(&cursor) && (last || cursor < line_start + end) {
+                let col = line[start..cursor - line_start].width();
+                at = Some((col, rows.len()));
+            }
+            rows.push((&line[start..end], depth));
+        }
+        line_start += line.len() + 1;
+    }
+
+    let focused = app.compose.focus == ComposeField::Body;
+    let top = match at {
+        Some((_, row)) if focused => (row + 1).saturating_sub(height),
+        _ => 0,
+    };
+
+    let quote = app.compose.quote.as_str();
+    if !quote.is_empty() && rows.len() < top + height {
+        if !body.trim().is_empty() {
+            rows.push(("", 0));
+        }
+        for line in quote.lines() {
+            if rows.len() >= top + height {
+                break;
+            }
+            let depth = edit::quote_depth(line);
+            rows.extend(wrap_segments(line, width).into_iter().map(|(s, e)| (&line[s..e], depth)));
+        }
+    }
+
+    let end = rows.len().min(top + height);
+    // quoted lines take the style of their depth, so what I type between them stands out
+    let text = Text::from(
+        rows[top.min(end)..end]
+            .iter()
+            .map(|&(row, depth)| match Role::quote(depth) {
+                Some(role) => Line::styled(row.to_string(), theme::of(app, role)),
+                None => Line::raw(row.to_string()),
+            })
+            .collect::<Vec<_>>(),
+    );
+    let at = at.filter(|_| focused).map(|(col, row)| (col.min(width - 1) as u16, (row - top) as u16));
+    (text, at)
+}
+
+// Where the cursor goes in a header field: the row under the block's top border and
+// the column after the "To: " style prefix.
+fn header_cursor(app: &App) -> Option<(u16, u16)> {
+    let c = &app.compose;
+    let (row, prefix, text) = match c.focus {
+        ComposeField::To if c.undisclosed => (0, "To: undisclosed-recipients:; (Bcc: ".len(), &c.to),
+        ComposeField::To => (0, "To: ".len(), &c.to),
+        ComposeField::Cc => (1, "Cc: ".len(), &c.cc),
+        ComposeField::Bcc => (2, "Bcc: ".len(), &c.bcc),
+        ComposeField::ReplyTo => (3, "Reply-To: ".len(), &c.reply_to),
+        ComposeField::Subject => (4, "Subject: ".len(), &c.subject),
+        ComposeField::Body => return None,
+    };
+    let col = prefix + text[..edit::clamp(text, c.cursor)].chars().count();
+    Some((col as u16, row))
+}
+
+// `[user] reply_to` shows in parentheses while the field is empty and not focused.
+fn reply_to_field(app: &App) -> String {
+    match app.config.user.reply_to.as_deref().map(str::trim) {
+        Some(default) if app.compose.reply_to.is_empty() && app.compose.focus != ComposeField::ReplyTo && !default.is_empty() => {
+            format!("({default})")
+        }
+        _ => app.compose.reply_to.clone(),
+    }
+}
+
+// The To line of the preview; undisclosed, Cc goes out as Bcc too.
+fn recipients(app: &App) -> String {
+    let c = &app.compose;
+    if c.undisclosed {
+        let hidden = [c.to.trim(), c.cc.trim()].into_iter().filter(|f| !f.is_empty()).collect::<Vec<_>>().join(", ");
+        format!("undisclosed-recipients:; (Bcc: {hidden})")
+    } else {
+        c.to.clone()
+    }
+}
+
+// Ctrl+S: the mail as it will go out, read-only, with anything that looks wrong on top.
+fn draw_preview(f: &mut Frame, app: &App, scroll: u16) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+
+    let c = &app.compose;
+    let mut lines = vec![
+        Line::from(format!("From: {} <{}>", app.config.user.name, app.config.user.email)),
+        Line::from(format!("To: {}", recipients(app))),
+    ];
+    let reply_to = match c.reply_to.trim() {
+        "" => app.config.user.reply_to.clone().unwrap_or_default(),
+        set => set.to_string(),
+    };
+    // undisclosed, Cc is part of the To line's Bcc list
+    let cc = if c.undisclosed { "" } else { c.cc.as_str() };
+    for (name, value) in [("Cc", cc), ("Bcc", &c.bcc), ("Reply-To", &reply_to)] {
+        if !value.trim().is_empty() {
+            lines.push(Line::from(format!("{name}: {}", value.trim())));
+        }
+    }
+    lines.push(Line::from(format!("Subject: {}", c.subject)));
+    if let Some(t) = c.send_at.and_then(|at| Local.timestamp_opt(at, 0).single()) {
+        lines.push(Line::from(format!("Send at: {}", t.format("%a %Y-%m-%d %H:%M"))));
+    }
+    if c.high_priority {
+        lines.push(Line::from("Priority: high"));
+    }
+    let mut files = c.attachments.iter().map(|p| p.display().to_string()).collect::<Vec<_>>();
+    if c.attached_message.is_some() {
+        files.push("original message".to_string());
+    }
+    if !files.is_empty() {
+        lines.push(Line::from(format!("Attachments: {}", files.join(", "))));
+    }
+
+    let warnings = app::send_warnings(app);
+    if !warnings.is_empty() {
+        lines.push(Line::from(""));
+        for w in warnings {
+            lines.push(Line::from(Span::styled(format!("! {w}"), theme::of(app, Role::Warning))));
+        }
+    }
+
+    lines.push(Line::from(""));
+    lines.extend(app::compose_full_body(&c.body, &c.quote).lines().map(|l| Line::from(l.to_string())));
+
+    let preview = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Send this?"))
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0));
+    f.render_widget(preview, chunks[0]);
+
+    let help = Paragraph::new(format!("{}   {}", app.status, "y send · n/Esc keep editing · j/k scroll"));
+    f.render_widget(help, chunks[1]);
+}
+
+// Address completions in a box just under the field, starting where the address does.
+fn draw_suggestions(f: &mut Frame, app: &App, header: Rect) {
+    let found = app::suggestions(app);
+    let (Some((x, y)), Some((_, typed))) = (header_cursor(app), app::completion_token(&app.compose)) else { return };
+    if found.is_empty() {
+        return;
+    }
+    let items: Vec<String> = found.iter().map(|c| c.display()).collect();
+    let screen = f.size();
+    let left = (header.x + 1 + x.saturating_sub(typed.chars().count() as u16)).min(screen.width.saturating_sub(10));
+    let width = (items.iter().map(|s| s.chars().count()).max().unwrap_or(0) as u16 + 4).min(screen.width - left);
+    let top = header.y + 2 + y;
+    let area = Rect { x: left, y: top, width, height: (items.len() as u16 + 2).min(screen.height.saturating_sub(top)) };
+
+    let list = List::new(items.into_iter().map(ListItem::new).collect::<Vec<_>>())
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(theme::of(app, Role::Selection));
+    let mut state = ListState::default();
+    state.select(Some(app.compose.suggestion.min(found.len() - 1)));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
 
 pub fn draw(f: &mut Frame, app: &App) {
+    if let Some(scroll) = app.compose.preview {
+        draw_preview(f, app, scroll);
+        return;
+    }
+
+    // one more header row for the attached files
+    let files = app.compose.attachments.iter().map(|p| {
+        let name = p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| p.display().to_string());
+        match app.compose.attachment_sizes.get(p) {
+            Some(&len) => format!("{name} ({})", human_size(len as usize)),
+            None => format!("{name} (missing)"),
+        }
+    }).collect::<Vec<_>>();
+    let header_rows = if files.is_empty() { 9 } else { 10 };
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(6), Constraint::Min(1), Constraint::Length(2)])
+        .constraints([Constraint::Length(header_rows), Constraint::Min(1), Constraint::Length(2)])
         .split(f.size());
 
     let body_style = if app.compose.focus == ComposeField::Body {
-        Style::default().add_modifier(Modifier::REVERSED)
+        theme::of(app, Role::Selection)
     } else {
         Style::default()
     };
 
-    let header = Paragraph::new(format!(
-        "To: {}\nSubject: {}\n\n(Tab to switch · Ctrl+S to send · Esc to cancel)",
-        app.compose.to,
-        app.compose.subject
-    ))
+    let to = if app.compose.undisclosed {
+        format!("undisclosed-recipients:; (Bcc: {})", app.compose.to)
+    } else {
+        app.compose.to.clone()
+    };
+
+    let priority = if app.compose.high_priority { "Priority: high" } else { "" };
+    let attached = match &app.compose.attached_message {
+        Some(raw) => format!("Attached: original message ({} KB)", raw.len().div_ceil(1024)),
+        None => String::new(),
+    };
+    let flags = [priority, attached.as_str()].into_iter().filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" · ");
+
+    let mut header_text = format!(
+        "To: {}\nCc: {}\nBcc: {}\nReply-To: {}\nSubject: {}\n{}\n({})",
+        to,
+        app.compose.cc,
+        app.compose.bcc,
+        reply_to_field(app),
+        app.compose.subject,
+        flags,
+        help::hint(app, View::Compose)
+    );
+    if !files.is_empty() {
+        header_text.push_str(&format!("\nFiles: {}", files.join(", ")));
+    }
+    let header = Paragraph::new(header_text)
         .block(Block::default().borders(Borders::ALL).title("Compose"));
 
     f.render_widget(header, chunks[0]);
 
-    let body_text = if app.compose.quote.is_empty() {
-        app.compose.body.clone()
-    } else if app.compose.body.trim().is_empty() {
-        app.compose.quote.clone()
-    } else {
-        format!("{}\n\n{}", app.compose.body, app.compose.quote)
-    };
-
-    let body = Paragraph::new(body_text)
-        .block(Block::default().borders(Borders::ALL).title("Body"))
-        .wrap(Wrap { trim: false })
+    let inner_width = chunks[1].width.saturating_sub(2);
+    let inner_height = chunks[1].height.saturating_sub(2);
+    let (visible, body_cursor) = visible_body(app, inner_width, inner_height);
+    let body = Paragraph::new(visible)
+        .block(Block::default().borders(Borders::ALL).title(format!("Body · {}", TextCount::of(&app.compose.body).label())))
         .style(body_style);
 
     f.render_widget(body, chunks[1]);
 
+    let cursor = match body_cursor {
+        Some((x, y)) => Some((chunks[1], x, y)),
+        None => header_cursor(app).map(|(x, y)| (chunks[0], x, y)),
+    };
+    if let Some((area, x, y)) = cursor {
+        if app.compose.attach_input.is_none() && app.compose.schedule_input.is_none() {
+            let x = x.min(area.width.saturating_sub(3));
+            f.set_cursor(area.x + 1 + x, area.y + 1 + y);
+            draw_suggestions(f, app, chunks[0]);
+        }
+    }
+
+    if let Some(input) = &app.compose.attach_input {
+        let prompt = Paragraph::new(format!(
+            "Attach: {input}_   {}\n(Tab completes · Enter attach · Esc back)",
+            app.status
+        ));
+        f.render_widget(prompt, chunks[2]);
+        return;
+    }
+
+    if let Some(input) = &app.compose.schedule_input {
+        let prompt = Paragraph::new(format!(
+            "Send at: {input}_   {}\n(Tab presets · e.g. \"fri 08:30\", \"in 2h\", \"2026-03-01 09:00 +09:00\" · Enter schedule · Esc back)",
+            app.status
+        ));
+        f.render_widget(prompt, chunks[2]);
+        return;
+    }
+
     let status = Paragraph::new(format!(
         "{}   Focus: {}",
         app.status,
         match app.compose.focus {
             ComposeField::To => "To",
+            ComposeField::Cc => "Cc",
+            ComposeField::Bcc => "Bcc",
+            ComposeField::ReplyTo => "Reply-To",
             ComposeField::Subject => "Subject",
             ComposeField::Body => "Body",
         }
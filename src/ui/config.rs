@@ -4,12 +4,17 @@ use ratatui::{
     widgets::{Block, Borders, Paragraph, Wrap},
 };
 
-use crate::app::{App, ConfigField};
+use super::help;
+use crate::app::{App, ConfigField, View};
 
 fn mask(s: &str) -> String {
     if s.is_empty() { "".to_string() } else { "********".to_string() }
 }
 
+fn password(source: Option<&str>, typed: &str) -> String {
+    source.map_or_else(|| mask(typed), str::to_string)
+}
+
 fn line(app: &App, field: ConfigField, label: &str, value: &str) -> String {
     let prefix = if app.cfg_edit.focus == field { "▶ " } else { "  " };
     format!("{prefix}{label:<10} {value}")
@@ -27,16 +32,20 @@ pub fn draw(f: &mut Frame, app: &App) {
     s.push_str(&line(app, ConfigField::ImapHost, "host", &app.cfg_edit.imap_host)); s.push('\n');
     s.push_str(&line(app, ConfigField::ImapPort, "port", &app.cfg_edit.imap_port)); s.push('\n');
     s.push_str(&line(app, ConfigField::ImapUser, "username", &app.cfg_edit.imap_user)); s.push('\n');
-    s.push_str(&line(app, ConfigField::ImapPass, "password", &mask(&app.cfg_edit.imap_pass))); s.push('\n');
+    s.push_str(&line(app, ConfigField::ImapPass, "password", &password(app.config.imap.password_source(), &app.cfg_edit.imap_pass))); s.push('\n');
     s.push_str(&line(app, ConfigField::ImapStarttls, "starttls", if app.cfg_edit.imap_starttls { "true" } else { "false" })); s.push('\n');
+    s.push_str(&line(app, ConfigField::ImapTlsVerify, "tls verify", if app.cfg_edit.imap_tls_verify { "true" } else { "false ⚠" })); s.push('\n');
+    s.push_str(&line(app, ConfigField::ImapCaFile, "ca file", &app.cfg_edit.imap_ca_file)); s.push('\n');
 
     s.push('\n');
     s.push_str("SMTP\n");
     s.push_str(&line(app, ConfigField::SmtpHost, "host", &app.cfg_edit.smtp_host)); s.push('\n');
     s.push_str(&line(app, ConfigField::SmtpPort, "port", &app.cfg_edit.smtp_port)); s.push('\n');
     s.push_str(&line(app, ConfigField::SmtpUser, "username", &app.cfg_edit.smtp_user)); s.push('\n');
-    s.push_str(&line(app, ConfigField::SmtpPass, "password", &mask(&app.cfg_edit.smtp_pass))); s.push('\n');
+    s.push_str(&line(app, ConfigField::SmtpPass, "password", &password(app.config.smtp.password_source(), &app.cfg_edit.smtp_pass))); s.push('\n');
     s.push_str(&line(app, ConfigField::SmtpStarttls, "starttls", if app.cfg_edit.smtp_starttls { "true" } else { "false" })); s.push('\n');
+    s.push_str(&line(app, ConfigField::SmtpTlsVerify, "tls verify", if app.cfg_edit.smtp_tls_verify { "true" } else { "false ⚠" })); s.push('\n');
+    s.push_str(&line(app, ConfigField::SmtpCaFile, "ca file", &app.cfg_edit.smtp_ca_file)); s.push('\n');
 
     s.push('\n');
     s.push_str("USER\n");
@@ -49,10 +58,6 @@ pub fn draw(f: &mut Frame, app: &App) {
 
     f.render_widget(body, chunks[0]);
 
-    let help = Paragraph::new(format!(
-        "{}   {}",
-        app.status,
-        "Tab/Shift+Tab navigate · Space toggle · Ctrl+S save · e editor · Esc back"
-    ));
+    let help = Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Config)));
     f.render_widget(help, chunks[1]);
 }
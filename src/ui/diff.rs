@@ -0,0 +1,60 @@
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, Paragraph},
+    text::{Line, Span},
+};
+
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{App, View};
+use crate::diff::Change;
+use crate::mail::MessageSummary;
+
+fn describe(m: &MessageSummary) -> String {
+    let subject = if m.subject.is_empty() { "(no subject)" } else { m.subject.as_str() };
+    format!("{} · {subject}", m.date)
+}
+
+/// Rows of diff on screen: all but the header box, the help lines and its own border.
+pub fn diff_height(term_height: u16) -> u16 {
+    term_height.saturating_sub(4 + 2 + 2)
+}
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+
+    let Some(d) = &app.diff else { return };
+    let removed = theme::of(app, Role::Error);
+    let added = theme::of(app, Role::Success);
+
+    let header = Paragraph::new(vec![
+        Line::from(Span::styled(format!("- {}", describe(&d.old)), removed)),
+        Line::from(Span::styled(format!("+ {}", describe(&d.new)), added)),
+    ])
+    .block(Block::default().borders(Borders::ALL).title("Compare"));
+    f.render_widget(header, chunks[0]);
+
+    // the +/- marks carry the meaning; color only helps
+    let lines: Vec<Line> = d
+        .lines
+        .iter()
+        .map(|(change, text)| match change {
+            Change::Same => Line::from(format!("  {text}")),
+            Change::Removed => Line::from(Span::styled(format!("- {text}"), removed)),
+            Change::Added => Line::from(Span::styled(format!("+ {text}"), added)),
+        })
+        .collect();
+    let max_scroll = d.lines.len().saturating_sub(diff_height(f.size().height) as usize);
+    let body = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL))
+        // a resize can leave the offset past the end
+        .scroll((d.scroll.min(max_scroll.min(u16::MAX as usize) as u16), 0));
+    f.render_widget(body, chunks[1]);
+
+    let help = Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Diff)));
+    f.render_widget(help, chunks[2]);
+}
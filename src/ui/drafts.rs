@@ -0,0 +1,41 @@
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{App, View};
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+
+    let items = if app.drafts.is_empty() {
+        vec![ListItem::new("No drafts (Ctrl+D in compose saves one)")]
+    } else {
+        app.drafts.iter().map(|(_, d)| {
+            let subject = if d.subject.is_empty() { "(no subject)" } else { d.subject.as_str() };
+            let to = if d.to.is_empty() { "(no recipient)" } else { d.to.as_str() };
+            ListItem::new(format!("{subject}\n  to {to}"))
+        }).collect::<Vec<_>>()
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Drafts"))
+        .highlight_style(theme::of(app, Role::Selection))
+        .highlight_symbol("▶ ");
+
+    let mut state = ListState::default();
+    if !app.drafts.is_empty() {
+        state.select(Some(app.draft_selected.min(app.drafts.len() - 1)));
+    }
+
+    f.render_stateful_widget(list, chunks[0], &mut state);
+
+    let help = Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Drafts)));
+    f.render_widget(help, chunks[1]);
+}
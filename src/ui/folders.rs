@@ -0,0 +1,42 @@
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{App, View};
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+
+    let items = if app.folders.is_empty() {
+        vec![ListItem::new("Loading...")]
+    } else {
+        app.folders.iter().map(|folder| {
+            let marker = if folder.name == app.current_folder { "● " } else { "  " };
+            let indent = "  ".repeat(folder.depth);
+            let access = if folder.read_only() { " (read-only)" } else { "" };
+            ListItem::new(format!("{marker}{indent}{}{access}", folder.display))
+        }).collect::<Vec<_>>()
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Folders"))
+        .highlight_style(theme::of(app, Role::Selection))
+        .highlight_symbol("▶ ");
+
+    let mut state = ListState::default();
+    if !app.folders.is_empty() {
+        state.select(Some(app.folder_selected.min(app.folders.len() - 1)));
+    }
+
+    f.render_stateful_widget(list, chunks[0], &mut state);
+
+    let help = Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Folders)));
+    f.render_widget(help, chunks[1]);
+}
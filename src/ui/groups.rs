@@ -0,0 +1,43 @@
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{App, View};
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+
+    let groups = &app.config.groups;
+    let items = if groups.is_empty() {
+        vec![ListItem::new("No groups yet (a adds one)")]
+    } else {
+        groups.iter().map(|(name, members)| ListItem::new(format!("{name}\n   {}", members.join(", ")))).collect::<Vec<_>>()
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Groups"))
+        .highlight_style(theme::of(app, Role::Selection))
+        .highlight_symbol("▶ ");
+
+    let mut state = ListState::default();
+    if !groups.is_empty() {
+        state.select(Some(app.group_selected.min(groups.len() - 1)));
+    }
+    f.render_stateful_widget(list, chunks[0], &mut state);
+
+    let help = match &app.group_input {
+        Some((_, input)) => Paragraph::new(format!(
+            "Group: {input}_   {}\n(name = a@x, b@x, other-group · Enter save · Esc cancel)",
+            app.status
+        )),
+        None => Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Groups))),
+    };
+    f.render_widget(help, chunks[1]);
+}
@@ -0,0 +1,113 @@
+use chrono::Local;
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    text::{Line, Span},
+};
+
+use super::help;
+use super::view::human_size;
+use super::theme::{self, Role};
+use crate::app::{App, View};
+use crate::redact;
+
+// Extensions worth knowing about when something seems off.
+const HIGHLIGHTS: [&str; 6] = ["IDLE", "MOVE", "UIDPLUS", "ACL", "QUOTA", "CONDSTORE"];
+
+// "14:02:11 (3 min ago)"
+fn when(at: chrono::DateTime<Local>) -> String {
+    let mins = (Local::now() - at).num_minutes();
+    let ago = match mins {
+        0 => "just now".to_string(),
+        m if m < 120 => format!("{m} min ago"),
+        m => format!("{} h ago", m / 60),
+    };
+    format!("{} ({ago})", at.format("%H:%M:%S"))
+}
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+    let muted = theme::of(app, Role::Muted);
+
+    let connection = if app.auth_failed {
+        Span::styled("login rejected", theme::of(app, Role::Error))
+    } else {
+        let summary = app.net.summary();
+        let role = if summary == "connected" { Role::Success } else { Role::Warning };
+        Span::styled(summary, theme::of(app, role))
+    };
+    let mut lines = vec![Line::from(vec![Span::raw("Connection  "), connection])];
+
+    let mut outbox = format!("{} scheduled", app.outbox.len());
+    if !app.outbox_inflight.is_empty() {
+        outbox += &format!(", {} sending", app.outbox_inflight.len());
+    }
+    lines.push(Line::from(format!("Outbox      {outbox}")));
+    lines.push(match app.health.drafts {
+        Some(n) => Line::from(format!("Drafts      {n} kept locally")),
+        None => Line::from(vec![Span::raw("Drafts      "), Span::styled("can't be read", muted)]),
+    });
+
+    let mut server = vec![Span::raw("Server      ")];
+    match &app.server_caps {
+        Some(caps) => {
+            for cap in HIGHLIGHTS {
+                let has = caps.iter().any(|c| c.eq_ignore_ascii_case(cap));
+                server.push(Span::styled(cap, if has { theme::of(app, Role::Success) } else { muted }));
+                server.push(Span::raw(" "));
+            }
+        }
+        None => server.push(Span::styled("capabilities not known yet", muted)),
+    }
+    lines.push(Line::from(server));
+
+    let has_quota = app.server_caps.iter().flatten().any(|c| c.eq_ignore_ascii_case("QUOTA"));
+    match app.health.quota {
+        Some((used, limit)) => {
+            let percent = (used * 100).checked_div(limit).unwrap_or(100);
+            let role = if percent >= 90 { Role::Warning } else { Role::Success };
+            lines.push(Line::from(vec![
+                Span::raw("Quota       "),
+                Span::styled(format!("{percent}%"), theme::of(app, role)),
+                Span::raw(format!(" · {} of {}", human_size(used as usize * 1024), human_size(limit as usize * 1024))),
+            ]));
+        }
+        None if has_quota => lines.push(Line::from(vec![Span::raw("Quota       "), Span::styled("not known yet", muted)])),
+        None => {}
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from("Last sync"));
+    if app.health.synced.is_empty() {
+        lines.push(Line::from(Span::styled("  nothing synced yet", muted)));
+    }
+    let width = app.health.synced.keys().map(|name| name.chars().count()).max().unwrap_or(0);
+    for (folder, at) in &app.health.synced {
+        lines.push(Line::from(format!("  {folder:width$}  {}", when(*at))));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from("Recent errors"));
+    if app.health.errors.is_empty() {
+        lines.push(Line::from(Span::styled("  none", muted)));
+    }
+    for (at, error) in app.health.errors.iter().rev() {
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {}  ", at.format("%H:%M:%S")), muted),
+            Span::raw(redact::clean(error, &app.config)),
+        ]));
+    }
+
+    let title = format!("Account health · {}@{}", app.config.imap.username, app.config.imap.host);
+    let body = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(Wrap { trim: false });
+    f.render_widget(body, chunks[0]);
+
+    let help = Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Health)));
+    f.render_widget(help, chunks[1]);
+}
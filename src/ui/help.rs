@@ -0,0 +1,168 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+use crate::app::{App, View};
+use crate::keys::Action;
+
+fn k(key: &str) -> String {
+    key.to_string()
+}
+
+/// The keys of `view` and what they do, in the order the help line shows them. Keys
+/// that `[keys]` can remap are read from the key map.
+pub fn bindings(app: &App, view: View) -> Vec<(String, &'static str)> {
+    let key = |a| app.keys.label(a);
+    let mut out = match view {
+        View::List => vec![
+            (format!("{}/{} or ↑↓", key(Action::ListDown), key(Action::ListUp)), "move"),
+            (key(Action::Open), "open"),
+            (k("/"), "search"),
+            (k(":"), "command"),
+            (key(Action::Reply), "reply"),
+            (k("f"), "forward"),
+            (key(Action::Refresh), "refresh"),
+            (k("O"), "reload"),
+            (k("m"), "older"),
+            (k("u"), "read/unread"),
+            (k("Space"), "mark"),
+            (k("="), "compare two marked"),
+            (k("v"), "density"),
+            (k("s"), "order"),
+            (k("~"), "saved searches"),
+            (k("1-9"), "open one"),
+            (k("b"), "folders"),
+            (k("[/]"), "next"),
+            (k("D"), "drafts"),
+            (k("S"), "outbox"),
+            (k("E"), "archive"),
+            (k("A"), "archive by year"),
+            (k("d"), "delete"),
+            (k("F"), "filter"),
+            (key(Action::Compose), "compose"),
+            (k("H"), "account health"),
+            (key(Action::Config), "config"),
+        ],
+        View::Mail => vec![
+            (k("j/k or ↑↓"), "scroll"),
+            (k("PgUp/PgDn"), "page"),
+            (k("Home/End"), "top/end"),
+            (k("Esc"), "back"),
+            (key(Action::Reply), "reply"),
+            (k("R"), "reply all"),
+            (k("f/F"), "forward inline/attached"),
+            (k("w"), "export .eml"),
+            (k("x"), "disclaimers"),
+            (k("z"), "focus"),
+            (k("i"), "open image"),
+            (k("a/s"), "save attachment"),
+            (k("E"), "archive"),
+            (k("d"), "delete"),
+            (key(Action::Compose), "compose"),
+            (key(Action::Config), "config"),
+        ],
+        View::Folders => vec![(k("j/k or ↑↓"), "move"), (k("Enter"), "open"), (k("Esc"), "back")],
+        View::Drafts => vec![(k("j/k or ↑↓"), "move"), (k("Enter"), "resume"), (k("d"), "delete"), (k("Esc"), "back")],
+        View::Outbox => vec![
+            (k("j/k or ↑↓"), "move"),
+            (k("Enter"), "edit (unschedules)"),
+            (k("d"), "cancel"),
+            (k("Esc"), "back"),
+        ],
+        View::Searches => vec![
+            (k("j/k or ↑↓"), "move"),
+            (k("Enter"), "open"),
+            (k("a"), "save current view"),
+            (k("u"), "update to current view"),
+            (k("r"), "rename"),
+            (k("d"), "delete"),
+            (k("Esc"), "back"),
+        ],
+        View::Groups => vec![
+            (k("j/k or ↑↓"), "move"),
+            (k("a"), "add"),
+            (k("Enter/e"), "edit"),
+            (k("d"), "delete"),
+            (k("Esc"), "back"),
+        ],
+        View::Capabilities | View::Health => vec![(k("o"), "ask the server again"), (k("Esc"), "back")],
+        View::Diff => vec![
+            (k("j/k or ↑↓"), "scroll"),
+            (k("PgUp/PgDn"), "page"),
+            (k("Home/End"), "top/end"),
+            (k("Esc"), "back"),
+        ],
+        View::Styles => vec![(k("Esc"), "back")],
+        // text is typed here, so `q`, `g` and `?` are letters too (see the `typing` check
+        // ahead of the global keys in `run`)
+        View::Compose => {
+            return vec![
+                (k("Tab"), "switch"),
+                (k("Ctrl+A"), "attach"),
+                (k("Ctrl+R"), "remove last"),
+                (k("Ctrl+P"), "priority"),
+                (k("Ctrl+D"), "save draft"),
+                (k("Ctrl+E"), "editor"),
+                (k("Ctrl+T"), "answer inline"),
+                (k("Ctrl+↑↓"), "quoted paragraphs"),
+                (k("Ctrl+O"), "split quote"),
+                (k("Ctrl+S"), "send"),
+                (k("Ctrl+L"), "send later"),
+                (k("Esc"), "cancel"),
+                (k("F1"), "help"),
+            ]
+        }
+        View::Config => {
+            return vec![
+                (k("Tab/Shift+Tab"), "navigate"),
+                (k("Space"), "toggle"),
+                (k("Ctrl+S"), "save"),
+                (k("Ctrl+K"), "password to keyring"),
+                (k("e"), "editor"),
+                (k("Esc"), "back"),
+                (k("F1"), "help"),
+            ]
+        }
+        View::Auth => {
+            return vec![
+                (k("Enter"), "retry"),
+                (k("Ctrl+K"), "retry, keep in keyring"),
+                (k("Esc"), "back (o prompts again)"),
+                (k("F1"), "help"),
+            ]
+        }
+    };
+    out.push((key(Action::Quit), "quit"));
+    out.push((key(Action::Help), "help"));
+    out
+}
+
+/// `bindings` as one line: `j/k or ↑↓ move · Enter open · …`.
+pub fn hint(app: &App, view: View) -> String {
+    bindings(app, view).iter().map(|(key, what)| format!("{key} {what}")).collect::<Vec<_>>().join(" · ")
+}
+
+/// The keys of the view underneath, in a box over the middle of the screen.
+pub fn draw(f: &mut Frame, app: &App) {
+    let rows = bindings(app, app.view);
+    let key_width = rows.iter().map(|(key, _)| key.chars().count()).max().unwrap_or(0);
+    let text = rows.iter().map(|(key, what)| format!("{key:>key_width$}  {what}")).collect::<Vec<_>>().join("\n");
+
+    let title = format!("Keys: {} (any key closes)", app.view.name());
+    let area = f.size();
+    let width = (rows.iter().map(|(_, what)| what.chars().count()).max().unwrap_or(0) + key_width + 6)
+        .max(title.chars().count() + 2)
+        .min(area.width as usize) as u16;
+    let height = (rows.len() + 2).min(area.height as usize) as u16;
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let body = Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(Clear, popup);
+    f.render_widget(body, popup);
+}
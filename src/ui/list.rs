@@ -2,10 +2,17 @@ use ratatui::{
     Frame,
     layout::{Layout, Direction, Constraint},
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
-    style::{Style, Modifier},
+    style::Style,
+    text::{Line, Span},
 };
 
-use crate::app::App;
+use chrono::{DateTime, Datelike, Local, TimeZone};
+
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{App, ConnState, View};
+use crate::keys::Action;
+use crate::config::Density;
 
 pub fn draw(f: &mut Frame, app: &App) {
     let chunks = Layout::default()
@@ -13,35 +20,118 @@ pub fn draw(f: &mut Frame, app: &App) {
         .constraints([Constraint::Min(1), Constraint::Length(2)])
         .split(f.size());
 
+    let visible = app.visible_indices();
+    let now = Local::now();
+
     let items = if app.messages.is_empty() {
-        vec![ListItem::new("Loading... (press o to refresh)")]
+        vec![ListItem::new(format!("Loading... (press {} to refresh)", app.keys.label(Action::Refresh)))]
+    } else if visible.is_empty() {
+        vec![ListItem::new("No matching messages (Esc clears filter)")]
     } else {
-        app.messages.iter().map(|m| {
+        visible.iter().map(|&i| &app.messages[i]).map(|m| {
             let subject = if m.subject.is_empty() { "(no subject)" } else { m.subject.as_str() };
             let from = if m.from.is_empty() { "(unknown)" } else { m.from.as_str() };
-            let date = if m.date.is_empty() { "" } else { m.date.as_str() };
-            ListItem::new(format!("{subject}\n  {from}  {date}"))
+            let date = display_date(m.timestamp, &m.date, now);
+            let mut first = Vec::new();
+            if app.marked.contains(&m.uid) {
+                first.push(Span::styled("* ", theme::of(app, Role::Marked)));
+            }
+            first.push(Span::raw(if m.seen { "  " } else { "● " }));
+            if m.high_priority {
+                first.push(Span::styled("! ", theme::of(app, Role::Flag)));
+            }
+
+            // the highlight style is patched over this, so unread rows stay bold when selected
+            let style = if m.seen { Style::default() } else { theme::of(app, Role::Unread) };
+            let lines = match app.density {
+                Density::Compact => {
+                    first.push(Span::raw(format!("{}  {}  {subject}", fit(&date, 11), fit(from, 24))));
+                    vec![Line::from(first)]
+                }
+                Density::Normal | Density::Relaxed => {
+                    first.push(Span::raw(subject));
+                    let mut lines = vec![Line::from(first), Line::from(format!("    {from}  {date}"))];
+                    if app.density == Density::Relaxed {
+                        let preview = if m.preview.is_empty() { "…" } else { m.preview.as_str() };
+                        lines.push(Line::from(Span::styled(format!("    {preview}"), theme::of(app, Role::Muted))));
+                    }
+                    lines
+                }
+            };
+            ListItem::new(lines).style(style)
         }).collect::<Vec<_>>()
     };
 
+    let mut title = match &app.saved_search {
+        Some(name) => format!("{name} · {}", app.current_folder_label()),
+        None => app.current_folder_label().to_string(),
+    };
+    if app.folders.iter().any(|f| f.name == app.current_folder && f.read_only()) {
+        title.push_str(" · read-only");
+    }
+    if let Some(query) = &app.search {
+        title.push_str(&format!(" · search {query:?}"));
+    }
+    if let Some(filter) = &app.filter {
+        title.push_str(&format!(" · {}", filter.label()));
+    }
+    match app.net.state {
+        ConnState::Connected => {}
+        ConnState::Reconnecting(n) => title.push_str(&format!(" [reconnecting {n}]")),
+        ConnState::Offline => title.push_str(" [offline]"),
+    }
+
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("Inbox"))
-        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(theme::of(app, Role::Selection))
         .highlight_symbol("▶ ");
 
     let mut state = ListState::default();
-    if !app.messages.is_empty() {
-        state.select(Some(app.selected.min(app.messages.len().saturating_sub(1))));
-    }
+    state.select(visible.iter().position(|&i| i == app.selected));
 
     f.render_stateful_widget(list, chunks[0], &mut state);
 
-    let help = Paragraph::new(format!(
-        "{}   {}",
-        app.status,
-        "j/k or ↑↓ move · Enter open · o refresh · c compose · g config · q quit"
-    ))
-        .wrap(Wrap { trim: true });
+    let help = match (&app.search_input, &app.command_input) {
+        (Some(input), _) => Paragraph::new(format!(
+            "/{input}_   (from: to: subject: body: · Enter search · Esc cancel)"
+        )),
+        (None, Some(input)) => Paragraph::new(format!(":{input}_   (profile · capabilities · groups · Enter run · Esc cancel)")),
+        (None, None) => Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::List))),
+    }
+    .wrap(Wrap { trim: true });
 
     f.render_widget(help, chunks[1]);
 }
+
+// `3m`, `2h` and `Yesterday` for recent mail, `Mar 4` this year, `2023-03-04` before;
+// the raw header when it didn't parse.
+fn display_date(timestamp: Option<i64>, raw: &str, now: DateTime<Local>) -> String {
+    let Some(t) = timestamp.and_then(|ts| Local.timestamp_opt(ts, 0).single()) else {
+        return raw.to_string();
+    };
+    let ago = now.signed_duration_since(t);
+    let days = now.date_naive().signed_duration_since(t.date_naive()).num_days();
+    if ago.num_minutes() < 1 && ago.num_seconds() >= -60 {
+        "now".to_string()
+    } else if (1..60).contains(&ago.num_minutes()) {
+        format!("{}m", ago.num_minutes())
+    } else if days == 0 && ago.num_seconds() > 0 {
+        format!("{}h", ago.num_hours())
+    } else if days == 1 {
+        "Yesterday".to_string()
+    } else if t.year() == now.year() {
+        t.format("%b %-d").to_string()
+    } else {
+        t.format("%Y-%m-%d").to_string()
+    }
+}
+
+// Cut or padded to exactly `width` characters, so compact rows line up.
+fn fit(s: &str, width: usize) -> String {
+    let n = s.chars().count();
+    if n > width {
+        format!("{}…", s.chars().take(width - 1).collect::<String>())
+    } else {
+        format!("{s}{}", " ".repeat(width - n))
+    }
+}
@@ -1,16 +1,59 @@
-use ratatui::Frame;
+use ratatui::{Frame, layout::Rect, widgets::Paragraph};
 use crate::app::{App, View};
 
 mod list;
+mod folders;
+mod drafts;
 mod view;
 mod compose;
 mod config;
+mod auth;
+mod capabilities;
+mod outbox;
+mod styles;
+mod diff;
+mod searches;
+mod groups;
+mod health;
+mod help;
+pub mod theme;
+
+pub use diff::diff_height;
+pub use view::{body_height, body_width, max_scroll, remap_scroll, BodyRows};
 
 pub fn draw(f: &mut Frame, app: &App) {
     match app.view {
         View::List => list::draw(f, app),
+        View::Folders => folders::draw(f, app),
+        View::Drafts => drafts::draw(f, app),
         View::Mail => view::draw(f, app),
         View::Compose => compose::draw(f, app),
         View::Config => config::draw(f, app),
+        View::Auth => auth::draw(f, app),
+        View::Capabilities => capabilities::draw(f, app),
+        View::Outbox => outbox::draw(f, app),
+        View::Styles => styles::draw(f, app),
+        View::Diff => diff::draw(f, app),
+        View::Searches => searches::draw(f, app),
+        View::Groups => groups::draw(f, app),
+        View::Health => health::draw(f, app),
+    }
+
+    // unchecked certificates are never silent: every screen says so in its top right corner
+    let unverified: Vec<&str> = [("IMAP", &app.config.imap), ("SMTP", &app.config.smtp)]
+        .into_iter()
+        .filter(|(_, m)| !m.verify_certs())
+        .map(|(name, _)| name)
+        .collect();
+    if !unverified.is_empty() {
+        let text = format!(" ⚠ {} certificate not verified ", unverified.join("/"));
+        let area = f.size();
+        let width = (text.chars().count() as u16).min(area.width);
+        let corner = Rect { x: area.right() - width, y: area.y, width, height: area.height.min(1) };
+        f.render_widget(Paragraph::new(text).style(theme::of(app, theme::Role::Warning)), corner);
+    }
+
+    if app.show_help {
+        help::draw(f, app);
     }
 }
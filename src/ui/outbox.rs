@@ -0,0 +1,47 @@
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{App, View};
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+
+    let items = if app.outbox.is_empty() {
+        vec![ListItem::new("Nothing scheduled (Ctrl+L in compose sends later)")]
+    } else {
+        app.outbox.iter().map(|e| {
+            let d = &e.mail.mail;
+            let subject = if d.subject.is_empty() { "(no subject)" } else { d.subject.as_str() };
+            let to = if d.to.is_empty() { "(no recipient)" } else { d.to.as_str() };
+            let state = match (e.claimed, app.outbox_inflight.contains(&e.path)) {
+                (true, true) => " · sending",
+                (true, false) => " · cut off while sending, may have gone out (check Sent)",
+                _ => "",
+            };
+            ListItem::new(format!("{} · {subject}{state}\n  to {to}", e.mail.local_time()))
+        }).collect::<Vec<_>>()
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Outbox"))
+        .highlight_style(theme::of(app, Role::Selection))
+        .highlight_symbol("▶ ");
+
+    let mut state = ListState::default();
+    if !app.outbox.is_empty() {
+        state.select(Some(app.outbox_selected.min(app.outbox.len() - 1)));
+    }
+
+    f.render_stateful_widget(list, chunks[0], &mut state);
+
+    let help = Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Outbox)));
+    f.render_widget(help, chunks[1]);
+}
@@ -0,0 +1,62 @@
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{App, View};
+use crate::config::SavedSearch;
+
+// `Invoices · /has:attachment · from: boss@x · oldest first`
+fn describe(s: &SavedSearch) -> String {
+    let mut parts = vec![s.folder.clone()];
+    if !s.query.is_empty() {
+        parts.push(format!("/{}", s.query));
+    }
+    if !s.from.is_empty() {
+        parts.push(format!("from: {}", s.from));
+    }
+    if !s.thread.is_empty() {
+        parts.push(format!("thread: {}", s.thread));
+    }
+    if !s.newest_first {
+        parts.push("oldest first".to_string());
+    }
+    parts.join(" · ")
+}
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+
+    let saved = &app.config.saved_searches;
+    let items = if saved.is_empty() {
+        vec![ListItem::new("Nothing saved yet (a saves what the list shows now)")]
+    } else {
+        saved.iter().enumerate().map(|(i, s)| {
+            let key = if i < 9 { format!("{}", i + 1) } else { " ".to_string() };
+            ListItem::new(format!("{key}  {}\n   {}", s.name, describe(s)))
+        }).collect::<Vec<_>>()
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Saved searches"))
+        .highlight_style(theme::of(app, Role::Selection))
+        .highlight_symbol("▶ ");
+
+    let mut state = ListState::default();
+    if !saved.is_empty() {
+        state.select(Some(app.saved_selected.min(saved.len() - 1)));
+    }
+    f.render_stateful_widget(list, chunks[0], &mut state);
+
+    let help = match &app.saved_name_input {
+        Some((_, input)) => Paragraph::new(format!("Name: {input}_   {}\n(Enter save · Esc cancel)", app.status)),
+        None => Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Searches))),
+    };
+    f.render_widget(help, chunks[1]);
+}
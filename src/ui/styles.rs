@@ -0,0 +1,44 @@
+use ratatui::{
+    Frame,
+    layout::{Layout, Direction, Constraint},
+    widgets::{Block, Borders, Paragraph},
+    text::{Line, Span},
+};
+
+use super::help;
+use super::theme::{self, ROLES, THEMES};
+use crate::app::{App, View};
+
+// Every role in every built-in theme, with what it resolves to in the configured one.
+pub fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(f.size());
+
+    let current = app.config.view.theme;
+    let mut header = vec![Span::raw(format!("{:<12}", "role"))];
+    for (t, name) in THEMES {
+        let mark = if t == current { "*" } else { "" };
+        header.push(Span::raw(format!("{:<14}", format!("{name}{mark}"))));
+    }
+    header.push(Span::raw("resolved"));
+
+    let mut lines = vec![Line::from(header), Line::from("")];
+    for role in ROLES {
+        let mut row = vec![Span::raw(format!("{:<12}", role.name()))];
+        for (t, _) in THEMES {
+            row.push(Span::styled(" Sample text ", theme::style(t, role)));
+            row.push(Span::raw(" "));
+        }
+        row.push(Span::raw(theme::describe(theme::style(current, role))));
+        lines.push(Line::from(row));
+    }
+
+    let swatch = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Styles (* = [view] theme)"));
+    f.render_widget(swatch, chunks[0]);
+
+    let help = Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Styles)));
+    f.render_widget(help, chunks[1]);
+}
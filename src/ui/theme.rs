@@ -0,0 +1,215 @@
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::app::App;
+use crate::config::Theme;
+
+/// What a piece of text means. Draw code asks for a role and the theme decides how
+/// it looks, so no state is told apart by color alone: every role that carries state
+/// also has a glyph or modifier where it is drawn (`!`, `*`, `●`, `>`, `on`/`off`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Error,
+    Warning,
+    Success,
+    Muted,
+    Unread,
+    Selection,
+    Flag,   // high priority
+    Marked, // picked with Space
+    Quote1,
+    Quote2,
+    Quote3,
+    Quote4, // and deeper
+}
+
+pub const ROLES: [Role; 12] = [
+    Role::Error,
+    Role::Warning,
+    Role::Success,
+    Role::Muted,
+    Role::Unread,
+    Role::Selection,
+    Role::Flag,
+    Role::Marked,
+    Role::Quote1,
+    Role::Quote2,
+    Role::Quote3,
+    Role::Quote4,
+];
+
+pub const THEMES: [(Theme, &str); 2] = [(Theme::Default, "default"), (Theme::Mono, "mono")];
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Error => "error",
+            Role::Warning => "warning",
+            Role::Success => "success",
+            Role::Muted => "muted",
+            Role::Unread => "unread",
+            Role::Selection => "selection",
+            Role::Flag => "flag",
+            Role::Marked => "marked",
+            Role::Quote1 => "quote-1",
+            Role::Quote2 => "quote-2",
+            Role::Quote3 => "quote-3",
+            Role::Quote4 => "quote-4",
+        }
+    }
+
+    /// The quote role for a line quoted `depth` times (0 for none).
+    pub fn quote(depth: usize) -> Option<Role> {
+        match depth {
+            0 => None,
+            1 => Some(Role::Quote1),
+            2 => Some(Role::Quote2),
+            3 => Some(Role::Quote3),
+            _ => Some(Role::Quote4),
+        }
+    }
+}
+
+// Okabe–Ito colors as 256-color indexes: no pair here differs only in red vs green.
+const ORANGE: Color = Color::Indexed(214);
+const SKY_BLUE: Color = Color::Indexed(75);
+const VERMILLION: Color = Color::Indexed(166);
+const PURPLE: Color = Color::Indexed(175);
+
+// Matching on both means a new role or theme doesn't build until every theme has a
+// style for it.
+pub fn style(theme: Theme, role: Role) -> Style {
+    let s = Style::default();
+    match (theme, role) {
+        (Theme::Default, Role::Error) => s.fg(VERMILLION).add_modifier(Modifier::BOLD),
+        (Theme::Default, Role::Warning) => s.fg(ORANGE),
+        (Theme::Default, Role::Success) => s.fg(SKY_BLUE),
+        (Theme::Default, Role::Muted) => s.fg(Color::DarkGray),
+        (Theme::Default, Role::Unread) => s.add_modifier(Modifier::BOLD),
+        (Theme::Default, Role::Selection) => s.add_modifier(Modifier::REVERSED),
+        (Theme::Default, Role::Flag) => s.fg(ORANGE).add_modifier(Modifier::BOLD),
+        (Theme::Default, Role::Marked) => s.fg(SKY_BLUE).add_modifier(Modifier::BOLD),
+        (Theme::Default, Role::Quote1) => s.fg(SKY_BLUE),
+        (Theme::Default, Role::Quote2) => s.fg(PURPLE),
+        (Theme::Default, Role::Quote3) => s.fg(ORANGE),
+        (Theme::Default, Role::Quote4) => s.fg(Color::DarkGray),
+
+        (Theme::Mono, Role::Error) => s.add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        (Theme::Mono, Role::Warning) => s.add_modifier(Modifier::BOLD),
+        (Theme::Mono, Role::Success) => s,
+        (Theme::Mono, Role::Muted) => s.add_modifier(Modifier::DIM),
+        (Theme::Mono, Role::Unread) => s.add_modifier(Modifier::BOLD),
+        (Theme::Mono, Role::Selection) => s.add_modifier(Modifier::REVERSED),
+        (Theme::Mono, Role::Flag) => s.add_modifier(Modifier::BOLD),
+        (Theme::Mono, Role::Marked) => s.add_modifier(Modifier::BOLD),
+        (Theme::Mono, Role::Quote1) => s.add_modifier(Modifier::ITALIC),
+        (Theme::Mono, Role::Quote2 | Role::Quote3 | Role::Quote4) => s.add_modifier(Modifier::ITALIC | Modifier::DIM),
+    }
+}
+
+/// `role` in the configured theme.
+pub fn of(app: &App, role: Role) -> Style {
+    style(app.config.view.theme, role)
+}
+
+/// e.g. `fg 214 · bold`, for the style swatch.
+pub fn describe(s: Style) -> String {
+    let mut parts = Vec::new();
+    match s.fg {
+        Some(Color::Indexed(i)) => parts.push(format!("fg {i}")),
+        Some(c) => parts.push(format!("fg {c:?}").to_lowercase()),
+        None => {}
+    }
+    for (m, name) in [
+        (Modifier::BOLD, "bold"),
+        (Modifier::DIM, "dim"),
+        (Modifier::ITALIC, "italic"),
+        (Modifier::UNDERLINED, "underlined"),
+        (Modifier::REVERSED, "reversed"),
+    ] {
+        if s.add_modifier.contains(m) {
+            parts.push(name.to_string());
+        }
+    }
+    if parts.is_empty() { "plain".to_string() } else { parts.join(" · ") }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The draw code in `ui/`, every file but this one: (file name, source).
+    fn draw_sources() -> Vec<(String, String)> {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/ui");
+        let mut out = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let file = path.file_name().unwrap().to_string_lossy().into_owned();
+            if file != "theme.rs" {
+                out.push((file, std::fs::read_to_string(&path).unwrap()));
+            }
+        }
+        out
+    }
+
+    // Every `Role::X` the draw code names.
+    fn roles_used() -> Vec<(String, String)> {
+        let re = regex::Regex::new(r"Role::([A-Z]\w*)").unwrap();
+        let mut out = Vec::new();
+        for (file, src) in draw_sources() {
+            out.extend(re.captures_iter(&src).map(|c| (file.clone(), c[1].to_string())));
+        }
+        out
+    }
+
+    fn named(ident: &str) -> Option<Role> {
+        ROLES.into_iter().find(|r| r.name().replace('-', "") == ident.to_lowercase())
+    }
+
+    #[test]
+    fn every_role_drawn_is_in_every_theme() {
+        let used = roles_used();
+        assert!(used.iter().any(|(file, _)| file == "list.rs"), "{used:?}");
+        for (file, ident) in &used {
+            let role = named(ident).unwrap_or_else(|| panic!("{file}: Role::{ident} is missing from ROLES"));
+            for (theme, name) in THEMES {
+                // mono has no colors to fall back on, so whatever carries meaning needs a modifier
+                let s = style(theme, role);
+                assert!(
+                    role == Role::Success || s.fg.is_some() || !s.add_modifier.is_empty(),
+                    "{file}: {} looks plain in the {name} theme",
+                    role.name()
+                );
+            }
+        }
+        for q in 1..=5 {
+            assert!(Role::quote(q).is_some_and(|r| ROLES.contains(&r)));
+        }
+    }
+
+    #[test]
+    fn roles_have_distinct_names() {
+        let mut names: Vec<_> = ROLES.iter().map(|r| r.name()).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), ROLES.len());
+    }
+
+    #[test]
+    fn draw_code_uses_roles_rather_than_colors() {
+        for (file, src) in draw_sources() {
+            assert!(!src.contains("Color::"), "{file} picks its own colors");
+        }
+    }
+
+    #[test]
+    fn default_theme_avoids_red_and_green() {
+        for role in ROLES {
+            let fg = style(Theme::Default, role).fg;
+            assert!(
+                !matches!(fg, Some(Color::Red | Color::Green | Color::LightRed | Color::LightGreen)),
+                "{} is red or green",
+                role.name()
+            );
+        }
+    }
+}
@@ -1,44 +1,414 @@
 use ratatui::{
     Frame,
-    layout::{Layout, Direction, Constraint},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    layout::{Layout, Direction, Constraint, Rect},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    text::{Line, Span, Text},
 };
 
-use crate::app::App;
+use std::cell::Ref;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::help;
+use super::theme::{self, Role};
+use crate::app::{App, BodyFetch, View};
+use crate::mail::Delivery;
+
+pub fn human_size(bytes: usize) -> String {
+    let b = bytes as f64;
+    if b >= 1024.0 * 1024.0 {
+        format!("{:.1} MiB", b / (1024.0 * 1024.0))
+    } else if b >= 1024.0 {
+        format!("{:.1} KiB", b / 1024.0)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+/// The mail body broken into screen rows, kept between frames so a long mail is
+/// wrapped once per width rather than on every draw.
+#[derive(Default)]
+pub struct BodyRows {
+    width: Option<u16>,
+    rows: Vec<(usize, usize, usize)>, // byte range in the body, quote depth of its line
+}
+
+impl BodyRows {
+    /// Forgets the rows; needed whenever the body text changes.
+    pub fn clear(&mut self) {
+        self.width = None;
+        self.rows.clear();
+    }
+}
+
+// `current_body`'s rows at `width`, wrapped now unless they are cached for it.
+fn body_layout(app: &App, width: u16) -> Ref<'_, Vec<(usize, usize, usize)>> {
+    {
+        let mut cache = app.body_rows.borrow_mut();
+        if cache.width != Some(width) {
+            cache.rows = layout_rows(&app.current_body, width);
+            cache.width = Some(width);
+        }
+    }
+    Ref::map(app.body_rows.borrow(), |c| &c.rows)
+}
+
+fn layout_rows(body: &str, width: u16) -> Vec<(usize, usize, usize)> {
+    let mut rows = vec![];
+    let mut at = 0;
+    for raw in body.split_inclusive('\n') {
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // quoted lines take the style of their depth (`>` is 1, `> >` or `>>` is 2)
+        let depth = crate::edit::quote_depth(line);
+        rows.extend(wrap_segments(line, width as usize).into_iter().map(|(s, e)| (at + s, at + e, depth)));
+        at += raw.len();
+    }
+    rows
+}
+
+// The `height` rows from `scroll` on, as text and quote depth.
+fn visible_rows<'a>(body: &'a str, rows: &[(usize, usize, usize)], scroll: usize, height: u16) -> Vec<(&'a str, usize)> {
+    let start = scroll.min(rows.len());
+    let end = (start + height as usize).min(rows.len());
+    rows[start..end].iter().map(|&(s, e, depth)| (&body[s..e], depth)).collect()
+}
+
+// Only the visible rows are styled and handed to ratatui.
+fn visible_body(app: &App, rows: &[(usize, usize, usize)], scroll: usize, height: u16) -> Text<'static> {
+    Text::from(
+        visible_rows(&app.current_body, rows, scroll, height)
+            .into_iter()
+            .map(|(text, depth)| match Role::quote(depth) {
+                Some(role) => Line::styled(text.to_string(), theme::of(app, role)),
+                None => Line::raw(text.to_string()),
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn progress_text(fetch: &BodyFetch) -> String {
+    let p = &fetch.progress;
+    let received = match p.total {
+        Some(total) if total > 0 => format!(
+            "{} / {} ({}%)",
+            human_size(p.bytes),
+            human_size(total),
+            (p.bytes * 100 / total).min(100)
+        ),
+        _ => human_size(p.bytes),
+    };
+    format!(
+        "Phase    {}\nReceived {}\nElapsed  {:.1}s\n\nEsc to cancel",
+        p.phase.label(),
+        received,
+        fetch.started.elapsed().as_secs_f32()
+    )
+}
+
+// Rows a line occupies when greedily word-wrapped at `width`; close to what
+// Paragraph's word wrapping produces.
+pub(super) fn wrap_rows(line: &str, width: usize) -> usize {
+    wrap_segments(line, width).len()
+}
+
+/// The byte ranges `line` is broken into at `width` terminal columns; wide
+/// characters (CJK, most emoji) take two. The spaces where a line breaks stay at the
+/// end of the row before.
+pub fn wrap_segments(line: &str, width: usize) -> Vec<(usize, usize)> {
+    let width = width.max(1);
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut col = 0;
+    let mut at = 0;
+    for word in line.split(' ') {
+        let len = word.width();
+        let needed = if col == 0 { len } else { len + 1 };
+        if col + needed <= width {
+            col += needed;
+        } else if len <= width {
+            out.push((start, at));
+            start = at;
+            col = len;
+        } else {
+            // a word longer than the line starts a new row and is broken across rows
+            if col > 0 {
+                out.push((start, at));
+                start = at;
+            }
+            col = 0;
+            for (i, c) in word.char_indices() {
+                let w = c.width().unwrap_or(0);
+                if col > 0 && col + w > width {
+                    out.push((start, at + i));
+                    start = at + i;
+                    col = 0;
+                }
+                col += w;
+            }
+        }
+        at += word.len() + 1;
+    }
+    out.push((start, line.len()));
+    out
+}
+
+/// Width the mail body is wrapped at for a terminal `term_width` columns wide.
+pub fn body_width(app: &App, term_width: u16) -> u16 {
+    if app.focus_mode {
+        term_width.saturating_sub(4).min(app.config.view.focus_width).max(1)
+    } else {
+        term_width.saturating_sub(2).max(1)
+    }
+}
+
+// The attachments box under the body, capped so the body keeps most of the screen.
+fn attach_rows(app: &App) -> u16 {
+    if app.body_fetch.is_none() && !app.attachments.is_empty() {
+        app.attachments.len().min(5) as u16 + 2
+    } else {
+        0
+    }
+}
+
+// The header box: From, To, Cc when there is one, Date, Subject and UID, plus borders.
+fn header_rows(app: &App) -> u16 {
+    match &app.current_header {
+        Some(h) if !h.cc.is_empty() => 8,
+        Some(_) => 7,
+        None => 3,
+    }
+}
+
+// One header field on one row; a long recipient list ends in "…" rather than
+// pushing the body down.
+fn header_line(label: &str, value: &str, width: u16) -> Line<'static> {
+    let room = (width as usize).saturating_sub(2 + 8).max(1);
+    let value = if value.chars().count() > room {
+        format!("{}…", value.chars().take(room - 1).collect::<String>())
+    } else {
+        value.to_string()
+    };
+    Line::from(format!("{label:<8}{value}"))
+}
+
+/// Rows the mail body gets on a terminal `term_height` rows high.
+pub fn body_height(app: &App, term_height: u16) -> u16 {
+    if app.focus_mode {
+        term_height.saturating_sub(1)
+    } else {
+        // header box, attachments, status lines and the body's own border
+        term_height.saturating_sub(header_rows(app) + attach_rows(app) + 2 + 2)
+    }
+}
+
+/// The furthest the body scrolls: its last row at the bottom, no empty space below.
+pub fn max_scroll(app: &App, term_width: u16, term_height: u16) -> usize {
+    let rows = body_layout(app, body_width(app, term_width)).len();
+    rows.saturating_sub(body_height(app, term_height) as usize)
+}
+
+/// Translates a scroll offset (in wrapped rows) from one wrap width to another,
+/// keeping the same logical line at the top.
+pub fn remap_scroll(text: &str, scroll: usize, from: u16, to: u16) -> usize {
+    let mut old_row = 0;
+    let mut new_row = 0;
+    for line in text.lines() {
+        let old = wrap_rows(line, from as usize);
+        let new = wrap_rows(line, to as usize);
+        if old_row + old > scroll {
+            let within = (scroll - old_row).min(new - 1);
+            return new_row + within;
+        }
+        old_row += old;
+        new_row += new;
+    }
+    new_row
+}
+
+fn draw_focus(f: &mut Frame, app: &App) {
+    let area = f.size();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(area);
+
+    let width = body_width(app, area.width);
+    let body_area = Rect {
+        x: area.x + (area.width.saturating_sub(width)) / 2,
+        width,
+        ..chunks[0]
+    };
+
+    let scroll = app.body_scroll.min(max_scroll(app, area.width, area.height));
+    // a copy kept on disk is readable while the fetch refreshes it
+    let (body, pos) = match app.body_fetch.as_ref().filter(|_| !app.body_from_cache) {
+        Some(fetch) => (Paragraph::new(progress_text(fetch)).wrap(Wrap { trim: false }), 0),
+        None => {
+            let rows = body_layout(app, width);
+            let total = rows.len().max(1);
+            let pos = ((scroll + body_area.height as usize).min(total) * 100) / total;
+            (Paragraph::new(visible_body(app, &rows, scroll, body_area.height)), pos)
+        }
+    };
+    f.render_widget(body, body_area);
+
+    let (from, subject) = match &app.current_header {
+        Some(h) => (h.from.as_str(), h.subject.as_str()),
+        None => ("", ""),
+    };
+    let indicator = Paragraph::new(match &app.export_input {
+        Some(input) => format!("Save .eml to: {input}_   (Tab completes · Enter save · Esc cancel)"),
+        None => format!("{from} · {subject} · {} min read · {pos}%", app.body_count.reading_minutes()),
+    })
+        .style(theme::of(app, Role::Muted));
+    f.render_widget(indicator, Rect { x: body_area.x, width, ..chunks[1] });
+}
 
 pub fn draw(f: &mut Frame, app: &App) {
+    if app.focus_mode {
+        draw_focus(f, app);
+        return;
+    }
+
+    let attach_rows = attach_rows(app);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(6), Constraint::Min(1), Constraint::Length(2)])
+        .constraints([
+            Constraint::Length(header_rows(app)),
+            Constraint::Min(1),
+            Constraint::Length(attach_rows),
+            Constraint::Length(2),
+        ])
         .split(f.size());
 
-    let header_text = if let Some(h) = &app.current_header {
-        format!(
-            "From    {}\nDate    {}\nSubject {}\nUID     {}",
-            if h.from.is_empty() { "(unknown)" } else { &h.from },
-            if h.date.is_empty() { "" } else { &h.date },
-            if h.subject.is_empty() { "(no subject)" } else { &h.subject },
-            h.uid
-        )
+    let header_text: Vec<Line> = if let Some(h) = &app.current_header {
+        let mut subject = vec![Span::raw("Subject ")];
+        if h.high_priority {
+            subject.push(Span::styled("! ", theme::of(app, Role::Flag)));
+        }
+        subject.push(Span::raw(if h.subject.is_empty() { "(no subject)" } else { h.subject.as_str() }));
+
+        let width = f.size().width;
+        // mail that reached me some other way than my address in To / Cc says so at the
+        // end of the To row, which gives up room for it
+        let via = match &app.current_delivery {
+            Some(Delivery::Alias(a)) => format!("  to {a}"),
+            Some(Delivery::Bcc(Some(a))) => format!("  via bcc? (delivered to {a})"),
+            Some(Delivery::Bcc(None)) => "  via bcc?".to_string(),
+            None => String::new(),
+        };
+        let to_width = width.saturating_sub(via.chars().count() as u16);
+        let mut to = header_line("To", if h.to.is_empty() { "(undisclosed)" } else { &h.to }, to_width);
+        if !via.is_empty() {
+            to.spans.push(Span::styled(via, theme::of(app, Role::Warning)));
+        }
+        let mut lines = vec![header_line("From", if h.from.is_empty() { "(unknown)" } else { &h.from }, width), to];
+        if !h.cc.is_empty() {
+            lines.push(header_line("Cc", &h.cc, width));
+        }
+        lines.extend([
+            Line::from(format!("Date    {}", h.date)),
+            Line::from(subject),
+            Line::from(match &app.body_fetch {
+                Some(_) => format!("UID     {}", h.uid),
+                None => format!(
+                    "UID     {}   {} · {} min read{}",
+                    h.uid,
+                    app.body_count.label(),
+                    app.body_count.reading_minutes(),
+                    if app.body_was_html { "   [converted from HTML]" } else { "" }
+                ),
+            }),
+        ]);
+        lines
     } else {
-        "Loading...".to_string()
+        vec![Line::from("Loading...")]
     };
 
     let header = Paragraph::new(header_text)
         .block(Block::default().borders(Borders::ALL).title("Mail"));
 
-    let body = Paragraph::new(app.current_body.clone())
-        .block(Block::default().borders(Borders::ALL))
-        .wrap(Wrap { trim: false })
-        .scroll((app.body_scroll, 0));
+    let body = match app.body_fetch.as_ref().filter(|_| !app.body_from_cache) {
+        Some(fetch) => Paragraph::new(progress_text(fetch)).wrap(Wrap { trim: false }),
+        None => {
+            // a resize can leave the offset past the end
+            let scroll = app.body_scroll.min(max_scroll(app, f.size().width, f.size().height));
+            let rows = body_layout(app, body_width(app, f.size().width));
+            Paragraph::new(visible_body(app, &rows, scroll, chunks[1].height.saturating_sub(2)))
+        }
+    }
+    .block(Block::default().borders(Borders::ALL));
 
     f.render_widget(header, chunks[0]);
     f.render_widget(body, chunks[1]);
 
-    let help = Paragraph::new(format!(
-        "{}   {}",
-        app.status,
-        "j/k or ↑↓ scroll · Esc back · r reply · c compose · g config · q quit"
-    ));
-    f.render_widget(help, chunks[2]);
+    if attach_rows > 0 {
+        let items: Vec<ListItem> = app
+            .attachments
+            .iter()
+            .enumerate()
+            .map(|(i, a)| ListItem::new(format!("{}. {}  {}  {}", i + 1, a.filename, a.content_type, human_size(a.size))))
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Attachments (Tab select · s save · a save by number)"))
+            .highlight_style(theme::of(app, Role::Selection));
+        let mut state = ListState::default();
+        state.select(Some(app.attachment_selected));
+        f.render_stateful_widget(list, chunks[2], &mut state);
+    }
+
+    let help = match &app.export_input {
+        Some(input) => Paragraph::new(format!("Save .eml to: {input}_\n(Tab completes · Enter save · Esc cancel)")),
+        None => Paragraph::new(format!("{}   {}", app.status, help::hint(app, View::Mail))),
+    };
+    f.render_widget(help, chunks[3]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(line: &str, width: usize) -> Vec<&str> {
+        wrap_segments(line, width).into_iter().map(|(s, e)| &line[s..e]).collect()
+    }
+
+    #[test]
+    fn wraps_ascii_at_word_boundaries() {
+        assert_eq!(rows("hello world again", 11), ["hello world ", "again"]);
+        assert_eq!(rows("hello world again", 10), ["hello ", "world ", "again"]);
+        assert_eq!(rows("abcdefgh", 3), ["abc", "def", "gh"]);
+        assert_eq!(rows("", 10), [""]);
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        // 6 Hangul syllables are 12 columns: at 10 the line can't stay whole
+        assert_eq!(rows("안녕하세요 세계", 10), ["안녕하세요 ", "세계"]);
+        assert_eq!(rows("가나다라마바", 6), ["가나다", "라마바"]);
+        // a wide character never straddles the edge
+        assert_eq!(rows("a가나", 4), ["a가", "나"]);
+        for row in rows("😀😀😀😀😀 日本語のテキストです", 7) {
+            assert!(row.trim_end().width() <= 7, "{row:?}");
+        }
+        assert_eq!(wrap_rows("日本語日本語", 4), 3);
+    }
+
+    #[test]
+    fn frame_work_follows_the_viewport_not_the_body() {
+        let line = "> quoted text that wraps once it is long enough to need a second row\n";
+        let body = line.repeat(50_000);
+        let layout = layout_rows(&body, 40);
+        assert_eq!(layout.len(), 100_000);
+
+        // however long the mail, a frame only touches `height` rows
+        let visible = visible_rows(&body, &layout, 50_000, 24);
+        assert_eq!(visible.len(), 24);
+        assert!(visible.iter().all(|(_, depth)| *depth == 1));
+        assert_eq!(visible_rows(&body, &layout, 99_990, 24).len(), 10);
+        // past what a u16 offset could hold, down to the last row
+        let end = visible_rows(&body, &layout, 100_000 - 24, 24);
+        assert_eq!(end.len(), 24);
+        assert_eq!(end[23].0, &body[layout[99_999].0..layout[99_999].1]);
+        assert!(visible_rows(&body, &layout, usize::MAX, 24).is_empty());
+    }
 }
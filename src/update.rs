@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Result};
+use std::{fs, path::PathBuf};
+
+use crate::config::Config;
+use crate::https;
+
+const HOST: &str = "api.github.com";
+const LATEST: &str = "/repos/wavefnd/zenmail/releases/latest";
+const CACHE_SECS: i64 = 24 * 60 * 60;
+
+/// A release newer than this build.
+pub struct Release {
+    pub version: String,
+    pub url: String,
+}
+
+// What the last check found, so a check happens at most once a day.
+#[derive(Deserialize, Serialize)]
+struct Cache {
+    checked_at: i64,
+    version: String,
+    url: String,
+}
+
+fn cache_path() -> Result<PathBuf> {
+    let cfg = Config::path()?;
+    let parent = cfg.parent().ok_or_else(|| anyhow!("no config dir"))?;
+    Ok(parent.join("update-check.toml"))
+}
+
+/// The latest release when it is newer than this build. Asks GitHub at most once every
+/// 24 hours and only ever reads: nothing is downloaded or installed.
+pub fn check() -> Result<Option<Release>> {
+    let path = cache_path()?;
+    let now = chrono::Local::now().timestamp();
+    let cached = fs::read_to_string(&path).ok().and_then(|data| toml::from_str::<Cache>(&data).ok());
+
+    let latest = match cached {
+        Some(c) if (0..CACHE_SECS).contains(&(now - c.checked_at)) => c,
+        _ => {
+            let (version, url) = fetch_latest()?;
+            let c = Cache { checked_at: now, version, url };
+            fs::write(&path, toml::to_string_pretty(&c)?)?;
+            c
+        }
+    };
+
+    let newer = version_parts(&latest.version) > version_parts(env!("CARGO_PKG_VERSION"));
+    Ok(newer.then_some(Release { version: latest.version, url: latest.url }))
+}
+
+// `v1.10.2` → [1, 10, 2]; a pre-release suffix is ignored.
+fn version_parts(v: &str) -> Vec<u64> {
+    v.trim_start_matches('v')
+        .split(['-', '+'])
+        .next()
+        .unwrap_or("")
+        .split('.')
+        .map(|n| n.parse().unwrap_or(0))
+        .collect()
+}
+
+fn fetch_latest() -> Result<(String, String)> {
+    let (status, body) = https::send(
+        HOST,
+        &format!(
+            "GET {LATEST} HTTP/1.0\r\nHost: {HOST}\r\nUser-Agent: zenmail/{}\r\nAccept: application/vnd.github+json\r\n\r\n",
+            env!("CARGO_PKG_VERSION")
+        ),
+    )?;
+    if status != 200 {
+        return Err(anyhow!("{HOST}: HTTP {status}"));
+    }
+
+    let field = |name: &str| https::json_field(&body, name).ok_or_else(|| anyhow!("no {name} in the reply"));
+    Ok((field("tag_name")?, field("html_url")?))
+}